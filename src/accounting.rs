@@ -0,0 +1,179 @@
+// Counts of the work done by the evaluator: a reduction is one evaluation of
+// a form and an allocation is one new object, with conses also counted
+// apart. They depend only on the program, not on the machine or how long it
+// takes, so they can be used to bill or limit scripts.
+//
+// The counters are per thread; a tally is the difference between two
+// readings on the thread that ran the evaluation.
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use super::object::ObjectKind;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Tally {
+    pub reductions: u64,
+    pub allocations: u64,
+    pub conses: u64,
+}
+
+impl Tally {
+    pub fn since(&self, start: &Tally) -> Tally {
+        Tally {
+            reductions: self.reductions - start.reductions,
+            allocations: self.allocations - start.allocations,
+            conses: self.conses - start.conses,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+mod counters {
+    use super::Tally;
+    use core::cell::Cell;
+
+    std::thread_local! {
+        static COUNTS: Cell<Tally> = const {
+            Cell::new(Tally {
+                reductions: 0,
+                allocations: 0,
+                conses: 0,
+            })
+        };
+    }
+
+    pub fn count(f: impl FnOnce(&mut Tally)) {
+        COUNTS.with(|counts| {
+            let mut tally = counts.get();
+            f(&mut tally);
+            counts.set(tally);
+        });
+    }
+
+    pub fn current() -> Tally {
+        COUNTS.with(Cell::get)
+    }
+}
+
+// Without std there are no threads to keep apart.
+#[cfg(not(feature = "std"))]
+mod counters {
+    use super::Tally;
+    use core::sync::atomic::{AtomicU64, Ordering};
+
+    static REDUCTIONS: AtomicU64 = AtomicU64::new(0);
+    static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+    static CONSES: AtomicU64 = AtomicU64::new(0);
+
+    pub fn count(f: impl FnOnce(&mut Tally)) {
+        let mut tally = current();
+        f(&mut tally);
+        REDUCTIONS.store(tally.reductions, Ordering::Relaxed);
+        ALLOCATIONS.store(tally.allocations, Ordering::Relaxed);
+        CONSES.store(tally.conses, Ordering::Relaxed);
+    }
+
+    pub fn current() -> Tally {
+        Tally {
+            reductions: REDUCTIONS.load(Ordering::Relaxed),
+            allocations: ALLOCATIONS.load(Ordering::Relaxed),
+            conses: CONSES.load(Ordering::Relaxed),
+        }
+    }
+}
+
+pub use counters::current;
+
+// Work done elsewhere on behalf of this thread, such as in a coroutine.
+pub fn add(tally: &Tally) {
+    counters::count(|t| {
+        t.reductions += tally.reductions;
+        t.allocations += tally.allocations;
+        t.conses += tally.conses;
+    });
+}
+
+pub fn count_reduction() {
+    counters::count(|tally| tally.reductions += 1);
+}
+
+pub fn count_allocation(is_cons: bool) {
+    counters::count(|tally| {
+        tally.allocations += 1;
+        tally.conses += is_cons as u64;
+    });
+}
+
+// Live objects by ObjectKind::kind_index, and live environments, for room.
+// An object can be freed by another thread than the one that made it, so
+// unlike tallies these are counted for the whole process.
+static LIVE_OBJECTS: [AtomicUsize; ObjectKind::KIND_NAMES.len()] =
+    [const { AtomicUsize::new(0) }; ObjectKind::KIND_NAMES.len()];
+static LIVE_ENVS: AtomicUsize = AtomicUsize::new(0);
+
+pub fn count_live(kind: usize) {
+    LIVE_OBJECTS[kind].fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn count_free(kind: usize) {
+    LIVE_OBJECTS[kind].fetch_sub(1, Ordering::Relaxed);
+}
+
+pub fn count_env(delta: isize) {
+    if delta > 0 {
+        LIVE_ENVS.fetch_add(1, Ordering::Relaxed);
+    } else {
+        LIVE_ENVS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// The number of live objects of each kind, by name.
+pub fn live_objects() -> Vec<(&'static str, usize)> {
+    ObjectKind::KIND_NAMES
+        .iter()
+        .zip(&LIVE_OBJECTS)
+        .map(|(name, count)| (*name, count.load(Ordering::Relaxed)))
+        .collect()
+}
+
+pub fn live_envs() -> usize {
+    LIVE_ENVS.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object;
+
+    #[test]
+    fn tally_test() {
+        let start = current();
+        let _ = object::cons(object::fixnum(1), object::nil());
+        count_reduction();
+        let tally = current().since(&start);
+        assert_eq!(
+            tally,
+            Tally {
+                reductions: 1,
+                allocations: 3,
+                conses: 1
+            }
+        );
+    }
+
+    // Other tests allocate concurrently, so only a lower bound holds.
+    #[test]
+    fn live_objects_test() {
+        let live = |kind: &str| {
+            live_objects()
+                .into_iter()
+                .find(|(name, _)| *name == kind)
+                .unwrap()
+                .1
+        };
+        let strings: Vec<_> = (0..100).map(|_| object::string("x")).collect();
+        assert!(live("string") >= 100);
+        drop(strings);
+        assert_eq!(ObjectKind::KIND_NAMES[object::nil().kind_index()], "nil");
+    }
+}
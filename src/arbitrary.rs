@@ -0,0 +1,380 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::bitvector::BitVector;
+use super::object::{self, Object, ObjectKind};
+use super::table::{HashTable, Set};
+
+// Random objects and programs for property tests, with shrinkers to cut a
+// failing case down. Everything generated prints as something read reads
+// back equal, so no functions, ports or other unreadable objects.
+
+// A splitmix64 generator, so a failing seed reproduces on every platform.
+pub struct Gen {
+    state: u64,
+}
+
+impl Gen {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    // A number in 0..n, which must not be empty.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    pub fn choose<'a, T>(&mut self, xs: &'a [T]) -> &'a T {
+        &xs[self.below(xs.len())]
+    }
+}
+
+const SYMBOL_START: &str = "abcdefghijklmnopqrstuvwxyz";
+const SYMBOL_REST: &str = "abcdefghijklmnopqrstuvwxyz0123456789-*+!?<>=/";
+const STRING_CHARS: [char; 14] = [
+    'a', 'z', ' ', '"', '\\', '\n', '\t', '\r', '\0', '\x1b', '\x7f', 'é', 'λ', '😀',
+];
+
+pub fn fixnum(g: &mut Gen) -> Object {
+    let n = match g.below(4) {
+        0 => *g.choose(&[0, 1, -1, isize::MAX, isize::MIN]),
+        1 => g.next_u64() as isize,
+        _ => g.below(200) as isize - 100,
+    };
+    object::fixnum(n)
+}
+
+// A symbol or keyword. Names start with a letter so that none reads as a
+// number, and nil, which reads as the empty list, is skipped.
+pub fn symbol(g: &mut Gen) -> Object {
+    let mut name = String::new();
+    if g.below(4) == 0 {
+        name.push(':');
+    }
+    name.push(*g.choose(SYMBOL_START.as_bytes()) as char);
+    for _ in 0..g.below(6) {
+        name.push(*g.choose(SYMBOL_REST.as_bytes()) as char);
+    }
+    if name == "nil" {
+        name.push('!');
+    }
+    object::symbol(&name)
+}
+
+pub fn string(g: &mut Gen) -> Object {
+    let s: String = (0..g.below(8)).map(|_| *g.choose(&STRING_CHARS)).collect();
+    object::string(&s)
+}
+
+fn atom(g: &mut Gen) -> Object {
+    match g.below(5) {
+        0 => object::nil(),
+        1 => fixnum(g),
+        2 => symbol(g),
+        3 => string(g),
+        _ => object::bit_vector(BitVector::from_bits(
+            (0..g.below(70)).map(|_| g.below(2) == 1),
+        )),
+    }
+}
+
+// An object whose lists, tables and sets nest at most depth deep.
+pub fn object(g: &mut Gen, depth: usize) -> Object {
+    if depth == 0 {
+        return atom(g);
+    }
+    let elements = |g: &mut Gen| {
+        (0..g.below(5))
+            .map(|_| object(g, depth - 1))
+            .collect::<Vec<_>>()
+    };
+    match g.below(8) {
+        0 | 1 => object::from_iter(elements(g)),
+        2 => {
+            let mut list = elements(g);
+            list.push(object(g, depth - 1));
+            let last = atom(g);
+            list.into_iter()
+                .rev()
+                .fold(last, |cdr, car| object::cons(car, cdr))
+        }
+        3 => {
+            let entries = elements(g)
+                .into_iter()
+                .map(|key| (key, object(g, depth - 1)));
+            object::hash_table(HashTable::from_entries(entries.collect::<Vec<_>>()))
+        }
+        4 => object::set(Set::from_elements(elements(g))),
+        _ => atom(g),
+    }
+}
+
+// A program that evaluates without error in a fresh interpreter, built from
+// +, if, let, quote and list operations. Variables are only referred to
+// inside the let binding them.
+pub fn program(g: &mut Gen, depth: usize) -> Object {
+    program_in(g, depth, &mut Vec::new())
+}
+
+fn program_in(g: &mut Gen, depth: usize, scope: &mut Vec<String>) -> Object {
+    if depth == 0 || g.below(4) == 0 {
+        return match g.below(3) {
+            0 if !scope.is_empty() => object::symbol(g.choose(scope).as_str()),
+            1 => crate::list![object::symbol("quote"), object(g, 2)],
+            _ => object::fixnum(g.below(200) as isize - 100),
+        };
+    }
+    let depth = depth - 1;
+    match g.below(5) {
+        0 => {
+            let mut form = alloc::vec![object::symbol("+")];
+            for _ in 0..g.below(4) {
+                form.push(object::fixnum(g.below(200) as isize - 100));
+            }
+            form.push(crate::list![
+                object::symbol("length"),
+                program_list(g, depth, scope)
+            ]);
+            object::from_iter(form)
+        }
+        1 => {
+            let test = *g.choose(&["null?", "atom?", "symbol?"]);
+            crate::list![
+                object::symbol("if"),
+                crate::list![object::symbol(test), program_in(g, depth, scope)],
+                program_in(g, depth, scope),
+                program_in(g, depth, scope)
+            ]
+        }
+        2 => {
+            let name = format!("v{}", scope.len());
+            let value = program_in(g, depth, scope);
+            scope.push(name.clone());
+            let body = program_in(g, depth, scope);
+            scope.pop();
+            crate::list![
+                object::symbol("let"),
+                crate::list![crate::list![object::symbol(&name), value]],
+                body
+            ]
+        }
+        3 => crate::list![
+            object::symbol("cons"),
+            program_in(g, depth, scope),
+            program_in(g, depth, scope)
+        ],
+        _ => program_list(g, depth, scope),
+    }
+}
+
+// A program whose value is a proper list.
+fn program_list(g: &mut Gen, depth: usize, scope: &mut Vec<String>) -> Object {
+    let elements = (0..g.below(4)).map(|_| program_in(g, depth, scope));
+    object::cons(
+        object::symbol("list"),
+        object::from_iter(elements.collect::<Vec<_>>()),
+    )
+}
+
+// Objects one step simpler than x, simplest first: the parts of a list or
+// table, the list without each element, and each element shrunk in turn.
+pub fn shrink(x: &Object) -> Vec<Object> {
+    let mut smaller = Vec::new();
+    match &**x {
+        ObjectKind::Nil => (),
+        ObjectKind::Fixnum(n) => {
+            smaller.push(object::fixnum(0));
+            if n.unsigned_abs() > 1 {
+                smaller.push(object::fixnum(n / 2));
+            }
+            smaller.retain(|y| !matches!(&**y, ObjectKind::Fixnum(m) if m == n));
+        }
+        ObjectKind::Symbol(name) | ObjectKind::String(name) => {
+            let is_string = matches!(&**x, ObjectKind::String(_));
+            let chars: Vec<char> = name.chars().collect();
+            let min = if is_string {
+                0
+            } else {
+                1 + name.starts_with(':') as usize
+            };
+            for i in (min..chars.len()).rev() {
+                let mut shorter = chars.clone();
+                shorter.remove(i);
+                let s: String = shorter.into_iter().collect();
+                if is_string {
+                    smaller.push(object::string(&s));
+                } else if s != "nil" {
+                    smaller.push(object::symbol(&s));
+                }
+            }
+        }
+        ObjectKind::Cons(cons) => {
+            let (mut elements, last) = split_list(x);
+            smaller.push(cons.car.clone());
+            smaller.push(cons.cdr.clone());
+            for i in 0..elements.len() {
+                let mut fewer = elements.clone();
+                fewer.remove(i);
+                smaller.push(join_list(fewer, last.clone()));
+            }
+            if !matches!(&*last, ObjectKind::Nil) {
+                smaller.push(join_list(elements.clone(), object::nil()));
+            }
+            for i in 0..elements.len() {
+                for y in shrink(&elements[i]) {
+                    let original = core::mem::replace(&mut elements[i], y);
+                    smaller.push(join_list(elements.clone(), last.clone()));
+                    elements[i] = original;
+                }
+            }
+        }
+        ObjectKind::BitVector(bits) if !bits.is_empty() => {
+            let shorter = (0..bits.len() - 1).map(|i| bits.get(i) == Some(true));
+            smaller.push(object::bit_vector(BitVector::from_bits(shorter)));
+        }
+        ObjectKind::HashTable(table) => {
+            let entries = table.entries();
+            for (key, value) in &entries {
+                smaller.push(key.clone());
+                smaller.push(value.clone());
+            }
+            for i in 0..entries.len() {
+                let mut fewer = entries.clone();
+                fewer.remove(i);
+                smaller.push(object::hash_table(HashTable::from_entries(fewer)));
+            }
+        }
+        ObjectKind::Set(set) => {
+            let elements = set.elements();
+            smaller.extend(elements.iter().cloned());
+            for i in 0..elements.len() {
+                let mut fewer = elements.clone();
+                fewer.remove(i);
+                smaller.push(object::set(Set::from_elements(fewer)));
+            }
+        }
+        _ => (),
+    }
+    smaller
+}
+
+fn split_list(x: &Object) -> (Vec<Object>, Object) {
+    let mut elements = Vec::new();
+    let mut x = x.clone();
+    while let ObjectKind::Cons(cons) = &*x {
+        elements.push(cons.car.clone());
+        let cdr = cons.cdr.clone();
+        x = cdr;
+    }
+    (elements, x)
+}
+
+fn join_list(elements: Vec<Object>, last: Object) -> Object {
+    elements
+        .into_iter()
+        .rev()
+        .fold(last, |cdr, car| object::cons(car, cdr))
+}
+
+// Shrinks a case the property fails for until no simpler one fails too.
+pub fn minimize(mut x: Object, property: impl Fn(&Object) -> bool) -> Object {
+    while let Some(y) = shrink(&x).into_iter().find(|y| !property(y)) {
+        x = y;
+    }
+    x
+}
+
+// Checks property on cases generated from seeds 0..cases, and panics with
+// the smallest failing case shrinking finds.
+pub fn for_all(
+    cases: u64,
+    mut generate: impl FnMut(&mut Gen) -> Object,
+    property: impl Fn(&Object) -> bool,
+) {
+    for seed in 0..cases {
+        let x = generate(&mut Gen::new(seed));
+        if !property(&x) {
+            let x = minimize(x, &property);
+            panic!("property failed for seed {}, shrunk to {}", seed, x);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::equal::{equal, hash};
+    use super::super::interpreter::Interpreter;
+    use super::super::reader::read_from_string;
+    use super::*;
+    use alloc::string::ToString;
+
+    fn reads_back(x: &Object) -> bool {
+        match read_from_string(&x.to_string()) {
+            Ok((y, _)) => equal(x.clone(), y),
+            Err(_) => false,
+        }
+    }
+
+    #[test]
+    fn print_read_test() {
+        for_all(500, |g| object(g, 4), reads_back);
+    }
+
+    #[test]
+    fn equal_test() {
+        for_all(
+            500,
+            |g| object(g, 4),
+            |x| {
+                let (y, _) = read_from_string(&x.to_string()).unwrap();
+                equal(x.clone(), x.clone()) && hash(x) == hash(&y)
+            },
+        );
+    }
+
+    #[test]
+    fn program_test() {
+        let interpreter = Interpreter::new();
+        for_all(
+            200,
+            |g| program(g, 4),
+            |x| {
+                let (y, _) = read_from_string(&x.to_string()).unwrap();
+                match (interpreter.eval(x.clone()), interpreter.eval(y)) {
+                    (Ok(a), Ok(b)) => equal(a, b),
+                    _ => false,
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn shrink_test() {
+        let (x, _) = read_from_string("(1 (\"ab\" 40) #{k (v)} . :key)").unwrap();
+        // The smallest list holding a fixnum other than zero is (1).
+        let holds_nonzero = |x: &Object| match &**x {
+            ObjectKind::Cons(cons) => {
+                matches!(&*cons.car, ObjectKind::Fixnum(n) if *n != 0)
+            }
+            _ => false,
+        };
+        assert_eq!(minimize(x, |x| !holds_nonzero(x)).to_string(), "(1)");
+        assert!(shrink(&object::nil()).is_empty());
+        assert_eq!(
+            shrink(&object::fixnum(9))
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            ["0", "4"]
+        );
+    }
+}
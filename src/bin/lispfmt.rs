@@ -0,0 +1,89 @@
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::process;
+
+use lisp::pretty::reformat;
+
+fn usage() -> ! {
+    eprintln!("usage: lispfmt [--check] [FILE...]");
+    process::exit(2);
+}
+
+// The line and column, counting from 1, of a byte offset.
+fn position(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap().chars().count() + 1;
+    (line, column)
+}
+
+fn formatted(name: &str, source: &str) -> Option<String> {
+    reformat(source)
+        .map_err(|range| {
+            let (line, column) = position(source, range.start);
+            eprintln!(
+                "{}:{}:{}: cannot format unreadable syntax",
+                name, line, column
+            );
+        })
+        .ok()
+}
+
+// Formats the files in place, or standard input to standard output. With
+// --check nothing is written and the exit status is 1 if anything would
+// change.
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (check, files) = match args.split_first() {
+        Some((flag, files)) if flag == "--check" => (true, files),
+        _ => (false, &args[..]),
+    };
+    if files.iter().any(|file| file.starts_with("--")) {
+        usage();
+    }
+
+    let mut ok = true;
+    if files.is_empty() {
+        let mut source = String::new();
+        if let Err(e) = io::stdin().read_to_string(&mut source) {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+        match formatted("<stdin>", &source) {
+            Some(formatted) if check => ok = formatted == source,
+            Some(formatted) => {
+                print!("{}", formatted);
+                io::stdout().flush().unwrap();
+            }
+            None => ok = false,
+        }
+    }
+    for name in files {
+        let source = match fs::read_to_string(name) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("Cannot read {}: {}", name, e);
+                ok = false;
+                continue;
+            }
+        };
+        match formatted(name, &source) {
+            Some(formatted) if formatted == source => (),
+            Some(_) if check => {
+                println!("Would reformat {}", name);
+                ok = false;
+            }
+            Some(formatted) => {
+                if let Err(e) = fs::write(name, formatted) {
+                    eprintln!("Cannot write {}: {}", name, e);
+                    ok = false;
+                }
+            }
+            None => ok = false,
+        }
+    }
+    if !ok {
+        process::exit(1);
+    }
+}
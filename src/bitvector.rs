@@ -0,0 +1,283 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::env::Env;
+use super::error::RuntimeError;
+use super::eval::{check_num_args, check_num_args_range, EvalResult};
+use super::object::{self, Object, ObjectKind, ObjectType};
+use super::rc::{Rc, RefCell};
+
+// A fixed-length vector of bits packed into words, written #*1010. Unlike
+// lists it can be updated in place with bit-set!.
+pub struct BitVector {
+    len: usize,
+    words: RefCell<Vec<u64>>,
+}
+
+const WORD_BITS: usize = 64;
+
+impl BitVector {
+    pub fn new(len: usize, bit: bool) -> Self {
+        let word = if bit { u64::MAX } else { 0 };
+        let bit_vector = Self {
+            len,
+            words: RefCell::new(vec![word; len.div_ceil(WORD_BITS)]),
+        };
+        bit_vector.clear_unused();
+        bit_vector
+    }
+
+    pub fn from_bits(bits: impl IntoIterator<Item = bool>) -> Self {
+        let mut words = Vec::new();
+        let mut len = 0;
+        for bit in bits {
+            if len % WORD_BITS == 0 {
+                words.push(0);
+            }
+            if bit {
+                words[len / WORD_BITS] |= 1 << (len % WORD_BITS);
+            }
+            len += 1;
+        }
+        Self {
+            len,
+            words: RefCell::new(words),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, i: usize) -> Option<bool> {
+        if i >= self.len {
+            return None;
+        }
+        Some(self.words.borrow()[i / WORD_BITS] & (1 << (i % WORD_BITS)) != 0)
+    }
+
+    // Returns false if i is out of range.
+    pub fn set(&self, i: usize, bit: bool) -> bool {
+        if i >= self.len {
+            return false;
+        }
+        let mask = 1 << (i % WORD_BITS);
+        let word = &mut self.words.borrow_mut()[i / WORD_BITS];
+        if bit {
+            *word |= mask;
+        } else {
+            *word &= !mask;
+        }
+        true
+    }
+
+    pub fn count(&self) -> usize {
+        self.words
+            .borrow()
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    // Combines the bits of two vectors of the same length; None if their
+    // lengths differ.
+    pub fn zip_with(&self, other: &BitVector, f: impl Fn(u64, u64) -> u64) -> Option<BitVector> {
+        if self.len != other.len {
+            return None;
+        }
+        let words = self
+            .words
+            .borrow()
+            .iter()
+            .zip(other.words.borrow().iter())
+            .map(|(x, y)| f(*x, *y))
+            .collect();
+        let bit_vector = BitVector {
+            len: self.len,
+            words: RefCell::new(words),
+        };
+        bit_vector.clear_unused();
+        Some(bit_vector)
+    }
+
+    pub fn not(&self) -> BitVector {
+        self.zip_with(self, |x, _| !x).unwrap()
+    }
+
+    // The bits past len in the last word are kept at 0, so that words can
+    // be compared, counted and hashed as they are.
+    fn clear_unused(&self) {
+        let used = self.len % WORD_BITS;
+        if used != 0 {
+            if let Some(last) = self.words.borrow_mut().last_mut() {
+                *last &= (1 << used) - 1;
+            }
+        }
+    }
+
+    pub fn words(&self) -> Vec<u64> {
+        self.words.borrow().clone()
+    }
+}
+
+impl fmt::Display for BitVector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#*")?;
+        for i in 0..self.len {
+            write!(f, "{}", if self.get(i) == Some(true) { 1 } else { 0 })?;
+        }
+        Ok(())
+    }
+}
+
+pub fn init(env: &mut Env) {
+    env.define_builtin(
+        "make-bit-vector",
+        "length &rest initial-bit",
+        make_bit_vector,
+    );
+    env.define_builtin("bit-vector?", "x", is_bit_vector);
+    env.define_builtin("bit-vector-length", "bit-vector", bit_vector_length);
+    env.define_builtin("bit-ref", "bit-vector index", bit_ref);
+    env.define_builtin("bit-set!", "bit-vector index bit", bit_set);
+    env.define_builtin("bit-count", "bit-vector", bit_count);
+    env.define_builtin("bit-and", "x y", bit_and);
+    env.define_builtin("bit-or", "x y", bit_or);
+    env.define_builtin("bit-xor", "x y", bit_xor);
+    env.define_builtin("bit-not", "x", bit_not);
+}
+
+fn bit_vector(x: &Object) -> Result<&BitVector, RuntimeError> {
+    match &**x {
+        ObjectKind::BitVector(bit_vector) => Ok(bit_vector),
+        _ => Err(RuntimeError::MismatchType(
+            Rc::clone(x),
+            ObjectType::BitVector,
+        )),
+    }
+}
+
+fn index(x: &Object) -> Result<usize, RuntimeError> {
+    match **x {
+        ObjectKind::Fixnum(n) if n >= 0 => Ok(n as usize),
+        _ => Err(RuntimeError::MismatchType(Rc::clone(x), ObjectType::Number)),
+    }
+}
+
+fn bit(x: &Object) -> Result<bool, RuntimeError> {
+    match **x {
+        ObjectKind::Fixnum(0) => Ok(false),
+        ObjectKind::Fixnum(1) => Ok(true),
+        _ => Err(RuntimeError::MismatchType(Rc::clone(x), ObjectType::Bit)),
+    }
+}
+
+fn make_bit_vector(args: &[Object]) -> EvalResult {
+    check_num_args_range(args, 1, 2)?;
+    let initial = match args.get(1) {
+        Some(x) => bit(x)?,
+        None => false,
+    };
+    Ok(object::bit_vector(BitVector::new(
+        index(&args[0])?,
+        initial,
+    )))
+}
+
+fn boolean(b: bool) -> Object {
+    if b {
+        object::symbol("t")
+    } else {
+        object::nil()
+    }
+}
+
+fn is_bit_vector(args: &[Object]) -> EvalResult {
+    check_num_args(args, 1)?;
+    Ok(boolean(ObjectType::BitVector.contains(&args[0])))
+}
+
+fn bit_vector_length(args: &[Object]) -> EvalResult {
+    check_num_args(args, 1)?;
+    Ok(object::fixnum(bit_vector(&args[0])?.len() as isize))
+}
+
+fn bit_ref(args: &[Object]) -> EvalResult {
+    check_num_args(args, 2)?;
+    let bits = bit_vector(&args[0])?;
+    let i = index(&args[1])?;
+    match bits.get(i) {
+        Some(bit) => Ok(object::fixnum(bit as isize)),
+        None => Err(RuntimeError::IndexOutOfRange(i, bits.len())),
+    }
+}
+
+fn bit_set(args: &[Object]) -> EvalResult {
+    check_num_args(args, 3)?;
+    let bits = bit_vector(&args[0])?;
+    let i = index(&args[1])?;
+    if bits.set(i, bit(&args[2])?) {
+        Ok(Rc::clone(&args[2]))
+    } else {
+        Err(RuntimeError::IndexOutOfRange(i, bits.len()))
+    }
+}
+
+fn bit_count(args: &[Object]) -> EvalResult {
+    check_num_args(args, 1)?;
+    Ok(object::fixnum(bit_vector(&args[0])?.count() as isize))
+}
+
+fn combine(args: &[Object], f: fn(u64, u64) -> u64) -> EvalResult {
+    check_num_args(args, 2)?;
+    let (x, y) = (bit_vector(&args[0])?, bit_vector(&args[1])?);
+    x.zip_with(y, f)
+        .map(object::bit_vector)
+        .ok_or(RuntimeError::LengthMismatch(x.len(), y.len()))
+}
+
+fn bit_and(args: &[Object]) -> EvalResult {
+    combine(args, |x, y| x & y)
+}
+
+fn bit_or(args: &[Object]) -> EvalResult {
+    combine(args, |x, y| x | y)
+}
+
+fn bit_xor(args: &[Object]) -> EvalResult {
+    combine(args, |x, y| x ^ y)
+}
+
+fn bit_not(args: &[Object]) -> EvalResult {
+    check_num_args(args, 1)?;
+    Ok(object::bit_vector(bit_vector(&args[0])?.not()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn bits_test() {
+        let bits = BitVector::new(70, true);
+        assert_eq!(bits.count(), 70);
+        assert!(bits.set(65, false));
+        assert!(!bits.set(70, false));
+        assert_eq!(bits.get(65), Some(false));
+        assert_eq!(bits.get(70), None);
+        assert_eq!(bits.not().count(), 1);
+
+        let x = BitVector::from_bits([true, false, true, false]);
+        let y = BitVector::from_bits([true, true, false, false]);
+        assert_eq!(x.to_string(), "#*1010");
+        assert_eq!(x.zip_with(&y, |x, y| x ^ y).unwrap().to_string(), "#*0110");
+        assert!(x.zip_with(&bits, |x, y| x & y).is_none());
+        assert_eq!(BitVector::new(0, false).to_string(), "#*");
+    }
+}
@@ -0,0 +1,132 @@
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+use super::interpreter::Interpreter;
+
+// C-compatible embedding API. Build a shared library with
+// `cargo rustc --release --features capi --crate-type cdylib`.
+
+pub struct LispResult {
+    error: bool,
+    text: CString,
+}
+
+impl LispResult {
+    fn new(error: bool, text: String) -> Self {
+        let text = CString::new(text.replace('\0', "")).unwrap();
+        Self { error, text }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn lisp_new() -> *mut Interpreter {
+    Box::into_raw(Box::new(Interpreter::new()))
+}
+
+/// # Safety
+///
+/// `interpreter` must come from `lisp_new` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn lisp_free(interpreter: *mut Interpreter) {
+    if !interpreter.is_null() {
+        drop(Box::from_raw(interpreter));
+    }
+}
+
+/// # Safety
+///
+/// `interpreter` must come from `lisp_new` and `source` must be a valid
+/// NUL-terminated string. The returned result must be released with
+/// `lisp_result_free`.
+#[no_mangle]
+pub unsafe extern "C" fn lisp_eval_cstr(
+    interpreter: *mut Interpreter,
+    source: *const c_char,
+) -> *mut LispResult {
+    if interpreter.is_null() || source.is_null() {
+        return ptr::null_mut();
+    }
+    let result = match CStr::from_ptr(source).to_str() {
+        Ok(source) => match (*interpreter).eval_str(source) {
+            Ok(value) => LispResult::new(false, value.to_string()),
+            Err(e) => LispResult::new(true, e.to_string()),
+        },
+        Err(e) => LispResult::new(true, e.to_string()),
+    };
+    Box::into_raw(Box::new(result))
+}
+
+/// # Safety
+///
+/// `result` must come from `lisp_eval_cstr`. The returned string is owned by
+/// the result and is valid until `lisp_result_free` is called.
+#[no_mangle]
+pub unsafe extern "C" fn lisp_result_string(result: *const LispResult) -> *const c_char {
+    if result.is_null() {
+        return ptr::null();
+    }
+    (*result).text.as_ptr()
+}
+
+/// # Safety
+///
+/// `result` must come from `lisp_eval_cstr`.
+#[no_mangle]
+pub unsafe extern "C" fn lisp_result_is_error(result: *const LispResult) -> c_int {
+    if result.is_null() {
+        return 1;
+    }
+    (*result).error as c_int
+}
+
+/// # Safety
+///
+/// `result` must come from `lisp_eval_cstr` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn lisp_result_free(result: *mut LispResult) {
+    if !result.is_null() {
+        drop(Box::from_raw(result));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe fn eval(interpreter: *mut Interpreter, source: &str) -> (bool, String) {
+        let source = CString::new(source).unwrap();
+        let result = lisp_eval_cstr(interpreter, source.as_ptr());
+        let text = CStr::from_ptr(lisp_result_string(result))
+            .to_str()
+            .unwrap()
+            .to_string();
+        let error = lisp_result_is_error(result) != 0;
+        lisp_result_free(result);
+        (error, text)
+    }
+
+    #[test]
+    fn eval_test() {
+        unsafe {
+            let interpreter = lisp_new();
+            assert_eq!(eval(interpreter, "(define x 2)"), (false, "2".to_string()));
+            assert_eq!(eval(interpreter, "(+ x 1)"), (false, "3".to_string()));
+            assert_eq!(
+                eval(interpreter, "y"),
+                (true, "Unbound variable: y".to_string())
+            );
+            lisp_free(interpreter);
+        }
+    }
+
+    #[test]
+    fn null_test() {
+        unsafe {
+            assert!(lisp_eval_cstr(ptr::null_mut(), ptr::null()).is_null());
+            assert!(lisp_result_string(ptr::null()).is_null());
+            lisp_free(ptr::null_mut());
+            lisp_result_free(ptr::null_mut());
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Instant;
+
+use super::error::RuntimeError;
+use super::object::{self, Object, ObjectKind};
+use super::rc::Rc;
+use super::timer;
+
+// A queue that interpreters on different threads can share. Objects aren't
+// shared between interpreters, so what's sent is copied out into a Datum
+// and copied back in by the receiver.
+#[derive(Clone, Default)]
+pub struct Channel {
+    inner: Arc<(Mutex<VecDeque<Datum>>, Condvar)>,
+}
+
+pub enum Datum {
+    Nil,
+    Fixnum(isize),
+    Symbol(String),
+    String(String),
+    Cons(Box<Datum>, Box<Datum>),
+    Channel(Channel),
+}
+
+impl Datum {
+    pub fn from_object(x: &Object) -> Result<Datum, RuntimeError> {
+        let datum = match &**x {
+            ObjectKind::Nil => Datum::Nil,
+            ObjectKind::Fixnum(n) => Datum::Fixnum(*n),
+            ObjectKind::Symbol(s) => Datum::Symbol(s.clone()),
+            ObjectKind::String(s) => Datum::String(s.clone()),
+            ObjectKind::Cons(cons) => Datum::Cons(
+                Box::new(Datum::from_object(&cons.car)?),
+                Box::new(Datum::from_object(&cons.cdr)?),
+            ),
+            ObjectKind::Channel(channel) => Datum::Channel(channel.clone()),
+            _ => return Err(RuntimeError::NotTransferable(Rc::clone(x))),
+        };
+        Ok(datum)
+    }
+
+    pub fn to_object(&self) -> Object {
+        match self {
+            Datum::Nil => object::nil(),
+            Datum::Fixnum(n) => object::fixnum(*n),
+            Datum::Symbol(s) => object::symbol(s),
+            Datum::String(s) => object::string(s),
+            Datum::Cons(car, cdr) => object::cons(car.to_object(), cdr.to_object()),
+            Datum::Channel(channel) => object::channel(channel.clone()),
+        }
+    }
+}
+
+impl Channel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn send(&self, x: &Object) -> Result<(), RuntimeError> {
+        let datum = Datum::from_object(x)?;
+        let (queue, ready) = &*self.inner;
+        queue.lock().unwrap().push_back(datum);
+        ready.notify_one();
+        Ok(())
+    }
+
+    pub fn try_recv(&self) -> Option<Object> {
+        let (queue, _) = &*self.inner;
+        let datum = queue.lock().unwrap().pop_front();
+        datum.map(|datum| datum.to_object())
+    }
+
+    // Waits for a value, or until a with-timeout around it runs out.
+    pub fn recv(&self) -> Result<Object, RuntimeError> {
+        loop {
+            timer::check()?;
+            let (queue, ready) = &*self.inner;
+            let mut queue = queue.lock().unwrap();
+            if let Some(datum) = queue.pop_front() {
+                drop(queue);
+                return Ok(datum.to_object());
+            }
+            match timer::next_deadline() {
+                Some(at) => {
+                    let timeout = at.saturating_duration_since(Instant::now());
+                    drop(ready.wait_timeout(queue, timeout).unwrap());
+                }
+                None => drop(ready.wait(queue).unwrap()),
+            }
+        }
+    }
+
+    pub fn is_same(&self, other: &Channel) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+
+    pub fn address(&self) -> usize {
+        Arc::as_ptr(&self.inner) as *const u8 as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::equal::equal;
+
+    #[test]
+    fn datum_test() {
+        let x = crate::list![
+            object::fixnum(1),
+            object::string("a"),
+            object::cons(object::symbol("b"), object::nil())
+        ];
+        assert!(equal(
+            Datum::from_object(&x).unwrap().to_object(),
+            Rc::clone(&x)
+        ));
+        let port = object::port(crate::port::Port::output_string());
+        assert!(matches!(
+            Datum::from_object(&object::cons(object::fixnum(1), port)),
+            Err(RuntimeError::NotTransferable(_))
+        ));
+    }
+
+    #[test]
+    fn thread_test() {
+        let channel = Channel::new();
+        let sender = channel.clone();
+        std::thread::spawn(move || {
+            sender
+                .send(&crate::list![object::fixnum(1), object::fixnum(2)])
+                .unwrap();
+        });
+        let x = channel.recv().unwrap();
+        assert_eq!(x.to_string(), "(1 2)");
+    }
+}
@@ -0,0 +1,7 @@
+// Without the `std` feature there is no HashMap, so fall back to the
+// ordered collections from alloc.
+
+#[cfg(not(feature = "std"))]
+pub use alloc::collections::{BTreeMap as Map, BTreeSet as Set};
+#[cfg(feature = "std")]
+pub use std::collections::{HashMap as Map, HashSet as Set};
@@ -0,0 +1,138 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use super::collections::Set;
+use super::env::Env;
+use super::eval::SPECIAL_FORMS;
+use super::object::{Object, ObjectKind};
+use super::walk::{Visitor, Walk};
+
+// What a completion names, in the order candidates of the same length are
+// listed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Kind {
+    SpecialForm,
+    Macro,
+    Function,
+    Variable,
+    Keyword,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    pub name: String,
+    pub kind: Kind,
+    // The docstring of a function or macro written in Lisp.
+    pub doc: Option<String>,
+}
+
+// The keywords special forms take, which appear in no function body.
+const KEYWORDS: &[&str] = &[":direction", ":input", ":output", ":export", ":import"];
+
+struct Keywords<'a>(&'a mut Set<String>);
+
+impl Visitor for Keywords<'_> {
+    fn visit_atom(&mut self, atom: &Object) {
+        if let ObjectKind::Symbol(name) = &**atom {
+            if name.starts_with(':') {
+                self.0.insert(name.clone());
+            }
+        }
+    }
+}
+
+// The names visible from env that start with prefix: an exact match first,
+// then the shortest. Internal names, which start with %, come last unless
+// the prefix asks for them.
+pub fn complete(prefix: &str, env: &Env) -> Vec<Candidate> {
+    let mut candidates = Vec::new();
+    let mut found: Set<String> = KEYWORDS.iter().map(ToString::to_string).collect();
+    for name in SPECIAL_FORMS {
+        candidates.push(Candidate {
+            name: name.to_string(),
+            kind: Kind::SpecialForm,
+            doc: None,
+        });
+    }
+    for (name, value) in env.visible_bindings() {
+        let (kind, doc) = match &*value {
+            ObjectKind::Macro(closure) => (Kind::Macro, closure.doc.clone()),
+            ObjectKind::Closure(closure) => (Kind::Function, closure.doc.clone()),
+            ObjectKind::Func(_) => (Kind::Function, None),
+            _ => (Kind::Variable, None),
+        };
+        if let ObjectKind::Macro(closure) | ObjectKind::Closure(closure) = &*value {
+            for form in &closure.body {
+                form.walk(&mut Keywords(&mut found));
+            }
+        }
+        candidates.push(Candidate { name, kind, doc });
+    }
+    candidates.extend(found.into_iter().map(|name| Candidate {
+        name,
+        kind: Kind::Keyword,
+        doc: None,
+    }));
+
+    candidates.retain(|candidate| candidate.name.starts_with(prefix));
+    candidates.sort_by(|a, b| {
+        let rank = |c: &Candidate| {
+            (
+                c.name != prefix,
+                c.name.starts_with('%') && !prefix.starts_with('%'),
+                c.name.len(),
+            )
+        };
+        rank(a)
+            .cmp(&rank(b))
+            .then_with(|| a.kind.cmp(&b.kind))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    // A special form redefined as a function is still the special form.
+    candidates.dedup_by(|a, b| a.name == b.name);
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load;
+    use crate::rc::Rc;
+
+    fn names(prefix: &str, env: &Env) -> Vec<String> {
+        complete(prefix, env).into_iter().map(|c| c.name).collect()
+    }
+
+    #[test]
+    fn complete_test() {
+        let env = Env::global_env();
+        load::load_str(
+            "(defun string-pad (s &rest options) \"Pads S.\" (getf options :width))",
+            Rc::clone(&env),
+        )
+        .unwrap();
+
+        let candidates = complete("string-p", &env.borrow());
+        assert_eq!(
+            candidates,
+            alloc::vec![Candidate {
+                name: "string-pad".to_string(),
+                kind: Kind::Function,
+                doc: Some("Pads S.".to_string()),
+            }]
+        );
+        assert_eq!(names("car", &env.borrow())[0], "car");
+        assert_eq!(complete("if", &env.borrow())[0].kind, Kind::SpecialForm);
+        assert_eq!(complete("when", &env.borrow())[0].kind, Kind::Macro);
+        assert!(names(":w", &env.borrow()).contains(&":width".to_string()));
+        assert!(names(":d", &env.borrow()).contains(&":direction".to_string()));
+
+        // Internal names come after the others.
+        let read = names("read", &env.borrow());
+        assert_eq!(read[0], "read");
+        assert!(names("", &env.borrow())
+            .iter()
+            .skip_while(|name| !name.starts_with('%'))
+            .all(|name| name.starts_with('%')));
+    }
+}
@@ -0,0 +1,328 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::collections::Map;
+use super::error::ConvertError;
+use super::object::{self, Object, ObjectKind, ObjectType};
+use super::rc::Rc;
+
+pub trait FromObject: Sized {
+    fn from_object(obj: &Object) -> Result<Self, ConvertError>;
+}
+
+pub trait ToObject {
+    fn to_object(&self) -> Object;
+}
+
+impl FromObject for Object {
+    fn from_object(obj: &Object) -> Result<Self, ConvertError> {
+        Ok(Rc::clone(obj))
+    }
+}
+
+impl ToObject for Object {
+    fn to_object(&self) -> Object {
+        Rc::clone(self)
+    }
+}
+
+impl FromObject for isize {
+    fn from_object(obj: &Object) -> Result<Self, ConvertError> {
+        match **obj {
+            ObjectKind::Fixnum(n) => Ok(n),
+            _ => Err(ConvertError::MismatchType(
+                Rc::clone(obj),
+                ObjectType::Number,
+            )),
+        }
+    }
+}
+
+impl ToObject for isize {
+    fn to_object(&self) -> Object {
+        object::fixnum(*self)
+    }
+}
+
+impl FromObject for bool {
+    fn from_object(obj: &Object) -> Result<Self, ConvertError> {
+        Ok(!matches!(**obj, ObjectKind::Nil))
+    }
+}
+
+impl ToObject for bool {
+    fn to_object(&self) -> Object {
+        if *self {
+            object::symbol("t")
+        } else {
+            object::nil()
+        }
+    }
+}
+
+impl FromObject for String {
+    fn from_object(obj: &Object) -> Result<Self, ConvertError> {
+        match &**obj {
+            ObjectKind::String(s) | ObjectKind::Symbol(s) => Ok(s.clone()),
+            _ => Err(ConvertError::MismatchType(
+                Rc::clone(obj),
+                ObjectType::String,
+            )),
+        }
+    }
+}
+
+impl ToObject for String {
+    fn to_object(&self) -> Object {
+        object::string(self)
+    }
+}
+
+impl ToObject for &str {
+    fn to_object(&self) -> Object {
+        object::string(self)
+    }
+}
+
+impl<T: FromObject> FromObject for Vec<T> {
+    fn from_object(obj: &Object) -> Result<Self, ConvertError> {
+        match obj.as_proper_list() {
+            Some(elements) => elements.iter().map(T::from_object).collect(),
+            None => Err(ConvertError::MismatchType(Rc::clone(obj), ObjectType::List)),
+        }
+    }
+}
+
+impl<T: ToObject> ToObject for Vec<T> {
+    fn to_object(&self) -> Object {
+        object::from_iter(self.iter().map(ToObject::to_object))
+    }
+}
+
+// Parses a form such as `(point :x 1 :y 2)` for typed field extraction.
+pub struct FormParser {
+    name: String,
+    fields: Map<String, Object>,
+}
+
+impl FormParser {
+    pub fn new(form: &Object, name: &str) -> Result<Self, ConvertError> {
+        let elements = match form.as_proper_list() {
+            Some(elements) => elements,
+            None => return Err(ConvertError::MalformedForm(Rc::clone(form))),
+        };
+        match elements.first().map(|head| &**head) {
+            Some(ObjectKind::Symbol(head)) if head == name => (),
+            _ => return Err(ConvertError::WrongHead(name.to_string(), Rc::clone(form))),
+        }
+
+        let mut fields = Map::new();
+        for pair in elements[1..].chunks(2) {
+            let key = match (&*pair[0], pair.get(1)) {
+                (ObjectKind::Symbol(key), Some(_)) if key.starts_with(':') => key[1..].to_string(),
+                _ => return Err(ConvertError::MalformedForm(Rc::clone(form))),
+            };
+            fields.insert(key, Rc::clone(&pair[1]));
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            fields,
+        })
+    }
+
+    fn convert<T: FromObject>(&self, field: &str, value: &Object) -> Result<T, ConvertError> {
+        T::from_object(value)
+            .map_err(|e| ConvertError::InField(self.name.clone(), field.to_string(), Box::new(e)))
+    }
+
+    pub fn field<T: FromObject>(&self, field: &str) -> Result<T, ConvertError> {
+        match self.fields.get(field) {
+            Some(value) => self.convert(field, value),
+            None => Err(ConvertError::MissingField(
+                self.name.clone(),
+                field.to_string(),
+            )),
+        }
+    }
+
+    pub fn optional<T: FromObject>(&self, field: &str) -> Result<Option<T>, ConvertError> {
+        match self.fields.get(field) {
+            Some(value) => self.convert(field, value).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    // Rejects fields that weren't extracted, catching typos in the source.
+    pub fn finish(self, known: &[&str]) -> Result<(), ConvertError> {
+        let mut unknown: Vec<&String> = self
+            .fields
+            .keys()
+            .filter(|key| !known.contains(&key.as_str()))
+            .collect();
+        unknown.sort();
+        match unknown.first() {
+            Some(field) => Err(ConvertError::UnknownField(
+                self.name.clone(),
+                field.to_string(),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+// Builds a form such as `(point :x 1 :y 2)` from typed fields.
+pub struct FormBuilder {
+    elements: Vec<Object>,
+}
+
+impl FormBuilder {
+    pub fn new(name: &str) -> Self {
+        Self {
+            elements: vec![object::symbol(name)],
+        }
+    }
+
+    pub fn field(mut self, field: &str, value: impl ToObject) -> Self {
+        self.elements.push(object::symbol(&format!(":{}", field)));
+        self.elements.push(value.to_object());
+        self
+    }
+
+    pub fn optional<T: ToObject>(self, field: &str, value: Option<T>) -> Self {
+        match value {
+            Some(value) => self.field(field, value),
+            None => self,
+        }
+    }
+
+    pub fn build(self) -> Object {
+        self.elements.to_object()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::reader::read_from_string;
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Point {
+        x: isize,
+        y: isize,
+        label: Option<String>,
+    }
+
+    impl FromObject for Point {
+        fn from_object(obj: &Object) -> Result<Self, ConvertError> {
+            let form = FormParser::new(obj, "point")?;
+            let point = Point {
+                x: form.field("x")?,
+                y: form.field("y")?,
+                label: form.optional("label")?,
+            };
+            form.finish(&["x", "y", "label"])?;
+            Ok(point)
+        }
+    }
+
+    impl ToObject for Point {
+        fn to_object(&self) -> Object {
+            FormBuilder::new("point")
+                .field("x", self.x)
+                .field("y", self.y)
+                .optional("label", self.label.clone())
+                .build()
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Polygon {
+        points: Vec<Point>,
+    }
+
+    impl FromObject for Polygon {
+        fn from_object(obj: &Object) -> Result<Self, ConvertError> {
+            let form = FormParser::new(obj, "polygon")?;
+            Ok(Polygon {
+                points: form.field("points")?,
+            })
+        }
+    }
+
+    fn read(input: &str) -> Object {
+        read_from_string(input).unwrap().0
+    }
+
+    #[test]
+    fn from_object_test() {
+        assert_eq!(
+            Point::from_object(&read("(point :x 1 :y 2)")).unwrap(),
+            Point {
+                x: 1,
+                y: 2,
+                label: None
+            }
+        );
+        assert_eq!(
+            Point::from_object(&read("(point :y 2 :label \"origin\" :x 1)")).unwrap(),
+            Point {
+                x: 1,
+                y: 2,
+                label: Some("origin".to_string())
+            }
+        );
+        assert_eq!(
+            Polygon::from_object(&read("(polygon :points ((point :x 0 :y 0)))")).unwrap(),
+            Polygon {
+                points: vec![Point {
+                    x: 0,
+                    y: 0,
+                    label: None
+                }]
+            }
+        );
+    }
+
+    #[test]
+    fn to_object_test() {
+        let point = Point {
+            x: 1,
+            y: 2,
+            label: None,
+        };
+        assert_eq!(point.to_object(), read("(point :x 1 :y 2)"));
+        assert_eq!(Point::from_object(&point.to_object()).unwrap(), point);
+    }
+
+    #[test]
+    fn error_test() {
+        let message = |input: &str| Point::from_object(&read(input)).unwrap_err().to_string();
+        assert_eq!(
+            message("(line :x 1 :y 2)"),
+            "Expected a (point ...) form, but got (line :x 1 :y 2)"
+        );
+        assert_eq!(message("(point :x 1)"), "Missing field :y in point");
+        assert_eq!(
+            message("(point :x 1 :y 2 :z 3)"),
+            "Unknown field :z in point"
+        );
+        assert_eq!(
+            message("(point :x 1 :y)"),
+            "Malformed form: (point :x 1 :y)"
+        );
+        assert_eq!(
+            message("(point :x a :y 2)"),
+            "In field :x of point: The value a is not of type Number"
+        );
+        assert_eq!(
+            Polygon::from_object(&read("(polygon :points ((point :x 0)))"))
+                .unwrap_err()
+                .to_string(),
+            "In field :points of polygon: Missing field :y in point"
+        );
+    }
+}
@@ -0,0 +1,323 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use std::panic;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+use super::accounting::{self, Tally};
+use super::error::RuntimeError;
+use super::eval::EvalResult;
+use super::future::{self, BoxFuture};
+use super::object::{self, Object};
+use super::timer::{self, Deadline};
+
+// The evaluator is recursive, so stopping in the middle of a form means
+// keeping its Rust stack around. A coroutine runs on a thread of its own and
+// hands control back and forth with whoever resumes it, so only one side
+// ever runs at a time. Objects cross between the threads, which is why
+// coroutines need the sync feature.
+
+enum Message {
+    // What suspend returns, the number of evaluation steps allowed before
+    // pausing, None running until the next suspend, and the deadlines of
+    // the with-timeout forms around the resume.
+    Resume(EvalResult, Option<usize>, Vec<Deadline>),
+    Cancel,
+}
+
+pub enum Event {
+    Yielded(Object),
+    // The step budget ran out.
+    Paused,
+    // An async builtin was called; resume with what its future returns.
+    Awaiting(BoxFuture),
+    Returned(EvalResult),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    // Suspends at each yield.
+    Generator,
+    // Also hands the futures of async builtins to its owner.
+    Evaluation,
+}
+
+struct Current {
+    to_owner: Sender<(Event, Tally)>,
+    from_owner: Receiver<Message>,
+    budget: Option<usize>,
+    kind: Kind,
+    cancelled: bool,
+    // Where accounting stood when control last came in.
+    resumed_at: Tally,
+}
+
+std::thread_local! {
+    static CURRENT: RefCell<Option<Current>> = const { RefCell::new(None) };
+    static DEFERRED: RefCell<Vec<Box<dyn FnOnce()>>> = const { RefCell::new(Vec::new()) };
+}
+
+// Work that has to touch the environment but may be triggered while it's
+// borrowed, such as cancelling a generator when it's dropped. It runs at the
+// next evaluation step, or before control leaves this thread.
+pub fn defer(f: impl FnOnce() + 'static) {
+    DEFERRED.with(|deferred| deferred.borrow_mut().push(Box::new(f)));
+}
+
+fn run_deferred() {
+    while let Some(f) = DEFERRED.with(|deferred| deferred.borrow_mut().pop()) {
+        f();
+    }
+}
+
+const STACK_SIZE: usize = 8 * 1024 * 1024;
+
+pub struct Coroutine {
+    to: Sender<Message>,
+    from: Receiver<(Event, Tally)>,
+    thread: Option<JoinHandle<()>>,
+    finished: bool,
+}
+
+impl Coroutine {
+    pub fn spawn(kind: Kind, f: impl FnOnce() -> EvalResult + Send + 'static) -> Self {
+        let (to, from_owner) = channel();
+        let (to_owner, from) = channel();
+        // Hash-consing on the new thread is as set where it was spawned, and
+        // keywords are interned in the same table.
+        let on = object::hash_consing();
+        let keywords = object::keywords();
+        let f = move || object::with_keywords(keywords, || object::with_hash_consing(on, f).0);
+        let thread = thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(move || {
+                let budget = match from_owner.recv() {
+                    Ok(Message::Resume(_, budget, deadlines)) => {
+                        timer::resume_with(deadlines);
+                        budget
+                    }
+                    _ => {
+                        drop(f);
+                        return run_deferred();
+                    }
+                };
+                let to_owner_for_result = to_owner.clone();
+                CURRENT.with(|current| {
+                    *current.borrow_mut() = Some(Current {
+                        to_owner,
+                        from_owner,
+                        budget,
+                        kind,
+                        cancelled: false,
+                        resumed_at: accounting::current(),
+                    })
+                });
+                let result = f();
+                run_deferred();
+                let tally = CURRENT.with(|current| {
+                    let current = current.borrow_mut().take().unwrap();
+                    accounting::current().since(&current.resumed_at)
+                });
+                let _ = to_owner_for_result.send((Event::Returned(result), tally));
+            })
+            .expect("failed to spawn a coroutine thread");
+        Self {
+            to,
+            from,
+            thread: Some(thread),
+            finished: false,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    // Runs the coroutine until it suspends, pauses or returns. The work it
+    // did is added to the accounting of this thread.
+    pub fn resume(&mut self, value: EvalResult, budget: Option<usize>) -> Event {
+        assert!(!self.finished, "resumed a finished coroutine");
+        let deadlines = timer::deadlines();
+        if self
+            .to
+            .send(Message::Resume(value, budget, deadlines))
+            .is_err()
+        {
+            self.propagate_panic();
+        }
+        let (event, tally) = match self.from.recv() {
+            Ok(received) => received,
+            Err(_) => self.propagate_panic(),
+        };
+        accounting::add(&tally);
+        if let Event::Returned(_) = event {
+            self.finished = true;
+            self.join();
+        }
+        event
+    }
+
+    fn join(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            if let Err(payload) = thread.join() {
+                panic::resume_unwind(payload);
+            }
+        }
+    }
+
+    fn propagate_panic(&mut self) -> ! {
+        self.finished = true;
+        self.join();
+        panic!("coroutine thread exited without returning");
+    }
+}
+
+// A coroutine dropped before it returns is cancelled: every evaluation step
+// in it fails with Cancelled until it unwinds.
+impl Drop for Coroutine {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        if self.to.send(Message::Cancel).is_ok() {
+            while let Ok((event, _)) = self.from.recv() {
+                if let Event::Returned(_) = event {
+                    break;
+                }
+            }
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+pub fn in_coroutine() -> bool {
+    CURRENT.with(|current| current.borrow().is_some())
+}
+
+// Hands value to whoever resumed this coroutine and waits to be resumed.
+// Outside of a coroutine there's no one to hand it to.
+pub fn suspend(value: Object) -> Result<Object, RuntimeError> {
+    if current_kind() != Some(Kind::Generator) {
+        return Err(RuntimeError::NotInCoroutine);
+    }
+    handoff(Event::Yielded(value))?.ok_or(RuntimeError::NotInCoroutine)
+}
+
+// Waits for what an async builtin returns. Inside eval_async the future is
+// handed to the executor polling the evaluation; anywhere else it's run to
+// completion on this thread.
+pub fn await_future(future: BoxFuture) -> EvalResult {
+    if current_kind() != Some(Kind::Evaluation) {
+        return future::block_on(future);
+    }
+    handoff(Event::Awaiting(future))?.ok_or(RuntimeError::NotInCoroutine)
+}
+
+fn current_kind() -> Option<Kind> {
+    CURRENT.with(|current| current.borrow().as_ref().map(|c| c.kind))
+}
+
+// Called for each evaluation step; pauses when the step budget is used up.
+pub fn step() -> Result<(), RuntimeError> {
+    run_deferred();
+    let exhausted = CURRENT.with(|current| match &mut *current.borrow_mut() {
+        Some(current) if current.cancelled => Err(RuntimeError::Cancelled),
+        Some(Current {
+            budget: Some(0), ..
+        }) => Ok(true),
+        Some(Current {
+            budget: Some(n), ..
+        }) => {
+            *n -= 1;
+            Ok(false)
+        }
+        _ => Ok(false),
+    })?;
+    if exhausted {
+        handoff(Event::Paused)?;
+        return step();
+    }
+    Ok(())
+}
+
+fn handoff(event: Event) -> Result<Option<Object>, RuntimeError> {
+    run_deferred();
+    CURRENT.with(|current| {
+        let mut current = current.borrow_mut();
+        let current = match &mut *current {
+            Some(current) => current,
+            None => return Ok(None),
+        };
+        if current.cancelled {
+            return Err(RuntimeError::Cancelled);
+        }
+        let tally = accounting::current().since(&current.resumed_at);
+        let message = match current.to_owner.send((event, tally)) {
+            Ok(()) => current.from_owner.recv(),
+            Err(_) => Ok(Message::Cancel),
+        };
+        current.resumed_at = accounting::current();
+        match message {
+            Ok(Message::Resume(value, budget, deadlines)) => {
+                current.budget = budget;
+                timer::resume_with(deadlines);
+                value.map(Some)
+            }
+            Ok(Message::Cancel) | Err(_) => {
+                current.cancelled = true;
+                Err(RuntimeError::Cancelled)
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object;
+
+    fn fixnum(event: Event) -> Option<isize> {
+        match event {
+            Event::Yielded(x) | Event::Returned(Ok(x)) => match &*x {
+                object::ObjectKind::Fixnum(n) => Some(*n),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn suspend_test() {
+        let mut coroutine = Coroutine::spawn(Kind::Generator, || {
+            let x = suspend(object::fixnum(1))?;
+            suspend(x)?;
+            Ok(object::fixnum(3))
+        });
+        assert_eq!(fixnum(coroutine.resume(Ok(object::nil()), None)), Some(1));
+        assert_eq!(
+            fixnum(coroutine.resume(Ok(object::fixnum(2)), None)),
+            Some(2)
+        );
+        assert_eq!(fixnum(coroutine.resume(Ok(object::nil()), None)), Some(3));
+        assert!(coroutine.is_finished());
+        assert!(matches!(
+            suspend(object::nil()),
+            Err(RuntimeError::NotInCoroutine)
+        ));
+    }
+
+    #[test]
+    fn cancel_test() {
+        let mut coroutine = Coroutine::spawn(Kind::Generator, || loop {
+            suspend(object::nil())?;
+        });
+        assert!(matches!(
+            coroutine.resume(Ok(object::nil()), None),
+            Event::Yielded(_)
+        ));
+        drop(coroutine);
+    }
+}
@@ -0,0 +1,315 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use super::env::Env;
+use super::error::RuntimeError;
+use super::eval::{check_num_args, check_num_args_range, EvalResult};
+use super::object::{self, Object, ObjectKind, ObjectType};
+use super::rc::Rc;
+
+// Times are fixnums counting seconds since 1970-01-01T00:00:00Z, without
+// leap seconds, and calendar components are always in UTC.
+
+pub fn init(env: &mut Env) {
+    env.define_builtin("decode-time", "time", decode_time);
+    env.define_builtin(
+        "encode-time",
+        "year month day &rest hour-minute-second",
+        encode_time,
+    );
+    env.define_builtin("format-iso8601", "time", format_iso8601);
+    env.define_builtin("parse-iso8601", "string", parse_iso8601);
+    #[cfg(feature = "std")]
+    {
+        env.define_builtin("current-time", "", current_time);
+        env.define_builtin("monotonic-time", "", monotonic_time);
+        env.define_builtin("elapsed-time", "start", elapsed_time);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: isize,
+    pub month: isize,
+    pub day: isize,
+    pub hour: isize,
+    pub minute: isize,
+    pub second: isize,
+}
+
+const SECONDS_PER_DAY: isize = 24 * 60 * 60;
+
+// Days since 1970-01-01 of a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: isize, month: isize, day: isize) -> isize {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: isize) -> (isize, isize, isize) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn days_in_month(year: isize, month: isize) -> isize {
+    days_from_civil(if month == 12 { year + 1 } else { year }, month % 12 + 1, 1)
+        - days_from_civil(year, month, 1)
+}
+
+impl DateTime {
+    pub fn from_time(time: isize) -> Self {
+        let (year, month, day) = civil_from_days(time.div_euclid(SECONDS_PER_DAY));
+        let seconds = time.rem_euclid(SECONDS_PER_DAY);
+        Self {
+            year,
+            month,
+            day,
+            hour: seconds / 3600,
+            minute: seconds / 60 % 60,
+            second: seconds % 60,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        (1..=12).contains(&self.month)
+            && (1..=days_in_month(self.year, self.month)).contains(&self.day)
+            && (0..24).contains(&self.hour)
+            && (0..60).contains(&self.minute)
+            && (0..60).contains(&self.second)
+    }
+
+    pub fn to_time(&self) -> isize {
+        days_from_civil(self.year, self.month, self.day) * SECONDS_PER_DAY
+            + self.hour * 3600
+            + self.minute * 60
+            + self.second
+    }
+
+    // 0 is Monday, as in CL.
+    pub fn weekday(&self) -> isize {
+        (days_from_civil(self.year, self.month, self.day) + 3).rem_euclid(7)
+    }
+
+    pub fn to_iso8601(&self) -> String {
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+// Accepts a date (2024-02-29), or a date and a time with an optional
+// offset (2024-02-29T12:30:00, ...Z or ...+09:00), and returns the time it
+// denotes.
+pub fn parse(s: &str) -> Option<isize> {
+    fn number(s: &str, digits: usize) -> Option<isize> {
+        if s.len() == digits && s.bytes().all(|b| b.is_ascii_digit()) {
+            s.parse().ok()
+        } else {
+            None
+        }
+    }
+    let (date, rest) = match s.find(['T', 't', ' ']) {
+        Some(i) => (&s[..i], Some(&s[i + 1..])),
+        None => (s, None),
+    };
+    let fields: Vec<&str> = date.split('-').collect();
+    let [year, month, day] = fields[..] else {
+        return None;
+    };
+    let mut date_time = DateTime {
+        year: number(year, 4)?,
+        month: number(month, 2)?,
+        day: number(day, 2)?,
+        hour: 0,
+        minute: 0,
+        second: 0,
+    };
+    let mut offset = 0;
+    if let Some(rest) = rest {
+        let (time, zone) = match rest.find(['Z', 'z', '+', '-']) {
+            Some(i) => rest.split_at(i),
+            None => (rest, ""),
+        };
+        let fields: Vec<&str> = time.split(':').collect();
+        let (hour, minute, second) = match fields[..] {
+            [hour, minute] => (hour, minute, "00"),
+            [hour, minute, second] => (hour, minute, second),
+            _ => return None,
+        };
+        date_time.hour = number(hour, 2)?;
+        date_time.minute = number(minute, 2)?;
+        date_time.second = number(second, 2)?;
+        offset = match zone {
+            "" | "Z" | "z" => 0,
+            _ => {
+                let (hours, minutes) = zone[1..].split_once(':')?;
+                let offset = number(hours, 2)? * 3600 + number(minutes, 2)? * 60;
+                if zone.starts_with('-') {
+                    -offset
+                } else {
+                    offset
+                }
+            }
+        };
+    }
+    if !date_time.is_valid() {
+        return None;
+    }
+    Some(date_time.to_time() - offset)
+}
+
+fn fixnum(x: &Object) -> Result<isize, RuntimeError> {
+    match **x {
+        ObjectKind::Fixnum(n) => Ok(n),
+        _ => Err(RuntimeError::MismatchType(Rc::clone(x), ObjectType::Number)),
+    }
+}
+
+// (decode-time time) returns (year month day hour minute second weekday).
+fn decode_time(args: &[Object]) -> EvalResult {
+    check_num_args(args, 1)?;
+    let date_time = DateTime::from_time(fixnum(&args[0])?);
+    Ok(object::from_iter(
+        [
+            date_time.year,
+            date_time.month,
+            date_time.day,
+            date_time.hour,
+            date_time.minute,
+            date_time.second,
+            date_time.weekday(),
+        ]
+        .iter()
+        .map(|n| object::fixnum(*n)),
+    ))
+}
+
+fn encode_time(args: &[Object]) -> EvalResult {
+    check_num_args_range(args, 3, 6)?;
+    let mut fields = [0; 6];
+    for (field, arg) in fields.iter_mut().zip(args) {
+        *field = fixnum(arg)?;
+    }
+    let [year, month, day, hour, minute, second] = fields;
+    let date_time = DateTime {
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+    };
+    if !date_time.is_valid() {
+        return Err(RuntimeError::InvalidTime(format!(
+            "{} {} {} {} {} {}",
+            year, month, day, hour, minute, second
+        )));
+    }
+    Ok(object::fixnum(date_time.to_time()))
+}
+
+fn format_iso8601(args: &[Object]) -> EvalResult {
+    check_num_args(args, 1)?;
+    Ok(object::string(
+        &DateTime::from_time(fixnum(&args[0])?).to_iso8601(),
+    ))
+}
+
+fn parse_iso8601(args: &[Object]) -> EvalResult {
+    check_num_args(args, 1)?;
+    match &*args[0] {
+        ObjectKind::String(s) => parse(s)
+            .map(object::fixnum)
+            .ok_or_else(|| RuntimeError::InvalidTime(s.to_string())),
+        _ => Err(RuntimeError::MismatchType(
+            Rc::clone(&args[0]),
+            ObjectType::String,
+        )),
+    }
+}
+
+#[cfg(feature = "std")]
+fn current_time(args: &[Object]) -> EvalResult {
+    check_num_args(args, 0)?;
+    let since_epoch = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok(object::fixnum(since_epoch.as_secs() as isize))
+}
+
+// Milliseconds from an arbitrary point, for measuring durations: it never
+// goes back, unlike current-time.
+#[cfg(feature = "std")]
+fn monotonic_millis() -> isize {
+    static START: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+    START
+        .get_or_init(std::time::Instant::now)
+        .elapsed()
+        .as_millis() as isize
+}
+
+#[cfg(feature = "std")]
+fn monotonic_time(args: &[Object]) -> EvalResult {
+    check_num_args(args, 0)?;
+    Ok(object::fixnum(monotonic_millis()))
+}
+
+// (elapsed-time start) is the number of milliseconds since the
+// (monotonic-time) that returned start.
+#[cfg(feature = "std")]
+fn elapsed_time(args: &[Object]) -> EvalResult {
+    check_num_args(args, 1)?;
+    Ok(object::fixnum(monotonic_millis() - fixnum(&args[0])?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calendar_test() {
+        assert_eq!(DateTime::from_time(0).to_iso8601(), "1970-01-01T00:00:00Z");
+        let leap_day = DateTime {
+            year: 2024,
+            month: 2,
+            day: 29,
+            hour: 23,
+            minute: 59,
+            second: 58,
+        };
+        assert_eq!(leap_day.to_time(), 1709251198);
+        assert_eq!(DateTime::from_time(1709251198), leap_day);
+        assert_eq!(leap_day.weekday(), 3);
+        assert_eq!(DateTime::from_time(-1).to_iso8601(), "1969-12-31T23:59:59Z");
+        assert!(!DateTime {
+            day: 29,
+            ..DateTime::from_time(1677628800 - SECONDS_PER_DAY)
+        }
+        .is_valid());
+    }
+
+    #[test]
+    fn parse_test() {
+        assert_eq!(parse("1970-01-02"), Some(SECONDS_PER_DAY));
+        assert_eq!(parse("2024-02-29T23:59:58Z"), Some(1709251198));
+        assert_eq!(parse("2024-03-01T08:59:58+09:00"), Some(1709251198));
+        assert_eq!(parse("2024-02-29T23:59"), Some(1709251140));
+        assert_eq!(parse("2023-02-29"), None);
+        assert_eq!(parse("2024-2-1"), None);
+        assert_eq!(parse("2024-02-01T25:00:00"), None);
+    }
+}
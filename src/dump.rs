@@ -0,0 +1,231 @@
+use alloc::string::{String, ToString};
+use core::fmt::{self, Write};
+
+use super::collections::Map;
+use super::object::{Object, ObjectKind};
+use super::rc::Rc;
+
+// Renders an object as an indented tree with each node's type and reference
+// count. Conses reachable more than once are labelled `#n=` where they first
+// appear and referred to as `#n#` afterwards, which also cuts cycles short.
+pub trait Dump {
+    fn dump(&self) -> String;
+
+    // The same graph in Graphviz's dot language, where shared structure and
+    // cycles show up as edges meeting at one node. Conses are two-field
+    // records, and every other object is a box, except nil in a field.
+    fn dump_graph(&self) -> String;
+}
+
+impl Dump for Object {
+    fn dump(&self) -> String {
+        let mut references = Map::new();
+        count_references(self, &mut references);
+        let mut dumper = Dumper {
+            references,
+            labels: Map::new(),
+            out: String::new(),
+        };
+        dumper.dump(self, 0, "");
+        dumper.out
+    }
+
+    fn dump_graph(&self) -> String {
+        let mut graph = Graph {
+            nodes: Map::new(),
+            out: String::from("digraph objects {\n  node [shape=box];\n"),
+        };
+        graph.node(self);
+        graph.out.push_str("}\n");
+        graph.out
+    }
+}
+
+struct Graph {
+    // Node numbers by address, in the order the nodes were reached.
+    nodes: Map<usize, usize>,
+    out: String,
+}
+
+impl Graph {
+    // Writes the node for x and everything reachable from it, unless that's
+    // been done already, and returns its name.
+    fn node(&mut self, x: &Object) -> String {
+        if let Some(n) = self.nodes.get(&address(x)) {
+            return alloc::format!("n{}", n);
+        }
+        let n = self.nodes.len();
+        self.nodes.insert(address(x), n);
+        let name = alloc::format!("n{}", n);
+        let cons = match &**x {
+            ObjectKind::Cons(cons) => cons,
+            _ => {
+                let _ = writeln!(
+                    self.out,
+                    "  {} [label=\"{}\"];",
+                    name,
+                    escape(&x.to_string())
+                );
+                return name;
+            }
+        };
+        let field = |x: &Object| match **x {
+            ObjectKind::Nil => "nil",
+            _ => "",
+        };
+        let _ = writeln!(
+            self.out,
+            "  {} [shape=record, label=\"<car>{}|<cdr>{}\"];",
+            name,
+            field(&cons.car),
+            field(&cons.cdr)
+        );
+        for (port, x) in [("car", &cons.car), ("cdr", &cons.cdr)] {
+            if !matches!(**x, ObjectKind::Nil) {
+                let target = self.node(x);
+                let _ = writeln!(self.out, "  {}:{} -> {};", name, port, target);
+            }
+        }
+        name
+    }
+}
+
+fn escape(label: &str) -> String {
+    let mut escaped = String::new();
+    for c in label.chars() {
+        match c {
+            '"' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn address(x: &Object) -> usize {
+    Rc::as_ptr(x) as *const () as usize
+}
+
+fn count_references(x: &Object, references: &mut Map<usize, usize>) {
+    if let ObjectKind::Cons(cons) = &**x {
+        let count = references.entry(address(x)).or_insert(0);
+        *count += 1;
+        if *count == 1 {
+            count_references(&cons.car, references);
+            count_references(&cons.cdr, references);
+        }
+    }
+}
+
+struct Dumper {
+    references: Map<usize, usize>,
+    labels: Map<usize, usize>,
+    out: String,
+}
+
+impl Dumper {
+    fn dump(&mut self, x: &Object, depth: usize, field: &str) {
+        let _ = write!(self.out, "{:width$}{}", "", field, width = depth * 2);
+        let rc = Rc::strong_count(x);
+        let cons = match &**x {
+            ObjectKind::Cons(cons) => cons,
+            ObjectKind::Nil => return self.line(format_args!("Nil rc={}", rc)),
+            ObjectKind::Fixnum(n) => return self.line(format_args!("Fixnum {} rc={}", n, rc)),
+            ObjectKind::Symbol(s) => return self.line(format_args!("Symbol {} rc={}", s, rc)),
+            ObjectKind::String(_) => return self.line(format_args!("String {} rc={}", x, rc)),
+            ObjectKind::Func(_) => return self.line(format_args!("Func {} rc={}", x, rc)),
+            ObjectKind::Closure(_) => return self.line(format_args!("Closure {} rc={}", x, rc)),
+            ObjectKind::Macro(_) => return self.line(format_args!("Macro {} rc={}", x, rc)),
+            ObjectKind::Port(_) => return self.line(format_args!("Port {} rc={}", x, rc)),
+            ObjectKind::Condition(_) => {
+                return self.line(format_args!("Condition {} rc={}", x, rc))
+            }
+            ObjectKind::BitVector(_) => {
+                return self.line(format_args!("BitVector {} rc={}", x, rc))
+            }
+            ObjectKind::Promise(_) => return self.line(format_args!("Promise {} rc={}", x, rc)),
+            ObjectKind::WeakRef(_) => return self.line(format_args!("WeakRef {} rc={}", x, rc)),
+            ObjectKind::WeakTable(_) => {
+                return self.line(format_args!("WeakTable {} rc={}", x, rc))
+            }
+            ObjectKind::HashTable(_) => {
+                return self.line(format_args!("HashTable {} rc={}", x, rc))
+            }
+            ObjectKind::Set(_) => return self.line(format_args!("Set {} rc={}", x, rc)),
+            #[cfg(feature = "sync")]
+            ObjectKind::Generator(_) => {
+                return self.line(format_args!("Generator {} rc={}", x, rc))
+            }
+            #[cfg(feature = "std")]
+            ObjectKind::Channel(_) => return self.line(format_args!("Channel {} rc={}", x, rc)),
+        };
+
+        let key = address(x);
+        if let Some(&label) = self.labels.get(&key) {
+            return self.line(format_args!("#{}#", label));
+        }
+        if self.references[&key] > 1 {
+            let label = self.labels.len() + 1;
+            self.labels.insert(key, label);
+            let _ = write!(self.out, "#{}=", label);
+        }
+        self.line(format_args!("Cons rc={}", rc));
+        self.dump(&cons.car, depth + 1, "car: ");
+        self.dump(&cons.cdr, depth + 1, "cdr: ");
+    }
+
+    fn line(&mut self, args: fmt::Arguments<'_>) {
+        let _ = self.out.write_fmt(args);
+        self.out.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::object::{cons, fixnum, nil, string, symbol};
+    use super::*;
+
+    #[test]
+    fn dump_graph_test() {
+        let shared = cons(string("a\"b"), nil());
+        let x = cons(Rc::clone(&shared), cons(fixnum(1), Rc::clone(&shared)));
+        assert_eq!(
+            x.dump_graph(),
+            "digraph objects {\n  node [shape=box];\n\
+             \x20 n0 [shape=record, label=\"<car>|<cdr>\"];\n\
+             \x20 n1 [shape=record, label=\"<car>|<cdr>nil\"];\n\
+             \x20 n2 [label=\"\\\"a\\\\\\\"b\\\"\"];\n\
+             \x20 n1:car -> n2;\n\
+             \x20 n0:car -> n1;\n\
+             \x20 n3 [shape=record, label=\"<car>|<cdr>\"];\n\
+             \x20 n4 [label=\"1\"];\n\
+             \x20 n3:car -> n4;\n\
+             \x20 n3:cdr -> n1;\n\
+             \x20 n0:cdr -> n3;\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn dump_test() {
+        let x = cons(fixnum(1), nil());
+        assert_eq!(
+            x.dump(),
+            "Cons rc=1\n  car: Fixnum 1 rc=1\n  cdr: Nil rc=1\n"
+        );
+
+        let shared = cons(symbol("a"), nil());
+        let y = cons(Rc::clone(&shared), Rc::clone(&shared));
+        assert_eq!(
+            y.dump(),
+            "Cons rc=1\n\
+             \x20 car: #1=Cons rc=3\n\
+             \x20   car: Symbol a rc=1\n\
+             \x20   cdr: Nil rc=1\n\
+             \x20 cdr: #1#\n"
+        );
+    }
+}
@@ -1,33 +1,572 @@
-use std::cell::RefCell;
-use std::collections::HashMap;
-use std::rc::Rc;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::path::PathBuf;
+#[cfg(feature = "std")]
+use std::time::SystemTime;
 
-use super::object::Object;
+use super::accounting::{self, Tally};
+use super::collections::{Map, Set};
+use super::eval::EvalOptions;
+use super::load;
+use super::object::{self, Keywords, Object, ObjectKind};
+use super::package::Packages;
+#[cfg(feature = "std")]
+use super::permissions::Permissions;
+use super::profile::{Capability, Module, Profile};
+use super::rc::{Rc, RefCell, Weak};
+use super::reader::ReaderOptions;
+use super::restart::{Debugger, Restart};
+use super::warning::Warning;
+
+const STDLIB: &str = include_str!("stdlib.lisp");
+const SCHEME: &str = include_str!("scheme.lisp");
+#[cfg(feature = "std")]
+const STDLIB_STD: &str = include_str!("stdlib_std.lisp");
+#[cfg(feature = "sync")]
+const STDLIB_SYNC: &str = include_str!("stdlib_sync.lisp");
+
+// The frames, restarts and handlers a suspended computation such as a
+// generator had established, set aside while it isn't running.
+#[derive(Default)]
+pub struct Dynamic {
+    frames: Vec<(Object, Object)>,
+    restarts: Vec<Restart>,
+    handlers: usize,
+}
+
+// Where a global was defined: the file it was loaded from, if it was, and
+// the line of the top-level form defining it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Source {
+    pub file: Option<String>,
+    pub line: usize,
+}
+
+#[derive(Clone, Copy)]
+pub struct Mark {
+    frames: usize,
+    restarts: usize,
+    handlers: usize,
+}
 
 pub struct Env {
     parent: Option<Rc<RefCell<Env>>>,
-    table: HashMap<String, Object>,
+    table: Map<String, Object>,
+    packages: Option<Packages>,
+    keywords: Option<Keywords>,
+    hooks: Vec<Object>,
+    warnings: Vec<Warning>,
+    frames: Vec<(Object, Object)>,
+    restarts: Vec<Restart>,
+    next_restart_id: usize,
+    handlers: usize,
+    debugger: Option<Debugger>,
+    // Objects with finalizers, and the functions to call once they're gone.
+    finalizers: Vec<(Weak<ObjectKind>, Object)>,
+    // The values of the last values form, while nothing has been called
+    // since.
+    values: Option<Vec<Object>>,
+    profile: Profile,
+    modules: Vec<Module>,
+    // Frames have those of the frame they were made in.
+    eval_options: EvalOptions,
+    reader_options: ReaderOptions,
+    // The keys of the global variables defvar and defparameter made
+    // special.
+    specials: Set<String>,
+    // The top-level form being loaded, and the source of each global by
+    // key.
+    loading: Option<Source>,
+    sources: Map<String, Source>,
+    // The tally of the last evaluation, when accounting is on.
+    tally: Option<Tally>,
+    // Whether the constructors hash-cons while evaluating in this
+    // environment.
+    hash_consing: bool,
+    #[cfg(feature = "std")]
+    permissions: Permissions,
+    // The global environment itself, for the builtins defined with
+    // define_env_builtin.
+    this: Weak<RefCell<Env>>,
+    // The files watch-load loaded, with when each was last modified then.
+    #[cfg(feature = "std")]
+    watched: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+#[derive(Default)]
+pub struct EnvBuilder {
+    modules: Vec<Module>,
+    profile: Profile,
+    eval_options: EvalOptions,
+    reader_options: ReaderOptions,
+    scheme: bool,
+}
+
+impl EnvBuilder {
+    pub fn with(mut self, module: Module) -> Self {
+        if !self.modules.contains(&module) {
+            self.modules.push(module);
+        }
+        self
+    }
+
+    pub fn profile(mut self, profile: Profile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    pub fn eval_options(mut self, options: EvalOptions) -> Self {
+        self.eval_options = options;
+        self
+    }
+
+    pub fn reader_options(mut self, options: ReaderOptions) -> Self {
+        self.reader_options = options;
+        self
+    }
+
+    // Scheme's booleans, with Scheme's names for the predicates that are
+    // named differently here.
+    pub fn scheme(self) -> Self {
+        let mut builder = self
+            .eval_options(EvalOptions::scheme())
+            .reader_options(ReaderOptions::scheme());
+        builder.scheme = true;
+        builder
+    }
+
+    // The stdlib is loaded whatever the modules, and its definitions that
+    // use builtins which were left out fail when they're called.
+    pub fn build(self) -> Rc<RefCell<Env>> {
+        let mut modules = if self.modules.is_empty() {
+            Module::all().to_vec()
+        } else {
+            self.modules
+        };
+        if !modules.contains(&Module::Core) {
+            modules.insert(0, Module::Core);
+        }
+        let profile = self.profile;
+        let env = Rc::new_cyclic(|this| {
+            let mut env = Env::new(None);
+            env.profile = profile;
+            env.this = Weak::clone(this);
+            for &module in modules.iter() {
+                env.init(module);
+            }
+            env.modules = modules;
+            RefCell::new(env)
+        });
+        load::load_prelude(STDLIB, "stdlib.lisp", Rc::clone(&env))
+            .expect("failed to load stdlib.lisp");
+        #[cfg(feature = "std")]
+        load::load_prelude(STDLIB_STD, "stdlib_std.lisp", Rc::clone(&env))
+            .expect("failed to load stdlib_std.lisp");
+        #[cfg(feature = "sync")]
+        load::load_prelude(STDLIB_SYNC, "stdlib_sync.lisp", Rc::clone(&env))
+            .expect("failed to load stdlib_sync.lisp");
+        // The stdlib is written without the options, whatever the prelude
+        // after it is, and its closures keep evaluating without them.
+        env.borrow_mut().eval_options = self.eval_options;
+        env.borrow_mut().reader_options = self.reader_options;
+        if self.scheme {
+            load::load_prelude(SCHEME, "scheme.lisp", Rc::clone(&env))
+                .expect("failed to load scheme.lisp");
+        }
+        env
+    }
+}
+
+impl Drop for Env {
+    fn drop(&mut self) {
+        accounting::count_env(-1);
+    }
 }
 
 impl Env {
     pub fn new(parent: Option<Rc<RefCell<Env>>>) -> Self {
+        let (packages, keywords) = match parent {
+            None => (Some(Packages::default()), Some(Keywords::default())),
+            Some(_) => (None, None),
+        };
+        let (eval_options, reader_options) = match &parent {
+            Some(parent) => {
+                let parent = parent.borrow();
+                (parent.eval_options, parent.reader_options)
+            }
+            None => Default::default(),
+        };
+        accounting::count_env(1);
         Self {
             parent,
-            table: HashMap::new(),
+            table: Map::new(),
+            packages,
+            keywords,
+            hooks: Vec::new(),
+            warnings: Vec::new(),
+            frames: Vec::new(),
+            restarts: Vec::new(),
+            next_restart_id: 0,
+            handlers: 0,
+            debugger: None,
+            finalizers: Vec::new(),
+            values: None,
+            profile: Profile::default(),
+            modules: Vec::new(),
+            eval_options,
+            reader_options,
+            specials: Set::new(),
+            loading: None,
+            sources: Map::new(),
+            tally: None,
+            hash_consing: false,
+            #[cfg(feature = "std")]
+            permissions: Permissions::default(),
+            this: Weak::new(),
+            #[cfg(feature = "std")]
+            watched: Vec::new(),
         }
     }
 
     pub fn global_env() -> Rc<RefCell<Self>> {
-        let mut env = Self::new(None);
-        env.init();
-        Rc::new(RefCell::new(env))
+        Self::global_env_with(Profile::default())
+    }
+
+    pub fn global_env_with(profile: Profile) -> Rc<RefCell<Self>> {
+        Self::builder().profile(profile).build()
+    }
+
+    // Every module, unless some are picked with EnvBuilder::with.
+    pub fn builder() -> EnvBuilder {
+        EnvBuilder::default()
+    }
+
+    pub fn root(env: &Rc<RefCell<Env>>) -> Rc<RefCell<Env>> {
+        match &env.borrow().parent {
+            None => Rc::clone(env),
+            Some(parent) => Env::root(parent),
+        }
+    }
+
+    pub(crate) fn this(&self) -> Weak<RefCell<Env>> {
+        Weak::clone(&self.this)
+    }
+
+    pub fn profile(&self) -> Profile {
+        self.profile
+    }
+
+    pub fn allows(env: &Rc<RefCell<Env>>, capability: Capability) -> bool {
+        Env::root(env).borrow().profile.allows(capability)
+    }
+
+    // Whether the global environment was built with module.
+    pub fn includes(env: &Rc<RefCell<Env>>, module: Module) -> bool {
+        Env::root(env).borrow().modules.contains(&module)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn permissions(env: &Rc<RefCell<Env>>) -> Permissions {
+        Env::root(env).borrow().permissions.clone()
+    }
+
+    #[cfg(feature = "std")]
+    pub fn set_permissions(&mut self, permissions: Permissions) {
+        self.permissions = permissions;
+    }
+
+    #[cfg(feature = "std")]
+    pub fn watch(&mut self, path: PathBuf, modified: Option<SystemTime>) {
+        match self
+            .watched
+            .iter_mut()
+            .find(|(watched, _)| *watched == path)
+        {
+            Some(watched) => watched.1 = modified,
+            None => self.watched.push((path, modified)),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn watched(&self) -> Vec<(PathBuf, Option<SystemTime>)> {
+        self.watched.clone()
+    }
+
+    pub fn eval_options(&self) -> EvalOptions {
+        self.eval_options
+    }
+
+    pub fn set_eval_options(&mut self, options: EvalOptions) {
+        self.eval_options = options;
+    }
+
+    pub fn reader_options(&self) -> ReaderOptions {
+        self.reader_options
+    }
+
+    // The radix integers in source are read in, from *read-base*.
+    pub fn read_base(&self) -> u32 {
+        match self.get("*read-base*").as_deref() {
+            Some(ObjectKind::Fixnum(n)) if (2..=36).contains(n) => *n as u32,
+            _ => 10,
+        }
+    }
+
+    pub fn set_accounting(&mut self, on: bool) {
+        self.tally = if on { Some(Tally::default()) } else { None };
+    }
+
+    pub fn is_accounting(&self) -> bool {
+        self.tally.is_some()
+    }
+
+    pub fn hash_consing(&self) -> bool {
+        self.hash_consing
+    }
+
+    pub fn set_hash_consing(&mut self, on: bool) {
+        self.hash_consing = on;
+    }
+
+    pub fn set_tally(&mut self, tally: Tally) {
+        if self.tally.is_some() {
+            self.tally = Some(tally);
+        }
+    }
+
+    pub fn tally(&self) -> Option<Tally> {
+        self.tally
+    }
+
+    pub fn keywords(&self) -> Option<Keywords> {
+        self.keywords.clone()
+    }
+
+    pub fn packages(&self) -> &Packages {
+        self.packages.as_ref().expect("not a global environment")
+    }
+
+    pub fn packages_mut(&mut self) -> &mut Packages {
+        self.packages.as_mut().expect("not a global environment")
+    }
+
+    // Expansion hooks are functions of one form that return the form to
+    // evaluate in its place. Only the global environment holds them.
+    pub fn add_hook(&mut self, hook: Object) {
+        self.hooks.push(hook);
+    }
+
+    pub fn remove_hook(&mut self, hook: &Object) -> bool {
+        let len = self.hooks.len();
+        self.hooks.retain(|h| !Rc::ptr_eq(h, hook));
+        self.hooks.len() != len
+    }
+
+    // Hooks are taken out while they run so that the forms they evaluate
+    // aren't rewritten by themselves.
+    pub fn take_hooks(&mut self) -> Vec<Object> {
+        core::mem::take(&mut self.hooks)
+    }
+
+    pub fn restore_hooks(&mut self, hooks: Vec<Object>) {
+        self.hooks.splice(0..0, hooks);
+    }
+
+    pub fn is_root(env: &Rc<RefCell<Env>>) -> bool {
+        env.borrow().parent.is_none()
+    }
+
+    pub fn warn(&mut self, warning: Warning) {
+        self.warnings.push(warning);
+    }
+
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        core::mem::take(&mut self.warnings)
+    }
+
+    // The calls in progress as (function form), kept in the global
+    // environment.
+    pub fn push_frame(&mut self, function: Object, form: Object) {
+        self.frames.push((function, form));
+    }
+
+    pub fn pop_frame(&mut self) {
+        self.frames.pop();
+    }
+
+    pub fn add_finalizer(&mut self, x: &Object, finalizer: Object) {
+        self.finalizers.push((Rc::downgrade(x), finalizer));
+    }
+
+    // Removes and returns the finalizers whose objects are gone.
+    pub fn take_due_finalizers(&mut self) -> Vec<Object> {
+        let (due, pending) = core::mem::take(&mut self.finalizers)
+            .into_iter()
+            .partition(|(x, _)| x.strong_count() == 0);
+        self.finalizers = pending;
+        due.into_iter().map(|(_, finalizer)| finalizer).collect()
+    }
+
+    pub fn set_values(&mut self, values: Option<Vec<Object>>) {
+        self.values = values;
+    }
+
+    pub fn take_values(&mut self) -> Option<Vec<Object>> {
+        self.values.take()
+    }
+
+    pub fn backtrace(&self) -> Object {
+        object::from_iter(
+            self.frames
+                .iter()
+                .rev()
+                .map(|(function, form)| crate::list![Rc::clone(function), Rc::clone(form)]),
+        )
+    }
+
+    // Restarts in effect, innermost last.
+    pub fn restarts(&self) -> &[Restart] {
+        &self.restarts
+    }
+
+    pub fn push_restart(&mut self, name: &str, parameters: Vec<String>) -> usize {
+        let id = self.next_restart_id;
+        self.next_restart_id += 1;
+        self.restarts.push(Restart {
+            id,
+            name: name.to_string(),
+            parameters,
+        });
+        id
+    }
+
+    pub fn pop_restarts(&mut self, n: usize) {
+        self.restarts.truncate(self.restarts.len() - n);
+    }
+
+    pub fn find_restart(&self, name: &str) -> Option<usize> {
+        self.restarts
+            .iter()
+            .rev()
+            .find(|restart| restart.name == name)
+            .map(|restart| restart.id)
+    }
+
+    // The number of %try forms being evaluated. Errors inside them are
+    // handled there, so the debugger isn't called.
+    pub fn enter_handler(&mut self) {
+        self.handlers += 1;
+    }
+
+    pub fn leave_handler(&mut self) {
+        self.handlers -= 1;
+    }
+
+    pub fn mark(&self) -> Mark {
+        Mark {
+            frames: self.frames.len(),
+            restarts: self.restarts.len(),
+            handlers: self.handlers,
+        }
+    }
+
+    // Takes out what was established since mark.
+    pub fn unwind(&mut self, mark: Mark) -> Dynamic {
+        let handlers = self.handlers - mark.handlers;
+        self.handlers = mark.handlers;
+        Dynamic {
+            frames: self.frames.split_off(mark.frames),
+            restarts: self.restarts.split_off(mark.restarts),
+            handlers,
+        }
+    }
+
+    pub fn rewind(&mut self, dynamic: Dynamic) {
+        self.frames.extend(dynamic.frames);
+        self.restarts.extend(dynamic.restarts);
+        self.handlers += dynamic.handlers;
+    }
+
+    pub fn proclaim_special(&mut self, name: &str) {
+        let key = match &self.packages {
+            Some(packages) => packages.definition_key(name),
+            None => name.to_string(),
+        };
+        self.specials.insert(key);
+    }
+
+    pub fn has_specials(&self) -> bool {
+        !self.specials.is_empty()
+    }
+
+    pub fn is_special(&self, name: &str) -> bool {
+        match &self.packages {
+            Some(packages) => packages
+                .find(name, |key| self.specials.contains(key).then_some(()))
+                .is_some(),
+            None => self.specials.contains(name),
+        }
+    }
+
+    // As stored, like the keys of bindings.
+    pub fn is_special_key(&self, key: &str) -> bool {
+        self.specials.contains(key)
+    }
+
+    // Returns what was being loaded before.
+    pub fn set_loading(&mut self, source: Option<Source>) -> Option<Source> {
+        core::mem::replace(&mut self.loading, source)
+    }
+
+    // A global defined outside of anything being loaded, as at the REPL,
+    // has no source.
+    pub fn record_source(&mut self, name: &str) {
+        let key = match &self.packages {
+            Some(packages) => packages.definition_key(name),
+            None => name.to_string(),
+        };
+        match &self.loading {
+            Some(source) => self.sources.insert(key, source.clone()),
+            None => self.sources.remove(&key),
+        };
+    }
+
+    pub fn definition_source(&self, name: &str) -> Option<Source> {
+        match &self.packages {
+            Some(packages) => packages.find(name, |key| self.sources.get(key).cloned()),
+            None => self.sources.get(name).cloned(),
+        }
+    }
+
+    pub fn set_debugger(&mut self, debugger: Option<Debugger>) {
+        self.debugger = debugger;
+    }
+
+    pub fn debugger(&self) -> Option<Debugger> {
+        match self.handlers {
+            0 => self.debugger,
+            _ => None,
+        }
     }
 
     pub fn insert(&mut self, name: &str, value: Object) {
-        self.table.insert(name.to_string(), value);
+        let key = match &self.packages {
+            Some(packages) => packages.definition_key(name),
+            None => name.to_string(),
+        };
+        self.table.insert(key, value);
     }
 
     pub fn set(&mut self, name: &str, value: Object) -> bool {
+        if let Some(packages) = &self.packages {
+            let table = &mut self.table;
+            return packages
+                .find(name, |key| {
+                    table.get_mut(key).map(|v| *v = Rc::clone(&value))
+                })
+                .is_some();
+        }
         if let Some(v) = self.table.get_mut(name) {
             *v = Rc::clone(&value);
             return true;
@@ -38,7 +577,60 @@ impl Env {
         }
     }
 
+    // This environment's own bindings, keyed as stored (`pkg:sym` for
+    // globals outside the default package).
+    pub fn bindings(&self) -> impl Iterator<Item = (&String, &Object)> {
+        self.table.iter()
+    }
+
+    // The bindings reachable from here by their unqualified names, the
+    // innermost first where one shadows another.
+    pub fn visible_bindings(&self) -> Vec<(String, Object)> {
+        let mut bindings: Vec<(String, Object)> = Vec::new();
+        let mut seen = Set::new();
+        let mut add = |table: &Map<String, Object>, packages: Option<&Packages>| {
+            for (key, value) in table {
+                let name = match packages {
+                    Some(packages) => match key.split_once(':') {
+                        Some((package, symbol)) if package == packages.current() => symbol,
+                        Some((package, _)) if !package.is_empty() => continue,
+                        _ => key,
+                    },
+                    None => key,
+                };
+                if seen.insert(name.to_string()) {
+                    bindings.push((name.to_string(), Rc::clone(value)));
+                }
+            }
+        };
+        add(&self.table, self.packages.as_ref());
+        let mut parent = self.parent.clone();
+        while let Some(env) = parent {
+            let env = env.borrow();
+            add(&env.table, env.packages.as_ref());
+            parent = env.parent.clone();
+        }
+        bindings
+    }
+
+    // Names reachable from here that are a likely misspelling of name,
+    // closest first.
+    pub fn similar_names(&self, name: &str) -> Vec<String> {
+        let limit = name.chars().count() / 3;
+        let mut names: Vec<(usize, String)> = self
+            .visible_bindings()
+            .into_iter()
+            .map(|(candidate, _)| (edit_distance(name, &candidate), candidate))
+            .filter(|&(distance, _)| distance > 0 && distance <= limit)
+            .collect();
+        names.sort();
+        names.into_iter().take(3).map(|(_, name)| name).collect()
+    }
+
     pub fn get(&self, name: &str) -> Option<Object> {
+        if let Some(packages) = &self.packages {
+            return packages.find(name, |key| self.table.get(key).map(Rc::clone));
+        }
         if let Some(v) = self.table.get(name) {
             return Some(Rc::clone(v));
         }
@@ -48,3 +640,25 @@ impl Env {
         }
     }
 }
+
+// The number of single character insertions, deletions, substitutions and
+// transpositions of adjacent characters that turn a into b.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = alloc::vec![(0..=b.len()).collect::<Vec<usize>>()];
+    for i in 1..=a.len() {
+        let mut row = alloc::vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (rows[i - 1][j] + 1)
+                .min(row[j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+    rows[a.len()][b.len()]
+}
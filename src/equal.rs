@@ -1,47 +1,217 @@
-use super::object::{Cons, Object, ObjectKind};
-use std::rc::Rc;
+use super::object::{Condition, Cons, Object, ObjectKind};
+use core::hash::{Hash, Hasher};
+use core::mem;
 
-fn equal_cons(x: &Cons, y: &Cons) -> bool {
-    if !equal(Rc::clone(&x.car), Rc::clone(&y.car)) {
-        return false;
+// The cdrs are followed in a loop, so only nesting, not length, takes stack.
+fn equal_cons(mut x: &Cons, mut y: &Cons) -> bool {
+    loop {
+        if core::ptr::eq(x, y) {
+            return true;
+        }
+        if !equal_kind(&x.car, &y.car) {
+            return false;
+        }
+        match (&*x.cdr, &*y.cdr) {
+            (ObjectKind::Cons(x_cdr), ObjectKind::Cons(y_cdr)) => {
+                x = x_cdr;
+                y = y_cdr;
+            }
+            (x_cdr, y_cdr) => return equal_kind(x_cdr, y_cdr),
+        }
     }
-    equal(Rc::clone(&x.cdr), Rc::clone(&y.cdr))
 }
 
-pub fn equal(x: Object, y: Object) -> bool {
+// Conditions are the records of the language, so two of them are equal when
+// their types and fields are, wherever they were signalled.
+fn equal_condition(x: &Condition, y: &Condition) -> bool {
+    x.type_name == y.type_name
+        && x.message == y.message
+        && equal_kind(&x.irritants, &y.irritants)
+        && equal_kind(&x.fields, &y.fields)
+}
+
+fn equal_kind(x: &ObjectKind, y: &ObjectKind) -> bool {
     use ObjectKind::*;
-    match &*x {
-        Nil => match &*y {
-            Nil => true,
-            _ => false,
-        },
-        Fixnum(x) => match &*y {
+    if core::ptr::eq(x, y) {
+        return true;
+    }
+    match x {
+        Nil => matches!(y, Nil),
+        Fixnum(x) => match y {
             Fixnum(y) => x == y,
             _ => false,
         },
-        Symbol(x) => match &*y {
+        Symbol(x) => match y {
             Symbol(y) => x == y,
             _ => false,
         },
-        Cons(x) => match &*y {
+        String(x) => match y {
+            String(y) => x == y,
+            _ => false,
+        },
+        Cons(x) => match y {
             Cons(y) => equal_cons(x, y),
             _ => false,
         },
-        Func(x) => match &*y {
-            Func(y) => std::ptr::eq(x, y),
+        Func(x) => match y {
+            Func(y) => core::ptr::eq(x, y),
+            _ => false,
+        },
+        Closure(x) => match y {
+            Closure(y) => core::ptr::eq(x, y),
+            _ => false,
+        },
+        Macro(x) => match y {
+            Macro(y) => core::ptr::eq(x, y),
+            _ => false,
+        },
+        Port(x) => match y {
+            Port(y) => core::ptr::eq(x, y),
+            _ => false,
+        },
+        Condition(x) => match y {
+            Condition(y) => equal_condition(x, y),
+            _ => false,
+        },
+        BitVector(x) => match y {
+            BitVector(y) => x.len() == y.len() && x.words() == y.words(),
+            _ => false,
+        },
+        Promise(x) => match y {
+            Promise(y) => core::ptr::eq(x, y),
+            _ => false,
+        },
+        WeakRef(x) => match y {
+            WeakRef(y) => core::ptr::eq(x, y),
+            _ => false,
+        },
+        HashTable(x) => match y {
+            HashTable(y) => x.equal(y),
             _ => false,
         },
-        Closure(x) => match &*y {
-            Closure(y) => std::ptr::eq(x, y),
+        Set(x) => match y {
+            Set(y) => x.equal(y),
             _ => false,
         },
+        WeakTable(x) => match y {
+            WeakTable(y) => core::ptr::eq(x, y),
+            _ => false,
+        },
+        #[cfg(feature = "sync")]
+        Generator(x) => match y {
+            Generator(y) => core::ptr::eq(x, y),
+            _ => false,
+        },
+        #[cfg(feature = "std")]
+        Channel(x) => match y {
+            Channel(y) => x.is_same(y),
+            _ => false,
+        },
+    }
+}
+
+pub fn equal(x: Object, y: Object) -> bool {
+    equal_kind(&x, &y)
+}
+
+// FNV-1a, which is available without std, unlike the default hasher.
+struct Fnv(u64);
+
+impl Hasher for Fnv {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+// The finalizer of splitmix64. FNV alone spreads the last bytes it's fed
+// poorly, so every hash goes through this before it's combined or returned.
+fn mix(mut h: u64) -> u64 {
+    h = (h ^ (h >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94d049bb133111eb);
+    h ^ (h >> 31)
+}
+
+// Order matters: combining a then b differs from b then a.
+fn combine(h: u64, x: u64) -> u64 {
+    mix(h.rotate_left(17) ^ x)
+}
+
+// A hash consistent with equal: equal objects have the same hash. Equal
+// hash tables, memoize and the Hash impl below all go through it; hash-consing
+// needn't, as it keys conses by their parts, which are shared. Functions,
+// ports, promises and weak objects are compared by identity, so they hash by
+// address.
+pub fn hash(x: &ObjectKind) -> u64 {
+    use ObjectKind::*;
+    let mut state = Fnv(0xcbf29ce484222325);
+    mem::discriminant(x).hash(&mut state);
+    match x {
+        Nil => (),
+        Fixnum(n) => n.hash(&mut state),
+        Symbol(s) | String(s) => s.hash(&mut state),
+        Cons(_) => return hash_list(mix(state.finish()), x),
+        Func(func) => (func as *const _ as usize).hash(&mut state),
+        Closure(closure) | Macro(closure) => (closure as *const _ as usize).hash(&mut state),
+        Port(port) => (port as *const _ as usize).hash(&mut state),
+        Condition(condition) => {
+            condition.type_name.hash(&mut state);
+            condition.message.hash(&mut state);
+            let h = combine(mix(state.finish()), hash(&condition.irritants));
+            return combine(h, hash(&condition.fields));
+        }
+        BitVector(bit_vector) => {
+            bit_vector.len().hash(&mut state);
+            bit_vector.words().hash(&mut state);
+        }
+        Promise(promise) => (promise as *const _ as usize).hash(&mut state),
+        WeakRef(weak_ref) => (weak_ref as *const _ as usize).hash(&mut state),
+        WeakTable(table) => (table as *const _ as usize).hash(&mut state),
+        HashTable(table) => table.hash().hash(&mut state),
+        Set(set) => set.hash().hash(&mut state),
+        #[cfg(feature = "sync")]
+        Generator(generator) => (generator as *const _ as usize).hash(&mut state),
+        #[cfg(feature = "std")]
+        Channel(channel) => channel.address().hash(&mut state),
+    }
+    mix(state.finish())
+}
+
+// Each element is combined in turn, then the tail, nil for a proper list.
+fn hash_list(mut h: u64, mut x: &ObjectKind) -> u64 {
+    while let ObjectKind::Cons(cons) = x {
+        h = combine(h, hash(&cons.car));
+        x = &cons.cdr;
+    }
+    combine(h, hash(x))
+}
+
+impl PartialEq for ObjectKind {
+    fn eq(&self, other: &Self) -> bool {
+        equal_kind(self, other)
+    }
+}
+
+impl Eq for ObjectKind {}
+
+impl Hash for ObjectKind {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(hash(self));
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::super::object::{cons, fixnum, nil, symbol};
+    use super::super::bitvector::BitVector;
+    use super::super::object::{bit_vector, condition, cons, fixnum, nil, string, symbol};
     use super::*;
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::HashSet;
     #[test]
     fn nil_test() {
         assert!(equal(nil(), nil()));
@@ -63,6 +233,50 @@ mod tests {
         assert!(!equal(symbol("foo"), fixnum(1)));
     }
 
+    #[test]
+    fn string_test() {
+        assert!(equal(string("foo"), string("foo")));
+        assert!(!equal(string("foo"), string("bar")));
+        assert!(!equal(string("foo"), symbol("foo")));
+        assert!(!equal(string("foo"), string("foo ")));
+        assert!(equal(string(""), string("")));
+    }
+
+    #[test]
+    fn bit_vector_test() {
+        let bits = |bits: &[bool]| bit_vector(BitVector::from_bits(bits.iter().copied()));
+        assert!(equal(bits(&[true, false]), bits(&[true, false])));
+        assert!(!equal(bits(&[true, false]), bits(&[true, true])));
+        assert!(!equal(bits(&[true, false]), bits(&[true, false, false])));
+        assert!(!equal(bits(&[]), nil()));
+    }
+
+    #[test]
+    fn condition_test() {
+        let error = |field: isize, backtrace: Object| {
+            condition(
+                "my-error",
+                "failed",
+                cons(fixnum(1), nil()),
+                cons(cons(symbol("code"), fixnum(field)), nil()),
+                backtrace,
+            )
+        };
+        assert!(equal(error(1, nil()), error(1, cons(symbol("f"), nil()))));
+        assert!(!equal(error(1, nil()), error(2, nil())));
+        assert!(!equal(
+            error(1, nil()),
+            condition(
+                "other-error",
+                "failed",
+                cons(fixnum(1), nil()),
+                nil(),
+                nil()
+            )
+        ));
+        assert_eq!(hash(&error(1, nil())), hash(&error(1, symbol("f"))));
+    }
+
     #[test]
     fn cons_test() {
         assert!(equal(
@@ -80,4 +294,70 @@ mod tests {
 
         assert!(!equal(cons(symbol("+"), nil()), fixnum(100)));
     }
+
+    fn hash(x: &Object) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        x.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn equal_hash_test() {
+        let x = cons(fixnum(1), cons(string("a"), nil()));
+        let y = cons(fixnum(1), cons(string("a"), nil()));
+        assert_eq!(super::hash(&x), super::hash(&y));
+        assert_ne!(super::hash(&x), super::hash(&cons(fixnum(1), nil())));
+    }
+
+    #[test]
+    fn nested_hash_test() {
+        use super::super::reader::read_from_string;
+        let read = |s| read_from_string(s).unwrap().0;
+        // Lists regrouped or reordered at any depth hash apart.
+        let hashes = [
+            "((a b) c)",
+            "((a) b c)",
+            "(a (b c))",
+            "(a b c)",
+            "(a b . c)",
+            "((b a) c)",
+            "(((a b) c))",
+            "(\"a\" b c)",
+        ]
+        .map(|s| super::hash(&read(s)));
+        let distinct: HashSet<_> = hashes.iter().collect();
+        assert_eq!(distinct.len(), hashes.len());
+
+        crate::arbitrary::for_all(
+            300,
+            |g| crate::arbitrary::object(g, 4),
+            |x| {
+                let (y, _) = read_from_string(&x.to_string()).unwrap();
+                equal(x.clone(), y.clone()) && super::hash(x) == super::hash(&y)
+            },
+        );
+    }
+
+    #[test]
+    fn partial_eq_test() {
+        assert_eq!(cons(fixnum(1), string("a")), cons(fixnum(1), string("a")));
+        assert_ne!(cons(fixnum(1), string("a")), cons(fixnum(1), symbol("a")));
+        assert_ne!(nil(), symbol("t"));
+    }
+
+    // Closures hash by address, so their mutable environments don't matter.
+    #[allow(clippy::mutable_key_type)]
+    #[test]
+    fn hash_test() {
+        assert_eq!(hash(&cons(fixnum(1), nil())), hash(&cons(fixnum(1), nil())));
+        assert_eq!(hash(&string("foo")), hash(&string("foo")));
+
+        let mut set = HashSet::new();
+        set.insert(cons(symbol("a"), fixnum(1)));
+        set.insert(cons(symbol("a"), fixnum(1)));
+        set.insert(string("a"));
+        set.insert(symbol("a"));
+        assert_eq!(set.len(), 3);
+        assert!(set.contains(&cons(symbol("a"), fixnum(1))));
+    }
 }
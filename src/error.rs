@@ -1,24 +1,209 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::error::Error as StdError;
 use core::fmt;
+#[cfg(feature = "std")]
+use std::io;
 
-use super::object::{Object, ObjectType};
+use super::object::{Object, ObjectKind, ObjectType};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReadError {
+    EndOfFile,
+    UnmatchedClosedParen,
+    UnexpectedChar(char, char),
+    InvalidBit(char),
+    // There are no bignums, so integers beyond isize can't be read.
+    IntegerOverflow,
+    // The letter after the backslash of an escape the reader doesn't know,
+    // or whose code point is invalid.
+    InvalidEscape(char),
+    // A #{...} hash table literal with an odd number of forms.
+    UnpairedKey,
+    // Forms nested beyond ReaderOptions::max_depth.
+    TooDeep,
+    // The input ended inside a string; where its opening quote was.
+    UnterminatedString(usize),
+    // #( would start a vector, but bit vectors are the only vectors.
+    VectorLiteral,
+}
+
+impl ReadError {
+    // The input ended before the form did, so more of it might complete the
+    // form rather than it being malformed.
+    pub fn is_incomplete(&self) -> bool {
+        matches!(
+            self,
+            ReadError::EndOfFile | ReadError::UnterminatedString(_)
+        )
+    }
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use self::ReadError::*;
+        match self {
+            EndOfFile => write!(f, "End of file"),
+            UnmatchedClosedParen => write!(f, "Unmatched closed parenthesis"),
+            InvalidBit(c) => write!(f, "Bit vectors only contain 0 and 1, not {:?}", c),
+            IntegerOverflow => write!(f, "Integer literal out of fixnum range"),
+            UnpairedKey => write!(f, "Hash table literal has a key without a value"),
+            TooDeep => write!(f, "Forms are nested too deeply to read"),
+            UnterminatedString(start) => {
+                write!(f, "Unterminated string starting at byte {}", start)
+            }
+            VectorLiteral => write!(f, "There are no vector literals but bit vectors like #*01"),
+            InvalidEscape(c) => write!(f, "Invalid escape sequence \\{} in string", c),
+            UnexpectedChar(actual, expected) => write!(
+                f,
+                "Expecting character {:?}, but it's character {:?}",
+                expected, actual
+            ),
+        }
+    }
+}
+
+impl StdError for ReadError {}
 
 #[derive(Debug)]
 pub enum RuntimeError {
-    UnboundVariable(String),
+    // The name, and bound names it may be a misspelling of.
+    UnboundVariable(String, Vec<String>),
     MismatchType(Object, ObjectType),
+    // The call form and the value its head evaluated to.
+    NotCallable(Object, Object),
+    // The value, the declared type and the declaration that failed.
+    MismatchDeclaredType(Object, ObjectType, Object),
     WrongNumArgs(usize, usize),
     TooFewArguments(usize, usize),
     TooManyArguments(usize, usize),
+    // The function, the number of arguments given and its arity.
+    WrongArity(String, usize, (usize, Option<usize>)),
+    // A define in a body after its first expression.
+    MisplacedDefine(String),
+    ModuleNotFound(String),
+    #[cfg(feature = "std")]
+    LoadError(String, io::Error),
+    ReadError(ReadError),
+    PackageNotFound(String),
+    SymbolNotExported(String, String),
+    MalformedLambdaList(Object),
+    MalformedForm(Object),
+    AssertionFailed(Object),
+    ClosedPort,
+    PermissionDenied(String),
+    // The name and value of an environment variable that can't be set.
+    #[cfg(feature = "std")]
+    InvalidEnvVar(String, String),
+    #[cfg(feature = "std")]
+    Io(io::Error),
+    // A condition signalled by error.
+    Signal(Object),
+    // Unwinds to the with-restarts that established the restart.
+    InvokeRestart(usize, Vec<Object>),
+    RestartNotFound(String),
+    // The coroutine running the evaluation was dropped before it finished.
+    Cancelled,
+    NotInCoroutine,
+    GeneratorRunning,
+    NotTransferable(Object),
+    // A builtin called after the global environment it consults is gone.
+    EnvDropped(String),
+    InvalidFormat(String),
+    InvalidTime(String),
+    // The index and the length of the sequence.
+    IndexOutOfRange(usize, usize),
+    LengthMismatch(usize, usize),
+    DivisionByZero,
+    InvalidRadix(Object),
+    // A keyword argument that isn't one of those the function takes.
+    UnknownKeyword(Object, Vec<String>),
+    // The with-timeout around the evaluation ran out after this many seconds.
+    Timeout(isize),
+    // The innermost form whose evaluation failed, and why.
+    InForm(Object, Box<RuntimeError>),
+}
+
+impl RuntimeError {
+    // Errors that already name the form they're about aren't wrapped again.
+    pub fn in_form(self, form: &Object) -> Self {
+        use self::RuntimeError::*;
+        match self {
+            InForm(..)
+            | MalformedForm(_)
+            | AssertionFailed(_)
+            | MismatchDeclaredType(..)
+            | NotCallable(..)
+            | Signal(_)
+            | InvokeRestart(..)
+            | Cancelled => self,
+            e => InForm(Object::clone(form), Box::new(e)),
+        }
+    }
+
+    pub fn form(&self) -> Option<&Object> {
+        match self {
+            RuntimeError::InForm(form, _) => Some(form),
+            _ => None,
+        }
+    }
+
+    // The error without the form it occurred in.
+    pub fn inner(&self) -> &RuntimeError {
+        match self {
+            RuntimeError::InForm(_, e) => e.inner(),
+            e => e,
+        }
+    }
+
+    pub fn into_inner(self) -> RuntimeError {
+        match self {
+            RuntimeError::InForm(_, e) => e.into_inner(),
+            e => e,
+        }
+    }
+}
+
+pub(crate) fn plural(n: usize) -> &'static str {
+    if n == 1 {
+        ""
+    } else {
+        "s"
+    }
 }
 
 impl fmt::Display for RuntimeError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use self::RuntimeError::*;
         match self {
-            UnboundVariable(name) => write!(f, "Unbound variable: {}", name),
+            UnboundVariable(name, similar) => {
+                write!(f, "Unbound variable: {}", name)?;
+                if let Some((last, rest)) = similar.split_last() {
+                    write!(f, " \u{2014} did you mean ")?;
+                    if !rest.is_empty() {
+                        write!(f, "{} or ", rest.join(", "))?;
+                    }
+                    write!(f, "{}?", last)?;
+                }
+                Ok(())
+            }
             MismatchType(value, expected_type) => {
                 write!(f, "The value {} is not of type {:?}", value, expected_type)
             }
+            NotCallable(form, value) => match &**form {
+                ObjectKind::Cons(call) if call.car != *value => write!(
+                    f,
+                    "Cannot call {}, the value of {}, in {}",
+                    value, call.car, form
+                ),
+                _ => write!(f, "Cannot call {} in {}", value, form),
+            },
+            MismatchDeclaredType(value, expected_type, declaration) => write!(
+                f,
+                "The value {} is not of type {:?} declared by {}",
+                value, expected_type, declaration
+            ),
             WrongNumArgs(actual, expected) => write!(
                 f,
                 "Wrong number of arguments: expected = {}, actual = {}",
@@ -34,6 +219,278 @@ impl fmt::Display for RuntimeError {
                 "Too many arguments ({} arguments provided, at most {} required)",
                 actual, max
             ),
+            WrongArity(name, actual, (min, max)) => {
+                match max {
+                    Some(max) if max == min => {
+                        write!(f, "{} expects {} argument{}", name, min, plural(*min))?
+                    }
+                    Some(max) => write!(f, "{} expects {} to {} arguments", name, min, max)?,
+                    None => write!(
+                        f,
+                        "{} expects at least {} argument{}",
+                        name,
+                        min,
+                        plural(*min)
+                    )?,
+                }
+                write!(f, ", got {}", actual)
+            }
+            MisplacedDefine(name) => {
+                write!(f, "The definition of {} isn't at the start of a body", name)
+            }
+            ModuleNotFound(name) => write!(f, "Cannot find module {} in load path", name),
+            #[cfg(feature = "std")]
+            LoadError(path, e) => write!(f, "Cannot load {}: {}", path, e),
+            ReadError(e) => e.fmt(f),
+            PackageNotFound(name) => write!(f, "Package not found: {}", name),
+            SymbolNotExported(package, symbol) => {
+                write!(f, "The symbol {} is not exported from {}", symbol, package)
+            }
+            MalformedLambdaList(list) => write!(f, "Malformed lambda list: {}", list),
+            MalformedForm(form) => write!(f, "Malformed form: {}", form),
+            AssertionFailed(form) => write!(f, "Assertion failed: {}", form),
+            ClosedPort => write!(f, "The port is closed"),
+            PermissionDenied(action) => write!(f, "Permission denied to {}", action),
+            #[cfg(feature = "std")]
+            InvalidEnvVar(name, value) => {
+                write!(
+                    f,
+                    "Can't set environment variable {:?} to {:?}",
+                    name, value
+                )
+            }
+            #[cfg(feature = "std")]
+            Io(e) => e.fmt(f),
+            Signal(condition) => match &**condition {
+                ObjectKind::Condition(condition) => condition.fmt(f),
+                x => x.fmt(f),
+            },
+            InvokeRestart(..) => write!(f, "Restart invoked outside of its with-restarts"),
+            RestartNotFound(name) => write!(f, "No restart named {} is active", name),
+            Cancelled => write!(f, "The evaluation was cancelled"),
+            NotInCoroutine => write!(f, "yield called outside of a generator"),
+            GeneratorRunning => write!(f, "The generator is already running"),
+            NotTransferable(x) => write!(f, "{} can't be sent through a channel", x),
+            EnvDropped(name) => write!(f, "{} outlived its global environment", name),
+            InvalidFormat(message) => write!(f, "Invalid format string: {}", message),
+            InvalidTime(time) => write!(f, "Invalid date or time: {}", time),
+            IndexOutOfRange(index, len) => {
+                write!(f, "Index {} is out of range for length {}", index, len)
+            }
+            LengthMismatch(x, y) => write!(f, "Lengths {} and {} don't match", x, y),
+            DivisionByZero => write!(f, "Division by zero"),
+            InvalidRadix(radix) => write!(f, "Radix must be between 2 and 36, not {}", radix),
+            UnknownKeyword(key, keys) => {
+                write!(f, "Unknown keyword argument {}, expecting", key)?;
+                match keys.as_slice() {
+                    [] => write!(f, " none"),
+                    keys => write!(f, " one of {}", keys.join(" ")),
+                }
+            }
+            Timeout(seconds) => write!(f, "Timed out after {} seconds", seconds),
+            InForm(form, e) => write!(f, "{} (in {})", e, form),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for RuntimeError {
+    fn from(e: io::Error) -> Self {
+        RuntimeError::Io(e)
+    }
+}
+
+impl StdError for RuntimeError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            RuntimeError::LoadError(_, e) => Some(e),
+            RuntimeError::ReadError(e) => Some(e),
+            #[cfg(feature = "std")]
+            RuntimeError::Io(e) => Some(e),
+            RuntimeError::InForm(_, e) => Some(&**e),
+            _ => None,
         }
     }
 }
+
+#[derive(Debug)]
+pub enum ConvertError {
+    WrongHead(String, Object),
+    MalformedForm(Object),
+    MissingField(String, String),
+    UnknownField(String, String),
+    MismatchType(Object, ObjectType),
+    InField(String, String, Box<ConvertError>),
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use self::ConvertError::*;
+        match self {
+            WrongHead(name, form) => {
+                write!(f, "Expected a ({} ...) form, but got {}", name, form)
+            }
+            MalformedForm(form) => write!(f, "Malformed form: {}", form),
+            MissingField(name, field) => write!(f, "Missing field :{} in {}", field, name),
+            UnknownField(name, field) => write!(f, "Unknown field :{} in {}", field, name),
+            MismatchType(value, expected_type) => {
+                write!(f, "The value {} is not of type {:?}", value, expected_type)
+            }
+            InField(name, field, e) => write!(f, "In field :{} of {}: {}", field, name, e),
+        }
+    }
+}
+
+impl StdError for ConvertError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            ConvertError::InField(_, _, e) => Some(&**e),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Read(ReadError),
+    Runtime(RuntimeError),
+    #[cfg(feature = "std")]
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Read(e) => e.fmt(f),
+            Error::Runtime(e) => e.fmt(f),
+            #[cfg(feature = "std")]
+            Error::Io(e) => e.fmt(f),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Error::Read(e) => Some(e),
+            Error::Runtime(e) => Some(e),
+            #[cfg(feature = "std")]
+            Error::Io(e) => Some(e),
+        }
+    }
+}
+
+impl From<ReadError> for Error {
+    fn from(e: ReadError) -> Self {
+        Error::Read(e)
+    }
+}
+
+impl From<RuntimeError> for Error {
+    fn from(e: RuntimeError) -> Self {
+        match e {
+            RuntimeError::ReadError(e) => Error::Read(e),
+            #[cfg(feature = "std")]
+            RuntimeError::Io(e) => Error::Io(e),
+            e => Error::Runtime(e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn source_test() {
+        let e = RuntimeError::ReadError(ReadError::EndOfFile);
+        assert_eq!(e.source().unwrap().to_string(), "End of file");
+
+        assert!(RuntimeError::UnboundVariable("x".to_string(), Vec::new())
+            .source()
+            .is_none());
+
+        let e = ConvertError::InField(
+            "point".to_string(),
+            "x".to_string(),
+            Box::new(ConvertError::MissingField(
+                "point".to_string(),
+                "y".to_string(),
+            )),
+        );
+        assert_eq!(e.source().unwrap().to_string(), "Missing field :y in point");
+    }
+
+    #[test]
+    fn boxed_error_test() {
+        fn run() -> Result<(), Box<dyn StdError>> {
+            Err(RuntimeError::UnboundVariable("x".to_string(), Vec::new()))?;
+            Ok(())
+        }
+        assert_eq!(run().unwrap_err().to_string(), "Unbound variable: x");
+    }
+
+    #[test]
+    fn unbound_variable_test() {
+        let similar = ["lenth", "length", "lengths"].map(String::from).to_vec();
+        assert_eq!(
+            RuntimeError::UnboundVariable("lenght".to_string(), similar[1..2].to_vec()).to_string(),
+            "Unbound variable: lenght \u{2014} did you mean length?"
+        );
+        assert_eq!(
+            RuntimeError::UnboundVariable("lenght".to_string(), similar).to_string(),
+            "Unbound variable: lenght \u{2014} did you mean lenth, length or lengths?"
+        );
+    }
+
+    #[test]
+    fn in_form_test() {
+        let form = crate::list![crate::object::symbol("car"), crate::object::fixnum(1)];
+        let e = RuntimeError::MismatchType(crate::object::fixnum(1), ObjectType::Cons)
+            .in_form(&form)
+            .in_form(&crate::object::nil());
+        assert_eq!(e.form(), Some(&form));
+        assert!(matches!(e.inner(), RuntimeError::MismatchType(..)));
+        assert_eq!(
+            e.to_string(),
+            "The value 1 is not of type Cons (in (car 1))"
+        );
+        assert!(matches!(e.into_inner(), RuntimeError::MismatchType(..)));
+    }
+
+    #[test]
+    fn from_test() {
+        assert!(matches!(
+            Error::from(RuntimeError::ReadError(ReadError::EndOfFile)),
+            Error::Read(ReadError::EndOfFile)
+        ));
+        assert!(matches!(
+            Error::from(RuntimeError::UnboundVariable("x".to_string(), Vec::new())),
+            Error::Runtime(RuntimeError::UnboundVariable(..))
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn io_error_test() {
+        let e = RuntimeError::LoadError(
+            "foo.lisp".to_string(),
+            io::Error::new(io::ErrorKind::NotFound, "not found"),
+        );
+        assert_eq!(e.source().unwrap().to_string(), "not found");
+
+        assert!(matches!(Error::from(io::Error::other("io")), Error::Io(_)));
+        assert!(matches!(
+            Error::from(RuntimeError::from(io::Error::other("io"))),
+            Error::Io(_)
+        ));
+    }
+}
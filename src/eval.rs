@@ -1,21 +1,71 @@
-use std::cell::RefCell;
-use std::rc::Rc;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 
+use super::accounting;
+use super::bitvector;
+#[cfg(feature = "std")]
+use super::channel::Channel;
+#[cfg(feature = "sync")]
+use super::coroutine;
+use super::date;
+use super::dump::Dump;
 use super::env::Env;
 use super::equal;
-use super::object::{self, Object, ObjectKind, ObjectType};
-use super::error::RuntimeError;
+use super::error::{ReadError, RuntimeError};
+use super::expand;
+use super::format;
+#[cfg(all(feature = "std", not(feature = "sync")))]
+use super::future;
+#[cfg(feature = "sync")]
+use super::generator::Generator;
+use super::load;
+use super::object::{self, BuiltinBody, Object, ObjectKind, ObjectType};
+use super::package::Packages;
+use super::port::Port;
+#[cfg(feature = "std")]
+use super::process;
+#[cfg(feature = "std")]
+use super::profile::Capability;
+use super::profile::Module;
+use super::promise;
+use super::quasiquote;
+use super::rc::{Rc, RefCell};
+use super::restart::Restart;
+#[cfg(feature = "std")]
+use super::snapshot;
+use super::table;
+#[cfg(feature = "std")]
+use super::timer;
+use super::warning::{self, Warning};
+use super::weak;
 
 pub type EvalResult = Result<Object, RuntimeError>;
 
-fn check_num_args(args: &[Object], expected: usize) -> Result<(), RuntimeError> {
+pub(crate) fn check_num_args(args: &[Object], expected: usize) -> Result<(), RuntimeError> {
     if args.len() != expected {
         return Err(RuntimeError::WrongNumArgs(args.len(), expected));
     }
     Ok(())
 }
 
-fn check_num_args_range(
+// Checks a call against the lambda list of the function, so the error names
+// it.
+fn check_arity(
+    name: &str,
+    (min, max): (usize, Option<usize>),
+    args: &[Object],
+) -> Result<(), RuntimeError> {
+    if args.len() < min || max.is_some_and(|max| args.len() > max) {
+        return Err(RuntimeError::WrongArity(
+            name.to_string(),
+            args.len(),
+            (min, max),
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) fn check_num_args_range(
     args: &[Object],
     min: usize,
     max: impl Into<Option<usize>>,
@@ -31,24 +81,105 @@ fn check_num_args_range(
     Ok(())
 }
 
+// Parses &key style arguments, alternating keywords from keys and their
+// values, so builtins take options as Lisp functions do with getf. The
+// values come in the order of keys, None for those not given; as with getf,
+// the first of a repeated keyword wins.
+pub fn key_args<const N: usize>(
+    args: &[Object],
+    keys: [&str; N],
+) -> Result<[Option<Object>; N], RuntimeError> {
+    if !args.len().is_multiple_of(2) {
+        return Err(RuntimeError::MalformedForm(object::from_iter(
+            args.iter().cloned(),
+        )));
+    }
+    let mut values: [Option<Object>; N] = core::array::from_fn(|_| None);
+    for pair in args.chunks(2) {
+        let i = match &*pair[0] {
+            ObjectKind::Symbol(name) => keys.iter().position(|key| key == name),
+            _ => None,
+        }
+        .ok_or_else(|| {
+            RuntimeError::UnknownKeyword(
+                Rc::clone(&pair[0]),
+                keys.iter().map(ToString::to_string).collect(),
+            )
+        })?;
+        values[i].get_or_insert_with(|| Rc::clone(&pair[1]));
+    }
+    Ok(values)
+}
+
 fn eval_quote(args: &[Object]) -> EvalResult {
-    check_num_args(&args, 1)?;
-    return Ok(Rc::clone(&args[0]));
+    check_num_args(args, 1)?;
+    Ok(Rc::clone(&args[0]))
+}
+
+// Semantics that differ between dialects. The default is this lisp's own.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EvalOptions {
+    // The test of an if has to be t or nil.
+    pub strict_booleans: bool,
+}
+
+impl EvalOptions {
+    pub fn scheme() -> Self {
+        Self {
+            strict_booleans: true,
+        }
+    }
 }
 
 fn eval_if(args: &[Object], env: Rc<RefCell<Env>>) -> EvalResult {
-    check_num_args_range(&args, 2, 3)?;
-    match &*eval_internal(Rc::clone(&args[0]), Rc::clone(&env))? {
+    check_num_args_range(args, 2, 3)?;
+    let test = eval_internal(Rc::clone(&args[0]), Rc::clone(&env))?;
+    match &*test {
         ObjectKind::Nil => match args.get(2) {
             Some(x) => eval_internal(Rc::clone(x), Rc::clone(&env)),
             None => Ok(object::nil()),
         },
+        _ if !ObjectType::Boolean.contains(&test)
+            && env.borrow().eval_options().strict_booleans =>
+        {
+            Err(RuntimeError::MismatchType(test, ObjectType::Boolean))
+        }
         _ => eval_internal(Rc::clone(&args[1]), Rc::clone(&env)),
     }
 }
 
+fn eval_assert(args: &[Object], env: Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args(args, 1)?;
+    match &*eval_internal(Rc::clone(&args[0]), env)? {
+        ObjectKind::Nil => Err(RuntimeError::AssertionFailed(Rc::clone(&args[0]))),
+        _ => Ok(object::symbol("t")),
+    }
+}
+
+// (define (f x) body...) is (define f (lambda (x) body...)), and the
+// curried (define ((f x) y) body...) is (define (f x) (lambda (y) body...)).
+pub(crate) fn define_shorthand(args: &[Object]) -> Option<(Object, Object)> {
+    let (signature, body) = args.split_first()?;
+    let mut var = Rc::clone(signature);
+    let mut body = object::from_iter(body.iter().cloned());
+    let mut value = None;
+    while let ObjectKind::Cons(cons) = &*Rc::clone(&var) {
+        let lambda = object::cons(
+            object::symbol("lambda"),
+            object::cons(Rc::clone(&cons.cdr), body),
+        );
+        body = crate::list![Rc::clone(&lambda)];
+        value = Some(lambda);
+        var = Rc::clone(&cons.car);
+    }
+    Some((var, value?))
+}
+
 fn eval_define(args: &[Object], env: Rc<RefCell<Env>>) -> EvalResult {
-    check_num_args(&args, 2)?;
+    if let Some((var, value)) = define_shorthand(args) {
+        return eval_define(&[var, value], env);
+    }
+    check_num_args(args, 2)?;
     let var = Rc::clone(&args[0]);
     let value = Rc::clone(&args[1]);
 
@@ -57,39 +188,144 @@ fn eval_define(args: &[Object], env: Rc<RefCell<Env>>) -> EvalResult {
         _ => return Err(RuntimeError::MismatchType(var, ObjectType::Symbol)),
     };
 
-    let value = eval_internal(value, Rc::clone(&env))?;
+    if warning::is_special_form(name) && Env::is_root(&env) {
+        env.borrow_mut()
+            .warn(Warning::ShadowsSpecialForm(name.clone()));
+    }
+
+    // A lambda defined directly is named after its variable.
+    let value = match &*value {
+        ObjectKind::Cons(cons) if matches!(&*cons.car, ObjectKind::Symbol(s) if s == "lambda") => {
+            let mut iter = cons.cdr.iter();
+            eval_lambda(Some(name.clone()), &mut iter, Rc::clone(&env))?
+        }
+        _ => eval_internal(value, Rc::clone(&env))?,
+    };
+    if Env::is_root(&env) {
+        define_global(name, &value, &env);
+    }
     env.borrow_mut().insert(name, Rc::clone(&value));
     Ok(value)
 }
 
-fn eval_lambda(args_iter: &mut object::ListIter, env: Rc<RefCell<Env>>) -> EvalResult {
-    let list = match args_iter.next() {
-        Some(arg) => match &*arg {
-            ObjectKind::Cons(cons) => cons.iter().collect(),
-            ObjectKind::Nil => Vec::new(),
-            _ => return Err(RuntimeError::MismatchType(arg, ObjectType::List)),
-        },
-        None => return Err(RuntimeError::TooFewArguments(0, 1)),
+// Records where a global is defined, warning when it replaces a builtin or
+// a function or macro that took a different number of arguments.
+fn define_global(name: &str, value: &Object, root: &Rc<RefCell<Env>>) {
+    let previous = root.borrow().get(name);
+    let warning = match (previous.as_deref(), &**value) {
+        (Some(ObjectKind::Func(_)), _) => Some(Warning::ShadowsBuiltin(name.to_string())),
+        (
+            Some(ObjectKind::Closure(old) | ObjectKind::Macro(old)),
+            ObjectKind::Closure(new) | ObjectKind::Macro(new),
+        ) if old.arity() != new.arity() => Some(Warning::ArityChanged(
+            name.to_string(),
+            old.arity(),
+            new.arity(),
+        )),
+        _ => None,
     };
+    let mut root = root.borrow_mut();
+    if let Some(warning) = warning {
+        root.warn(warning);
+    }
+    root.record_source(name);
+}
+
+pub(crate) fn parse_lambda_list(
+    list: Object,
+) -> Result<(Vec<String>, Option<String>), RuntimeError> {
+    let params_list = object::to_vec(&list)?;
 
     let mut params = Vec::new();
-    for param in list {
-        match &*param {
+    let mut rest = None;
+    let mut iter = params_list.iter();
+    while let Some(param) = iter.next() {
+        match &**param {
+            ObjectKind::Symbol(name) if name == "&rest" => {
+                rest = match (iter.next(), iter.next()) {
+                    (Some(param), None) => Some(symbol_name(param)?.to_string()),
+                    _ => return Err(RuntimeError::MalformedLambdaList(list)),
+                };
+            }
             ObjectKind::Symbol(name) => params.push(name.clone()), // XXX
             _ => {
                 return Err(RuntimeError::MismatchType(
-                    Rc::clone(&param),
+                    Rc::clone(param),
                     ObjectType::Symbol,
                 ))
             }
         }
     }
 
-    return Ok(object::closure(params, args_iter.collect(), env));
+    Ok((params, rest))
+}
+
+// A leading string is a docstring, unless it's the only body form.
+fn parse_body(mut body: Vec<Object>) -> (Option<String>, Vec<Object>) {
+    match body.first().map(|x| &**x) {
+        Some(ObjectKind::String(doc)) if body.len() > 1 => {
+            let doc = doc.clone();
+            body.remove(0);
+            (Some(doc), body)
+        }
+        _ => (None, body),
+    }
+}
+
+fn eval_lambda(
+    name: Option<String>,
+    args_iter: &mut object::ListIter,
+    env: Rc<RefCell<Env>>,
+) -> EvalResult {
+    let (params, rest) = match args_iter.next() {
+        Some(list) => parse_lambda_list(list)?,
+        None => return Err(RuntimeError::TooFewArguments(0, 1)),
+    };
+    let (doc, body) = parse_body(args_iter.collect());
+    // Lambdas in function bodies are checked along with the outermost one.
+    check_body(&params, &rest, &body, &env)?;
+    if Env::is_root(&env) {
+        let warnings = warning::check_lambda(name.as_deref(), &params, &rest, &body, &env);
+        for w in warnings {
+            env.borrow_mut().warn(w);
+        }
+    }
+    Ok(object::closure(name, params, rest, doc, body, env))
+}
+
+fn eval_defmacro(args_iter: &mut object::ListIter, env: Rc<RefCell<Env>>) -> EvalResult {
+    let (name, (params, rest)) = match (args_iter.next(), args_iter.next()) {
+        (Some(name), Some(list)) => (name, parse_lambda_list(list)?),
+        (name, _) => return Err(RuntimeError::TooFewArguments(name.iter().count(), 2)),
+    };
+    let (doc, body) = parse_body(args_iter.collect());
+    check_body(&params, &rest, &body, &env)?;
+    let macro_name = symbol_name(&name)?;
+    let value = object::macro_(
+        Some(macro_name.to_string()),
+        params,
+        rest,
+        doc,
+        body,
+        Rc::clone(&env),
+    );
+    if Env::is_root(&env) {
+        define_global(macro_name, &value, &env);
+    }
+    env.borrow_mut().insert(macro_name, value);
+    Ok(name)
+}
+
+fn eval_progn(args: &[Object], env: Rc<RefCell<Env>>) -> EvalResult {
+    let mut result = object::nil();
+    for form in args {
+        result = eval_internal(Rc::clone(form), Rc::clone(&env))?;
+    }
+    Ok(result)
 }
 
 fn eval_set(args: &[Object], env: Rc<RefCell<Env>>) -> EvalResult {
-    check_num_args(&args, 2)?;
+    check_num_args(args, 2)?;
     let var = Rc::clone(&args[0]);
     let value = Rc::clone(&args[1]);
 
@@ -103,167 +339,2285 @@ fn eval_set(args: &[Object], env: Rc<RefCell<Env>>) -> EvalResult {
     if env.set(name, Rc::clone(&value)) {
         Ok(value)
     } else {
-        Err(RuntimeError::UnboundVariable(name.clone()))
+        Err(unbound(name, &env))
     }
 }
 
-fn apply_closure(closure: &object::Closure, args: Vec<Object>) -> EvalResult {
-    if closure.parameters.len() != args.len() {
-        return Err(RuntimeError::WrongNumArgs(
-            args.len(),
-            closure.parameters.len(),
-        ));
+fn eval_symbol_arg(arg: &Object, env: Rc<RefCell<Env>>) -> Result<String, RuntimeError> {
+    let value = eval_internal(Rc::clone(arg), env)?;
+    match &*value {
+        ObjectKind::Symbol(name) => Ok(name.clone()),
+        _ => Err(RuntimeError::MismatchType(value, ObjectType::Symbol)),
     }
+}
 
-    let parent = Rc::clone(&closure.env);
-    let env = Rc::new(RefCell::new(Env::new(Some(parent))));
+fn eval_require(args: &[Object], env: Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args(args, 1)?;
+    let name = eval_symbol_arg(&args[0], Rc::clone(&env))?;
+    load::require(&name, env)
+}
+
+fn eval_provide(args: &[Object], env: Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args(args, 1)?;
+    let name = eval_symbol_arg(&args[0], Rc::clone(&env))?;
+    load::provide(&name, &env)
+}
 
-    for (param, arg) in closure.parameters.iter().zip(args.iter()) {
-        env.borrow_mut().insert(param, Rc::clone(arg));
+#[cfg(feature = "std")]
+fn eval_load(args: &[Object], env: Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args(args, 1)?;
+    let path = eval_internal(Rc::clone(&args[0]), Rc::clone(&env))?;
+    match &*path {
+        ObjectKind::String(path) => load::load_file(path.as_ref(), Env::root(&env)),
+        _ => Err(RuntimeError::MismatchType(path, ObjectType::String)),
     }
+}
 
-    let mut result = object::nil();
-    for form in closure.body.iter() {
-        result = eval_internal(Rc::clone(form), Rc::clone(&env))?;
+#[cfg(feature = "std")]
+fn eval_watch_load(args: &[Object], env: Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args(args, 1)?;
+    let path = eval_internal(Rc::clone(&args[0]), Rc::clone(&env))?;
+    match &*path {
+        ObjectKind::String(path) => load::watch_load(path.as_ref(), env),
+        _ => Err(RuntimeError::MismatchType(path, ObjectType::String)),
     }
+}
 
-    Ok(result)
+#[cfg(feature = "std")]
+fn save_image(args: &[Object], env: &Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args(args, 1)?;
+    match &*args[0] {
+        ObjectKind::String(name) => {
+            Env::permissions(env).check_write(name)?;
+            std::fs::write(name, snapshot::image(env))?;
+            Ok(Rc::clone(&args[0]))
+        }
+        _ => Err(RuntimeError::MismatchType(
+            Rc::clone(&args[0]),
+            ObjectType::String,
+        )),
+    }
 }
 
-fn apply_function(first: Object, iter: object::ListIter, env: Rc<RefCell<Env>>) -> EvalResult {
-    let first = eval_internal(first, Rc::clone(&env))?;
-    let mut args = Vec::new();
-    for arg in iter {
-        args.push(eval_internal(arg, Rc::clone(&env))?);
+#[cfg(feature = "std")]
+fn open_file(path: &Object, direction: &str, env: &Rc<RefCell<Env>>) -> EvalResult {
+    let name = match &**path {
+        ObjectKind::String(name) => name,
+        _ => {
+            return Err(RuntimeError::MismatchType(
+                Rc::clone(path),
+                ObjectType::String,
+            ))
+        }
+    };
+    let permissions = Env::permissions(env);
+    let port = match direction {
+        ":input" => {
+            permissions.check_read(name)?;
+            Port::open_input_file(name)?
+        }
+        ":output" => {
+            permissions.check_write(name)?;
+            Port::open_output_file(name)?
+        }
+        _ => {
+            return Err(RuntimeError::MismatchType(
+                object::symbol(direction),
+                ObjectType::Keyword,
+            ))
+        }
+    };
+    Ok(object::port(port))
+}
+
+#[cfg(feature = "std")]
+fn open_input_file(args: &[Object], env: &Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args(args, 1)?;
+    open_file(&args[0], ":input", env)
+}
+
+#[cfg(feature = "std")]
+fn open_output_file(args: &[Object], env: &Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args(args, 1)?;
+    open_file(&args[0], ":output", env)
+}
+
+// (with-open-file (var path [:direction :input|:output]) body...) closes
+// the port however body finishes.
+#[cfg(feature = "std")]
+fn eval_with_open_file(args: &[Object], env: Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args_range(args, 1, None)?;
+    let spec = object::to_vec(&args[0])?;
+    let (var, path, options) = match spec.as_slice() {
+        [var, path, options @ ..] => (var, path, options),
+        _ => return Err(RuntimeError::MalformedForm(Rc::clone(&args[0]))),
+    };
+    let [direction] = key_args(options, [":direction"])?;
+    let direction = match &direction {
+        Some(direction) => symbol_name(direction)?,
+        None => ":input",
+    };
+    let var = symbol_name(var)?;
+    let path = eval_internal(Rc::clone(path), Rc::clone(&env))?;
+    let port = open_file(&path, direction, &env)?;
+
+    check_body(&[var.to_string()], &None, &args[1..], &env)?;
+    let body_env = Rc::new(RefCell::new(Env::new(Some(Rc::clone(&env)))));
+    body_env.borrow_mut().insert(var, Rc::clone(&port));
+    unwind_protect(
+        &env,
+        || eval_progn(&args[1..], body_env),
+        || match &*port {
+            ObjectKind::Port(port) => port.close(),
+            _ => Ok(()),
+        },
+    )
+}
+
+// Runs cleanup after protected however it finishes, even by an error or a
+// restart unwinding through it, and returns what protected did, multiple
+// values included. An error from cleanup takes the place of protected's.
+fn unwind_protect(
+    env: &Rc<RefCell<Env>>,
+    protected: impl FnOnce() -> EvalResult,
+    cleanup: impl FnOnce() -> Result<(), RuntimeError>,
+) -> EvalResult {
+    let result = protected();
+    let root = Env::root(env);
+    let values = root.borrow_mut().take_values();
+    cleanup()?;
+    root.borrow_mut().set_values(values);
+    result
+}
+
+// (unwind-protect protected cleanup...)
+fn eval_unwind_protect(args: &[Object], env: Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args_range(args, 1, None)?;
+    unwind_protect(
+        &env,
+        || eval_internal(Rc::clone(&args[0]), Rc::clone(&env)),
+        || eval_progn(&args[1..], Rc::clone(&env)).map(drop),
+    )
+}
+
+// (definition-source 'name) is (file line) for a global defined by a file
+// being loaded, where file is nil if it was loaded from a string, and nil
+// for anything else.
+fn definition_source(args: &[Object], env: &Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args(args, 1)?;
+    let source = env.borrow().definition_source(symbol_name(&args[0])?);
+    Ok(match source {
+        Some(source) => crate::list![
+            source
+                .file
+                .as_deref()
+                .map_or_else(object::nil, object::string),
+            object::fixnum(source.line as isize)
+        ],
+        None => object::nil(),
+    })
+}
+
+fn eval_quasiquote(args: &[Object], env: Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args(args, 1)?;
+    eval_internal(quasiquote::expand(&args[0])?, env)
+}
+
+fn eval_doc(args: &[Object], env: Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args(args, 1)?;
+    let name = eval_symbol_arg(&args[0], Rc::clone(&env))?;
+    let value = env
+        .borrow()
+        .get(&name)
+        .ok_or_else(|| unbound(&name, &env.borrow()))?;
+    match &*value {
+        ObjectKind::Closure(closure) | ObjectKind::Macro(closure) => Ok(closure
+            .doc
+            .as_deref()
+            .map_or_else(object::nil, object::string)),
+        _ => Ok(object::nil()),
     }
+}
 
-    match &*first {
-        ObjectKind::Func(func) => func(&args),
-        ObjectKind::Closure(closure) => apply_closure(closure, args),
-        _ => Err(RuntimeError::MismatchType(first, ObjectType::Function)),
+// (macro-step form [index]) expands the index-th of (macro-calls form),
+// the first by default, once; the other calls are left for later steps.
+fn eval_macro_step(args: &[Object], env: Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args_range(args, 1, 2)?;
+    let form = eval_internal(Rc::clone(&args[0]), Rc::clone(&env))?;
+    let index = match args.get(1) {
+        Some(index) => eval_internal(Rc::clone(index), Rc::clone(&env))?,
+        None => object::fixnum(0),
+    };
+    match *index {
+        ObjectKind::Fixnum(n) if n >= 0 => expand::macro_step(&form, &env, n as usize),
+        _ => Err(RuntimeError::MismatchType(index, ObjectType::Number)),
     }
 }
 
-fn eval_internal(x: Object, env: Rc<RefCell<Env>>) -> EvalResult {
-    match &*x {
-        ObjectKind::Nil | ObjectKind::Fixnum(_) | ObjectKind::Func(_) | ObjectKind::Closure(_) => {
-            Ok(x)
+fn eval_macro_calls(args: &[Object], env: Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args(args, 1)?;
+    let form = eval_internal(Rc::clone(&args[0]), Rc::clone(&env))?;
+    expand::macro_calls(&form, &env).map(object::from_iter)
+}
+
+fn eval_with_output_to_string(args: &[Object], env: Rc<RefCell<Env>>) -> EvalResult {
+    let port = object::port(Port::output_string());
+    let root = Env::root(&env);
+    let previous = root.borrow().get("*standard-output*").ok_or_else(|| {
+        RuntimeError::UnboundVariable("*standard-output*".to_string(), Vec::new())
+    })?;
+    root.borrow_mut().set("*standard-output*", Rc::clone(&port));
+    let result = eval_progn(args, env);
+    root.borrow_mut().set("*standard-output*", previous);
+    result?;
+    match &*port {
+        ObjectKind::Port(port) => Ok(object::string(&port.output().unwrap_or_default())),
+        _ => unreachable!(),
+    }
+}
+
+// (values x...) returns x, or nil without arguments, and keeps all of its
+// arguments for an enclosing multiple-value-list. Evaluating any other form
+// or calling a function drops them, so only forms that return the value of
+// a values form, like if and progn or a function whose body ends with it,
+// return multiple values.
+fn values(args: &[Object], env: &Rc<RefCell<Env>>) -> EvalResult {
+    let primary = args.first().cloned().unwrap_or_else(object::nil);
+    env.borrow_mut().set_values(Some(args.to_vec()));
+    Ok(primary)
+}
+
+fn eval_multiple_value_list(args: &[Object], env: Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args(args, 1)?;
+    let result = eval_internal(Rc::clone(&args[0]), Rc::clone(&env))?;
+    match Env::root(&env).borrow_mut().take_values() {
+        Some(values) => Ok(object::from_iter(values)),
+        None => Ok(object::from_iter([result])),
+    }
+}
+
+pub(crate) enum LoopClause {
+    In(String, Object),
+    FromTo(String, Object, Object),
+    Collect(Object),
+    When(Object),
+    While(Object),
+    Do(Vec<Object>),
+}
+
+pub(crate) fn parse_loop(form: &Object, args: &[Object]) -> Result<Vec<LoopClause>, RuntimeError> {
+    let malformed = || RuntimeError::MalformedForm(Rc::clone(form));
+    fn keyword(x: Option<&Object>) -> Option<&str> {
+        match x.map(|x| &**x) {
+            Some(ObjectKind::Symbol(name)) => Some(name),
+            _ => None,
         }
-        ObjectKind::Symbol(s) => env
-            .borrow()
-            .get(s)
-            .ok_or(RuntimeError::UnboundVariable(s.to_string())),
-        ObjectKind::Cons(list) => {
-            let mut iter = list.iter();
-            let first = iter.next().unwrap();
+    }
+    let mut clauses = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        let clause = match keyword(args.get(i)) {
+            Some("for") => {
+                let var = symbol_name(args.get(i + 1).ok_or_else(malformed)?)?.to_string();
+                match (keyword(args.get(i + 2)), keyword(args.get(i + 4))) {
+                    (Some("in"), _) => {
+                        let list = args.get(i + 3).ok_or_else(malformed)?;
+                        i += 4;
+                        LoopClause::In(var, Rc::clone(list))
+                    }
+                    (Some("from"), Some("to")) => {
+                        let to = args.get(i + 5).ok_or_else(malformed)?;
+                        i += 6;
+                        LoopClause::FromTo(var, Rc::clone(&args[i - 3]), Rc::clone(to))
+                    }
+                    _ => return Err(malformed()),
+                }
+            }
+            Some(name @ ("collect" | "when" | "while")) => {
+                let x = Rc::clone(args.get(i + 1).ok_or_else(malformed)?);
+                i += 2;
+                match name {
+                    "collect" => LoopClause::Collect(x),
+                    "when" => LoopClause::When(x),
+                    _ => LoopClause::While(x),
+                }
+            }
+            // do takes the compound forms up to the next clause.
+            Some("do") => {
+                i += 1;
+                let from = i;
+                while matches!(args.get(i).map(|x| &**x), Some(ObjectKind::Cons(_))) {
+                    i += 1;
+                }
+                LoopClause::Do(args[from..i].to_vec())
+            }
+            _ => return Err(malformed()),
+        };
+        clauses.push(clause);
+    }
+    Ok(clauses)
+}
 
-            if let ObjectKind::Symbol(name) = &*first {
-                match &**name {
-                    "quote" => {
-                        let args: Vec<Object> = iter.collect();
-                        return eval_quote(&args);
+fn loop_fixnum(x: Object) -> Result<isize, RuntimeError> {
+    match *x {
+        ObjectKind::Fixnum(n) => Ok(n),
+        _ => Err(RuntimeError::MismatchType(x, ObjectType::Number)),
+    }
+}
+
+// (loop clause...) runs its clauses in order on each iteration:
+//
+//   for var in list       binds var to each element of list
+//   for var from a to b   binds var to each integer from a to b inclusive
+//   collect form          adds the value of form to the result
+//   when form             skips the rest of the iteration if form is false
+//   while form            stops the loop if form is false
+//   do form...            evaluates the forms
+//
+// The loop stops when any for clause runs out, and returns the collected
+// values, or nil if it has no collect clause. It iterates without growing
+// the stack, unlike a recursive function.
+fn eval_loop(form: &Object, args: &[Object], env: Rc<RefCell<Env>>) -> EvalResult {
+    enum Iteration {
+        In(Object),
+        FromTo(isize, isize),
+    }
+
+    let clauses = parse_loop(form, args)?;
+    let env = Rc::new(RefCell::new(Env::new(Some(env))));
+    let mut iterations = Vec::new();
+    for clause in &clauses {
+        match clause {
+            LoopClause::In(var, list) => {
+                let list = eval_internal(Rc::clone(list), Rc::clone(&env))?;
+                env.borrow_mut().insert(var, object::nil());
+                iterations.push((var, Iteration::In(list)));
+            }
+            LoopClause::FromTo(var, from, to) => {
+                let from = loop_fixnum(eval_internal(Rc::clone(from), Rc::clone(&env))?)?;
+                let to = loop_fixnum(eval_internal(Rc::clone(to), Rc::clone(&env))?)?;
+                env.borrow_mut().insert(var, object::nil());
+                iterations.push((var, Iteration::FromTo(from, to)));
+            }
+            _ => (),
+        }
+    }
+
+    let mut collected = Vec::new();
+    'iterate: loop {
+        for (var, iteration) in &mut iterations {
+            let value = match iteration {
+                Iteration::In(list) => match &*Rc::clone(list) {
+                    ObjectKind::Nil => break 'iterate,
+                    ObjectKind::Cons(cons) => {
+                        *list = Rc::clone(&cons.cdr);
+                        Rc::clone(&cons.car)
                     }
-                    "if" => {
-                        let args: Vec<Object> = iter.collect();
-                        return eval_if(&args, env);
+                    _ => {
+                        return Err(RuntimeError::MismatchType(
+                            Rc::clone(list),
+                            ObjectType::List,
+                        ))
                     }
-                    "define" => {
-                        let args: Vec<Object> = iter.collect();
-                        return eval_define(&args, env);
+                },
+                Iteration::FromTo(from, to) => {
+                    if from > to {
+                        break 'iterate;
                     }
-                    "lambda" => {
-                        return eval_lambda(&mut iter, env);
+                    *from += 1;
+                    object::fixnum(*from - 1)
+                }
+            };
+            env.borrow_mut().set(var, value);
+        }
+        for clause in &clauses {
+            match clause {
+                LoopClause::Collect(x) => {
+                    collected.push(eval_internal(Rc::clone(x), Rc::clone(&env))?);
+                }
+                LoopClause::When(x) => {
+                    if let ObjectKind::Nil = *eval_internal(Rc::clone(x), Rc::clone(&env))? {
+                        break;
                     }
-                    "set!" => {
-                        let args: Vec<Object> = iter.collect();
-                        return eval_set(&args, env);
+                }
+                LoopClause::While(x) => {
+                    if let ObjectKind::Nil = *eval_internal(Rc::clone(x), Rc::clone(&env))? {
+                        break 'iterate;
                     }
-                    _ => (),
                 }
+                LoopClause::Do(forms) => {
+                    for x in forms {
+                        eval_internal(Rc::clone(x), Rc::clone(&env))?;
+                    }
+                }
+                LoopClause::In(..) | LoopClause::FromTo(..) => (),
             }
-            apply_function(first, iter, env)
         }
     }
+    Ok(object::from_iter(collected))
 }
 
-mod builtin {
-    use super::object::Object;
-    use super::*;
+// (add-finalizer x function) calls function without arguments once x has
+// been freed, which is checked after each top-level form, and by
+// (run-finalizers). Since function runs after x is gone it can't refer to
+// x, or x would never be freed; it should close over the resources x used.
+fn add_finalizer(args: &[Object], env: &Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args(args, 2)?;
+    let (x, function) = (&args[0], &args[1]);
+    if !ObjectType::Function.contains(function) {
+        return Err(RuntimeError::MismatchType(
+            Rc::clone(function),
+            ObjectType::Function,
+        ));
+    }
+    env.borrow_mut().add_finalizer(x, Rc::clone(function));
+    Ok(Rc::clone(x))
+}
 
-    pub fn plus(args: &[Object]) -> EvalResult {
-        let mut acc = 0;
-        for arg in args {
-            match **arg {
-                ObjectKind::Fixnum(n) => {
-                    acc += n;
-                }
-                _ => {
-                    return Err(RuntimeError::MismatchType(
-                        Rc::clone(arg),
-                        ObjectType::Number,
-                    ))
-                }
+fn run_finalizers(args: &[Object], env: &Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args(args, 0)?;
+    Ok(object::fixnum(run_due_finalizers(env) as isize))
+}
+
+// Runs the finalizers that are due, including those of objects freed by
+// other finalizers, and returns how many ran. The due ones are already
+// taken off the list, so one that fails doesn't stop the rest; each error
+// becomes a warning.
+fn run_due_finalizers(env: &Rc<RefCell<Env>>) -> usize {
+    let root = Env::root(env);
+    let mut count = 0;
+    loop {
+        let due = root.borrow_mut().take_due_finalizers();
+        if due.is_empty() {
+            return count;
+        }
+        for finalizer in due {
+            count += 1;
+            if let Err(e) = apply(finalizer, Vec::new()) {
+                root.borrow_mut()
+                    .warn(Warning::FinalizerFailed(e.to_string()));
             }
         }
-        Ok(object::fixnum(acc))
     }
+}
 
-    pub fn is_atom(args: &[Object]) -> EvalResult {
-        check_num_args(args, 1)?;
-        match &*args[0] {
-            ObjectKind::Cons(_) => Ok(object::nil()),
-            _ => Ok(object::symbol("t")),
+// (parameterize ((var value) ...) body...) sets the variables, which must
+// already be defined, to the values while body runs, and then back to what
+// they were, even if body fails or a restart unwinds through it. Unlike let
+// the new values are seen by every function body calls, as with
+// *standard-output* in with-output-to-string.
+fn eval_parameterize(args: &[Object], env: Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args_range(args, 1, None)?;
+    let mut bindings = Vec::new();
+    for binding in object::to_vec(&args[0])? {
+        match object::to_vec(&binding)?.as_slice() {
+            [var, value] => {
+                let name = symbol_name(var)?.to_string();
+                let value = eval_internal(Rc::clone(value), Rc::clone(&env))?;
+                bindings.push((name, value));
+            }
+            _ => return Err(RuntimeError::MalformedForm(binding)),
         }
     }
+    with_dynamic_bindings(&bindings, &env, || eval_progn(&args[1..], Rc::clone(&env)))
+}
 
-    pub fn cons(args: &[Object]) -> EvalResult {
-        check_num_args(args, 2)?;
-        Ok(object::cons(Rc::clone(&args[0]), Rc::clone(&args[1])))
+// Sets the variables to the values while f runs, and then back to what
+// they were.
+fn with_dynamic_bindings(
+    bindings: &[(String, Object)],
+    env: &Rc<RefCell<Env>>,
+    f: impl FnOnce() -> EvalResult,
+) -> EvalResult {
+    let mut saved = Vec::new();
+    for (name, _) in bindings {
+        let previous = env
+            .borrow()
+            .get(name)
+            .ok_or_else(|| unbound(name, &env.borrow()))?;
+        saved.push((name.as_str(), previous));
+    }
+    for (name, value) in bindings {
+        env.borrow_mut().set(name, Rc::clone(value));
+    }
+    let result = f();
+    for (name, previous) in saved.into_iter().rev() {
+        env.borrow_mut().set(name, previous);
+    }
+    result
+}
+
+// (defvar name value) defines the global variable unless it's bound
+// already, so reloading a file keeps what was set since, and
+// (defparameter name value) sets it either way. Both make it special: a
+// function or let with a parameter of that name binds the global variable
+// while it runs, as parameterize does, instead of a local one.
+fn eval_defvar(args: &[Object], env: Rc<RefCell<Env>>, always: bool) -> EvalResult {
+    check_num_args(args, 2)?;
+    let name = symbol_name(&args[0])?;
+    let root = Env::root(&env);
+    root.borrow_mut().proclaim_special(name);
+    if always || root.borrow().get(name).is_none() {
+        let value = eval_internal(Rc::clone(&args[1]), env)?;
+        define_global(name, &value, &root);
+        root.borrow_mut().insert(name, value);
     }
+    Ok(Rc::clone(&args[0]))
+}
 
-    fn cxr<F>(args: &[Object], accessor: F) -> EvalResult
-    where
-        F: Fn(&object::Cons) -> Object,
-    {
-        check_num_args(args, 1)?;
-        match &*args[0] {
-            ObjectKind::Cons(cons) => Ok(accessor(cons)),
-            _ => Err(RuntimeError::MismatchType(
-                Rc::clone(&args[0]),
-                ObjectType::Cons,
-            )),
+fn symbol_name(x: &Object) -> Result<&str, RuntimeError> {
+    match &**x {
+        ObjectKind::Symbol(name) => Ok(name),
+        _ => Err(RuntimeError::MismatchType(Rc::clone(x), ObjectType::Symbol)),
+    }
+}
+
+fn eval_defpackage(args: &[Object], env: Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args_range(args, 1, None)?;
+    let name = symbol_name(&args[0])?;
+    let root = Env::root(&env);
+    let mut root = root.borrow_mut();
+    let packages = root.packages_mut();
+    packages.define(name);
+
+    let previous = packages.current().to_string();
+    packages.set_current(name)?;
+    let result = define_package_options(&args[1..], packages);
+    packages.set_current(&previous)?;
+    result?;
+
+    Ok(Rc::clone(&args[0]))
+}
+
+fn define_package_options(options: &[Object], packages: &mut Packages) -> Result<(), RuntimeError> {
+    for option in options {
+        let (keyword, symbols): (Object, Vec<Object>) = match &**option {
+            ObjectKind::Cons(cons) => (Rc::clone(&cons.car), cons.iter().skip(1).collect()),
+            _ => {
+                return Err(RuntimeError::MismatchType(
+                    Rc::clone(option),
+                    ObjectType::List,
+                ))
+            }
+        };
+        for symbol in symbols.iter() {
+            match symbol_name(&keyword)? {
+                ":export" => packages.export(symbol_name(symbol)?),
+                ":import" => packages.import(symbol_name(symbol)?)?,
+                _ => return Err(RuntimeError::MismatchType(keyword, ObjectType::Keyword)),
+            }
         }
     }
+    Ok(())
+}
 
-    pub fn car(args: &[Object]) -> EvalResult {
-        cxr(args, |cons| Rc::clone(&cons.car))
+fn eval_in_package(args: &[Object], env: Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args(args, 1)?;
+    let name = symbol_name(&args[0])?;
+    Env::root(&env)
+        .borrow_mut()
+        .packages_mut()
+        .set_current(name)?;
+    Ok(Rc::clone(&args[0]))
+}
+
+fn eval_export(args: &[Object], env: Rc<RefCell<Env>>) -> EvalResult {
+    for arg in args {
+        let name = eval_symbol_arg(arg, Rc::clone(&env))?;
+        Env::root(&env).borrow_mut().packages_mut().export(&name);
     }
+    Ok(object::symbol("t"))
+}
 
-    pub fn cdr(args: &[Object]) -> EvalResult {
-        cxr(args, |cons| Rc::clone(&cons.cdr))
+fn eval_import(args: &[Object], env: Rc<RefCell<Env>>) -> EvalResult {
+    for arg in args {
+        let name = eval_symbol_arg(arg, Rc::clone(&env))?;
+        Env::root(&env).borrow_mut().packages_mut().import(&name)?;
     }
+    Ok(object::symbol("t"))
+}
 
-    pub fn equal(args: &[Object]) -> EvalResult {
-        check_num_args(args, 2)?;
-        if equal::equal(Rc::clone(&args[0]), Rc::clone(&args[1])) {
-            Ok(object::symbol("t"))
+pub(crate) fn apply_closure(closure: &object::Closure, args: Vec<Object>) -> EvalResult {
+    check_arity(
+        closure.name.as_deref().unwrap_or("lambda"),
+        closure.arity(),
+        &args,
+    )?;
+
+    let parent = Rc::clone(&closure.env);
+    let root = Env::root(&parent);
+    let env = Rc::new(RefCell::new(Env::new(Some(parent))));
+    env.borrow_mut().set_eval_options(closure.eval_options);
+
+    let mut bindings: Vec<(&String, Object)> = closure
+        .parameters
+        .iter()
+        .zip(args.iter().cloned())
+        .collect();
+    if let Some(rest) = &closure.rest {
+        let rest_args = object::from_iter(args[closure.parameters.len()..].iter().cloned());
+        bindings.push((rest, rest_args));
+    }
+    // Special variables are bound the way parameterize binds them.
+    let mut specials = Vec::new();
+    let has_specials = root.borrow().has_specials();
+    for (param, arg) in bindings {
+        if has_specials && root.borrow().is_special(param) {
+            specials.push((param.clone(), arg));
         } else {
-            Ok(object::nil())
+            env.borrow_mut().insert(param, arg);
+        }
+    }
+
+    if specials.is_empty() {
+        return eval_progn(&closure.body, env);
+    }
+    with_dynamic_bindings(&specials, &root, || eval_progn(&closure.body, env))
+}
+
+// A body may start with definitions, which bind in its own frame, and
+// define anywhere else in it is an error. That's checked when the lambda
+// is made, with the macro calls in the body expanded to see what they
+// define. The lambdas inside are checked when they're made in turn.
+struct Placement<'a> {
+    env: &'a Rc<RefCell<Env>>,
+    // The parameters of the lambda, which shadow macros of the same name.
+    params: &'a [String],
+}
+
+impl Placement<'_> {
+    fn head<'x>(&self, x: &'x [Object]) -> Option<&'x str> {
+        match x.first().map(|head| &**head) {
+            Some(ObjectKind::Symbol(name)) if !self.params.contains(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    // Expands x until it isn't a macro call or quasiquote. One that fails
+    // is left as it is, to fail again when it's evaluated.
+    fn expand(&self, x: &Object) -> Object {
+        let mut x = Rc::clone(x);
+        loop {
+            let list = x.as_proper_list().unwrap_or_default();
+            let function = match (self.head(&list), list.get(1)) {
+                (Some("quasiquote"), Some(template)) => match quasiquote::expand(template) {
+                    Ok(expansion) => {
+                        x = expansion;
+                        continue;
+                    }
+                    Err(_) => return x,
+                },
+                (Some(name), _) => self.env.borrow().get(name),
+                (None, _) => return x,
+            };
+            x = match function.as_deref() {
+                Some(ObjectKind::Macro(closure)) => {
+                    match apply_closure(closure, list[1..].to_vec()) {
+                        Ok(expansion) => expansion,
+                        Err(_) => return x,
+                    }
+                }
+                _ => return x,
+            };
+        }
+    }
+
+    fn body(&self, body: &[Object]) -> Result<(), RuntimeError> {
+        let mut definitions = true;
+        for form in body {
+            let form = self.expand(form);
+            definitions = definitions && self.is_definition(&form);
+            if definitions {
+                self.definition(&form)?;
+            } else {
+                self.expression(&form)?;
+            }
+        }
+        Ok(())
+    }
+
+    // (define ...), or a progn of definitions as a macro may expand to.
+    fn is_definition(&self, x: &Object) -> bool {
+        let list = x.as_proper_list().unwrap_or_default();
+        match self.head(&list) {
+            Some("define") => true,
+            Some("progn") => list[1..]
+                .iter()
+                .all(|form| self.is_definition(&self.expand(form))),
+            _ => false,
+        }
+    }
+
+    fn definition(&self, x: &Object) -> Result<(), RuntimeError> {
+        let list = x.as_proper_list().unwrap_or_default();
+        match self.head(&list) {
+            Some("progn") => list[1..]
+                .iter()
+                .try_for_each(|form| self.definition(&self.expand(form))),
+            // (define (f x) ...) defines a lambda.
+            _ if define_shorthand(&list[1..]).is_some() => Ok(()),
+            _ => list.get(2).map_or(Ok(()), |value| self.expression(value)),
+        }
+    }
+
+    fn expression(&self, x: &Object) -> Result<(), RuntimeError> {
+        let x = self.expand(x);
+        let list = match x.as_proper_list() {
+            Some(list) if !list.is_empty() => list,
+            _ => return Ok(()),
+        };
+        match self.head(&list) {
+            Some("quote" | "declare" | "lambda" | "defmacro") => Ok(()),
+            Some("define") => {
+                let name = list.get(1).map_or_else(String::new, ToString::to_string);
+                Err(RuntimeError::MisplacedDefine(name).in_form(&x))
+            }
+            // Its body is checked when it's evaluated.
+            Some("with-open-file") => {
+                let spec = list[1].as_proper_list().unwrap_or_default();
+                spec.iter()
+                    .skip(1)
+                    .try_for_each(|form| self.expression(form))
+            }
+            Some(_) => list[1..].iter().try_for_each(|form| self.expression(form)),
+            None => list.iter().try_for_each(|form| self.expression(form)),
+        }
+    }
+}
+
+fn check_body(
+    params: &[String],
+    rest: &Option<String>,
+    body: &[Object],
+    env: &Rc<RefCell<Env>>,
+) -> Result<(), RuntimeError> {
+    let params: Vec<String> = params.iter().chain(rest).cloned().collect();
+    Placement {
+        env,
+        params: &params,
+    }
+    .body(body)
+}
+
+fn frame_name(function: &Object) -> Object {
+    match &**function {
+        ObjectKind::Func(builtin) => object::symbol(&builtin.name),
+        ObjectKind::Closure(closure) => object::symbol(closure.name.as_deref().unwrap_or("lambda")),
+        _ => Rc::clone(function),
+    }
+}
+
+fn apply_function(
+    form: &Object,
+    first: Object,
+    iter: object::ListIter,
+    env: Rc<RefCell<Env>>,
+) -> EvalResult {
+    let first = eval_internal(first, Rc::clone(&env))?;
+    match &*first {
+        ObjectKind::Macro(closure) => {
+            let expansion = apply_closure(closure, iter.collect())?;
+            return eval_internal(expansion, env);
+        }
+        ObjectKind::Func(_) | ObjectKind::Closure(_) => (),
+        _ => return Err(RuntimeError::NotCallable(Rc::clone(form), first)),
+    }
+
+    let mut args = Vec::new();
+    for arg in iter {
+        args.push(eval_internal(arg, Rc::clone(&env))?);
+    }
+    let root = Env::root(&env);
+    root.borrow_mut().set_values(None);
+    root.borrow_mut()
+        .push_frame(frame_name(&first), Rc::clone(form));
+    let result = apply(first, args);
+    root.borrow_mut().pop_frame();
+    result
+}
+
+pub fn apply(function: Object, args: Vec<Object>) -> EvalResult {
+    if let ObjectKind::Func(builtin) = &*function {
+        check_arity(&builtin.name, builtin.arity(), &args)?;
+    }
+    match &*function {
+        ObjectKind::Func(builtin) => match &builtin.func {
+            BuiltinBody::Sync(func) => func(&args),
+            BuiltinBody::WithEnv(func, env) => match env.upgrade() {
+                Some(env) => func(&args, &env),
+                None => Err(RuntimeError::EnvDropped(builtin.name.clone())),
+            },
+            #[cfg(feature = "sync")]
+            BuiltinBody::Async(func) => coroutine::await_future(func(args)),
+            #[cfg(all(feature = "std", not(feature = "sync")))]
+            BuiltinBody::Async(func) => future::block_on(func(args)),
+            BuiltinBody::Partial(function, supplied) => {
+                let mut all = supplied.clone();
+                all.extend(args);
+                apply(Rc::clone(function), all)
+            }
+            BuiltinBody::Compose(functions) => match functions.split_last() {
+                Some((last, rest)) => rest
+                    .iter()
+                    .rev()
+                    .try_fold(apply(Rc::clone(last), args)?, |x, function| {
+                        apply(Rc::clone(function), alloc::vec![x])
+                    }),
+                None => {
+                    check_num_args(&args, 1)?;
+                    Ok(Rc::clone(&args[0]))
+                }
+            },
+            BuiltinBody::Memoize(function, cache) => {
+                let key = object::from_iter(args.iter().cloned());
+                let hash = equal::hash(&key);
+                let cached = cache.borrow().get(&hash).and_then(|bucket| {
+                    bucket
+                        .iter()
+                        .find(|(args, _)| equal::equal(Rc::clone(args), Rc::clone(&key)))
+                        .map(|(_, value)| Rc::clone(value))
+                });
+                if let Some(value) = cached {
+                    return Ok(value);
+                }
+                let value = apply(Rc::clone(function), args)?;
+                cache
+                    .borrow_mut()
+                    .entry(hash)
+                    .or_default()
+                    .push((key, Rc::clone(&value)));
+                Ok(value)
+            }
+        },
+        ObjectKind::Closure(closure) => apply_closure(closure, args),
+        _ => Err(RuntimeError::MismatchType(function, ObjectType::Function)),
+    }
+}
+
+fn add_expansion_hook(args: &[Object], env: &Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args(args, 1)?;
+    env.borrow_mut().add_hook(Rc::clone(&args[0]));
+    Ok(Rc::clone(&args[0]))
+}
+
+fn remove_expansion_hook(args: &[Object], env: &Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args(args, 1)?;
+    if env.borrow_mut().remove_hook(&args[0]) {
+        Ok(object::symbol("t"))
+    } else {
+        Ok(object::nil())
+    }
+}
+
+fn declared_type(name: &Object, declaration: &Object) -> Result<ObjectType, RuntimeError> {
+    match &**name {
+        ObjectKind::Symbol(name) => ObjectType::from_name(name),
+        _ => None,
+    }
+    .ok_or_else(|| RuntimeError::MalformedForm(Rc::clone(declaration)))
+}
+
+fn check_declared_type(value: Object, expected: ObjectType, declaration: &Object) -> EvalResult {
+    if expected.contains(&value) {
+        Ok(value)
+    } else {
+        Err(RuntimeError::MismatchDeclaredType(
+            value,
+            expected,
+            Rc::clone(declaration),
+        ))
+    }
+}
+
+// (the type x) evaluates x and checks that the value is of the given type.
+fn eval_the(form: &Object, args: &[Object], env: Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args(args, 2)?;
+    let expected = declared_type(&args[0], form)?;
+    let value = eval_internal(Rc::clone(&args[1]), env)?;
+    check_declared_type(value, expected, form)
+}
+
+// (declare (type type var...) ...) checks the current values of the
+// variables. Other declarations are accepted and ignored.
+fn eval_declare(args: &[Object], env: Rc<RefCell<Env>>) -> EvalResult {
+    for spec in args {
+        let spec_args = object::to_vec(spec)?;
+        match spec_args.split_first() {
+            Some((head, rest)) if matches!(&**head, ObjectKind::Symbol(s) if s == "type") => {
+                let (type_name, vars) = rest
+                    .split_first()
+                    .ok_or_else(|| RuntimeError::MalformedForm(Rc::clone(spec)))?;
+                let expected = declared_type(type_name, spec)?;
+                for var in vars {
+                    let value = eval_internal(Rc::clone(var), Rc::clone(&env))?;
+                    check_declared_type(value, expected, spec)?;
+                }
+            }
+            _ => (),
+        }
+    }
+    Ok(object::nil())
+}
+
+// Errors signalled by error carry their condition; the others become one
+// whose type is that of the innermost error and whose message is the
+// whole error.
+pub(crate) fn condition_of(e: RuntimeError) -> Object {
+    let message = e.to_string();
+    let (type_name, fields) = match e.into_inner() {
+        RuntimeError::Signal(condition) => return condition,
+        RuntimeError::MismatchType(datum, expected)
+        | RuntimeError::MismatchDeclaredType(datum, expected, _) => (
+            "type-error",
+            crate::list![
+                object::cons(object::symbol("datum"), datum),
+                object::cons(
+                    object::symbol("expected-type"),
+                    object::symbol(expected.name())
+                )
+            ],
+        ),
+        RuntimeError::NotCallable(form, datum) => (
+            "not-callable",
+            crate::list![
+                object::cons(object::symbol("datum"), datum),
+                object::cons(object::symbol("expected-type"), object::symbol("function")),
+                object::cons(object::symbol("form"), form)
+            ],
+        ),
+        RuntimeError::WrongArity(name, actual, _) => (
+            "arity-error",
+            crate::list![
+                object::cons(object::symbol("actual"), object::fixnum(actual as isize)),
+                object::cons(object::symbol("name"), object::symbol(&name))
+            ],
+        ),
+        RuntimeError::WrongNumArgs(actual, _)
+        | RuntimeError::TooFewArguments(actual, _)
+        | RuntimeError::TooManyArguments(actual, _) => (
+            "arity-error",
+            crate::list![object::cons(
+                object::symbol("actual"),
+                object::fixnum(actual as isize)
+            )],
+        ),
+        RuntimeError::PermissionDenied(_) => ("permission-error", object::nil()),
+        RuntimeError::Timeout(seconds) => (
+            "timeout-error",
+            crate::list![object::cons(
+                object::symbol("seconds"),
+                object::fixnum(seconds)
+            )],
+        ),
+        RuntimeError::UnboundVariable(name, _) => (
+            "unbound-variable",
+            crate::list![object::cons(object::symbol("name"), object::symbol(&name))],
+        ),
+        _ => ("error", object::nil()),
+    };
+    object::condition(type_name, &message, object::nil(), fields, object::nil())
+}
+
+// (%try thunk) calls thunk and returns (t . value), or (nil . condition)
+// if it fails.
+fn eval_try(args: &[Object], env: Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args(args, 1)?;
+    let thunk = eval_internal(Rc::clone(&args[0]), Rc::clone(&env))?;
+    let root = Env::root(&env);
+    root.borrow_mut().enter_handler();
+    let result = apply(thunk, Vec::new());
+    root.borrow_mut().leave_handler();
+    match result {
+        Ok(value) => Ok(object::cons(object::symbol("t"), value)),
+        Err(e @ (RuntimeError::InvokeRestart(..) | RuntimeError::Cancelled)) => Err(e),
+        #[cfg(feature = "std")]
+        Err(e) if matches!(e.inner(), RuntimeError::Timeout(_)) && timer::expired() => Err(e),
+        Err(e) => Ok(object::cons(object::nil(), condition_of(e))),
+    }
+}
+
+// The generator is given the environment whose frames its body pushes.
+#[cfg(feature = "sync")]
+fn make_generator(args: &[Object], env: &Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args(args, 1)?;
+    let thunk = &args[0];
+    match &**thunk {
+        ObjectKind::Func(_) | ObjectKind::Closure(_) => (),
+        _ => {
+            return Err(RuntimeError::MismatchType(
+                Rc::clone(thunk),
+                ObjectType::Function,
+            ))
+        }
+    }
+    Ok(object::generator(Rc::clone(thunk), Rc::clone(env)))
+}
+
+#[cfg(feature = "std")]
+fn seconds(x: Object) -> Result<isize, RuntimeError> {
+    match *x {
+        ObjectKind::Fixnum(n) => Ok(n),
+        _ => Err(RuntimeError::MismatchType(x, ObjectType::Number)),
+    }
+}
+
+// (with-timeout seconds body...) evaluates body until the timeout runs out,
+// when it fails with a timeout-error.
+#[cfg(feature = "std")]
+fn eval_with_timeout(args: &[Object], env: Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args_range(args, 1, None)?;
+    let seconds = seconds(eval_internal(Rc::clone(&args[0]), Rc::clone(&env))?)?;
+    timer::with_timeout(seconds, || eval_progn(&args[1..], env))
+}
+
+// (with-restarts ((name lambda-list body...) ...) body...) evaluates body;
+// (invoke-restart 'name args...) inside it returns from with-restarts with
+// the value of the restart applied to args.
+fn eval_with_restarts(args: &[Object], env: Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args_range(args, 1, None)?;
+    let mut restarts = Vec::new();
+    for spec in object::to_vec(&args[0])? {
+        let mut iter = spec.iter();
+        let name = match iter.next() {
+            Some(name) => symbol_name(&name)?.to_string(),
+            None => return Err(RuntimeError::MalformedForm(spec)),
+        };
+        restarts.push(eval_lambda(Some(name), &mut iter, Rc::clone(&env))?);
+    }
+
+    let root = Env::root(&env);
+    let mut ids = Vec::new();
+    for restart in restarts.iter() {
+        if let ObjectKind::Closure(closure) = &**restart {
+            let parameters = closure
+                .parameters
+                .iter()
+                .chain(&closure.rest)
+                .cloned()
+                .collect();
+            let name = closure.name.as_deref().unwrap_or_default();
+            ids.push(root.borrow_mut().push_restart(name, parameters));
+        }
+    }
+    let result = eval_progn(&args[1..], env);
+    root.borrow_mut().pop_restarts(ids.len());
+
+    match result {
+        Err(RuntimeError::InvokeRestart(id, args)) => match ids.iter().position(|&i| i == id) {
+            Some(n) => apply(Rc::clone(&restarts[n]), args),
+            None => Err(RuntimeError::InvokeRestart(id, args)),
+        },
+        result => result,
+    }
+}
+
+fn invoke_restart(args: &[Object], env: &Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args_range(args, 1, None)?;
+    let name = symbol_name(&args[0])?;
+    match env.borrow().find_restart(name) {
+        Some(id) => Err(RuntimeError::InvokeRestart(id, args[1..].to_vec())),
+        None => Err(RuntimeError::RestartNotFound(name.to_string())),
+    }
+}
+
+// The frames of the calls in progress, innermost first, without the call to
+// backtrace itself.
+fn backtrace(args: &[Object], env: &Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args(args, 0)?;
+    let frames = env.borrow().backtrace();
+    match &*frames {
+        ObjectKind::Cons(cons) => Ok(Rc::clone(&cons.cdr)),
+        _ => Ok(frames),
+    }
+}
+
+fn compute_restarts(args: &[Object], env: &Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args(args, 0)?;
+    let root = env.borrow();
+    Ok(object::from_iter(
+        root.restarts()
+            .iter()
+            .rev()
+            .map(|restart| object::symbol(&restart.name)),
+    ))
+}
+
+fn unbound(name: &str, env: &Env) -> RuntimeError {
+    RuntimeError::UnboundVariable(name.to_string(), env.similar_names(name))
+}
+
+// Lets the debugger, if there is one, continue with a value for the
+// variable, define it, or choose a restart in effect.
+fn unbound_variable(name: &str, env: &Rc<RefCell<Env>>) -> EvalResult {
+    let error = unbound(name, &env.borrow());
+    let root = Env::root(env);
+    let (debugger, mut restarts) = {
+        let root = root.borrow();
+        match root.debugger() {
+            Some(debugger) => (debugger, root.restarts().to_vec()),
+            None => return Err(error),
+        }
+    };
+    restarts.reverse();
+    for name in ["define-it", "use-value"] {
+        restarts.insert(
+            0,
+            Restart {
+                id: 0,
+                name: name.to_string(),
+                parameters: alloc::vec!["value".to_string()],
+            },
+        );
+    }
+
+    let (n, forms) = match debugger(&error, &restarts) {
+        Some(choice) if choice.0 < restarts.len() => choice,
+        _ => return Err(error),
+    };
+    let mut args = Vec::new();
+    for form in forms {
+        args.push(eval_internal(form, Rc::clone(&root))?);
+    }
+    match n {
+        0 | 1 => {
+            check_num_args(&args, 1)?;
+            if n == 1 {
+                root.borrow_mut().insert(name, Rc::clone(&args[0]));
+            }
+            Ok(Rc::clone(&args[0]))
+        }
+        n => Err(RuntimeError::InvokeRestart(restarts[n].id, args)),
+    }
+}
+
+// Every compound form goes through the expansion hooks, in the order they
+// were added, before special forms and macros are looked at. A macro's
+// expansion is a new form, so the hooks see it as well.
+fn run_hooks(x: Object, root: &Rc<RefCell<Env>>) -> EvalResult {
+    let hooks = root.borrow_mut().take_hooks();
+    if hooks.is_empty() {
+        return Ok(x);
+    }
+    let result = hooks
+        .iter()
+        .try_fold(x, |x, hook| apply(Rc::clone(hook), alloc::vec![x]));
+    root.borrow_mut().restore_hooks(hooks);
+    result
+}
+
+// The names dispatched on by eval_form.
+pub const SPECIAL_FORMS: &[&str] = &[
+    "quote",
+    "quasiquote",
+    "if",
+    "assert",
+    "the",
+    "declare",
+    "define",
+    "lambda",
+    "defmacro",
+    "progn",
+    "set!",
+    "require",
+    "provide",
+    "load",
+    "watch-load",
+    "with-open-file",
+    "doc",
+    "macro-step",
+    "macro-calls",
+    "%try",
+    "with-timeout",
+    "with-restarts",
+    "with-output-to-string",
+    "unwind-protect",
+    "parameterize",
+    "defvar",
+    "defparameter",
+    "loop",
+    "multiple-value-list",
+    "defpackage",
+    "in-package",
+    "export",
+    "import",
+];
+
+fn eval_internal(x: Object, env: Rc<RefCell<Env>>) -> EvalResult {
+    accounting::count_reduction();
+    #[cfg(feature = "sync")]
+    coroutine::step()?;
+    #[cfg(feature = "std")]
+    timer::check()?;
+    // Whatever values were kept belong to an earlier form, and the values
+    // form that this one returns, if any, keeps its own.
+    let root = Env::root(&env);
+    root.borrow_mut().set_values(None);
+    if !matches!(&*x, ObjectKind::Cons(_)) {
+        return eval_form(x, env);
+    }
+    let form = run_hooks(Rc::clone(&x), &root).map_err(|e| e.in_form(&x))?;
+    eval_form(Rc::clone(&form), env).map_err(|e| e.in_form(&form))
+}
+
+fn eval_form(x: Object, env: Rc<RefCell<Env>>) -> EvalResult {
+    match &*x {
+        ObjectKind::Nil
+        | ObjectKind::Fixnum(_)
+        | ObjectKind::String(_)
+        | ObjectKind::Func(_)
+        | ObjectKind::Closure(_)
+        | ObjectKind::Macro(_)
+        | ObjectKind::Port(_)
+        | ObjectKind::Condition(_)
+        | ObjectKind::BitVector(_)
+        | ObjectKind::Promise(_)
+        | ObjectKind::WeakRef(_)
+        | ObjectKind::WeakTable(_)
+        | ObjectKind::HashTable(_)
+        | ObjectKind::Set(_) => Ok(x),
+        #[cfg(feature = "sync")]
+        ObjectKind::Generator(_) => Ok(x),
+        #[cfg(feature = "std")]
+        ObjectKind::Channel(_) => Ok(x),
+        ObjectKind::Symbol(s) if s.starts_with(':') => Ok(x),
+        ObjectKind::Symbol(s) => {
+            let value = env.borrow().get(s);
+            match value {
+                Some(value) => Ok(value),
+                None => unbound_variable(s, &env),
+            }
+        }
+        ObjectKind::Cons(list) => {
+            let mut iter = list.iter();
+            let first = iter.next().unwrap();
+
+            if let ObjectKind::Symbol(name) = &*first {
+                match &**name {
+                    "quote" => {
+                        let args: Vec<Object> = iter.collect();
+                        return eval_quote(&args);
+                    }
+                    "quasiquote" => {
+                        let args: Vec<Object> = iter.collect();
+                        return eval_quasiquote(&args, env);
+                    }
+                    "if" => {
+                        let args: Vec<Object> = iter.collect();
+                        return eval_if(&args, env);
+                    }
+                    "assert" => {
+                        let args: Vec<Object> = iter.collect();
+                        return eval_assert(&args, env);
+                    }
+                    "the" => {
+                        let args: Vec<Object> = iter.collect();
+                        return eval_the(&x, &args, env);
+                    }
+                    "declare" => {
+                        let args: Vec<Object> = iter.collect();
+                        return eval_declare(&args, env);
+                    }
+                    "define" => {
+                        let args: Vec<Object> = iter.collect();
+                        return eval_define(&args, env);
+                    }
+                    "lambda" => {
+                        return eval_lambda(None, &mut iter, env);
+                    }
+                    "defmacro" => {
+                        return eval_defmacro(&mut iter, env);
+                    }
+                    "progn" => {
+                        let args: Vec<Object> = iter.collect();
+                        return eval_progn(&args, env);
+                    }
+                    "set!" => {
+                        let args: Vec<Object> = iter.collect();
+                        return eval_set(&args, env);
+                    }
+                    "require" => {
+                        let args: Vec<Object> = iter.collect();
+                        return eval_require(&args, env);
+                    }
+                    "provide" => {
+                        let args: Vec<Object> = iter.collect();
+                        return eval_provide(&args, env);
+                    }
+                    #[cfg(feature = "std")]
+                    "load"
+                        if Env::allows(&env, Capability::Io) && Env::includes(&env, Module::Io) =>
+                    {
+                        let args: Vec<Object> = iter.collect();
+                        return eval_load(&args, env);
+                    }
+                    #[cfg(feature = "std")]
+                    "watch-load"
+                        if Env::allows(&env, Capability::Io) && Env::includes(&env, Module::Io) =>
+                    {
+                        let args: Vec<Object> = iter.collect();
+                        return eval_watch_load(&args, env);
+                    }
+                    #[cfg(feature = "std")]
+                    "with-open-file"
+                        if Env::allows(&env, Capability::Io) && Env::includes(&env, Module::Io) =>
+                    {
+                        let args: Vec<Object> = iter.collect();
+                        return eval_with_open_file(&args, env);
+                    }
+                    "doc" => {
+                        let args: Vec<Object> = iter.collect();
+                        return eval_doc(&args, env);
+                    }
+                    "macro-step" => {
+                        let args: Vec<Object> = iter.collect();
+                        return eval_macro_step(&args, env);
+                    }
+                    "macro-calls" => {
+                        let args: Vec<Object> = iter.collect();
+                        return eval_macro_calls(&args, env);
+                    }
+                    "%try" => {
+                        let args: Vec<Object> = iter.collect();
+                        return eval_try(&args, env);
+                    }
+                    #[cfg(feature = "std")]
+                    "with-timeout" => {
+                        let args: Vec<Object> = iter.collect();
+                        return eval_with_timeout(&args, env);
+                    }
+                    "with-restarts" => {
+                        let args: Vec<Object> = iter.collect();
+                        return eval_with_restarts(&args, env);
+                    }
+                    "with-output-to-string" => {
+                        let args: Vec<Object> = iter.collect();
+                        return eval_with_output_to_string(&args, env);
+                    }
+                    "unwind-protect" => {
+                        let args: Vec<Object> = iter.collect();
+                        return eval_unwind_protect(&args, env);
+                    }
+                    "parameterize" => {
+                        let args: Vec<Object> = iter.collect();
+                        return eval_parameterize(&args, env);
+                    }
+                    "defvar" | "defparameter" => {
+                        let args: Vec<Object> = iter.collect();
+                        return eval_defvar(&args, env, name == "defparameter");
+                    }
+                    "multiple-value-list" => {
+                        let args: Vec<Object> = iter.collect();
+                        return eval_multiple_value_list(&args, env);
+                    }
+                    "loop" => {
+                        let args: Vec<Object> = iter.collect();
+                        return eval_loop(&x, &args, env);
+                    }
+                    "defpackage" => {
+                        let args: Vec<Object> = iter.collect();
+                        return eval_defpackage(&args, env);
+                    }
+                    "in-package" => {
+                        let args: Vec<Object> = iter.collect();
+                        return eval_in_package(&args, env);
+                    }
+                    "export" => {
+                        let args: Vec<Object> = iter.collect();
+                        return eval_export(&args, env);
+                    }
+                    "import" => {
+                        let args: Vec<Object> = iter.collect();
+                        return eval_import(&args, env);
+                    }
+                    _ => (),
+                }
+            }
+            apply_function(&x, first, iter, env)
+        }
+    }
+}
+
+mod builtin {
+    use super::object::Object;
+    use super::*;
+    use alloc::format;
+
+    pub fn plus(args: &[Object]) -> EvalResult {
+        let mut acc = 0;
+        for arg in args {
+            match **arg {
+                ObjectKind::Fixnum(n) => {
+                    acc += n;
+                }
+                _ => {
+                    return Err(RuntimeError::MismatchType(
+                        Rc::clone(arg),
+                        ObjectType::Number,
+                    ))
+                }
+            }
+        }
+        Ok(object::fixnum(acc))
+    }
+
+    pub fn is_atom(args: &[Object]) -> EvalResult {
+        check_num_args(args, 1)?;
+        match &*args[0] {
+            ObjectKind::Cons(_) => Ok(object::nil()),
+            _ => Ok(object::symbol("t")),
+        }
+    }
+
+    pub fn cons(args: &[Object]) -> EvalResult {
+        check_num_args(args, 2)?;
+        Ok(object::cons(Rc::clone(&args[0]), Rc::clone(&args[1])))
+    }
+
+    fn cxr<F>(args: &[Object], accessor: F) -> EvalResult
+    where
+        F: Fn(&object::Cons) -> Object,
+    {
+        check_num_args(args, 1)?;
+        match &*args[0] {
+            ObjectKind::Cons(cons) => Ok(accessor(cons)),
+            _ => Err(RuntimeError::MismatchType(
+                Rc::clone(&args[0]),
+                ObjectType::Cons,
+            )),
+        }
+    }
+
+    pub fn car(args: &[Object]) -> EvalResult {
+        cxr(args, |cons| Rc::clone(&cons.car))
+    }
+
+    pub fn cdr(args: &[Object]) -> EvalResult {
+        cxr(args, |cons| Rc::clone(&cons.cdr))
+    }
+
+    pub fn apply(args: &[Object]) -> EvalResult {
+        check_num_args(args, 2)?;
+        super::apply(Rc::clone(&args[0]), object::to_vec(&args[1])?)
+    }
+
+    fn input_port(x: &Object) -> Result<&Port, RuntimeError> {
+        match &**x {
+            ObjectKind::Port(port) if port.is_closed() => Err(RuntimeError::ClosedPort),
+            ObjectKind::Port(port) if port.is_input() => Ok(port),
+            _ => Err(RuntimeError::MismatchType(
+                Rc::clone(x),
+                ObjectType::InputPort,
+            )),
+        }
+    }
+
+    fn output_port(x: &Object) -> Result<&Port, RuntimeError> {
+        match &**x {
+            ObjectKind::Port(port) if port.is_closed() => Err(RuntimeError::ClosedPort),
+            ObjectKind::Port(port) if port.is_output() => Ok(port),
+            _ => Err(RuntimeError::MismatchType(
+                Rc::clone(x),
+                ObjectType::OutputPort,
+            )),
+        }
+    }
+
+    pub fn read_line(args: &[Object]) -> EvalResult {
+        check_num_args(args, 1)?;
+        match input_port(&args[0])?.read_line()? {
+            Some(line) => Ok(object::string(&line)),
+            None => Ok(object::nil()),
+        }
+    }
+
+    pub fn read_char(args: &[Object]) -> EvalResult {
+        check_num_args(args, 1)?;
+        match input_port(&args[0])?.read_char()? {
+            Some(c) => Ok(object::string(c.encode_utf8(&mut [0; 4]))),
+            None => Ok(object::nil()),
+        }
+    }
+
+    pub fn is_char_ready(args: &[Object]) -> EvalResult {
+        check_num_args(args, 1)?;
+        if input_port(&args[0])?.is_ready()? {
+            Ok(object::symbol("t"))
+        } else {
+            Ok(object::nil())
+        }
+    }
+
+    pub fn write_string(args: &[Object]) -> EvalResult {
+        check_num_args(args, 2)?;
+        let port = output_port(&args[1])?;
+        match &*args[0] {
+            ObjectKind::String(s) => port.write_str(s)?,
+            _ => {
+                return Err(RuntimeError::MismatchType(
+                    Rc::clone(&args[0]),
+                    ObjectType::String,
+                ))
+            }
+        }
+        Ok(Rc::clone(&args[0]))
+    }
+
+    fn radix(x: &Object) -> Result<u32, RuntimeError> {
+        match **x {
+            ObjectKind::Fixnum(n) if (2..=36).contains(&n) => Ok(n as u32),
+            ObjectKind::Fixnum(_) => Err(RuntimeError::InvalidRadix(Rc::clone(x))),
+            _ => Err(RuntimeError::MismatchType(Rc::clone(x), ObjectType::Number)),
+        }
+    }
+
+    // The radix of the printed fixnums is an optional last argument.
+    fn print_radix(args: &[Object], n: usize) -> Result<u32, RuntimeError> {
+        args.get(n).map_or(Ok(10), radix)
+    }
+
+    // (%read port base [eof-value]) signals end of file unless eof-value is
+    // given.
+    pub fn read(args: &[Object]) -> EvalResult {
+        check_num_args_range(args, 2, 3)?;
+        let base = radix(&args[1])?;
+        match (input_port(&args[0])?.read_in_base(base)?, args.get(2)) {
+            (Some(x), _) => Ok(x),
+            (None, Some(eof)) => Ok(Rc::clone(eof)),
+            (None, None) => Err(RuntimeError::ReadError(ReadError::EndOfFile)),
+        }
+    }
+
+    pub fn write(args: &[Object]) -> EvalResult {
+        check_num_args_range(args, 2, 3)?;
+        let base = print_radix(args, 2)?;
+        output_port(&args[1])?.write_str(&object::Radix(&args[0], base).to_string())?;
+        Ok(Rc::clone(&args[0]))
+    }
+
+    pub fn display(args: &[Object]) -> EvalResult {
+        check_num_args_range(args, 2, 3)?;
+        let base = print_radix(args, 2)?;
+        let port = output_port(&args[1])?;
+        match &*args[0] {
+            ObjectKind::String(s) => port.write_str(s)?,
+            x => port.write_str(&object::Radix(x, base).to_string())?,
+        }
+        Ok(Rc::clone(&args[0]))
+    }
+
+    pub fn number_to_string(args: &[Object]) -> EvalResult {
+        check_num_args_range(args, 1, 2)?;
+        if !matches!(*args[0], ObjectKind::Fixnum(_)) {
+            return Err(RuntimeError::MismatchType(
+                Rc::clone(&args[0]),
+                ObjectType::Number,
+            ));
+        }
+        let base = print_radix(args, 1)?;
+        Ok(object::string(&object::Radix(&args[0], base).to_string()))
+    }
+
+    pub fn format(args: &[Object]) -> EvalResult {
+        check_num_args_range(args, 1, None)?;
+        match &*args[0] {
+            ObjectKind::String(control) => {
+                Ok(object::string(&format::format(control, &args[1..])?))
+            }
+            _ => Err(RuntimeError::MismatchType(
+                Rc::clone(&args[0]),
+                ObjectType::String,
+            )),
+        }
+    }
+
+    pub fn open_input_string(args: &[Object]) -> EvalResult {
+        check_num_args(args, 1)?;
+        match &*args[0] {
+            ObjectKind::String(s) => Ok(object::port(Port::input_string(s))),
+            _ => Err(RuntimeError::MismatchType(
+                Rc::clone(&args[0]),
+                ObjectType::String,
+            )),
+        }
+    }
+
+    pub fn open_output_string(args: &[Object]) -> EvalResult {
+        check_num_args(args, 0)?;
+        Ok(object::port(Port::output_string()))
+    }
+
+    fn function(x: &Object) -> Result<Object, RuntimeError> {
+        if ObjectType::Function.contains(x) {
+            Ok(Rc::clone(x))
+        } else {
+            Err(RuntimeError::MismatchType(
+                Rc::clone(x),
+                ObjectType::Function,
+            ))
+        }
+    }
+
+    // (partial f a b) is a function that calls f with a and b before its
+    // own arguments.
+    pub fn partial(args: &[Object]) -> EvalResult {
+        check_num_args_range(args, 1, None)?;
+        Ok(object::partial(function(&args[0])?, args[1..].to_vec()))
+    }
+
+    // ((compose f g) x) is (f (g x)). (compose) is the identity.
+    pub fn compose(args: &[Object]) -> EvalResult {
+        let functions = args.iter().map(function).collect::<Result<_, _>>()?;
+        Ok(object::compose(functions))
+    }
+
+    fn integer(x: &Object) -> Result<isize, RuntimeError> {
+        match **x {
+            ObjectKind::Fixnum(n) => Ok(n),
+            _ => Err(RuntimeError::MismatchType(Rc::clone(x), ObjectType::Number)),
+        }
+    }
+
+    fn divide(args: &[Object], f: fn(isize, isize) -> isize) -> EvalResult {
+        check_num_args(args, 2)?;
+        match (integer(&args[0])?, integer(&args[1])?) {
+            (_, 0) => Err(RuntimeError::DivisionByZero),
+            (n, d) => Ok(object::fixnum(f(n, d))),
+        }
+    }
+
+    // Both round towards zero, so the remainder has the sign of the dividend.
+    pub fn quotient(args: &[Object]) -> EvalResult {
+        divide(args, isize::wrapping_div)
+    }
+
+    pub fn remainder(args: &[Object]) -> EvalResult {
+        divide(args, isize::wrapping_rem)
+    }
+
+    // Calls with arguments equal to those of an earlier call return its
+    // result without calling the function again.
+    pub fn memoize(args: &[Object]) -> EvalResult {
+        check_num_args(args, 1)?;
+        Ok(object::memoize(function(&args[0])?))
+    }
+
+    // An alist of the live objects by type, the environments and the
+    // objects allocated by this thread so far. Reference counting frees
+    // objects as soon as they're unreachable, except for cycles, such as a
+    // closure defined in the environment it closes over, which are never
+    // freed; counts that keep growing point to those.
+    pub fn gc_stats(args: &[Object]) -> EvalResult {
+        check_num_args(args, 0)?;
+        let live = accounting::live_objects();
+        let total: usize = live.iter().map(|(_, count)| count).sum();
+        let by_type = object::from_iter(live.iter().filter(|(_, count)| *count > 0).map(
+            |(name, count)| object::cons(object::symbol(name), object::fixnum(*count as isize)),
+        ));
+        let entry = |name: &str, value: Object| object::cons(object::symbol(name), value);
+        Ok(crate::list![
+            entry("live-objects", object::fixnum(total as isize)),
+            entry("by-type", by_type),
+            entry(
+                "environments",
+                object::fixnum(accounting::live_envs() as isize)
+            ),
+            entry(
+                "allocations",
+                object::fixnum(accounting::current().allocations as isize)
+            )
+        ])
+    }
+
+    // With no argument, whether hash-consing is on for this environment;
+    // otherwise turns it on or off. It stays off without std.
+    pub fn hash_consing(args: &[Object]) -> EvalResult {
+        check_num_args_range(args, 0, 1)?;
+        let on = match args.first() {
+            Some(on) => object::set_hash_consing(!matches!(**on, ObjectKind::Nil)),
+            None => object::hash_consing(),
+        };
+        Ok(if on {
+            object::symbol("t")
+        } else {
+            object::nil()
+        })
+    }
+
+    pub fn is_symbol(args: &[Object]) -> EvalResult {
+        check_num_args(args, 1)?;
+        if ObjectType::Symbol.contains(&args[0]) {
+            Ok(object::symbol("t"))
+        } else {
+            Ok(object::nil())
+        }
+    }
+
+    pub fn is_keyword(args: &[Object]) -> EvalResult {
+        check_num_args(args, 1)?;
+        if ObjectType::Keyword.contains(&args[0]) {
+            Ok(object::symbol("t"))
+        } else {
+            Ok(object::nil())
+        }
+    }
+
+    pub fn keyword_to_symbol(args: &[Object]) -> EvalResult {
+        check_num_args(args, 1)?;
+        match &*args[0] {
+            ObjectKind::Symbol(s) if s.starts_with(':') => Ok(object::symbol(&s[1..])),
+            _ => Err(RuntimeError::MismatchType(
+                Rc::clone(&args[0]),
+                ObjectType::Keyword,
+            )),
+        }
+    }
+
+    pub fn symbol_to_keyword(args: &[Object]) -> EvalResult {
+        check_num_args(args, 1)?;
+        let name = symbol_name(&args[0])?;
+        if name.starts_with(':') {
+            Ok(Rc::clone(&args[0]))
+        } else {
+            Ok(object::symbol(&format!(":{}", name)))
+        }
+    }
+
+    // (getf plist key [default]) returns the value after key in a list of
+    // alternating keys and values, or default if key isn't there.
+    pub fn getf(args: &[Object]) -> EvalResult {
+        check_num_args_range(args, 2, 3)?;
+        let plist = object::to_vec(&args[0])?;
+        for pair in plist.chunks(2) {
+            if equal::equal(Rc::clone(&pair[0]), Rc::clone(&args[1])) {
+                return pair
+                    .get(1)
+                    .cloned()
+                    .ok_or_else(|| RuntimeError::MalformedForm(Rc::clone(&args[0])));
+            }
+        }
+        Ok(args.get(2).cloned().unwrap_or_else(object::nil))
+    }
+
+    // (plist-member plist key) returns the tail of plist starting at key, so
+    // that a key whose value is nil can be told from one that isn't there.
+    pub fn plist_member(args: &[Object]) -> EvalResult {
+        check_num_args(args, 2)?;
+        let mut rest = &args[0];
+        while let ObjectKind::Cons(cons) = &**rest {
+            if equal::equal(Rc::clone(&cons.car), Rc::clone(&args[1])) {
+                return Ok(Rc::clone(rest));
+            }
+            rest = match &*cons.cdr {
+                ObjectKind::Cons(value) => &value.cdr,
+                _ => return Err(RuntimeError::MalformedForm(Rc::clone(&args[0]))),
+            };
+        }
+        Ok(object::nil())
+    }
+
+    // (plist-put plist key value) returns a copy of plist with key's value
+    // replaced, or with key and value added at the end.
+    pub fn plist_put(args: &[Object]) -> EvalResult {
+        check_num_args(args, 3)?;
+        let mut plist = object::to_vec(&args[0])?;
+        if !plist.len().is_multiple_of(2) {
+            return Err(RuntimeError::MalformedForm(Rc::clone(&args[0])));
+        }
+        match plist
+            .chunks(2)
+            .position(|pair| equal::equal(Rc::clone(&pair[0]), Rc::clone(&args[1])))
+        {
+            Some(i) => plist[2 * i + 1] = Rc::clone(&args[2]),
+            None => plist.extend([Rc::clone(&args[1]), Rc::clone(&args[2])]),
+        }
+        Ok(object::from_iter(plist))
+    }
+
+    // Characters are strings of length one, as read-char returns them.
+    pub fn string_to_list(args: &[Object]) -> EvalResult {
+        check_num_args(args, 1)?;
+        match &*args[0] {
+            ObjectKind::String(s) => Ok(object::from_iter(
+                s.chars()
+                    .map(|c| object::string(c.encode_utf8(&mut [0; 4]))),
+            )),
+            _ => Err(RuntimeError::MismatchType(
+                Rc::clone(&args[0]),
+                ObjectType::String,
+            )),
+        }
+    }
+
+    pub fn list_to_string(args: &[Object]) -> EvalResult {
+        check_num_args(args, 1)?;
+        let mut s = String::new();
+        for x in object::to_vec(&args[0])? {
+            match &*x {
+                ObjectKind::String(part) => s.push_str(part),
+                _ => return Err(RuntimeError::MismatchType(x, ObjectType::String)),
+            }
+        }
+        Ok(object::string(&s))
+    }
+
+    // Copies the conses of the list's spine, keeping its elements and any
+    // dotted tail.
+    pub fn copy_list(args: &[Object]) -> EvalResult {
+        check_num_args(args, 1)?;
+        Ok(copy(&args[0], false))
+    }
+
+    // Copies every cons reachable through cars and cdrs.
+    pub fn copy_tree(args: &[Object]) -> EvalResult {
+        check_num_args(args, 1)?;
+        Ok(copy(&args[0], true))
+    }
+
+    fn copy(x: &Object, deep: bool) -> Object {
+        let mut elements = Vec::new();
+        let mut rest = x;
+        while let ObjectKind::Cons(cons) = &**rest {
+            elements.push(if deep {
+                copy(&cons.car, true)
+            } else {
+                Rc::clone(&cons.car)
+            });
+            rest = &cons.cdr;
+        }
+        elements
+            .into_iter()
+            .rev()
+            .fold(Rc::clone(rest), |tail, car| object::cons(car, tail))
+    }
+
+    pub fn get_output_string(args: &[Object]) -> EvalResult {
+        check_num_args(args, 1)?;
+        match output_port(&args[0])?.output() {
+            Some(s) => Ok(object::string(&s)),
+            None => Err(RuntimeError::MismatchType(
+                Rc::clone(&args[0]),
+                ObjectType::OutputPort,
+            )),
+        }
+    }
+
+    pub fn close_port(args: &[Object]) -> EvalResult {
+        check_num_args(args, 1)?;
+        match &*args[0] {
+            ObjectKind::Port(port) => {
+                port.close()?;
+                Ok(object::nil())
+            }
+            _ => Err(RuntimeError::MismatchType(
+                Rc::clone(&args[0]),
+                ObjectType::InputPort,
+            )),
+        }
+    }
+
+    // (%make-condition type message irritants fields backtrace)
+    pub fn make_condition(args: &[Object]) -> EvalResult {
+        check_num_args(args, 5)?;
+        let type_name = match &*args[0] {
+            ObjectKind::Symbol(s) => s,
+            _ => {
+                return Err(RuntimeError::MismatchType(
+                    Rc::clone(&args[0]),
+                    ObjectType::Symbol,
+                ))
+            }
+        };
+        let message = match &*args[1] {
+            ObjectKind::String(s) => s,
+            _ => {
+                return Err(RuntimeError::MismatchType(
+                    Rc::clone(&args[1]),
+                    ObjectType::String,
+                ))
+            }
+        };
+        Ok(object::condition(
+            type_name,
+            message,
+            Rc::clone(&args[2]),
+            Rc::clone(&args[3]),
+            Rc::clone(&args[4]),
+        ))
+    }
+
+    pub fn signal(args: &[Object]) -> EvalResult {
+        check_num_args(args, 1)?;
+        match &*args[0] {
+            ObjectKind::Condition(_) => Err(RuntimeError::Signal(Rc::clone(&args[0]))),
+            _ => Err(RuntimeError::MismatchType(
+                Rc::clone(&args[0]),
+                ObjectType::Condition,
+            )),
+        }
+    }
+
+    fn condition_field(
+        args: &[Object],
+        field: impl Fn(&object::Condition) -> Object,
+    ) -> EvalResult {
+        check_num_args(args, 1)?;
+        match &*args[0] {
+            ObjectKind::Condition(condition) => Ok(field(condition)),
+            _ => Err(RuntimeError::MismatchType(
+                Rc::clone(&args[0]),
+                ObjectType::Condition,
+            )),
+        }
+    }
+
+    pub fn condition_type(args: &[Object]) -> EvalResult {
+        condition_field(args, |c| object::symbol(&c.type_name))
+    }
+
+    pub fn condition_message(args: &[Object]) -> EvalResult {
+        condition_field(args, |c| object::string(&c.message))
+    }
+
+    pub fn condition_irritants(args: &[Object]) -> EvalResult {
+        condition_field(args, |c| Rc::clone(&c.irritants))
+    }
+
+    pub fn condition_fields(args: &[Object]) -> EvalResult {
+        condition_field(args, |c| Rc::clone(&c.fields))
+    }
+
+    pub fn condition_backtrace(args: &[Object]) -> EvalResult {
+        condition_field(args, |c| Rc::clone(&c.backtrace))
+    }
+
+    pub fn dump_form(args: &[Object]) -> EvalResult {
+        check_num_args(args, 1)?;
+        Ok(object::string(&args[0].dump()))
+    }
+
+    pub fn dump_graph(args: &[Object]) -> EvalResult {
+        check_num_args(args, 1)?;
+        Ok(object::string(&args[0].dump_graph()))
+    }
+
+    pub fn closure_source(args: &[Object]) -> EvalResult {
+        check_num_args(args, 1)?;
+        match &*args[0] {
+            ObjectKind::Closure(closure) | ObjectKind::Macro(closure) => Ok(closure.source()),
+            ObjectKind::Func(_) => Ok(object::nil()),
+            _ => Err(RuntimeError::MismatchType(
+                Rc::clone(&args[0]),
+                ObjectType::Function,
+            )),
+        }
+    }
+
+    // Symbols made by gensym print as #:G1, #:G2, ..., but aren't equal to
+    // those names read back.
+    pub fn gensym(args: &[Object]) -> EvalResult {
+        check_num_args_range(args, 0, 1)?;
+        let prefix = match args.first().map(|x| &**x) {
+            None => "G",
+            Some(ObjectKind::String(s)) | Some(ObjectKind::Symbol(s)) => s,
+            Some(_) => {
+                return Err(RuntimeError::MismatchType(
+                    Rc::clone(&args[0]),
+                    ObjectType::String,
+                ))
+            }
+        };
+        Ok(object::uninterned(prefix))
+    }
+
+    #[cfg(feature = "sync")]
+    pub fn yield_value(args: &[Object]) -> EvalResult {
+        check_num_args(args, 1)?;
+        coroutine::suspend(Rc::clone(&args[0]))
+    }
+
+    #[cfg(feature = "sync")]
+    fn generator(x: &Object) -> Result<&Generator, RuntimeError> {
+        match &**x {
+            ObjectKind::Generator(generator) => Ok(generator),
+            _ => Err(RuntimeError::MismatchType(
+                Rc::clone(x),
+                ObjectType::Generator,
+            )),
+        }
+    }
+
+    #[cfg(feature = "sync")]
+    pub fn generator_next(args: &[Object]) -> EvalResult {
+        check_num_args_range(args, 1, Some(2))?;
+        let eof_value = args.get(1).cloned().unwrap_or_else(object::nil);
+        generator(&args[0])?.next(eof_value)
+    }
+
+    #[cfg(feature = "sync")]
+    pub fn is_generator_done(args: &[Object]) -> EvalResult {
+        check_num_args(args, 1)?;
+        if generator(&args[0])?.is_done() {
+            Ok(object::symbol("t"))
+        } else {
+            Ok(object::nil())
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn channel(x: &Object) -> Result<&Channel, RuntimeError> {
+        match &**x {
+            ObjectKind::Channel(channel) => Ok(channel),
+            _ => Err(RuntimeError::MismatchType(
+                Rc::clone(x),
+                ObjectType::Channel,
+            )),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn make_channel(args: &[Object]) -> EvalResult {
+        check_num_args(args, 0)?;
+        Ok(object::channel(Channel::new()))
+    }
+
+    // Values are copied, so only data and channels can be sent.
+    #[cfg(feature = "std")]
+    pub fn send(args: &[Object]) -> EvalResult {
+        check_num_args(args, 2)?;
+        channel(&args[0])?.send(&args[1])?;
+        Ok(Rc::clone(&args[1]))
+    }
+
+    #[cfg(feature = "std")]
+    pub fn try_recv(args: &[Object]) -> EvalResult {
+        check_num_args_range(args, 1, Some(2))?;
+        let default = args.get(1).cloned().unwrap_or_else(object::nil);
+        Ok(channel(&args[0])?.try_recv().unwrap_or(default))
+    }
+
+    // (recv channel) waits for a value.
+    #[cfg(feature = "std")]
+    pub fn recv(args: &[Object]) -> EvalResult {
+        check_num_args(args, 1)?;
+        channel(&args[0])?.recv()
+    }
+
+    // (sleep seconds) waits, or until a with-timeout around it runs out.
+    #[cfg(feature = "std")]
+    pub fn sleep(args: &[Object]) -> EvalResult {
+        check_num_args(args, 1)?;
+        let seconds = seconds(Rc::clone(&args[0]))?;
+        timer::sleep(core::time::Duration::from_secs(seconds.max(0) as u64))?;
+        Ok(object::nil())
+    }
+
+    pub fn equal(args: &[Object]) -> EvalResult {
+        check_num_args(args, 2)?;
+        if equal::equal(Rc::clone(&args[0]), Rc::clone(&args[1])) {
+            Ok(object::symbol("t"))
+        } else {
+            Ok(object::nil())
         }
     }
 }
 
 impl Env {
-    pub fn init(&mut self) {
-        self.insert("+", Object::new(ObjectKind::Func(builtin::plus)));
-        self.insert("atom?", Object::new(ObjectKind::Func(builtin::is_atom)));
-        self.insert("cons", Object::new(ObjectKind::Func(builtin::cons)));
-        self.insert("car", Object::new(ObjectKind::Func(builtin::car)));
-        self.insert("cdr", Object::new(ObjectKind::Func(builtin::cdr)));
-        self.insert("equal", Object::new(ObjectKind::Func(builtin::equal)));
+    pub(crate) fn define_builtin(
+        &mut self,
+        name: &str,
+        lambda_list: &str,
+        func: object::BuiltinFn,
+    ) {
+        self.insert(name, object::builtin(name, lambda_list, func));
+    }
+
+    pub(crate) fn define_env_builtin(
+        &mut self,
+        name: &str,
+        lambda_list: &str,
+        func: object::EnvBuiltinFn,
+    ) {
+        let builtin = object::env_builtin(name, lambda_list, func, self.this());
+        self.insert(name, builtin);
+    }
+
+    pub fn init(&mut self, module: Module) {
+        match module {
+            Module::Core => self.init_core(),
+            Module::List => self.init_list(),
+            Module::Math => self.init_math(),
+            Module::String => self.init_string(),
+            Module::Io => self.init_io(),
+            Module::System => self.init_system(),
+            Module::Control => self.init_control(),
+            Module::Memory => self.init_memory(),
+        }
+    }
+
+    fn init_core(&mut self) {
+        self.insert("t", object::symbol("t"));
+        self.define_builtin("atom?", "x", builtin::is_atom);
+        self.define_builtin("cons", "car cdr", builtin::cons);
+        self.define_builtin("car", "list", builtin::car);
+        self.define_builtin("cdr", "list", builtin::cdr);
+        self.define_builtin("equal", "x y", builtin::equal);
+        self.define_builtin("apply", "function args", builtin::apply);
+        self.define_builtin("closure-source", "function", builtin::closure_source);
+        self.define_builtin("gensym", "&rest prefix", builtin::gensym);
+        self.define_builtin(
+            "%make-condition",
+            "type message irritants fields backtrace",
+            builtin::make_condition,
+        );
+        self.define_builtin("signal", "condition", builtin::signal);
+        self.define_builtin("condition-type", "condition", builtin::condition_type);
+        self.define_builtin("condition-message", "condition", builtin::condition_message);
+        self.define_builtin(
+            "condition-irritants",
+            "condition",
+            builtin::condition_irritants,
+        );
+        self.define_builtin("condition-fields", "condition", builtin::condition_fields);
+        self.define_builtin(
+            "condition-backtrace",
+            "condition",
+            builtin::condition_backtrace,
+        );
+        quasiquote::init(self);
+        self.define_env_builtin("values", "&rest values", values);
+        self.define_env_builtin("backtrace", "", backtrace);
+        self.define_env_builtin("invoke-restart", "name &rest args", invoke_restart);
+        self.define_env_builtin("compute-restarts", "", compute_restarts);
+        self.define_env_builtin("add-expansion-hook", "hook", add_expansion_hook);
+        self.define_env_builtin("remove-expansion-hook", "hook", remove_expansion_hook);
+        self.define_env_builtin("definition-source", "name", definition_source);
+        self.define_builtin("symbol?", "x", builtin::is_symbol);
+        self.define_builtin("keyword?", "x", builtin::is_keyword);
+        self.define_builtin("keyword->symbol", "keyword", builtin::keyword_to_symbol);
+        self.define_builtin("symbol->keyword", "symbol", builtin::symbol_to_keyword);
+        self.insert(
+            "*load-path*",
+            object::cons(object::string("."), object::nil()),
+        );
+        self.insert("*loaded-modules*", object::nil());
+        self.insert("*args*", object::nil());
+        self.insert("*script-path*", object::nil());
+    }
+
+    fn init_list(&mut self) {
+        self.define_builtin("getf", "plist key &rest default", builtin::getf);
+        self.define_builtin("plist-get", "plist key &rest default", builtin::getf);
+        self.define_builtin("plist-member", "plist key", builtin::plist_member);
+        self.define_builtin("plist-put", "plist key value", builtin::plist_put);
+        self.define_builtin("copy-list", "list", builtin::copy_list);
+        self.define_builtin("copy-tree", "tree", builtin::copy_tree);
+        bitvector::init(self);
+        table::init(self);
+    }
+
+    fn init_math(&mut self) {
+        self.define_builtin("+", "&rest numbers", builtin::plus);
+        self.define_builtin("quotient", "n d", builtin::quotient);
+        self.define_builtin("remainder", "n d", builtin::remainder);
+        self.define_builtin(
+            "%number->string",
+            "n &rest radix",
+            builtin::number_to_string,
+        );
+    }
+
+    fn init_string(&mut self) {
+        self.define_builtin("%format", "control &rest args", builtin::format);
+        self.define_builtin("string->list", "string", builtin::string_to_list);
+        self.define_builtin("list->string", "list", builtin::list_to_string);
+    }
+
+    fn init_io(&mut self) {
+        self.define_builtin("%read-line", "port", builtin::read_line);
+        self.define_builtin("%read-char", "port", builtin::read_char);
+        self.define_builtin("%char-ready?", "port", builtin::is_char_ready);
+        self.define_builtin("%write-string", "string port", builtin::write_string);
+        self.define_builtin("%read", "port base &rest eof-value", builtin::read);
+        self.define_builtin("%write", "x port &rest base", builtin::write);
+        self.define_builtin("%display", "x port &rest base", builtin::display);
+        self.define_builtin("open-input-string", "string", builtin::open_input_string);
+        self.define_builtin("open-output-string", "", builtin::open_output_string);
+        self.define_builtin("get-output-string", "port", builtin::get_output_string);
+        self.define_builtin("close-port", "port", builtin::close_port);
+        #[cfg(feature = "std")]
+        if self.profile().allows(Capability::Io) {
+            self.define_env_builtin("open-input-file", "path", open_input_file);
+            self.define_env_builtin("open-output-file", "path", open_output_file);
+        }
+        #[cfg(feature = "std")]
+        let (input, output, error) = (Port::stdin(), Port::stdout(), Port::stderr());
+        #[cfg(not(feature = "std"))]
+        let (input, output, error) = (
+            Port::input_string(""),
+            Port::output_string(),
+            Port::output_string(),
+        );
+        self.insert("*standard-input*", object::port(input));
+        self.insert("*standard-output*", object::port(output));
+        self.insert("*standard-error*", object::port(error));
+        #[cfg(feature = "std")]
+        if self.profile().allows(Capability::Io) {
+            self.define_env_builtin("save-image", "path", save_image);
+        }
+    }
+
+    fn init_system(&mut self) {
+        date::init(self);
+        #[cfg(feature = "std")]
+        {
+            process::init(self);
+            self.define_builtin("make-channel", "", builtin::make_channel);
+            self.define_builtin("send", "channel value", builtin::send);
+            self.define_builtin("try-recv", "channel &rest default", builtin::try_recv);
+            self.define_builtin("recv", "channel", builtin::recv);
+            self.define_builtin("sleep", "seconds", builtin::sleep);
+        }
+    }
+
+    fn init_control(&mut self) {
+        promise::init(self);
+        self.define_builtin("partial", "function &rest args", builtin::partial);
+        self.define_builtin("compose", "&rest functions", builtin::compose);
+        self.define_builtin("memoize", "function", builtin::memoize);
+        #[cfg(feature = "sync")]
+        {
+            self.define_env_builtin("make-generator", "thunk", make_generator);
+            self.define_builtin("yield", "value", builtin::yield_value);
+            self.define_builtin(
+                "generator-next",
+                "generator &rest eof-value",
+                builtin::generator_next,
+            );
+            self.define_builtin("generator-done?", "generator", builtin::is_generator_done);
+        }
+    }
+
+    fn init_memory(&mut self) {
+        weak::init(self);
+        self.define_env_builtin("add-finalizer", "x function", add_finalizer);
+        self.define_env_builtin("run-finalizers", "", run_finalizers);
+        self.define_builtin("gc-stats", "", builtin::gc_stats);
+        self.define_builtin("hash-consing", "&rest on", builtin::hash_consing);
+        self.define_builtin("%dump-form", "x", builtin::dump_form);
+        self.define_builtin("%dump-graph", "x", builtin::dump_graph);
     }
 }
 
 pub fn eval(x: Object, env: Rc<RefCell<Env>>) -> EvalResult {
-    eval_internal(x, env)
+    // Hash-consing is set for each global environment, so one that turns it
+    // on doesn't turn it on for another evaluating on the same thread.
+    let root = Env::root(&env);
+    let on = root.borrow().hash_consing();
+    let keywords = root.borrow().keywords();
+    let (result, on) = object::with_keywords(keywords, || {
+        object::with_hash_consing(on, || eval_internal(x, Rc::clone(&env)))
+    });
+    root.borrow_mut().set_hash_consing(on);
+    run_due_finalizers(&env);
+    result
 }
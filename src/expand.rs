@@ -0,0 +1,278 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::env::Env;
+use super::error::RuntimeError;
+use super::eval::{self, EvalResult};
+use super::object::{self, Object, ObjectKind};
+use super::quasiquote;
+use super::rc::{Rc, RefCell};
+use super::warning::is_special_form;
+
+// Macro expansion ahead of evaluation, all at once or one call at a time.
+
+struct Expander<'a> {
+    env: &'a Rc<RefCell<Env>>,
+    // Local variables, which shadow macros of the same name.
+    scope: Vec<String>,
+    // When stepping, the macro calls met so far, of which only the one at
+    // the given index is expanded, once.
+    step: Option<(usize, Vec<Object>)>,
+}
+
+impl Expander<'_> {
+    // Whether to expand the macro call x, which is always when not stepping.
+    // Otherwise the arguments of a call that isn't expanded are searched
+    // for more calls as if they were forms.
+    fn expands(&mut self, x: &Object) -> bool {
+        match &mut self.step {
+            Some((index, calls)) => {
+                calls.push(Rc::clone(x));
+                calls.len() - 1 == *index
+            }
+            None => true,
+        }
+    }
+
+    fn forms(&mut self, forms: &[Object]) -> Result<Vec<Object>, RuntimeError> {
+        forms.iter().map(|form| self.form(form)).collect()
+    }
+
+    fn body(&mut self, vars: Vec<String>, body: &[Object]) -> Result<Vec<Object>, RuntimeError> {
+        let depth = self.scope.len();
+        self.scope.extend(vars);
+        let body = self.forms(body);
+        self.scope.truncate(depth);
+        body
+    }
+
+    fn form(&mut self, x: &Object) -> EvalResult {
+        let cons = match &**x {
+            ObjectKind::Cons(cons) => cons,
+            _ => return Ok(Rc::clone(x)),
+        };
+        let args = match cons.cdr.as_proper_list() {
+            Some(args) => args,
+            None => return Ok(Rc::clone(x)),
+        };
+        let name = match &*cons.car {
+            ObjectKind::Symbol(name) if !self.scope.contains(name) => name,
+            _ => {
+                let first = self.form(&cons.car)?;
+                return Ok(object::cons(first, object::from_iter(self.forms(&args)?)));
+            }
+        };
+        let args = match name.as_str() {
+            "lambda" => match args.split_first() {
+                Some((list, body)) => match eval::parse_lambda_list(Rc::clone(list)) {
+                    Ok((params, rest)) => {
+                        let body = self.body(params.into_iter().chain(rest).collect(), body)?;
+                        core::iter::once(Rc::clone(list)).chain(body).collect()
+                    }
+                    Err(_) => return Ok(Rc::clone(x)),
+                },
+                None => args,
+            },
+            "define" => match eval::define_shorthand(&args) {
+                Some((var, value)) => alloc::vec![var, self.form(&value)?],
+                None => match args.as_slice() {
+                    [var, value] => alloc::vec![Rc::clone(var), self.form(value)?],
+                    _ => args,
+                },
+            },
+            "with-open-file" => match args.split_first() {
+                Some((spec, body)) => match spec.as_proper_list().as_deref() {
+                    Some([var, path, options @ ..]) => {
+                        let spec = alloc::vec![Rc::clone(var), self.form(path)?]
+                            .into_iter()
+                            .chain(options.iter().cloned());
+                        let vars = match &**var {
+                            ObjectKind::Symbol(var) => alloc::vec![var.clone()],
+                            _ => Vec::new(),
+                        };
+                        core::iter::once(object::from_iter(spec))
+                            .chain(self.body(vars, body)?)
+                            .collect()
+                    }
+                    _ => args,
+                },
+                None => args,
+            },
+            "with-restarts" => match args.split_first() {
+                Some((specs, body)) => {
+                    let mut expanded = Vec::new();
+                    for spec in specs.as_proper_list().unwrap_or_default() {
+                        match spec.as_proper_list().as_deref() {
+                            Some([name, list, body @ ..]) => {
+                                let (params, rest) = eval::parse_lambda_list(Rc::clone(list))?;
+                                let body =
+                                    self.body(params.into_iter().chain(rest).collect(), body)?;
+                                expanded.push(object::from_iter(
+                                    alloc::vec![Rc::clone(name), Rc::clone(list)]
+                                        .into_iter()
+                                        .chain(body),
+                                ));
+                            }
+                            _ => expanded.push(spec),
+                        }
+                    }
+                    core::iter::once(object::from_iter(expanded))
+                        .chain(self.forms(body)?)
+                        .collect()
+                }
+                None => args,
+            },
+            "parameterize" => match args.split_first() {
+                Some((bindings, body)) => {
+                    let mut expanded = Vec::new();
+                    for binding in bindings.as_proper_list().unwrap_or_default() {
+                        match binding.as_proper_list().as_deref() {
+                            Some([var, value]) => expanded
+                                .push(object::from_iter([Rc::clone(var), self.form(value)?])),
+                            _ => expanded.push(binding),
+                        }
+                    }
+                    core::iter::once(object::from_iter(expanded))
+                        .chain(self.forms(body)?)
+                        .collect()
+                }
+                None => args,
+            },
+            "the" => match args.split_first() {
+                Some((ty, forms)) => core::iter::once(Rc::clone(ty))
+                    .chain(self.forms(forms)?)
+                    .collect(),
+                None => args,
+            },
+            "quasiquote" => match args.as_slice() {
+                [template] if self.expands(x) => {
+                    let expansion = quasiquote::expand(template)?;
+                    return match self.step {
+                        Some(_) => Ok(expansion),
+                        None => self.form(&expansion),
+                    };
+                }
+                _ => args,
+            },
+            "quote" | "declare" | "defmacro" | "require" | "provide" | "defpackage"
+            | "in-package" | "export" | "import" => args,
+            name if is_special_form(name) => self.forms(&args)?,
+            name => {
+                let function = self.env.borrow().get(name);
+                match function.as_deref() {
+                    Some(ObjectKind::Macro(closure)) if self.expands(x) => {
+                        let expansion = eval::apply_closure(closure, args)?;
+                        return match self.step {
+                            Some(_) => Ok(expansion),
+                            None => self.form(&expansion),
+                        };
+                    }
+                    _ => self.forms(&args)?,
+                }
+            }
+        };
+        Ok(object::cons(Rc::clone(&cons.car), object::from_iter(args)))
+    }
+}
+
+// Expands every macro call in x, including those in the expansions,
+// leaving quoted data and the macro definitions themselves alone.
+pub fn macroexpand_all(x: &Object, env: &Rc<RefCell<Env>>) -> EvalResult {
+    Expander {
+        env,
+        scope: Vec::new(),
+        step: None,
+    }
+    .form(x)
+}
+
+fn step(
+    x: &Object,
+    env: &Rc<RefCell<Env>>,
+    index: usize,
+) -> Result<(Object, Vec<Object>), RuntimeError> {
+    let mut expander = Expander {
+        env,
+        scope: Vec::new(),
+        step: Some((index, Vec::new())),
+    };
+    let x = expander.form(x)?;
+    Ok((x, expander.step.unwrap().1))
+}
+
+// The macro calls in x that macro_step can expand, outermost first.
+// Quasiquotes count as calls.
+pub fn macro_calls(x: &Object, env: &Rc<RefCell<Env>>) -> Result<Vec<Object>, RuntimeError> {
+    step(x, env, usize::MAX).map(|(_, calls)| calls)
+}
+
+// Expands the macro call macro_calls lists at index once, leaving the
+// others and what the expansion contains for later steps.
+pub fn macro_step(x: &Object, env: &Rc<RefCell<Env>>, index: usize) -> EvalResult {
+    step(x, env, index).map(|(x, _)| x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::{Reader, StringStream};
+    use alloc::string::ToString;
+
+    fn expand(source: &str, env: &Rc<RefCell<Env>>) -> String {
+        let form = StringStream::new(source).read().unwrap();
+        macroexpand_all(&form, env).unwrap().to_string()
+    }
+
+    #[test]
+    fn macroexpand_all_test() {
+        let env = Env::global_env();
+        assert_eq!(
+            expand("(when a (unless b c))", &env),
+            "(if a (progn (if b nil (progn c))))"
+        );
+        assert_eq!(expand("'(when a b)", &env), "(quote (when a b))");
+        assert_eq!(
+            expand("(define ((f x) y) (when x y))", &env),
+            "(define f (lambda (x) (lambda (y) (if x (progn y)))))"
+        );
+        assert_eq!(
+            expand("(lambda (when) (when 1))", &env),
+            "(lambda (when) (when 1))"
+        );
+    }
+
+    #[test]
+    fn macro_step_test() {
+        let env = Env::global_env();
+        let form = StringStream::new("(defun f (x) (when x `(a ,(unless x 1))))")
+            .read()
+            .unwrap();
+        let calls = macro_calls(&form, &env).unwrap();
+        assert_eq!(
+            calls.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [
+                "(defun f (x) (when x (quasiquote (a (unquote (unless x 1))))))",
+                "(when x (quasiquote (a (unquote (unless x 1)))))",
+                "(quasiquote (a (unquote (unless x 1))))",
+            ]
+        );
+        assert_eq!(
+            macro_step(&form, &env, 1).unwrap().to_string(),
+            "(defun f (x) (if x (progn (quasiquote (a (unquote (unless x 1)))))))"
+        );
+        let form = macro_step(&form, &env, 2).unwrap();
+        assert_eq!(
+            form.to_string(),
+            "(defun f (x) (when x (%qq-list (quote a) (unless x 1))))"
+        );
+        assert_eq!(macro_calls(&form, &env).unwrap().len(), 3);
+        assert_eq!(
+            macro_step(&form, &env, 0).unwrap().to_string(),
+            "(define f (lambda (x) (when x (%qq-list (quote a) (unless x 1)))))"
+        );
+        assert_eq!(
+            macro_step(&form, &env, 3).unwrap().to_string(),
+            form.to_string()
+        );
+    }
+}
@@ -0,0 +1,373 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use super::error::RuntimeError;
+use super::object::{self, Object, ObjectKind, ObjectType};
+use super::rc::Rc;
+
+// The directives of CL's format that are useful for reports:
+//
+//   ~a ~s      the next argument as display and write print it
+//   ~d         an integer; ~:d groups its digits with commas
+//   ~r ~b ~o ~x  an integer in radix n (~nr), 2, 8 or 16
+//   ~% ~~      a newline and a tilde
+//   ~{ ... ~}  the body for each element of a list argument
+//   ~^         stops the enclosing ~{ (or format) when no arguments are left
+//
+// Directives take parameters before the letter, separated by commas:
+// ~mincol,padcharA pads to at least mincol columns with padchar ('c),
+// on the right, or on the left with the @ modifier. Numbers pad on the
+// left, so ~5,'0d prints 42 as 00042 and ~16,4,'0r prints 255 as 00ff.
+pub fn format(control: &str, args: &[Object]) -> Result<String, RuntimeError> {
+    let control: Vec<char> = control.chars().collect();
+    let mut out = String::new();
+    run(&control, &mut Args { args, next: 0 }, &mut out)?;
+    Ok(out)
+}
+
+fn invalid(message: &str) -> RuntimeError {
+    RuntimeError::InvalidFormat(message.to_string())
+}
+
+struct Args<'a> {
+    args: &'a [Object],
+    next: usize,
+}
+
+impl Args<'_> {
+    fn next(&mut self) -> Result<Object, RuntimeError> {
+        let x = self
+            .args
+            .get(self.next)
+            .ok_or_else(|| invalid("not enough arguments"))?;
+        self.next += 1;
+        Ok(Rc::clone(x))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.next >= self.args.len()
+    }
+}
+
+enum Param {
+    Number(isize),
+    Char(char),
+}
+
+struct Directive {
+    params: Vec<Option<Param>>,
+    colon: bool,
+    at: bool,
+    letter: char,
+    // Where the control string continues after it.
+    end: usize,
+}
+
+impl Directive {
+    fn number(&self, i: usize, default: isize) -> Result<isize, RuntimeError> {
+        match self.params.get(i) {
+            Some(Some(Param::Number(n))) => Ok(*n),
+            Some(Some(Param::Char(_))) => Err(invalid("expected a number parameter")),
+            _ => Ok(default),
+        }
+    }
+
+    fn char(&self, i: usize, default: char) -> Result<char, RuntimeError> {
+        match self.params.get(i) {
+            Some(Some(Param::Char(c))) => Ok(*c),
+            Some(Some(Param::Number(_))) => Err(invalid("expected a character parameter")),
+            _ => Ok(default),
+        }
+    }
+}
+
+// Parses the directive whose ~ is at start.
+fn parse(control: &[char], start: usize) -> Result<Directive, RuntimeError> {
+    let mut i = start + 1;
+    let mut params = Vec::new();
+    loop {
+        let param = match control.get(i) {
+            Some('\'') => {
+                let c = *control
+                    .get(i + 1)
+                    .ok_or_else(|| invalid("missing character after '"))?;
+                i += 2;
+                Some(Param::Char(c))
+            }
+            Some(c) if c.is_ascii_digit() || *c == '-' || *c == '+' => {
+                let from = i;
+                i += 1;
+                while control.get(i).is_some_and(char::is_ascii_digit) {
+                    i += 1;
+                }
+                let digits: String = control[from..i].iter().collect();
+                let n = digits
+                    .parse()
+                    .map_err(|_| invalid("malformed number parameter"))?;
+                Some(Param::Number(n))
+            }
+            _ => None,
+        };
+        if control.get(i) == Some(&',') {
+            params.push(param);
+            i += 1;
+        } else {
+            if param.is_some() {
+                params.push(param);
+            }
+            break;
+        }
+    }
+    let (mut colon, mut at) = (false, false);
+    loop {
+        match control.get(i) {
+            Some(':') => colon = true,
+            Some('@') => at = true,
+            _ => break,
+        }
+        i += 1;
+    }
+    let letter = control
+        .get(i)
+        .ok_or_else(|| invalid("missing directive after ~"))?
+        .to_ascii_lowercase();
+    Ok(Directive {
+        params,
+        colon,
+        at,
+        letter,
+        end: i + 1,
+    })
+}
+
+// Finds the ~} closing the ~{ whose body starts at start, and returns
+// where the body ends and where the control string continues.
+fn find_close(control: &[char], start: usize) -> Result<(usize, usize), RuntimeError> {
+    let mut depth = 0;
+    let mut i = start;
+    while i < control.len() {
+        if control[i] != '~' {
+            i += 1;
+            continue;
+        }
+        let directive = parse(control, i)?;
+        match directive.letter {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Ok((i, directive.end)),
+            '}' => depth -= 1,
+            _ => (),
+        }
+        i = directive.end;
+    }
+    Err(invalid("~{ without ~}"))
+}
+
+// Whether ~^ stopped the run.
+fn run(control: &[char], args: &mut Args, out: &mut String) -> Result<bool, RuntimeError> {
+    let mut i = 0;
+    while i < control.len() {
+        if control[i] != '~' {
+            out.push(control[i]);
+            i += 1;
+            continue;
+        }
+        let directive = parse(control, i)?;
+        i = directive.end;
+        match directive.letter {
+            'a' => pad(out, &display(&args.next()?), &directive, directive.at)?,
+            's' => pad(out, &args.next()?.to_string(), &directive, directive.at)?,
+            'd' => integer(out, &args.next()?, 10, &directive, 0)?,
+            'b' => integer(out, &args.next()?, 2, &directive, 0)?,
+            'o' => integer(out, &args.next()?, 8, &directive, 0)?,
+            'x' => integer(out, &args.next()?, 16, &directive, 0)?,
+            'r' => {
+                let radix = directive.number(0, 10)?;
+                if !(2..=36).contains(&radix) {
+                    return Err(invalid("radix must be between 2 and 36"));
+                }
+                integer(out, &args.next()?, radix as u32, &directive, 1)?
+            }
+            '%' => out.push('\n'),
+            '~' => out.push('~'),
+            '^' => {
+                if args.is_empty() {
+                    return Ok(true);
+                }
+            }
+            '{' => {
+                let (body_end, end) = find_close(control, i)?;
+                let body = &control[i..body_end];
+                let elements = object::to_vec(&args.next()?)?;
+                let mut inner = Args {
+                    args: &elements,
+                    next: 0,
+                };
+                while !inner.is_empty() {
+                    let before = inner.next;
+                    if run(body, &mut inner, out)? || inner.next == before {
+                        break;
+                    }
+                }
+                i = end;
+            }
+            '}' => return Err(invalid("~} without ~{")),
+            c => return Err(invalid(&format!("unknown directive ~{}", c))),
+        }
+    }
+    Ok(false)
+}
+
+fn display(x: &Object) -> String {
+    match &**x {
+        ObjectKind::String(s) => s.clone(),
+        _ => x.to_string(),
+    }
+}
+
+// Pads s to the directive's mincol with its padchar, which are parameters
+// from `first` on.
+fn pad_from(
+    out: &mut String,
+    s: &str,
+    directive: &Directive,
+    first: usize,
+    left: bool,
+) -> Result<(), RuntimeError> {
+    let mincol = directive.number(first, 0)?.max(0) as usize;
+    let padchar = directive.char(first + 1, ' ')?;
+    let padding: String =
+        core::iter::repeat_n(padchar, mincol.saturating_sub(s.chars().count())).collect();
+    if left {
+        out.push_str(&padding);
+        out.push_str(s);
+    } else {
+        out.push_str(s);
+        out.push_str(&padding);
+    }
+    Ok(())
+}
+
+fn pad(out: &mut String, s: &str, directive: &Directive, left: bool) -> Result<(), RuntimeError> {
+    pad_from(out, s, directive, 0, left)
+}
+
+fn integer(
+    out: &mut String,
+    x: &Object,
+    radix: u32,
+    directive: &Directive,
+    first: usize,
+) -> Result<(), RuntimeError> {
+    let n = match **x {
+        ObjectKind::Fixnum(n) => n,
+        _ => return Err(RuntimeError::MismatchType(Rc::clone(x), ObjectType::Number)),
+    };
+    let mut digits = to_radix(n.unsigned_abs(), radix);
+    if directive.colon {
+        digits = group(&digits);
+    }
+    let sign = if n < 0 {
+        "-"
+    } else if directive.at {
+        "+"
+    } else {
+        ""
+    };
+    pad_from(out, &format!("{}{}", sign, digits), directive, first, true)
+}
+
+pub(crate) fn to_radix(mut n: usize, radix: u32) -> String {
+    let mut digits = Vec::new();
+    loop {
+        digits.push(core::char::from_digit((n % radix as usize) as u32, radix).unwrap());
+        n /= radix as usize;
+        if n == 0 {
+            break;
+        }
+    }
+    digits.iter().rev().collect()
+}
+
+fn group(digits: &str) -> String {
+    let mut grouped = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    grouped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::list;
+
+    fn fmt(control: &str, args: &[Object]) -> String {
+        format(control, args).unwrap()
+    }
+
+    #[test]
+    fn directive_test() {
+        let (n, s) = (object::fixnum(42), object::string("ab"));
+        assert_eq!(
+            fmt("~a ~s~%", &[Rc::clone(&s), Rc::clone(&s)]),
+            "ab \"ab\"\n"
+        );
+        assert_eq!(
+            fmt("[~5a][~5@a]", &[Rc::clone(&s), Rc::clone(&s)]),
+            "[ab   ][   ab]"
+        );
+        assert_eq!(
+            fmt("~5,'0d ~@d", &[Rc::clone(&n), Rc::clone(&n)]),
+            "00042 +42"
+        );
+        assert_eq!(fmt("~:d", &[object::fixnum(-1234567)]), "-1,234,567");
+        assert_eq!(
+            fmt(
+                "~16,4,'0r ~2r ~x",
+                &[object::fixnum(255), object::fixnum(5), object::fixnum(255)]
+            ),
+            "00ff 101 ff"
+        );
+        assert_eq!(fmt("100~~", &[]), "100~");
+    }
+
+    #[test]
+    fn iteration_test() {
+        let xs = list![object::fixnum(1), object::fixnum(2), object::fixnum(3)];
+        assert_eq!(fmt("~{~a~^, ~}", &[Rc::clone(&xs)]), "1, 2, 3");
+        let pairs = list![
+            object::fixnum(1),
+            object::fixnum(2),
+            object::fixnum(3),
+            object::fixnum(4)
+        ];
+        assert_eq!(fmt("~{<~a ~a>~}", &[pairs]), "<1 2><3 4>");
+        let rows = list![list![object::string("a"), object::fixnum(1)]];
+        assert_eq!(fmt("~{~{~3a|~}~%~}", &[rows]), "a  |1  |\n");
+        assert_eq!(fmt("~a~^ ~a", &[object::fixnum(1)]), "1");
+    }
+
+    #[test]
+    fn error_test() {
+        assert!(matches!(
+            format("~a", &[]),
+            Err(RuntimeError::InvalidFormat(_))
+        ));
+        assert!(matches!(
+            format("~{~a", &[object::nil()]),
+            Err(RuntimeError::InvalidFormat(_))
+        ));
+        assert!(matches!(
+            format("~q", &[]),
+            Err(RuntimeError::InvalidFormat(_))
+        ));
+        assert!(matches!(
+            format("~d", &[object::nil()]),
+            Err(RuntimeError::MismatchType(..))
+        ));
+    }
+}
@@ -0,0 +1,123 @@
+use alloc::boxed::Box;
+use core::future::Future;
+use core::pin::{pin, Pin};
+use core::task::{Context, Poll, Waker};
+use std::sync::Arc;
+use std::task::Wake;
+use std::thread::{self, Thread};
+
+#[cfg(feature = "sync")]
+use super::coroutine::{Coroutine, Event};
+use super::eval::EvalResult;
+#[cfg(feature = "sync")]
+use super::object;
+
+// What an async builtin returns. It's polled by whoever awaits the
+// evaluation that called it, which with sync may be on another thread.
+#[cfg(not(feature = "sync"))]
+pub type BoxFuture = Pin<Box<dyn Future<Output = EvalResult>>>;
+#[cfg(feature = "sync")]
+pub type BoxFuture = Pin<Box<dyn Future<Output = EvalResult> + Send>>;
+
+// Evaluation steps run per poll before giving the executor back control.
+#[cfg(feature = "sync")]
+const SLICE: usize = 1000;
+
+// An evaluation running on a coroutine. Each poll runs it for a slice and
+// wakes itself up again, so other tasks get to run in between; when it calls
+// an async builtin the builtin's future is polled here instead, with the
+// executor's waker.
+#[cfg(feature = "sync")]
+pub struct Evaluation {
+    coroutine: Coroutine,
+    awaiting: Option<BoxFuture>,
+    // What the coroutine is resumed with next.
+    input: EvalResult,
+}
+
+#[cfg(feature = "sync")]
+impl Evaluation {
+    pub fn new(coroutine: Coroutine) -> Self {
+        Self {
+            coroutine,
+            awaiting: None,
+            input: Ok(object::nil()),
+        }
+    }
+}
+
+#[cfg(feature = "sync")]
+impl Future for Evaluation {
+    type Output = EvalResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<EvalResult> {
+        let this = self.get_mut();
+        loop {
+            if let Some(future) = &mut this.awaiting {
+                match future.as_mut().poll(cx) {
+                    Poll::Ready(result) => {
+                        this.awaiting = None;
+                        this.input = result;
+                    }
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+            let input = core::mem::replace(&mut this.input, Ok(object::nil()));
+            match this.coroutine.resume(input, Some(SLICE)) {
+                Event::Returned(result) => return Poll::Ready(result),
+                Event::Awaiting(future) => this.awaiting = Some(future),
+                Event::Paused | Event::Yielded(_) => {
+                    cx.waker().wake_by_ref();
+                    return Poll::Pending;
+                }
+            }
+        }
+    }
+}
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+// Runs future to completion on this thread, for async builtins called where
+// there's no executor to hand them to.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct YieldOnce(bool);
+
+    impl Future for YieldOnce {
+        type Output = usize;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<usize> {
+            if self.0 {
+                return Poll::Ready(1);
+            }
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    #[test]
+    fn block_on_test() {
+        assert_eq!(block_on(YieldOnce(false)), 1);
+    }
+}
@@ -0,0 +1,94 @@
+use core::mem;
+use std::sync::Mutex;
+
+use super::coroutine::{self, Coroutine, Event, Kind};
+use super::env::{Dynamic, Env};
+use super::error::RuntimeError;
+use super::eval::{self, EvalResult};
+use super::object::{self, Object};
+use super::rc::{Rc, RefCell};
+
+// (make-generator thunk) runs thunk in a coroutine; each (generator-next g)
+// continues it until it calls (yield value) and returns the value.
+pub struct Generator {
+    state: Mutex<State>,
+}
+
+struct State {
+    coroutine: Option<Coroutine>,
+    env: Rc<RefCell<Env>>,
+    // What the body had established when it last yielded.
+    suspended: Dynamic,
+}
+
+impl Generator {
+    pub fn new(thunk: Object, env: Rc<RefCell<Env>>) -> Self {
+        let coroutine = Coroutine::spawn(Kind::Generator, move || eval::apply(thunk, Vec::new()));
+        Self {
+            state: Mutex::new(State {
+                coroutine: Some(coroutine),
+                env,
+                suspended: Dynamic::default(),
+            }),
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        match self.state.try_lock() {
+            Ok(state) => state.coroutine.is_none(),
+            Err(_) => false,
+        }
+    }
+
+    // Returns the next yielded value, or eof_value once the body has
+    // returned.
+    pub fn next(&self, eof_value: Object) -> EvalResult {
+        let mut state = self
+            .state
+            .try_lock()
+            .map_err(|_| RuntimeError::GeneratorRunning)?;
+        let State {
+            coroutine,
+            env,
+            suspended,
+        } = &mut *state;
+        let running = match coroutine {
+            Some(coroutine) => coroutine,
+            None => return Ok(eof_value),
+        };
+        let mark = env.borrow().mark();
+        env.borrow_mut().rewind(mem::take(suspended));
+        match running.resume(Ok(object::nil()), None) {
+            Event::Yielded(value) => {
+                *suspended = env.borrow_mut().unwind(mark);
+                Ok(value)
+            }
+            Event::Returned(result) => {
+                *coroutine = None;
+                result.map(|_| eof_value)
+            }
+            Event::Paused | Event::Awaiting(_) => {
+                unreachable!("generators only yield or return")
+            }
+        }
+    }
+}
+
+// A generator dropped before it's done is cancelled, which unwinds its body
+// with what it had established put back first.
+impl Drop for Generator {
+    fn drop(&mut self) {
+        let state = match self.state.get_mut() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if let Some(coroutine) = state.coroutine.take() {
+            let env = Rc::clone(&state.env);
+            let suspended = mem::take(&mut state.suspended);
+            coroutine::defer(move || {
+                env.borrow_mut().rewind(suspended);
+                drop(coroutine);
+            });
+        }
+    }
+}
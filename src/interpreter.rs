@@ -0,0 +1,303 @@
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::fs;
+#[cfg(feature = "std")]
+use std::io::{self, Read, Write};
+#[cfg(feature = "std")]
+use std::path::{Path, PathBuf};
+
+use super::accounting::{self, Tally};
+use super::complete::{self, Candidate};
+#[cfg(feature = "sync")]
+use super::coroutine::{Coroutine, Event, Kind};
+use super::env::Env;
+use super::error::Error;
+use super::eval::{self, EvalResult};
+#[cfg(feature = "std")]
+use super::future::BoxFuture;
+#[cfg(feature = "sync")]
+use super::future::{self, Evaluation};
+use super::load;
+use super::object::{self, Object};
+#[cfg(feature = "std")]
+use super::permissions::Permissions;
+use super::port::Port;
+use super::profile::Profile;
+use super::rc::{Rc, RefCell};
+use super::restart::Debugger;
+#[cfg(feature = "std")]
+use super::snapshot;
+use super::warning::Warning;
+
+// An evaluation that runs a limited number of steps at a time, so that a
+// host with an event loop can interleave it with other work. It runs on a
+// coroutine, so it needs the sync feature.
+#[cfg(feature = "sync")]
+pub struct Stepwise {
+    coroutine: Coroutine,
+}
+
+#[cfg(feature = "sync")]
+pub enum Step {
+    Paused,
+    Done(EvalResult),
+}
+
+#[cfg(feature = "sync")]
+impl Stepwise {
+    // Evaluates at most steps forms before returning Paused. Panics if the
+    // evaluation is already done. Async builtins are waited for in place.
+    pub fn resume(&mut self, steps: usize) -> Step {
+        let mut input = Ok(object::nil());
+        loop {
+            match self.coroutine.resume(input, Some(steps)) {
+                Event::Returned(result) => return Step::Done(result),
+                Event::Awaiting(future) => input = future::block_on(future),
+                Event::Paused | Event::Yielded(_) => return Step::Paused,
+            }
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.coroutine.is_finished()
+    }
+}
+
+// Each interpreter has a global environment of its own, and interpreters
+// share no mutable state, so one per document or plugin can't see or change
+// another's definitions and settings. What they do share is immutable: the
+// forms of the prelude, which is read once per thread and evaluated for
+// each new interpreter, keywords, and hash-consed data. The gensym counter
+// is shared too, so that gensyms from two interpreters never collide.
+pub struct Interpreter {
+    env: Rc<RefCell<Env>>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Self {
+            env: Env::global_env(),
+        }
+    }
+
+    // Builtins outside the profile's capabilities aren't defined, for
+    // running scripts that mustn't touch files or the host.
+    pub fn with_profile(profile: Profile) -> Self {
+        Self {
+            env: Env::global_env_with(profile),
+        }
+    }
+
+    // For an environment made with Env::builder, to pick the modules.
+    pub fn with_env(env: Rc<RefCell<Env>>) -> Self {
+        Self { env }
+    }
+
+    // Consulted by the builtins that open files, which fail with
+    // PermissionDenied outside of what's allowed.
+    #[cfg(feature = "std")]
+    pub fn set_permissions(&self, permissions: Permissions) {
+        self.env.borrow_mut().set_permissions(permissions);
+    }
+
+    #[cfg(feature = "std")]
+    pub fn permissions(&self) -> Permissions {
+        Env::permissions(&self.env)
+    }
+
+    pub fn env(&self) -> Rc<RefCell<Env>> {
+        Rc::clone(&self.env)
+    }
+
+    pub fn complete(&self, prefix: &str) -> Vec<Candidate> {
+        complete::complete(prefix, &self.env.borrow())
+    }
+
+    pub fn eval(&self, form: Object) -> EvalResult {
+        self.accounted(|| eval::eval(form, Rc::clone(&self.env)))
+    }
+
+    pub fn eval_str(&self, source: &str) -> Result<Object, Error> {
+        self.accounted(|| Ok(load::load_str(source, Rc::clone(&self.env))?))
+    }
+
+    // Nothing is evaluated until the first resume. Dropping the handle
+    // before it's done cancels the evaluation.
+    #[cfg(feature = "sync")]
+    pub fn eval_stepwise(&self, form: Object) -> Stepwise {
+        let env = Rc::clone(&self.env);
+        Stepwise {
+            coroutine: Coroutine::spawn(Kind::Evaluation, move || eval::eval(form, env)),
+        }
+    }
+
+    // Like eval_stepwise, but driven by an executor: the evaluation gives
+    // it back control every so many steps, and awaits async builtins on it
+    // rather than blocking.
+    #[cfg(feature = "sync")]
+    pub async fn eval_async(&self, form: Object) -> EvalResult {
+        let env = Rc::clone(&self.env);
+        Evaluation::new(Coroutine::spawn(Kind::Evaluation, move || {
+            eval::eval(form, env)
+        }))
+        .await
+    }
+
+    fn accounted<T>(&self, f: impl FnOnce() -> T) -> T {
+        if !self.env.borrow().is_accounting() {
+            return f();
+        }
+        let start = accounting::current();
+        let result = f();
+        let tally = accounting::current().since(&start);
+        self.env.borrow_mut().set_tally(tally);
+        result
+    }
+
+    // With accounting on, tally returns the reductions and allocations of
+    // the last eval, eval_str or eval_file.
+    pub fn set_accounting(&self, on: bool) {
+        self.env.borrow_mut().set_accounting(on);
+    }
+
+    pub fn tally(&self) -> Option<Tally> {
+        self.env.borrow().tally()
+    }
+
+    #[cfg(feature = "std")]
+    pub fn eval_file(&self, path: impl AsRef<Path>) -> Result<Object, Error> {
+        let source = fs::read_to_string(path)?;
+        self.eval_str(&source)
+    }
+
+    // Evaluates the file as a script run with args, which it sees as the
+    // strings in *args*, and its own path in *script-path*.
+    #[cfg(feature = "std")]
+    pub fn eval_script(&self, path: &str, args: &[&str]) -> Result<Object, Error> {
+        self.set("*script-path*", object::string(path));
+        self.set(
+            "*args*",
+            object::from_iter(args.iter().map(|arg| object::string(arg))),
+        );
+        self.eval_file(path)
+    }
+
+    // Evaluates the file, and again each time reload_changed finds it
+    // changed since, in the same global environment.
+    #[cfg(feature = "std")]
+    pub fn watch_load(&self, path: impl AsRef<Path>) -> Result<Object, Error> {
+        Ok(load::watch_load(path.as_ref(), Rc::clone(&self.env))?)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn reload_changed(&self) -> Vec<(PathBuf, Result<Object, Error>)> {
+        load::reload_changed(&self.env)
+            .into_iter()
+            .map(|(path, result)| (path, result.map_err(Error::from)))
+            .collect()
+    }
+
+    // Writes the global environment as Lisp source that restore reads back.
+    #[cfg(feature = "std")]
+    pub fn dump(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(snapshot::dump(&self.env).as_bytes())
+    }
+
+    #[cfg(feature = "std")]
+    pub fn restore(mut reader: impl Read) -> Result<Self, Error> {
+        let mut source = String::new();
+        reader.read_to_string(&mut source)?;
+        let interpreter = Self::new();
+        snapshot::restore(&source, Rc::clone(&interpreter.env))?;
+        Ok(interpreter)
+    }
+
+    // Like dump, but in the image format that save-image writes.
+    #[cfg(feature = "std")]
+    pub fn save_image(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(&snapshot::image(&self.env))
+    }
+
+    #[cfg(feature = "std")]
+    pub fn restore_image(mut reader: impl Read) -> Result<Self, Error> {
+        let mut image = Vec::new();
+        reader.read_to_end(&mut image)?;
+        let source = snapshot::image_source(&image)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Not a lisp image"))?;
+        let interpreter = Self::new();
+        snapshot::restore(source, Rc::clone(&interpreter.env))?;
+        Ok(interpreter)
+    }
+
+    pub fn define(&self, name: &str, value: Object) {
+        self.env.borrow_mut().insert(name, value);
+    }
+
+    pub fn define_fn(&self, name: &str, func: fn(&[Object]) -> EvalResult) {
+        self.define(name, object::builtin(name, "&rest args", func));
+    }
+
+    // Lisp code calls it like any other function. Under eval_async its
+    // future is awaited by the executor; elsewhere it blocks until ready.
+    #[cfg(feature = "std")]
+    pub fn define_async_fn(&self, name: &str, func: fn(Vec<Object>) -> BoxFuture) {
+        self.define(name, object::async_builtin(name, "&rest args", func));
+    }
+
+    // The hook receives each form before it's evaluated and returns the form
+    // to evaluate instead. The returned object removes it again.
+    pub fn add_expansion_hook(&self, name: &str, func: fn(&[Object]) -> EvalResult) -> Object {
+        let hook = object::builtin(name, "form", func);
+        self.env.borrow_mut().add_hook(Rc::clone(&hook));
+        hook
+    }
+
+    pub fn remove_expansion_hook(&self, hook: &Object) -> bool {
+        self.env.borrow_mut().remove_hook(hook)
+    }
+
+    // Warnings about code evaluated since the last call.
+    pub fn take_warnings(&self) -> Vec<Warning> {
+        self.env.borrow_mut().take_warnings()
+    }
+
+    pub fn set_debugger(&self, debugger: Option<Debugger>) {
+        self.env.borrow_mut().set_debugger(debugger);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Object> {
+        self.env.borrow().get(name)
+    }
+
+    pub fn set(&self, name: &str, value: Object) -> bool {
+        self.env.borrow_mut().set(name, value)
+    }
+
+    // Everything the program prints goes through these ports, so a host can
+    // capture it with Port::output_string().
+    pub fn output_port(&self) -> Object {
+        self.get("*standard-output*").unwrap()
+    }
+
+    pub fn error_port(&self) -> Object {
+        self.get("*standard-error*").unwrap()
+    }
+
+    pub fn set_output_port(&self, port: Port) -> Object {
+        let port = object::port(port);
+        self.set("*standard-output*", Rc::clone(&port));
+        port
+    }
+
+    pub fn set_error_port(&self, port: Port) -> Object {
+        let port = object::port(port);
+        self.set("*standard-error*", Rc::clone(&port));
+        port
+    }
+}
@@ -1,6 +1,63 @@
-pub mod object;
-pub mod error;
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+pub mod accounting;
+#[cfg(any(feature = "test-util", test))]
+pub mod arbitrary;
+pub mod bitvector;
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(feature = "std")]
+pub mod channel;
+pub mod collections;
+pub mod complete;
+pub mod convert;
+#[cfg(feature = "sync")]
+pub mod coroutine;
+pub mod date;
+pub mod dump;
+pub mod env;
 pub mod equal;
+pub mod error;
+pub mod eval;
+pub mod expand;
+pub mod format;
+#[cfg(feature = "std")]
+pub mod future;
+#[cfg(feature = "sync")]
+pub mod generator;
+pub mod interpreter;
+pub mod load;
+pub mod object;
+pub mod package;
+#[cfg(feature = "std")]
+pub mod permissions;
+pub mod port;
+pub mod pretty;
+#[cfg(feature = "std")]
+pub mod process;
+pub mod profile;
+pub mod promise;
+pub mod quasiquote;
+pub mod rc;
 pub mod reader;
-pub mod env;
-pub mod eval;
\ No newline at end of file
+pub mod restart;
+#[cfg(feature = "std")]
+pub mod server;
+pub mod snapshot;
+#[cfg(feature = "std")]
+pub mod suite;
+pub mod table;
+#[cfg(feature = "std")]
+pub mod timer;
+pub mod walk;
+pub mod warning;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod weak;
+
+pub use complete::{complete, Candidate};
+pub use error::Error;
+pub use interpreter::Interpreter;
+pub use warning::lint;
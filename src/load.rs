@@ -0,0 +1,335 @@
+use alloc::string::ToString;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::env as process_env;
+#[cfg(feature = "std")]
+use std::ffi::OsStr;
+#[cfg(feature = "std")]
+use std::fs;
+#[cfg(feature = "std")]
+use std::path::{Path, PathBuf};
+#[cfg(feature = "std")]
+use std::time::SystemTime;
+
+use super::env::{Env, Source};
+use super::error::RuntimeError;
+use super::eval::{eval, EvalResult};
+#[cfg(feature = "std")]
+use super::object::ObjectType;
+use super::object::{self, Object, ObjectKind};
+#[cfg(feature = "std")]
+use super::permissions::Permissions;
+#[cfg(feature = "std")]
+use super::profile::Capability;
+use super::rc::{Rc, RefCell};
+use super::reader::{ReadError, Reader, ReaderInternal, ReaderOptions, StringStream};
+
+#[cfg(feature = "std")]
+const LOAD_PATH: &str = "*load-path*";
+const LOADED_MODULES: &str = "*loaded-modules*";
+
+fn loaded_modules(env: &Env) -> Result<Vec<Object>, RuntimeError> {
+    env.get(LOADED_MODULES)
+        .map_or(Ok(Vec::new()), |list| object::to_vec(&list))
+}
+
+fn is_provided(name: &str, env: &Env) -> Result<bool, RuntimeError> {
+    Ok(loaded_modules(env)?.iter().any(|module| match &**module {
+        ObjectKind::Symbol(module) => module == name,
+        _ => false,
+    }))
+}
+
+// The directories in *load-path*, then those in lisp_path, which is the
+// value of LISP_PATH.
+#[cfg(feature = "std")]
+fn load_path(env: &Env, lisp_path: Option<&OsStr>) -> Result<Vec<PathBuf>, RuntimeError> {
+    let mut dirs = Vec::new();
+    if let Some(list) = env.get(LOAD_PATH) {
+        for dir in object::to_vec(&list)? {
+            match &*dir {
+                ObjectKind::String(dir) => dirs.push(PathBuf::from(dir)),
+                _ => return Err(RuntimeError::MismatchType(dir, ObjectType::String)),
+            }
+        }
+    }
+    if let Some(paths) = lisp_path {
+        dirs.extend(process_env::split_paths(paths));
+    }
+    Ok(dirs)
+}
+
+// Only files that may be read are looked for, so that whether a module is
+// found doesn't tell if one exists elsewhere.
+#[cfg(feature = "std")]
+fn find_module(name: &str, env: &Env, permissions: &Permissions) -> Result<PathBuf, RuntimeError> {
+    let file = format!("{}.lisp", name);
+    load_path(env, process_env::var_os("LISP_PATH").as_deref())?
+        .into_iter()
+        .map(|dir| dir.join(&file))
+        .find(|path| permissions.check_read(path).is_ok() && path.is_file())
+        .ok_or_else(|| RuntimeError::ModuleNotFound(name.to_string()))
+}
+
+pub fn eval_forms(source: &str, env: Rc<RefCell<Env>>) -> EvalResult {
+    eval_forms_from(source, None, env)
+}
+
+// The forms of source, each with the line it starts on.
+struct Forms<'a> {
+    source: &'a str,
+    stream: StringStream,
+    line: usize,
+    counted: usize,
+}
+
+impl<'a> Forms<'a> {
+    fn new(source: &'a str, options: ReaderOptions) -> Self {
+        Self {
+            source,
+            stream: StringStream::new(source).with_options(options),
+            line: 1,
+            counted: 0,
+        }
+    }
+}
+
+impl Iterator for Forms<'_> {
+    type Item = Result<(Object, usize), ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.stream.skip_spaces();
+        if let Err(ReadError::EndOfFile) = self.stream.peek_char() {
+            return None;
+        }
+        self.line += self.source[self.counted..self.stream.pos()]
+            .matches('\n')
+            .count();
+        self.counted = self.stream.pos();
+        Some(self.stream.read().map(|form| (form, self.line)))
+    }
+}
+
+fn eval_at(form: Object, loading: Source, env: &Rc<RefCell<Env>>) -> EvalResult {
+    let root = Env::root(env);
+    let previous = root.borrow_mut().set_loading(Some(loading));
+    let value = eval(form, Rc::clone(env));
+    root.borrow_mut().set_loading(previous);
+    value
+}
+
+// Globals defined by the forms get their file and line as their source.
+fn eval_forms_from(source: &str, file: Option<&str>, env: Rc<RefCell<Env>>) -> EvalResult {
+    let options = env.borrow().reader_options();
+    let root = Env::root(&env);
+    let mut forms = Forms::new(source, options);
+    let mut result = object::nil();
+    loop {
+        // Literals are hash-consed, and integers read in *read-base*, as
+        // the forms before them left it set.
+        forms.stream.set_base(root.borrow().read_base());
+        let on = root.borrow().hash_consing();
+        let keywords = root.borrow().keywords();
+        let read =
+            object::with_keywords(keywords, || object::with_hash_consing(on, || forms.next()));
+        let (form, line) = match read.0 {
+            Some(form) => form.map_err(RuntimeError::ReadError)?,
+            None => break,
+        };
+        let loading = Source {
+            file: file.map(ToString::to_string),
+            line,
+        };
+        result = eval_at(form, loading, &env)?;
+    }
+    Ok(result)
+}
+
+// The preludes every global environment loads are read once per thread, and
+// their forms, which are immutable, shared by all of them.
+#[cfg(feature = "std")]
+fn prelude_forms(source: &'static str, options: ReaderOptions) -> Rc<Vec<(Object, usize)>> {
+    use core::cell::RefCell;
+
+    type Preludes = Vec<((usize, ReaderOptions), Rc<Vec<(Object, usize)>>)>;
+    std::thread_local! {
+        static PRELUDES: RefCell<Preludes> = const { RefCell::new(Vec::new()) };
+    }
+    let key = (source.as_ptr() as usize, options);
+    PRELUDES.with(|preludes| {
+        if let Some((_, forms)) = preludes.borrow().iter().find(|(k, _)| *k == key) {
+            return Rc::clone(forms);
+        }
+        let forms = Rc::new(read_prelude(source, options));
+        preludes.borrow_mut().push((key, Rc::clone(&forms)));
+        forms
+    })
+}
+
+#[cfg(not(feature = "std"))]
+fn prelude_forms(source: &'static str, options: ReaderOptions) -> Rc<Vec<(Object, usize)>> {
+    Rc::new(read_prelude(source, options))
+}
+
+fn read_prelude(source: &str, options: ReaderOptions) -> Vec<(Object, usize)> {
+    Forms::new(source, options)
+        .collect::<Result<_, _>>()
+        .expect("failed to read a prelude")
+}
+
+// Like load_source for one of the sources built in.
+pub(crate) fn load_prelude(
+    source: &'static str,
+    file: &'static str,
+    env: Rc<RefCell<Env>>,
+) -> EvalResult {
+    let options = env.borrow().reader_options();
+    let mut result = object::nil();
+    for (form, line) in prelude_forms(source, options).iter() {
+        let loading = Source {
+            file: Some(file.to_string()),
+            line: *line,
+        };
+        result = eval_at(Rc::clone(form), loading, &env)?;
+    }
+    Ok(result)
+}
+
+// Reads every form of source without evaluating any, so a reader macro or
+// *read-base* set by the source itself has no effect.
+pub fn read_forms(source: &str) -> Result<Vec<Object>, ReadError> {
+    let mut stream = StringStream::new(source);
+    let mut forms = Vec::new();
+    loop {
+        stream.skip_spaces();
+        if let Err(ReadError::EndOfFile) = stream.peek_char() {
+            return Ok(forms);
+        }
+        forms.push(stream.read()?);
+    }
+}
+
+pub fn load_str(source: &str, env: Rc<RefCell<Env>>) -> EvalResult {
+    load_source(source, None, env)
+}
+
+// Like load_str, with file as the source of what source defines.
+pub fn load_source(source: &str, file: Option<&str>, env: Rc<RefCell<Env>>) -> EvalResult {
+    // An in-package inside the loaded source only lasts until its end.
+    let root = Env::root(&env);
+    let package = root.borrow().packages().current().to_string();
+    let result = eval_forms_from(source, file, env);
+    root.borrow_mut()
+        .packages_mut()
+        .set_current(&package)
+        .unwrap();
+    result
+}
+
+#[cfg(feature = "std")]
+pub fn load_file(path: &Path, env: Rc<RefCell<Env>>) -> EvalResult {
+    Env::permissions(&env).check_read(path)?;
+    let source = fs::read_to_string(path)
+        .map_err(|e| RuntimeError::LoadError(path.display().to_string(), e))?;
+    load_source(&source, Some(&path.display().to_string()), env)
+}
+
+#[cfg(feature = "std")]
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+// Loads the file like load_file, and again from reload_changed each time it
+// changes after.
+#[cfg(feature = "std")]
+pub fn watch_load(path: &Path, env: Rc<RefCell<Env>>) -> EvalResult {
+    let root = Env::root(&env);
+    root.borrow_mut().watch(path.to_path_buf(), modified(path));
+    load_file(path, root)
+}
+
+// Reloads the watched files modified since they were last loaded, in the
+// order they were first watched, with the result of each. A file that's
+// missing, as while an editor replaces it, is left until it's back.
+#[cfg(feature = "std")]
+pub fn reload_changed(env: &Rc<RefCell<Env>>) -> Vec<(PathBuf, EvalResult)> {
+    let root = Env::root(env);
+    let watched = root.borrow().watched();
+    let mut reloaded = Vec::new();
+    for (path, loaded) in watched {
+        let modified = match modified(&path) {
+            Some(modified) if Some(modified) != loaded => modified,
+            _ => continue,
+        };
+        root.borrow_mut().watch(path.clone(), Some(modified));
+        let result = load_file(&path, Rc::clone(&root));
+        reloaded.push((path, result));
+    }
+    reloaded
+}
+
+pub fn provide(name: &str, env: &Rc<RefCell<Env>>) -> EvalResult {
+    let feature = object::symbol(name);
+    if !is_provided(name, &env.borrow())? {
+        let root = Env::root(env);
+        let modules = root
+            .borrow()
+            .get(LOADED_MODULES)
+            .unwrap_or_else(object::nil);
+        root.borrow_mut()
+            .insert(LOADED_MODULES, object::cons(Rc::clone(&feature), modules));
+    }
+    Ok(feature)
+}
+
+pub fn require(name: &str, env: Rc<RefCell<Env>>) -> EvalResult {
+    if is_provided(name, &env.borrow())? {
+        return Ok(object::symbol(name));
+    }
+    load_module(name, env)
+}
+
+// Without a file system, only modules that were already provided are found.
+#[cfg(not(feature = "std"))]
+fn load_module(name: &str, _env: Rc<RefCell<Env>>) -> EvalResult {
+    Err(RuntimeError::ModuleNotFound(name.to_string()))
+}
+
+#[cfg(feature = "std")]
+fn load_module(name: &str, env: Rc<RefCell<Env>>) -> EvalResult {
+    if !Env::allows(&env, Capability::Io) {
+        return Err(RuntimeError::ModuleNotFound(name.to_string()));
+    }
+    let path = find_module(name, &env.borrow(), &Env::permissions(&env))?;
+    let root = Env::root(&env);
+    let modules = root
+        .borrow()
+        .get(LOADED_MODULES)
+        .unwrap_or_else(object::nil);
+
+    // Record the module before loading it so that mutually requiring
+    // modules don't recurse forever.
+    let feature = provide(name, &env)?;
+    if let Err(e) = load_file(&path, Rc::clone(&root)) {
+        root.borrow_mut().insert(LOADED_MODULES, modules);
+        return Err(e);
+    }
+    Ok(feature)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_path_test() {
+        let env = Env::global_env();
+        env.borrow_mut()
+            .insert(LOAD_PATH, object::cons(object::string("a"), object::nil()));
+        let lisp_path = process_env::join_paths(["b", "c"]).unwrap();
+        let dirs = load_path(&env.borrow(), Some(&lisp_path)).unwrap();
+        assert_eq!(dirs, ["a", "b", "c"].map(PathBuf::from));
+        let dirs = load_path(&env.borrow(), None).unwrap();
+        assert_eq!(dirs, [PathBuf::from("a")]);
+    }
+}
@@ -1,29 +1,362 @@
-use std::io::{self, stdin, stdout, Write};
-use std::rc::Rc;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, stdout, IsTerminal, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use lisp::reader::Reader;
+use lisp::accounting::Tally;
+use lisp::env::Env;
+use lisp::error::{Error, RuntimeError};
+use lisp::expand;
+use lisp::list;
+use lisp::load;
+use lisp::object::{self, symbol, Object, ObjectKind, Radix};
+use lisp::port::Port;
+use lisp::rc::Rc;
+use lisp::restart::Restart;
+use lisp::Interpreter;
 
 fn prompt(s: &str) -> io::Result<()> {
     let stdout = stdout();
     let mut stdout = stdout.lock();
-    stdout.write(s.as_bytes())?;
+    stdout.write_all(s.as_bytes())?;
     stdout.flush()
 }
 
+fn usage() -> ! {
+    eprintln!(
+        "usage: lisp [--image FILE | --scheme] [SCRIPT [ARGS...]] | lisp --serve PORT [--shared] | lisp --lint FILE... | lisp --watch FILE"
+    );
+    process::exit(2);
+}
+
+fn serve(port: &str, shared: bool) {
+    let port: u16 = port.parse().unwrap_or_else(|_| usage());
+    let listener = TcpListener::bind(("127.0.0.1", port)).unwrap_or_else(|e| {
+        eprintln!("Cannot listen on port {}: {}", port, e);
+        process::exit(1);
+    });
+    if let Err(e) = lisp::server::serve(listener, shared) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+// Prints the warnings about each file, which isn't evaluated, and exits
+// with 1 if there were any.
+fn lint(paths: &[&str]) -> ! {
+    let mut clean = true;
+    for path in paths {
+        let forms = match fs::read_to_string(path) {
+            Ok(source) => load::read_forms(&source).map_err(Error::from),
+            Err(e) => Err(Error::from(e)),
+        };
+        match forms {
+            Ok(forms) => {
+                for warning in lisp::lint(&forms) {
+                    println!("{}: {}", path, warning);
+                    clean = false;
+                }
+            }
+            Err(e) => {
+                eprintln!("Cannot read {}: {}", path, e);
+                clean = false;
+            }
+        }
+    }
+    process::exit(if clean { 0 } else { 1 });
+}
+
+enum Boot<'a> {
+    Prelude,
+    Scheme,
+    // An image written by save-image, instead of loading the prelude and
+    // whatever the image was saved after.
+    Image(&'a str),
+}
+
+fn new_interpreter(boot: &Boot) -> Interpreter {
+    match boot {
+        Boot::Image(path) => File::open(path)
+            .map_err(Error::from)
+            .and_then(Interpreter::restore_image)
+            .unwrap_or_else(|e| {
+                eprintln!("Cannot boot from {}: {}", path, e);
+                process::exit(1);
+            }),
+        Boot::Prelude => Interpreter::new(),
+        Boot::Scheme => Interpreter::with_env(Env::builder().scheme().build()),
+    }
+}
+
+fn run_script(boot: &Boot, path: &str, args: &[&str]) {
+    let interpreter = new_interpreter(boot);
+    let result = interpreter.eval_script(path, args);
+    for warning in interpreter.take_warnings() {
+        eprintln!("; Warning: {}", warning);
+    }
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+fn report_reloads(interpreter: &Interpreter, reloaded: Vec<(PathBuf, Result<Object, Error>)>) {
+    for (path, result) in reloaded {
+        match result {
+            Ok(_) => println!("; Reloaded {}", path.display()),
+            Err(e) => println!("; Reloading {}: {}", path.display(), e),
+        }
+        for warning in interpreter.take_warnings() {
+            println!("; Warning: {}", warning);
+        }
+    }
+}
+
+// Loads the file, then reloads it into the same environment whenever it
+// changes, until interrupted.
+fn watch(boot: &Boot, path: &str) -> ! {
+    let interpreter = new_interpreter(boot);
+    let result = interpreter.watch_load(path);
+    for warning in interpreter.take_warnings() {
+        println!("; Warning: {}", warning);
+    }
+    if let Err(e) = result {
+        println!("{}", e);
+    }
+    loop {
+        thread::sleep(Duration::from_millis(250));
+        report_reloads(&interpreter, interpreter.reload_changed());
+    }
+}
+
+// Results are printed in *print-base*, as print would.
+fn print_base(interpreter: &Interpreter) -> u32 {
+    match interpreter.get("*print-base*").as_deref() {
+        Some(ObjectKind::Fixnum(n)) if (2..=36).contains(n) => *n as u32,
+        _ => 10,
+    }
+}
+
+// ,doc NAME (read as (unquote doc) NAME) prints the docstring of a function or macro.
+fn show_doc(interpreter: &Interpreter, name: Object) {
+    let form = list![symbol("doc"), list![symbol("quote"), Rc::clone(&name)]];
+    match interpreter.eval(form) {
+        Ok(doc) => match &*doc {
+            ObjectKind::String(doc) => println!("{}", doc),
+            _ => println!("No documentation for {}", name),
+        },
+        Err(e) => println!("{}", e),
+    }
+}
+
+// With ,stats on, each evaluation is followed by what it cost, from the
+// interpreter's accounting.
+fn show_stats(elapsed: Duration, tally: Option<Tally>) {
+    let tally = tally.unwrap_or_default();
+    println!(
+        "; {:.3} ms, {} reductions, {} conses, {} allocations",
+        elapsed.as_secs_f64() * 1000.0,
+        tally.reductions,
+        tally.conses,
+        tally.allocations
+    );
+}
+
+// Shows form and the macro calls in it, then expands the one chosen, the
+// first by default, until there are none left or the user quits.
+fn step_expand(interpreter: &Interpreter, mut form: Object) {
+    let env = interpreter.env();
+    let stdin = Port::stdin();
+    loop {
+        println!("{}", form);
+        let calls = match expand::macro_calls(&form, &env) {
+            Ok(calls) if calls.is_empty() => return,
+            Ok(calls) => calls,
+            Err(e) => return println!("{}", e),
+        };
+        for (i, call) in calls.iter().enumerate() {
+            let call = call.to_string();
+            match call.char_indices().nth(60) {
+                Some((end, _)) => println!("  {}: {} ...", i, &call[..end]),
+                None => println!("  {}: {}", i, call),
+            }
+        }
+        if prompt("Expand which (q to quit) [0]: ").is_err() {
+            return;
+        }
+        let line = match stdin.read_line() {
+            Ok(Some(line)) => line,
+            _ => return,
+        };
+        let index = match line.trim() {
+            "" => 0,
+            line => match line.parse::<usize>() {
+                Ok(n) if n < calls.len() => n,
+                _ => return,
+            },
+        };
+        form = match expand::macro_step(&form, &env, index) {
+            Ok(form) => form,
+            Err(e) => return println!("{}", e),
+        };
+    }
+}
+
+// Lists the restarts and reads a choice, then a form for each argument.
+// Anything that isn't a restart number aborts.
+fn debugger(error: &RuntimeError, restarts: &[Restart]) -> Option<(usize, Vec<Object>)> {
+    println!("{}", error);
+    println!("Restarts:");
+    for (i, restart) in restarts.iter().enumerate() {
+        println!(
+            "  {}: {} ({})",
+            i,
+            restart.name,
+            restart.parameters.join(" ")
+        );
+    }
+    println!("  {}: abort", restarts.len());
+
+    let stdin = Port::stdin();
+    let line = loop {
+        prompt("Choose a restart: ").ok()?;
+        match stdin.read_line().ok()?? {
+            line if line.trim().is_empty() => continue,
+            line => break line,
+        }
+    };
+    let n: usize = line.trim().parse().ok()?;
+    let restart = restarts.get(n)?;
+    let mut forms = Vec::new();
+    for parameter in restart.parameters.iter() {
+        prompt(&format!("{}: ", parameter)).ok()?;
+        forms.push(stdin.read().ok()??);
+        skip_line_end(&stdin);
+    }
+    Some((n, forms))
+}
+
+// Drops the rest of the line after a form if it's only whitespace, so that
+// the next read-line or debugger prompt starts on a fresh line.
+fn skip_line_end(port: &Port) {
+    while let Ok(Some(b)) = port.peek_byte() {
+        match b {
+            b' ' | b'\t' | b'\r' => (),
+            b'\n' => {
+                let _ = port.read_byte();
+                return;
+            }
+            _ => return,
+        }
+        let _ = port.read_byte();
+    }
+}
+
 fn main() {
-    let stdin = stdin();
-    let stdin = stdin.lock();
-    let mut reader = lisp::reader::InputStream::from_reader(stdin);
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let (boot, args) = match args.as_slice() {
+        ["--image", image, args @ ..] => (Boot::Image(image), args),
+        ["--scheme", args @ ..] => (Boot::Scheme, args),
+        args => (Boot::Prelude, args),
+    };
+    match (&boot, args) {
+        (_, []) => (),
+        (Boot::Prelude, ["--serve", port]) => return serve(port, false),
+        (Boot::Prelude, ["--serve", port, "--shared"]) => return serve(port, true),
+        (Boot::Prelude, ["--lint", paths @ ..]) if !paths.is_empty() => lint(paths),
+        (_, ["--watch", path]) => watch(&boot, path),
+        (_, [script, script_args @ ..]) if !script.starts_with("--") => {
+            return run_script(&boot, script, script_args)
+        }
+        _ => usage(),
+    }
 
-    let env = lisp::env::Env::global_env();
+    // Forms are read through a port so that the debugger and Lisp code
+    // reading from standard input share its buffer.
+    let stdin = Port::stdin();
+
+    let interpreter = new_interpreter(&boot);
+    // With input piped in, the debugger's prompts would read the lines
+    // meant as forms, so errors are only printed.
+    if io::stdin().is_terminal() {
+        interpreter.set_debugger(Some(debugger));
+    }
+    let options = interpreter.env().borrow().reader_options();
+    let mut stats = false;
 
     loop {
         prompt("LISP> ").unwrap();
-        match reader.read() {
-            Ok(x) => match lisp::eval::eval(x, Rc::clone(&env)) {
-                Ok(result) => println!("{}", result),
+        // Literals are hash-consed if the interpreter has it on, keywords
+        // interned as it interns them, and integers read in *read-base*.
+        let on = interpreter.env().borrow().hash_consing();
+        let keywords = interpreter.env().borrow().keywords();
+        let base = interpreter.env().borrow().read_base();
+        let read = object::with_keywords(keywords, || {
+            object::with_hash_consing(on, || stdin.read_with(base, options))
+        });
+        match read.0 {
+            Ok(Some(x)) if x.to_string() == "(unquote doc)" => match stdin.read_with(base, options)
+            {
+                Ok(Some(name)) => {
+                    skip_line_end(&stdin);
+                    show_doc(&interpreter, name)
+                }
+                Ok(None) => (),
                 Err(e) => println!("{}", e),
             },
+            Ok(Some(x)) if x.to_string() == "(unquote step-expand)" => {
+                match stdin.read_with(base, options) {
+                    Ok(Some(form)) => {
+                        skip_line_end(&stdin);
+                        step_expand(&interpreter, form)
+                    }
+                    Ok(None) => (),
+                    Err(e) => println!("{}", e),
+                }
+            }
+            Ok(Some(x)) if x.to_string() == "(unquote stats)" => {
+                match stdin.read_with(base, options) {
+                    Ok(Some(mode)) => {
+                        skip_line_end(&stdin);
+                        match mode.to_string().as_str() {
+                            "on" => stats = true,
+                            "off" => stats = false,
+                            _ => println!("Usage: ,stats on|off"),
+                        }
+                        interpreter.set_accounting(stats);
+                    }
+                    Ok(None) => (),
+                    Err(e) => println!("{}", e),
+                }
+            }
+            Ok(Some(x)) => {
+                skip_line_end(&stdin);
+                // Files loaded with watch-load are reloaded before the next
+                // form that might use them.
+                report_reloads(&interpreter, interpreter.reload_changed());
+                let start = Instant::now();
+                let result = interpreter.eval(x);
+                let elapsed = start.elapsed();
+                for warning in interpreter.take_warnings() {
+                    println!("; Warning: {}", warning);
+                }
+                match result {
+                    Ok(result) => println!("{}", Radix(&result, print_base(&interpreter))),
+                    Err(e) => println!("{}", e),
+                }
+                if stats {
+                    show_stats(elapsed, interpreter.tally());
+                }
+            }
+            Ok(None) => {
+                println!();
+                break;
+            }
             Err(e) => {
                 println!("{}", e);
             }
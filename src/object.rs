@@ -1,17 +1,136 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
 use core::fmt;
-use std::cell::RefCell;
-use std::rc::Rc;
+use core::sync::atomic::{AtomicUsize, Ordering};
 
-use super::error::RuntimeError;
+use super::accounting;
+use super::bitvector::BitVector;
+#[cfg(feature = "std")]
+use super::channel::Channel;
+use super::collections::Map;
 use super::env::Env;
+use super::error::RuntimeError;
+use super::eval::EvalOptions;
+use super::format;
+#[cfg(feature = "std")]
+use super::future::BoxFuture;
+#[cfg(feature = "sync")]
+use super::generator::Generator;
+use super::port::Port;
+use super::promise::Promise;
+use super::rc::{Rc, RefCell, Weak};
+use super::table::{HashTable, Set};
+use super::weak::{WeakRef, WeakTable};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ObjectType {
     Number,
+    // t or nil.
+    Boolean,
     Function,
     Cons,
     Symbol,
+    String,
+    Keyword,
     List,
+    InputPort,
+    OutputPort,
+    Condition,
+    BitVector,
+    Bit,
+    Promise,
+    WeakRef,
+    WeakTable,
+    HashTable,
+    Set,
+    Generator,
+    Channel,
+}
+
+impl ObjectType {
+    // The type names accepted by (the type x) and (declare (type type x)).
+    pub fn from_name(name: &str) -> Option<ObjectType> {
+        let t = match name {
+            "fixnum" | "number" => ObjectType::Number,
+            "boolean" => ObjectType::Boolean,
+            "function" => ObjectType::Function,
+            "cons" => ObjectType::Cons,
+            "symbol" => ObjectType::Symbol,
+            "string" => ObjectType::String,
+            "keyword" => ObjectType::Keyword,
+            "list" => ObjectType::List,
+            "input-port" => ObjectType::InputPort,
+            "output-port" => ObjectType::OutputPort,
+            "condition" => ObjectType::Condition,
+            "bit-vector" => ObjectType::BitVector,
+            "bit" => ObjectType::Bit,
+            "promise" => ObjectType::Promise,
+            "weak-ref" => ObjectType::WeakRef,
+            "weak-table" => ObjectType::WeakTable,
+            "hash-table" => ObjectType::HashTable,
+            "set" => ObjectType::Set,
+            "generator" => ObjectType::Generator,
+            "channel" => ObjectType::Channel,
+            _ => return None,
+        };
+        Some(t)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ObjectType::Number => "number",
+            ObjectType::Boolean => "boolean",
+            ObjectType::Function => "function",
+            ObjectType::Cons => "cons",
+            ObjectType::Symbol => "symbol",
+            ObjectType::String => "string",
+            ObjectType::Keyword => "keyword",
+            ObjectType::List => "list",
+            ObjectType::InputPort => "input-port",
+            ObjectType::OutputPort => "output-port",
+            ObjectType::Condition => "condition",
+            ObjectType::BitVector => "bit-vector",
+            ObjectType::Bit => "bit",
+            ObjectType::Promise => "promise",
+            ObjectType::WeakRef => "weak-ref",
+            ObjectType::WeakTable => "weak-table",
+            ObjectType::HashTable => "hash-table",
+            ObjectType::Set => "set",
+            ObjectType::Generator => "generator",
+            ObjectType::Channel => "channel",
+        }
+    }
+
+    pub fn contains(&self, x: &ObjectKind) -> bool {
+        match (self, x) {
+            (ObjectType::Number, ObjectKind::Fixnum(_)) => true,
+            (ObjectType::Boolean, ObjectKind::Nil) => true,
+            (ObjectType::Boolean, ObjectKind::Symbol(s)) => s == "t",
+            (ObjectType::Function, ObjectKind::Func(_) | ObjectKind::Closure(_)) => true,
+            (ObjectType::Cons, ObjectKind::Cons(_)) => true,
+            (ObjectType::Symbol, ObjectKind::Nil | ObjectKind::Symbol(_)) => true,
+            (ObjectType::String, ObjectKind::String(_)) => true,
+            (ObjectType::Keyword, ObjectKind::Symbol(s)) => s.starts_with(':'),
+            (ObjectType::List, ObjectKind::Nil | ObjectKind::Cons(_)) => true,
+            (ObjectType::InputPort, ObjectKind::Port(port)) => port.is_input(),
+            (ObjectType::OutputPort, ObjectKind::Port(port)) => port.is_output(),
+            (ObjectType::Condition, ObjectKind::Condition(_)) => true,
+            (ObjectType::BitVector, ObjectKind::BitVector(_)) => true,
+            (ObjectType::Bit, ObjectKind::Fixnum(n)) => *n == 0 || *n == 1,
+            (ObjectType::Promise, ObjectKind::Promise(_)) => true,
+            (ObjectType::WeakRef, ObjectKind::WeakRef(_)) => true,
+            (ObjectType::WeakTable, ObjectKind::WeakTable(_)) => true,
+            (ObjectType::HashTable, ObjectKind::HashTable(_)) => true,
+            (ObjectType::Set, ObjectKind::Set(_)) => true,
+            #[cfg(feature = "sync")]
+            (ObjectType::Generator, ObjectKind::Generator(_)) => true,
+            #[cfg(feature = "std")]
+            (ObjectType::Channel, ObjectKind::Channel(_)) => true,
+            _ => false,
+        }
+    }
 }
 
 pub type Object = Rc<ObjectKind>;
@@ -19,9 +138,78 @@ pub enum ObjectKind {
     Nil,
     Fixnum(isize),
     Symbol(String),
+    String(String),
     Cons(Cons),
-    Func(fn(&[Object]) -> Result<Object, RuntimeError>),
+    Func(Builtin),
     Closure(Closure),
+    Macro(Closure),
+    Port(Port),
+    Condition(Condition),
+    BitVector(BitVector),
+    Promise(Promise),
+    WeakRef(WeakRef),
+    WeakTable(WeakTable),
+    HashTable(HashTable),
+    Set(Set),
+    #[cfg(feature = "sync")]
+    Generator(Generator),
+    #[cfg(feature = "std")]
+    Channel(Channel),
+}
+
+impl ObjectKind {
+    // The names room reports live objects under, in the order of kind_index.
+    pub const KIND_NAMES: [&'static str; 18] = [
+        "nil",
+        "fixnum",
+        "symbol",
+        "string",
+        "cons",
+        "builtin",
+        "closure",
+        "macro",
+        "port",
+        "condition",
+        "bit-vector",
+        "promise",
+        "weak-ref",
+        "weak-table",
+        "hash-table",
+        "set",
+        "generator",
+        "channel",
+    ];
+
+    pub fn kind_index(&self) -> usize {
+        match self {
+            ObjectKind::Nil => 0,
+            ObjectKind::Fixnum(_) => 1,
+            ObjectKind::Symbol(_) => 2,
+            ObjectKind::String(_) => 3,
+            ObjectKind::Cons(_) => 4,
+            ObjectKind::Func(_) => 5,
+            ObjectKind::Closure(_) => 6,
+            ObjectKind::Macro(_) => 7,
+            ObjectKind::Port(_) => 8,
+            ObjectKind::Condition(_) => 9,
+            ObjectKind::BitVector(_) => 10,
+            ObjectKind::Promise(_) => 11,
+            ObjectKind::WeakRef(_) => 12,
+            ObjectKind::WeakTable(_) => 13,
+            ObjectKind::HashTable(_) => 14,
+            ObjectKind::Set(_) => 15,
+            #[cfg(feature = "sync")]
+            ObjectKind::Generator(_) => 16,
+            #[cfg(feature = "std")]
+            ObjectKind::Channel(_) => 17,
+        }
+    }
+}
+
+impl Drop for ObjectKind {
+    fn drop(&mut self) {
+        accounting::count_free(self.kind_index());
+    }
 }
 
 #[derive(Debug)]
@@ -31,70 +219,565 @@ pub struct Cons {
 }
 
 impl Cons {
-    pub fn iter(&self) -> ListIter {
+    pub fn iter(&self) -> ListIter<'_> {
         ListIter {
-            cons: self,
-            is_end: false,
+            cons: Some(self),
+            tail: None,
         }
     }
 }
 
+impl ObjectKind {
+    pub fn iter(&self) -> ListIter<'_> {
+        match self {
+            ObjectKind::Cons(cons) => cons.iter(),
+            _ => ListIter {
+                cons: None,
+                tail: None,
+            },
+        }
+    }
+
+    // Returns None for atoms other than nil and for dotted lists.
+    pub fn as_proper_list(&self) -> Option<Vec<Object>> {
+        match self {
+            ObjectKind::Nil => Some(Vec::new()),
+            ObjectKind::Cons(cons) => {
+                let mut iter = cons.iter();
+                let elements = iter.by_ref().collect();
+                match iter.tail().map(|tail| &**tail) {
+                    Some(ObjectKind::Nil) => Some(elements),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_port(&self) -> Option<&Port> {
+        match self {
+            ObjectKind::Port(port) => Some(port),
+            _ => None,
+        }
+    }
+}
+
+pub fn to_vec(list: &Object) -> Result<Vec<Object>, RuntimeError> {
+    list.as_proper_list()
+        .ok_or_else(|| RuntimeError::MismatchType(Rc::clone(list), ObjectType::List))
+}
+
+pub type BuiltinFn = fn(&[Object]) -> Result<Object, RuntimeError>;
+
+// Called like any other builtin, but evaluation waits for the future.
+#[cfg(feature = "std")]
+pub type AsyncBuiltinFn = fn(Vec<Object>) -> BoxFuture;
+
+// Also given the global environment the builtin was defined in, for those
+// that consult its permissions or the state of the evaluation.
+pub type EnvBuiltinFn = fn(&[Object], &Rc<RefCell<Env>>) -> Result<Object, RuntimeError>;
+
+pub enum BuiltinBody {
+    Sync(BuiltinFn),
+    // The environment is held weakly, since it holds the builtin.
+    WithEnv(EnvBuiltinFn, Weak<RefCell<Env>>),
+    #[cfg(feature = "std")]
+    Async(AsyncBuiltinFn),
+    // What partial returns: the function with some arguments supplied.
+    Partial(Object, Vec<Object>),
+    // What compose returns, applying the functions from last to first.
+    Compose(Vec<Object>),
+    // What memoize returns: the function, and its results by the equal-hash
+    // of the argument lists they were computed for.
+    Memoize(Object, RefCell<Map<u64, Vec<(Object, Object)>>>),
+}
+
+pub struct Builtin {
+    pub name: String,
+    pub parameters: Vec<String>,
+    pub rest: Option<String>,
+    pub func: BuiltinBody,
+}
+
+impl Builtin {
+    pub fn arity(&self) -> (usize, Option<usize>) {
+        arity(&self.parameters, &self.rest)
+    }
+}
+
+// What error signals. The type names an entry in *condition-types*,
+// fields is an alist of type-specific slots such as the datum of a
+// type-error, and the backtrace is a list of (function form) frames,
+// innermost first, taken where the condition was signalled.
+pub struct Condition {
+    pub type_name: String,
+    pub message: String,
+    pub irritants: Object,
+    pub fields: Object,
+    pub backtrace: Object,
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        for irritant in self.irritants.iter() {
+            write!(f, " {}", irritant)?;
+        }
+        Ok(())
+    }
+}
+
 pub struct Closure {
+    pub name: Option<String>,
     pub env: Rc<RefCell<Env>>,
     pub parameters: Vec<String>,
+    pub rest: Option<String>,
+    pub doc: Option<String>,
     pub body: Vec<Object>,
+    // Those of the environment it was made in, which its calls evaluate
+    // with, so the stdlib's functions keep the default ones.
+    pub eval_options: EvalOptions,
+}
+
+impl Closure {
+    pub fn arity(&self) -> (usize, Option<usize>) {
+        arity(&self.parameters, &self.rest)
+    }
+
+    pub fn lambda_list(&self) -> Object {
+        let params = self.parameters.iter().map(|p| symbol(p));
+        match &self.rest {
+            Some(rest) => from_iter(params.chain([symbol("&rest"), symbol(rest)])),
+            None => from_iter(params),
+        }
+    }
+
+    // The (lambda params [doc] body...) form this closure was made from.
+    pub fn source(&self) -> Object {
+        let mut elements = vec![symbol("lambda"), self.lambda_list()];
+        elements.extend(self.doc.as_deref().map(string));
+        elements.extend(self.body.iter().cloned());
+        from_iter(elements)
+    }
+}
+
+// The minimum and, without a rest parameter, maximum number of arguments.
+pub(crate) fn arity(parameters: &[String], rest: &Option<String>) -> (usize, Option<usize>) {
+    match rest {
+        Some(_) => (parameters.len(), None),
+        None => (parameters.len(), Some(parameters.len())),
+    }
+}
+
+fn write_lambda_list(
+    f: &mut fmt::Formatter<'_>,
+    parameters: &[String],
+    rest: &Option<String>,
+) -> fmt::Result {
+    let rest = rest.iter().flat_map(|rest| ["&rest", rest.as_str()]);
+    let params: Vec<&str> = parameters.iter().map(String::as_str).chain(rest).collect();
+    write!(f, "({})", params.join(" "))
+}
+
+fn write_closure(f: &mut fmt::Formatter<'_>, kind: &str, closure: &Closure) -> fmt::Result {
+    write!(f, "#<{} ", kind)?;
+    if let Some(name) = &closure.name {
+        write!(f, "{} ", name)?;
+    }
+    write_lambda_list(f, &closure.parameters, &closure.rest)?;
+    write!(f, ">")
 }
 
 pub struct ListIter<'a> {
-    cons: &'a Cons,
-    is_end: bool,
+    cons: Option<&'a Cons>,
+    tail: Option<&'a Object>,
+}
+
+impl<'a> ListIter<'a> {
+    // The final cdr (nil for a proper list), once iteration has finished.
+    pub fn tail(&self) -> Option<&'a Object> {
+        self.tail
+    }
 }
 
 impl<'a> Iterator for ListIter<'a> {
     type Item = Object;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.is_end {
-            return None;
-        }
-        match &*self.cons.cdr {
-            ObjectKind::Cons(cons) => {
-                let result = Some(Rc::clone(&self.cons.car));
-                self.cons = cons;
-                result
-            }
+        let cons = self.cons?;
+        match &*cons.cdr {
+            ObjectKind::Cons(next) => self.cons = Some(next),
             _ => {
-                self.is_end = true;
-                Some(Rc::clone(&self.cons.car))
+                self.cons = None;
+                self.tail = Some(&cons.cdr);
             }
         }
+        Some(Rc::clone(&cons.car))
+    }
+}
+
+fn alloc_object(x: ObjectKind) -> Object {
+    accounting::count_allocation(matches!(x, ObjectKind::Cons(_)));
+    accounting::count_live(x.kind_index());
+    Rc::new(x)
+}
+
+// With hash-consing on, the constructors of immutable data hand out a live
+// object structurally equal to the one asked for when there is one, so equal
+// data is stored once and equal finds shared structure by identity. Conses
+// are keyed by their parts, which are shared themselves. Like keywords, the
+// table is per thread and never dropped; it only holds weak references.
+#[derive(PartialEq, Eq, Hash)]
+enum SharedKey {
+    Nil,
+    Fixnum(isize),
+    Symbol(String),
+    String(String),
+    Cons(usize, usize),
+}
+
+#[cfg(feature = "std")]
+std::thread_local! {
+    static HASH_CONSING: core::cell::Cell<bool> = const { core::cell::Cell::new(false) };
+    static SHARED: core::cell::RefCell<std::collections::HashMap<SharedKey, Weak<ObjectKind>>> =
+        core::cell::RefCell::new(std::collections::HashMap::new());
+}
+
+#[cfg(feature = "std")]
+pub fn hash_consing() -> bool {
+    HASH_CONSING.with(|on| on.get())
+}
+
+// Returns whether hash-consing is on afterwards; it's never on without std.
+#[cfg(feature = "std")]
+pub fn set_hash_consing(on: bool) -> bool {
+    HASH_CONSING.with(|flag| flag.set(on));
+    if !on {
+        SHARED.with(|table| table.borrow_mut().clear());
+    }
+    on
+}
+
+// Runs f with hash-consing on or off, as set for one global environment,
+// and returns whether it's on after f as well, then puts back the setting f
+// started under without emptying the table.
+#[cfg(feature = "std")]
+pub fn with_hash_consing<T>(on: bool, f: impl FnOnce() -> T) -> (T, bool) {
+    let outer = HASH_CONSING.with(|flag| flag.replace(on));
+    let result = f();
+    (result, HASH_CONSING.with(|flag| flag.replace(outer)))
+}
+
+#[cfg(not(feature = "std"))]
+pub fn with_hash_consing<T>(_on: bool, f: impl FnOnce() -> T) -> (T, bool) {
+    (f(), false)
+}
+
+#[cfg(not(feature = "std"))]
+pub fn hash_consing() -> bool {
+    false
+}
+
+#[cfg(not(feature = "std"))]
+pub fn set_hash_consing(_on: bool) -> bool {
+    false
+}
+
+#[cfg(feature = "std")]
+fn shared(key: impl FnOnce() -> SharedKey, make: impl FnOnce() -> ObjectKind) -> Object {
+    if !hash_consing() {
+        return alloc_object(make());
     }
+    SHARED.with(|table| {
+        let mut table = table.borrow_mut();
+        let key = key();
+        if let Some(x) = table.get(&key).and_then(Weak::upgrade) {
+            return x;
+        }
+        let x = alloc_object(make());
+        table.insert(key, Rc::downgrade(&x));
+        if table.len() >= 1024 && table.len().is_power_of_two() {
+            table.retain(|_, x| x.strong_count() > 0);
+        }
+        x
+    })
+}
+
+#[cfg(not(feature = "std"))]
+fn shared<K>(_key: K, make: impl FnOnce() -> ObjectKind) -> Object {
+    alloc_object(make())
 }
 
 pub fn cons(car: Object, cdr: Object) -> Object {
-    Rc::new(ObjectKind::Cons(Cons {
-        car: Rc::clone(&car),
-        cdr: Rc::clone(&cdr),
-    }))
+    shared(
+        || SharedKey::Cons(Rc::as_ptr(&car) as usize, Rc::as_ptr(&cdr) as usize),
+        || {
+            ObjectKind::Cons(Cons {
+                car: Rc::clone(&car),
+                cdr: Rc::clone(&cdr),
+            })
+        },
+    )
+}
+
+pub fn from_iter<I: IntoIterator<Item = Object>>(iter: I) -> Object {
+    dotted_from_iter(iter, nil())
+}
+
+pub fn dotted_from_iter<I: IntoIterator<Item = Object>>(iter: I, tail: Object) -> Object {
+    let elements: Vec<Object> = iter.into_iter().collect();
+    elements
+        .into_iter()
+        .rev()
+        .fold(tail, |list, x| cons(x, list))
+}
+
+#[macro_export]
+macro_rules! list {
+    () => {
+        $crate::object::nil()
+    };
+    ($($x:expr),+ ; $tail:expr) => {
+        $crate::object::dotted_from_iter([$($x),+], $tail)
+    };
+    ($($x:expr),+ $(,)?) => {
+        $crate::object::from_iter([$($x),+])
+    };
 }
 
 pub fn fixnum(n: isize) -> Object {
-    Rc::new(ObjectKind::Fixnum(n))
+    shared(|| SharedKey::Fixnum(n), || ObjectKind::Fixnum(n))
 }
 
 pub fn symbol(s: &str) -> Object {
     if s == "nil" {
         return nil();
     }
-    Rc::new(ObjectKind::Symbol(s.to_string()))
+    if s.starts_with(':') {
+        return keyword(s);
+    }
+    shared(
+        || SharedKey::Symbol(s.to_string()),
+        || ObjectKind::Symbol(s.to_string()),
+    )
+}
+
+// A symbol no other is equal to, which prints as #:<prefix><n>. It's named
+// with a space before n, which the reader never puts in a symbol, and n is
+// counted across every environment.
+pub fn uninterned(prefix: &str) -> Object {
+    static COUNTER: AtomicUsize = AtomicUsize::new(1);
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    alloc_object(ObjectKind::Symbol(format!("#:{} {}", prefix, n)))
+}
+
+// The keywords interned by one global environment, so reading the same
+// keyword twice gives the same object instead of a new one. They're still
+// compared by name, by equal and so by getf: outside of an environment, in
+// another one, or without std, the same keyword is a different object.
+pub type Keywords = Rc<RefCell<Map<String, Object>>>;
+
+#[cfg(feature = "std")]
+std::thread_local! {
+    static KEYWORDS: core::cell::RefCell<Option<Keywords>> =
+        const { core::cell::RefCell::new(None) };
+}
+
+#[cfg(feature = "std")]
+pub fn keywords() -> Option<Keywords> {
+    KEYWORDS.with(|keywords| keywords.borrow().clone())
+}
+
+// Runs f interning keywords in the table of one global environment, then
+// puts back the table f started under.
+#[cfg(feature = "std")]
+pub fn with_keywords<T>(keywords: Option<Keywords>, f: impl FnOnce() -> T) -> T {
+    let outer = KEYWORDS.with(|table| table.replace(keywords));
+    let result = f();
+    KEYWORDS.with(|table| table.replace(outer));
+    result
+}
+
+#[cfg(not(feature = "std"))]
+pub fn with_keywords<T>(_keywords: Option<Keywords>, f: impl FnOnce() -> T) -> T {
+    f()
+}
+
+#[cfg(feature = "std")]
+fn keyword(s: &str) -> Object {
+    match keywords() {
+        Some(keywords) => Rc::clone(
+            keywords
+                .borrow_mut()
+                .entry(s.to_string())
+                .or_insert_with(|| alloc_object(ObjectKind::Symbol(s.to_string()))),
+        ),
+        None => alloc_object(ObjectKind::Symbol(s.to_string())),
+    }
+}
+
+#[cfg(not(feature = "std"))]
+fn keyword(s: &str) -> Object {
+    alloc_object(ObjectKind::Symbol(s.to_string()))
+}
+
+pub fn string(s: &str) -> Object {
+    shared(
+        || SharedKey::String(s.to_string()),
+        || ObjectKind::String(s.to_string()),
+    )
+}
+
+pub fn port(port: Port) -> Object {
+    alloc_object(ObjectKind::Port(port))
+}
+
+pub fn condition(
+    type_name: &str,
+    message: &str,
+    irritants: Object,
+    fields: Object,
+    backtrace: Object,
+) -> Object {
+    alloc_object(ObjectKind::Condition(Condition {
+        type_name: type_name.to_string(),
+        message: message.to_string(),
+        irritants,
+        fields,
+        backtrace,
+    }))
+}
+
+#[cfg(feature = "sync")]
+pub fn generator(thunk: Object, env: Rc<RefCell<Env>>) -> Object {
+    alloc_object(ObjectKind::Generator(Generator::new(thunk, env)))
+}
+
+#[cfg(feature = "std")]
+pub fn channel(channel: Channel) -> Object {
+    alloc_object(ObjectKind::Channel(channel))
+}
+
+pub fn bit_vector(bit_vector: BitVector) -> Object {
+    alloc_object(ObjectKind::BitVector(bit_vector))
+}
+
+pub fn promise(promise: Promise) -> Object {
+    alloc_object(ObjectKind::Promise(promise))
+}
+
+pub fn weak_ref(weak_ref: WeakRef) -> Object {
+    alloc_object(ObjectKind::WeakRef(weak_ref))
+}
+
+pub fn weak_table(table: WeakTable) -> Object {
+    alloc_object(ObjectKind::WeakTable(table))
+}
+
+pub fn hash_table(table: HashTable) -> Object {
+    alloc_object(ObjectKind::HashTable(table))
+}
+
+pub fn set(set: Set) -> Object {
+    alloc_object(ObjectKind::Set(set))
 }
 
 pub fn nil() -> Object {
-    Rc::new(ObjectKind::Nil)
+    shared(|| SharedKey::Nil, || ObjectKind::Nil)
+}
+
+// `lambda_list` is written as in Lisp, e.g. "x y &rest more".
+pub fn builtin(name: &str, lambda_list: &str, func: BuiltinFn) -> Object {
+    builtin_with_body(name, lambda_list, BuiltinBody::Sync(func))
+}
+
+pub fn env_builtin(
+    name: &str,
+    lambda_list: &str,
+    func: EnvBuiltinFn,
+    env: Weak<RefCell<Env>>,
+) -> Object {
+    builtin_with_body(name, lambda_list, BuiltinBody::WithEnv(func, env))
+}
+
+#[cfg(feature = "std")]
+pub fn async_builtin(name: &str, lambda_list: &str, func: AsyncBuiltinFn) -> Object {
+    builtin_with_body(name, lambda_list, BuiltinBody::Async(func))
+}
+
+pub fn partial(function: Object, args: Vec<Object>) -> Object {
+    builtin_with_body(
+        "partial",
+        "&rest args",
+        BuiltinBody::Partial(function, args),
+    )
+}
+
+pub fn compose(functions: Vec<Object>) -> Object {
+    builtin_with_body("compose", "&rest args", BuiltinBody::Compose(functions))
+}
+
+pub fn memoize(function: Object) -> Object {
+    builtin_with_body(
+        "memoize",
+        "&rest args",
+        BuiltinBody::Memoize(function, RefCell::new(Map::new())),
+    )
+}
+
+fn builtin_with_body(name: &str, lambda_list: &str, func: BuiltinBody) -> Object {
+    let mut words = lambda_list.split_whitespace();
+    let parameters = words
+        .by_ref()
+        .take_while(|word| *word != "&rest")
+        .map(String::from)
+        .collect();
+    alloc_object(ObjectKind::Func(Builtin {
+        name: name.to_string(),
+        parameters,
+        rest: words.next().map(String::from),
+        func,
+    }))
 }
 
-pub fn closure(parameters: Vec<String>, body: Vec<Object>, env: Rc<RefCell<Env>>) -> Object {
-    Rc::new(ObjectKind::Closure(Closure { parameters, body, env}))
+pub fn closure(
+    name: Option<String>,
+    parameters: Vec<String>,
+    rest: Option<String>,
+    doc: Option<String>,
+    body: Vec<Object>,
+    env: Rc<RefCell<Env>>,
+) -> Object {
+    let eval_options = env.borrow().eval_options();
+    alloc_object(ObjectKind::Closure(Closure {
+        name,
+        parameters,
+        rest,
+        doc,
+        body,
+        eval_options,
+        env,
+    }))
+}
+
+pub fn macro_(
+    name: Option<String>,
+    parameters: Vec<String>,
+    rest: Option<String>,
+    doc: Option<String>,
+    body: Vec<Object>,
+    env: Rc<RefCell<Env>>,
+) -> Object {
+    let eval_options = env.borrow().eval_options();
+    alloc_object(ObjectKind::Macro(Closure {
+        name,
+        parameters,
+        rest,
+        doc,
+        body,
+        eval_options,
+        env,
+    }))
 }
 
 impl fmt::Display for ObjectKind {
@@ -102,12 +785,60 @@ impl fmt::Display for ObjectKind {
         match self {
             ObjectKind::Nil => write!(f, "nil"),
             ObjectKind::Fixnum(n) => n.fmt(f),
-            ObjectKind::Symbol(s) => s.fmt(f),
+            ObjectKind::Symbol(s) => match s.strip_prefix("#:").and_then(|s| s.rsplit_once(' ')) {
+                Some((prefix, n)) => write!(f, "#:{}{}", prefix, n),
+                None => s.fmt(f),
+            },
+            ObjectKind::String(s) => {
+                write!(f, "\"")?;
+                for c in s.chars() {
+                    match c {
+                        '"' => write!(f, "\\\"")?,
+                        '\\' => write!(f, "\\\\")?,
+                        '\n' => write!(f, "\\n")?,
+                        '\t' => write!(f, "\\t")?,
+                        c if c.is_ascii_control() => write!(f, "\\x{:02x}", c as u32)?,
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+                write!(f, "\"")
+            }
             ObjectKind::Cons(cons) => cons.fmt(f),
-            ObjectKind::Func(func) => write!(f, "<Fn {:p}>", &func),
-            ObjectKind::Closure(closure) => {
-                write!(f, "<Closure {:?} {:?}>", closure.parameters, closure.body)
+            ObjectKind::Func(func) => {
+                write!(f, "#<function {} ", func.name)?;
+                write_lambda_list(f, &func.parameters, &func.rest)?;
+                write!(f, ">")
+            }
+            ObjectKind::Closure(closure) => write_closure(f, "closure", closure),
+            ObjectKind::Macro(closure) => write_closure(f, "macro", closure),
+            ObjectKind::Port(port) => port.fmt(f),
+            ObjectKind::BitVector(bit_vector) => bit_vector.fmt(f),
+            ObjectKind::Promise(promise) => {
+                let state = if promise.is_forced() {
+                    "forced"
+                } else {
+                    "delayed"
+                };
+                write!(f, "#<promise {}>", state)
+            }
+            ObjectKind::WeakRef(weak_ref) => weak_ref.fmt(f),
+            ObjectKind::WeakTable(table) => table.fmt(f),
+            ObjectKind::HashTable(table) => table.fmt(f),
+            ObjectKind::Set(set) => set.fmt(f),
+            ObjectKind::Condition(condition) => {
+                write!(f, "#<{} {}>", condition.type_name, condition)
+            }
+            #[cfg(feature = "sync")]
+            ObjectKind::Generator(generator) => {
+                let state = if generator.is_done() {
+                    "done"
+                } else {
+                    "running"
+                };
+                write!(f, "#<generator {}>", state)
             }
+            #[cfg(feature = "std")]
+            ObjectKind::Channel(_) => write!(f, "#<channel>"),
         }
     }
 }
@@ -118,21 +849,49 @@ impl fmt::Debug for ObjectKind {
     }
 }
 
+fn write_cons(
+    f: &mut fmt::Formatter<'_>,
+    cons: &Cons,
+    write: impl Fn(&mut fmt::Formatter<'_>, &ObjectKind) -> fmt::Result,
+) -> fmt::Result {
+    let mut cur = cons;
+    write!(f, "(")?;
+    loop {
+        write(f, &cur.car)?;
+        match &*cur.cdr {
+            ObjectKind::Cons(ref cons) => {
+                write!(f, " ")?;
+                cur = cons;
+            }
+            ObjectKind::Nil => break write!(f, ")"),
+            cdr => {
+                write!(f, " . ")?;
+                write(f, cdr)?;
+                break write!(f, ")");
+            }
+        }
+    }
+}
+
 impl fmt::Display for Cons {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut cur = self;
-        write!(f, "(")?;
-        loop {
-            write!(f, "{}", cur.car)?;
-            match &*cur.cdr {
-                ObjectKind::Cons(ref cons) => {
-                    write!(f, " ")?;
-                    cur = cons;
-                    ()
-                }
-                ObjectKind::Nil => break write!(f, ")"),
-                cdr => break write!(f, " . {})", cdr),
+        write_cons(f, self, |f, x| x.fmt(f))
+    }
+}
+
+// Prints an object with its fixnums in another radix, as write and display
+// do for *print-base*.
+pub struct Radix<'a>(pub &'a ObjectKind, pub u32);
+
+impl fmt::Display for Radix<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            ObjectKind::Fixnum(n) if self.1 != 10 => {
+                let sign = if *n < 0 { "-" } else { "" };
+                write!(f, "{}{}", sign, format::to_radix(n.unsigned_abs(), self.1))
             }
+            ObjectKind::Cons(cons) => write_cons(f, cons, |f, x| Radix(x, self.1).fmt(f)),
+            x => x.fmt(f),
         }
     }
 }
@@ -141,6 +900,85 @@ impl fmt::Display for Cons {
 mod tests {
     use super::*;
 
+    #[cfg(feature = "std")]
+    #[test]
+    fn keyword_test() {
+        let keywords = Keywords::default();
+        with_keywords(Some(Rc::clone(&keywords)), || {
+            assert!(Rc::ptr_eq(&symbol(":key"), &symbol(":key")));
+        });
+        assert!(!Rc::ptr_eq(&symbol(":key"), &symbol(":key")));
+        assert_eq!(keywords.borrow().len(), 1);
+        assert!(!Rc::ptr_eq(&symbol("key"), &symbol("key")));
+        assert!(ObjectType::Keyword.contains(&symbol(":key")));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn hash_consing_test() {
+        let make = || list![fixnum(1), string("a"), symbol("b")];
+        assert!(!Rc::ptr_eq(&make(), &make()));
+        set_hash_consing(true);
+        let x = make();
+        assert!(Rc::ptr_eq(&x, &make()));
+        assert!(!Rc::ptr_eq(&x, &list![fixnum(1), string("a")]));
+        set_hash_consing(false);
+        assert!(!Rc::ptr_eq(&x, &make()));
+    }
+
+    #[test]
+    fn list_macro_test() {
+        assert_eq!(list![], nil());
+        assert_eq!(
+            list![fixnum(1), fixnum(2)],
+            cons(fixnum(1), cons(fixnum(2), nil()))
+        );
+        assert_eq!(
+            list![fixnum(1), fixnum(2); fixnum(3)],
+            cons(fixnum(1), cons(fixnum(2), fixnum(3)))
+        );
+        assert_eq!(
+            from_iter((1..4).map(fixnum)),
+            list![fixnum(1), fixnum(2), fixnum(3)]
+        );
+    }
+
+    #[test]
+    fn list_iter_test() {
+        let list = list![fixnum(1), fixnum(2); fixnum(3)];
+        let mut iter = list.iter();
+        assert_eq!(iter.tail(), None);
+        assert_eq!(
+            iter.by_ref().collect::<Vec<_>>(),
+            vec![fixnum(1), fixnum(2)]
+        );
+        assert_eq!(iter.tail(), Some(&fixnum(3)));
+
+        let list = list![fixnum(1)];
+        let mut iter = list.iter();
+        assert_eq!(iter.by_ref().count(), 1);
+        assert_eq!(iter.tail(), Some(&nil()));
+
+        assert_eq!(fixnum(1).iter().count(), 0);
+    }
+
+    #[test]
+    fn proper_list_test() {
+        assert_eq!(nil().as_proper_list(), Some(vec![]));
+        assert_eq!(
+            list![fixnum(1), fixnum(2)].as_proper_list(),
+            Some(vec![fixnum(1), fixnum(2)])
+        );
+        assert_eq!(list![fixnum(1); fixnum(2)].as_proper_list(), None);
+        assert_eq!(fixnum(1).as_proper_list(), None);
+
+        assert!(to_vec(&list![fixnum(1)]).is_ok());
+        assert!(matches!(
+            to_vec(&list![fixnum(1); fixnum(2)]),
+            Err(RuntimeError::MismatchType(_, ObjectType::List))
+        ));
+    }
+
     #[test]
     fn display_fixnum_test() {
         let s = format!("{}", fixnum(123));
@@ -153,6 +991,18 @@ mod tests {
         assert_eq!(s, "car");
     }
 
+    #[test]
+    fn display_string_test() {
+        let s = format!("{}", string("foo"));
+        assert_eq!(s, "\"foo\"");
+
+        let s = format!("{}", string("a\"b\\c\n"));
+        assert_eq!(s, "\"a\\\"b\\\\c\\n\"");
+
+        let s = format!("{}", string("\r\0\x7fé"));
+        assert_eq!(s, "\"\\x0d\\x00\\x7fé\"");
+    }
+
     #[test]
     fn display_cons_test() {
         let obj = cons(fixnum(1), fixnum(2));
@@ -171,4 +1021,36 @@ mod tests {
         let s = format!("{}", obj);
         assert_eq!(s, "(+ 123 456)");
     }
+
+    fn identity(args: &[Object]) -> Result<Object, RuntimeError> {
+        Ok(Rc::clone(&args[0]))
+    }
+
+    #[test]
+    fn builtin_test() {
+        let f = builtin("car", "list", identity);
+        assert_eq!(f.to_string(), "#<function car (list)>");
+        let g = builtin("+", "&rest numbers", identity);
+        assert_eq!(g.to_string(), "#<function + (&rest numbers)>");
+        assert_eq!(builtin("f", "", identity).to_string(), "#<function f ()>");
+        match (&*f, &*g) {
+            (ObjectKind::Func(f), ObjectKind::Func(g)) => {
+                assert_eq!(f.arity(), (1, Some(1)));
+                assert_eq!(g.arity(), (0, None));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn object_type_test() {
+        let t = ObjectType::from_name("fixnum").unwrap();
+        assert_eq!(t, ObjectType::Number);
+        assert!(t.contains(&fixnum(1)));
+        assert!(!t.contains(&string("1")));
+        assert!(ObjectType::List.contains(&nil()));
+        assert!(ObjectType::Keyword.contains(&symbol(":a")));
+        assert!(!ObjectType::Keyword.contains(&symbol("a")));
+        assert!(ObjectType::from_name("float").is_none());
+    }
 }
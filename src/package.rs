@@ -0,0 +1,243 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use super::collections::{Map, Set};
+use super::error::RuntimeError;
+use super::object::{self, Object};
+
+pub const DEFAULT_PACKAGE: &str = "user";
+
+#[derive(Default)]
+pub struct Package {
+    exports: Set<String>,
+    imports: Map<String, String>,
+}
+
+// Globals defined outside of the `user` package are stored under `pkg:sym`.
+// Unqualified names are looked up through the current package's imports,
+// then its own bindings, and finally the bindings shared by every package.
+pub struct Packages {
+    current: String,
+    table: Map<String, Package>,
+}
+
+/// Splits `pkg:sym` (exported symbol) or `pkg::sym` (internal symbol) into
+/// its package name, symbol name and whether it was written as internal.
+/// Keywords and uninterned symbols (`#:sym`) belong to no package.
+fn split_qualified(name: &str) -> Option<(&str, &str, bool)> {
+    let pos = name.find(':')?;
+    if pos == 0 || name.starts_with("#:") {
+        return None;
+    }
+    let (package, rest) = (&name[..pos], &name[pos + 1..]);
+    let (symbol, internal) = match rest.strip_prefix(':') {
+        Some(symbol) => (symbol, true),
+        None => (rest, false),
+    };
+    if symbol.is_empty() {
+        return None;
+    }
+    Some((package, symbol, internal))
+}
+
+fn key(package: &str, symbol: &str) -> String {
+    if package == DEFAULT_PACKAGE {
+        symbol.to_string()
+    } else {
+        format!("{}:{}", package, symbol)
+    }
+}
+
+impl Default for Packages {
+    fn default() -> Self {
+        let mut table = Map::new();
+        table.insert(DEFAULT_PACKAGE.to_string(), Package::default());
+        Self {
+            current: DEFAULT_PACKAGE.to_string(),
+            table,
+        }
+    }
+}
+
+impl Packages {
+    pub fn current(&self) -> &str {
+        &self.current
+    }
+
+    pub fn define(&mut self, name: &str) {
+        self.table.entry(name.to_string()).or_default();
+    }
+
+    pub fn set_current(&mut self, name: &str) -> Result<(), RuntimeError> {
+        if !self.table.contains_key(name) {
+            return Err(RuntimeError::PackageNotFound(name.to_string()));
+        }
+        self.current = name.to_string();
+        Ok(())
+    }
+
+    fn current_package(&self) -> &Package {
+        &self.table[&self.current]
+    }
+
+    fn current_package_mut(&mut self) -> &mut Package {
+        self.table.get_mut(&self.current).unwrap()
+    }
+
+    pub fn export(&mut self, symbol: &str) {
+        self.current_package_mut()
+            .exports
+            .insert(symbol.to_string());
+    }
+
+    pub fn import(&mut self, name: &str) -> Result<(), RuntimeError> {
+        let (package, symbol, _) = match split_qualified(name) {
+            Some(qualified) => qualified,
+            None => return Ok(()),
+        };
+        match self.table.get(package) {
+            None => return Err(RuntimeError::PackageNotFound(package.to_string())),
+            Some(p) if !p.exports.contains(symbol) => {
+                return Err(RuntimeError::SymbolNotExported(
+                    package.to_string(),
+                    symbol.to_string(),
+                ))
+            }
+            Some(_) => (),
+        }
+        let key = key(package, symbol);
+        self.current_package_mut()
+            .imports
+            .insert(symbol.to_string(), key);
+        Ok(())
+    }
+
+    // Forms that recreate every package: exports first, so that imports
+    // can refer to any of them, and finally the current package.
+    pub fn definitions(&self) -> Vec<Object> {
+        let mut names: Vec<&String> = self.table.keys().collect();
+        names.sort();
+        let option = |keyword: &str, mut symbols: Vec<String>| {
+            symbols.sort();
+            object::cons(
+                object::symbol(keyword),
+                object::from_iter(symbols.iter().map(|s| object::symbol(s))),
+            )
+        };
+
+        let mut forms = Vec::new();
+        for name in names.iter() {
+            let exports = self.table[*name].exports.iter().cloned().collect();
+            forms.push(crate::list![
+                object::symbol("defpackage"),
+                object::symbol(name),
+                option(":export", exports)
+            ]);
+        }
+        for name in names.iter() {
+            let imports: Vec<String> = self.table[*name]
+                .imports
+                .values()
+                .map(|key| match split_qualified(key) {
+                    Some(_) => key.clone(),
+                    None => format!("{}:{}", DEFAULT_PACKAGE, key),
+                })
+                .collect();
+            if !imports.is_empty() {
+                forms.push(crate::list![
+                    object::symbol("defpackage"),
+                    object::symbol(name),
+                    option(":import", imports)
+                ]);
+            }
+        }
+        forms.push(crate::list![
+            object::symbol("in-package"),
+            object::symbol(&self.current)
+        ]);
+        forms
+    }
+
+    /// Returns the key under which a global definition of `name` is stored.
+    pub fn definition_key(&self, name: &str) -> String {
+        if let Some((package, symbol, _)) = split_qualified(name) {
+            return key(package, symbol);
+        }
+        match self.current_package().imports.get(name) {
+            Some(key) => key.clone(),
+            None => key(&self.current, name),
+        }
+    }
+
+    /// Calls `f` with each key `name` may refer to, in lookup order, and
+    /// returns the first result.
+    pub fn find<T>(&self, name: &str, mut f: impl FnMut(&str) -> Option<T>) -> Option<T> {
+        if let Some((package, symbol, internal)) = split_qualified(name) {
+            let visible = match self.table.get(package) {
+                Some(p) => internal || package == self.current || p.exports.contains(symbol),
+                None => false,
+            };
+            return if visible {
+                f(&key(package, symbol))
+            } else {
+                None
+            };
+        }
+        if let Some(key) = self.current_package().imports.get(name) {
+            if let Some(result) = f(key) {
+                return Some(result);
+            }
+        }
+        if self.current != DEFAULT_PACKAGE {
+            if let Some(result) = f(&key(&self.current, name)) {
+                return Some(result);
+            }
+        }
+        f(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_qualified_test() {
+        assert_eq!(split_qualified("foo"), None);
+        assert_eq!(split_qualified(":foo"), None);
+        assert_eq!(split_qualified("#:G1"), None);
+        assert_eq!(split_qualified("pkg:"), None);
+        assert_eq!(split_qualified("pkg:foo"), Some(("pkg", "foo", false)));
+        assert_eq!(split_qualified("pkg::foo"), Some(("pkg", "foo", true)));
+    }
+
+    #[test]
+    fn definition_key_test() {
+        let mut packages = Packages::default();
+        assert_eq!(packages.definition_key("map"), "map");
+        packages.define("geometry");
+        packages.set_current("geometry").unwrap();
+        assert_eq!(packages.definition_key("map"), "geometry:map");
+        assert_eq!(packages.definition_key("user:map"), "map");
+    }
+
+    #[test]
+    fn import_test() {
+        let mut packages = Packages::default();
+        packages.define("a");
+        assert!(matches!(
+            packages.import("a:map"),
+            Err(RuntimeError::SymbolNotExported(_, _))
+        ));
+        assert!(matches!(
+            packages.import("b:map"),
+            Err(RuntimeError::PackageNotFound(_))
+        ));
+        packages.set_current("a").unwrap();
+        packages.export("map");
+        packages.set_current(DEFAULT_PACKAGE).unwrap();
+        packages.import("a:map").unwrap();
+        assert_eq!(packages.definition_key("map"), "a:map");
+    }
+}
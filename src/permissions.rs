@@ -0,0 +1,172 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use super::error::RuntimeError;
+
+// What the file and environment variable builtins may touch. None means any
+// path or variable; a list allows the paths under its directories, or the
+// variables it names. Everything is allowed by default.
+#[derive(Debug, Clone)]
+pub struct Permissions {
+    read: Option<Vec<PathBuf>>,
+    write: Option<Vec<PathBuf>>,
+    getenv: Option<Vec<String>>,
+    setenv: bool,
+}
+
+impl Default for Permissions {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+impl Permissions {
+    pub fn all() -> Self {
+        Self {
+            read: None,
+            write: None,
+            getenv: None,
+            setenv: true,
+        }
+    }
+
+    pub fn none() -> Self {
+        Self {
+            read: Some(Vec::new()),
+            write: Some(Vec::new()),
+            getenv: Some(Vec::new()),
+            setenv: false,
+        }
+    }
+
+    pub fn allow_read(mut self, path: impl AsRef<Path>) -> Self {
+        if let Some(paths) = &mut self.read {
+            paths.push(resolve(path.as_ref()));
+        }
+        self
+    }
+
+    pub fn allow_write(mut self, path: impl AsRef<Path>) -> Self {
+        if let Some(paths) = &mut self.write {
+            paths.push(resolve(path.as_ref()));
+        }
+        self
+    }
+
+    pub fn allow_getenv(mut self, name: &str) -> Self {
+        if let Some(names) = &mut self.getenv {
+            names.push(name.into());
+        }
+        self
+    }
+
+    pub fn allow_setenv(mut self, setenv: bool) -> Self {
+        self.setenv = setenv;
+        self
+    }
+
+    pub fn check_read(&self, path: impl AsRef<Path>) -> Result<(), RuntimeError> {
+        check(&self.read, path.as_ref(), "read")
+    }
+
+    pub fn check_write(&self, path: impl AsRef<Path>) -> Result<(), RuntimeError> {
+        check(&self.write, path.as_ref(), "write")
+    }
+
+    pub fn check_getenv(&self, name: &str) -> Result<(), RuntimeError> {
+        match &self.getenv {
+            Some(names) if !names.iter().any(|n| n == name) => Err(RuntimeError::PermissionDenied(
+                format!("read environment variable {}", name),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn check_setenv(&self, name: &str) -> Result<(), RuntimeError> {
+        if self.setenv {
+            Ok(())
+        } else {
+            Err(RuntimeError::PermissionDenied(format!(
+                "set environment variable {}",
+                name
+            )))
+        }
+    }
+}
+
+fn check(allowed: &Option<Vec<PathBuf>>, path: &Path, action: &str) -> Result<(), RuntimeError> {
+    let allowed = match allowed {
+        None => return Ok(()),
+        Some(allowed) => allowed,
+    };
+    let path = resolve(path);
+    if allowed.iter().any(|dir| path.starts_with(dir)) {
+        Ok(())
+    } else {
+        Err(RuntimeError::PermissionDenied(format!(
+            "{} {}",
+            action,
+            path.display()
+        )))
+    }
+}
+
+// Symlinks are followed as far as the path exists, so they can't lead out of
+// an allowed directory; the rest is made absolute without touching the disk.
+fn resolve(path: &Path) -> PathBuf {
+    if let Ok(path) = fs::canonicalize(path) {
+        return path;
+    }
+    let path = match env::current_dir() {
+        Ok(dir) => dir.join(path),
+        Err(_) => path.to_path_buf(),
+    };
+    let mut resolved = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::CurDir => (),
+            c => {
+                resolved.push(c);
+                if let Ok(path) = fs::canonicalize(&resolved) {
+                    resolved = path;
+                }
+            }
+        }
+    }
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_test() {
+        let dir = env::temp_dir().join(format!("lisp-permissions-{}", std::process::id()));
+        fs::create_dir_all(dir.join("data")).unwrap();
+
+        let permissions = Permissions::none().allow_read(dir.join("data"));
+        assert!(permissions.check_read(dir.join("data/a.lisp")).is_ok());
+        assert!(permissions.check_read(dir.join("data/../b.lisp")).is_err());
+        assert!(permissions.check_read("/etc/hosts").is_err());
+        assert!(permissions.check_write(dir.join("data/a.lisp")).is_err());
+        assert!(permissions.check_getenv("HOME").is_err());
+        assert!(permissions.check_setenv("HOME").is_err());
+        let permissions = permissions.allow_getenv("HOME");
+        assert!(permissions.check_getenv("HOME").is_ok());
+        assert!(permissions.check_getenv("PATH").is_err());
+
+        let permissions = Permissions::all();
+        assert!(permissions.check_getenv("PATH").is_ok());
+        assert!(permissions.check_write("/tmp/anything").is_ok());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
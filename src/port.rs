@@ -0,0 +1,464 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::fs::File;
+#[cfg(feature = "std")]
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+#[cfg(feature = "std")]
+use std::path::Path;
+
+use super::error::{ReadError, RuntimeError};
+use super::object::Object;
+use super::rc::RefCell;
+use super::reader::{Reader, ReaderInternal, ReaderOptions};
+
+enum State {
+    #[cfg(feature = "std")]
+    InputFile(BufReader<File>),
+    #[cfg(feature = "std")]
+    OutputFile(BufWriter<File>),
+    #[cfg(feature = "std")]
+    Stdin,
+    #[cfg(feature = "std")]
+    Stdout,
+    #[cfg(feature = "std")]
+    Stderr,
+    InputString(Vec<u8>, usize),
+    OutputString(String),
+    Closed,
+}
+
+pub struct Port {
+    state: RefCell<State>,
+}
+
+// Whether standard input has something to read can't be asked without
+// reading it, so the first time it's asked a thread starts reading it ahead
+// into a buffer, which stdin ports read from from then on. Until then they
+// read stdin directly, so nothing is taken from it that wasn't asked for.
+#[cfg(feature = "std")]
+mod pump {
+    use std::collections::VecDeque;
+    use std::io::{self, Read};
+    use std::sync::{Condvar, Mutex, OnceLock};
+    use std::thread;
+    use std::time::Duration;
+
+    #[derive(Default)]
+    struct Pump {
+        // The bytes read ahead, and whether stdin has ended.
+        buffer: Mutex<(VecDeque<u8>, bool)>,
+        filled: Condvar,
+    }
+
+    static PUMP: OnceLock<Pump> = OnceLock::new();
+
+    fn run(pump: &Pump) {
+        let mut chunk = [0; 4096];
+        loop {
+            let n = match io::stdin().lock().read(&mut chunk) {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Ok(n) => n,
+                Err(_) => 0,
+            };
+            let mut buffer = pump.buffer.lock().unwrap();
+            buffer.0.extend(&chunk[..n]);
+            buffer.1 = n == 0;
+            pump.filled.notify_all();
+            if n == 0 {
+                return;
+            }
+        }
+    }
+
+    pub fn is_started() -> bool {
+        PUMP.get().is_some()
+    }
+
+    // True at the end of input too, as reading won't wait then either. The
+    // thread is given a moment to read what's already there when it starts.
+    pub fn is_ready() -> bool {
+        let mut started = false;
+        let pump = PUMP.get_or_init(|| {
+            started = true;
+            Pump::default()
+        });
+        let mut buffer = pump.buffer.lock().unwrap();
+        if started {
+            thread::spawn(|| run(PUMP.get().unwrap()));
+            buffer = pump
+                .filled
+                .wait_timeout(buffer, Duration::from_millis(10))
+                .unwrap()
+                .0;
+        }
+        !buffer.0.is_empty() || buffer.1
+    }
+
+    // Waits for a byte, or the end of input.
+    pub fn next_byte(consume: bool) -> Option<u8> {
+        let pump = PUMP.get()?;
+        let mut buffer = pump.buffer.lock().unwrap();
+        while buffer.0.is_empty() && !buffer.1 {
+            buffer = pump.filled.wait(buffer).unwrap();
+        }
+        if consume {
+            buffer.0.pop_front()
+        } else {
+            buffer.0.front().copied()
+        }
+    }
+}
+
+impl Port {
+    fn new(state: State) -> Self {
+        Self {
+            state: RefCell::new(state),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn open_input_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(State::InputFile(BufReader::new(File::open(
+            path,
+        )?))))
+    }
+
+    #[cfg(feature = "std")]
+    pub fn open_output_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(State::OutputFile(BufWriter::new(File::create(
+            path,
+        )?))))
+    }
+
+    #[cfg(feature = "std")]
+    pub fn stdin() -> Self {
+        Self::new(State::Stdin)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn stdout() -> Self {
+        Self::new(State::Stdout)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn stderr() -> Self {
+        Self::new(State::Stderr)
+    }
+
+    pub fn input_string(s: &str) -> Self {
+        Self::new(State::InputString(s.as_bytes().to_vec(), 0))
+    }
+
+    pub fn output_string() -> Self {
+        Self::new(State::OutputString(String::new()))
+    }
+
+    pub fn is_input(&self) -> bool {
+        match &*self.state.borrow() {
+            #[cfg(feature = "std")]
+            State::InputFile(_) | State::Stdin => true,
+            State::InputString(..) => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_output(&self) -> bool {
+        match &*self.state.borrow() {
+            #[cfg(feature = "std")]
+            State::OutputFile(_) | State::Stdout | State::Stderr => true,
+            State::OutputString(_) => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        matches!(&*self.state.borrow(), State::Closed)
+    }
+
+    // The text written so far to a string output port.
+    pub fn output(&self) -> Option<String> {
+        match &*self.state.borrow() {
+            State::OutputString(s) => Some(s.clone()),
+            _ => None,
+        }
+    }
+
+    // Like output, but empties the port's buffer.
+    pub fn take_output(&self) -> Option<String> {
+        match &mut *self.state.borrow_mut() {
+            State::OutputString(s) => Some(core::mem::take(s)),
+            _ => None,
+        }
+    }
+
+    fn next_byte(&self, consume: bool) -> Result<Option<u8>, RuntimeError> {
+        match &mut *self.state.borrow_mut() {
+            #[cfg(feature = "std")]
+            State::InputFile(reader) => {
+                let byte = reader.fill_buf()?.first().copied();
+                if consume && byte.is_some() {
+                    reader.consume(1);
+                }
+                Ok(byte)
+            }
+            #[cfg(feature = "std")]
+            State::Stdin if pump::is_started() => Ok(pump::next_byte(consume)),
+            #[cfg(feature = "std")]
+            State::Stdin => {
+                let mut stdin = io::stdin().lock();
+                let byte = stdin.fill_buf()?.first().copied();
+                if consume && byte.is_some() {
+                    stdin.consume(1);
+                }
+                Ok(byte)
+            }
+            State::InputString(buffer, pos) => {
+                let byte = buffer.get(*pos).copied();
+                if consume && byte.is_some() {
+                    *pos += 1;
+                }
+                Ok(byte)
+            }
+            _ => Err(RuntimeError::ClosedPort),
+        }
+    }
+
+    // Whether reading would return without waiting for input, as it always
+    // does but for standard input.
+    pub fn is_ready(&self) -> Result<bool, RuntimeError> {
+        match &*self.state.borrow() {
+            #[cfg(feature = "std")]
+            State::InputFile(_) => Ok(true),
+            #[cfg(feature = "std")]
+            State::Stdin => Ok(pump::is_ready()),
+            State::InputString(..) => Ok(true),
+            _ => Err(RuntimeError::ClosedPort),
+        }
+    }
+
+    pub fn peek_byte(&self) -> Result<Option<u8>, RuntimeError> {
+        self.next_byte(false)
+    }
+
+    pub fn read_byte(&self) -> Result<Option<u8>, RuntimeError> {
+        self.next_byte(true)
+    }
+
+    // Decodes one UTF-8 character; invalid sequences read as U+FFFD.
+    pub fn read_char(&self) -> Result<Option<char>, RuntimeError> {
+        let first = match self.read_byte()? {
+            Some(b) => b,
+            None => return Ok(None),
+        };
+        let len = match first {
+            b if b < 0x80 => 1,
+            b if b >> 5 == 0b110 => 2,
+            b if b >> 4 == 0b1110 => 3,
+            b if b >> 3 == 0b11110 => 4,
+            _ => return Ok(Some(char::REPLACEMENT_CHARACTER)),
+        };
+        let mut bytes = Vec::with_capacity(len);
+        bytes.push(first);
+        while bytes.len() < len {
+            match self.read_byte()? {
+                Some(b) => bytes.push(b),
+                None => break,
+            }
+        }
+        Ok(Some(
+            core::str::from_utf8(&bytes)
+                .ok()
+                .and_then(|s| s.chars().next())
+                .unwrap_or(char::REPLACEMENT_CHARACTER),
+        ))
+    }
+
+    // Returns the next line without its terminator, or None at end of input.
+    pub fn read_line(&self) -> Result<Option<String>, RuntimeError> {
+        let mut bytes = Vec::new();
+        loop {
+            match self.read_byte()? {
+                Some(b'\n') => break,
+                Some(b) => bytes.push(b),
+                None if bytes.is_empty() => return Ok(None),
+                None => break,
+            }
+        }
+        if bytes.last() == Some(&b'\r') {
+            bytes.pop();
+        }
+        Ok(Some(String::from_utf8_lossy(&bytes).into_owned()))
+    }
+
+    // Reads one s-expression, or None if only whitespace remains.
+    pub fn read(&self) -> Result<Option<Object>, RuntimeError> {
+        self.read_in_base(10)
+    }
+
+    pub fn read_in_base(&self, base: u32) -> Result<Option<Object>, RuntimeError> {
+        self.read_with(base, ReaderOptions::default())
+    }
+
+    pub fn read_with(
+        &self,
+        base: u32,
+        options: ReaderOptions,
+    ) -> Result<Option<Object>, RuntimeError> {
+        let mut reader = PortReader(self, base, options, 0);
+        reader.skip_spaces();
+        if self.peek_byte()?.is_none() {
+            return Ok(None);
+        }
+        reader.read().map(Some).map_err(RuntimeError::ReadError)
+    }
+
+    pub fn write_str(&self, s: &str) -> Result<(), RuntimeError> {
+        match &mut *self.state.borrow_mut() {
+            #[cfg(feature = "std")]
+            State::OutputFile(writer) => Ok(writer.write_all(s.as_bytes())?),
+            #[cfg(feature = "std")]
+            State::Stdout => {
+                let mut stdout = io::stdout().lock();
+                stdout.write_all(s.as_bytes())?;
+                Ok(stdout.flush()?)
+            }
+            #[cfg(feature = "std")]
+            State::Stderr => Ok(io::stderr().write_all(s.as_bytes())?),
+            State::OutputString(buffer) => {
+                buffer.push_str(s);
+                Ok(())
+            }
+            _ => Err(RuntimeError::ClosedPort),
+        }
+    }
+
+    pub fn close(&self) -> Result<(), RuntimeError> {
+        let state = core::mem::replace(&mut *self.state.borrow_mut(), State::Closed);
+        match state {
+            #[cfg(feature = "std")]
+            State::OutputFile(mut writer) => Ok(writer.flush()?),
+            _ => Ok(()),
+        }
+    }
+}
+
+// The last field counts the bytes read, so positions are from where the read
+// started.
+struct PortReader<'a>(&'a Port, u32, ReaderOptions, usize);
+
+impl ReaderInternal for PortReader<'_> {
+    fn peek_char(&mut self) -> Result<u8, ReadError> {
+        match self.0.peek_byte() {
+            Ok(Some(b)) => Ok(b),
+            _ => Err(ReadError::EndOfFile),
+        }
+    }
+
+    fn next_char(&mut self) -> Result<u8, ReadError> {
+        match self.0.read_byte() {
+            Ok(Some(b)) => {
+                self.3 += 1;
+                Ok(b)
+            }
+            _ => Err(ReadError::EndOfFile),
+        }
+    }
+
+    // Skips the unmatched closing paren so reading can carry on after it.
+    fn clear(&mut self) {
+        let _ = self.next_char();
+    }
+
+    fn pos(&self) -> usize {
+        self.3
+    }
+
+    fn base(&self) -> u32 {
+        self.1
+    }
+
+    fn options(&self) -> ReaderOptions {
+        self.2
+    }
+}
+
+impl Reader for PortReader<'_> {}
+
+impl fmt::Display for Port {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_input() {
+            write!(f, "#<input-port>")
+        } else if self.is_output() {
+            write!(f, "#<output-port>")
+        } else {
+            write!(f, "#<closed-port>")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::object::{cons, fixnum, symbol};
+    use super::*;
+
+    #[test]
+    fn string_port_test() {
+        let port = Port::input_string("foo (1 . 2)\nbar ) 3  ");
+        assert!(port.is_input());
+        assert_eq!(port.read().unwrap(), Some(symbol("foo")));
+        assert_eq!(port.read().unwrap(), Some(cons(fixnum(1), fixnum(2))));
+        assert_eq!(port.read_line().unwrap(), Some("".to_string()));
+        assert_eq!(port.read().unwrap(), Some(symbol("bar")));
+        assert!(matches!(
+            port.read(),
+            Err(RuntimeError::ReadError(ReadError::UnmatchedClosedParen))
+        ));
+        assert_eq!(port.read().unwrap(), Some(fixnum(3)));
+        assert_eq!(port.read().unwrap(), None);
+        assert!(matches!(
+            Port::input_string("(a").read(),
+            Err(RuntimeError::ReadError(ReadError::EndOfFile))
+        ));
+
+        let port = Port::input_string("a");
+        assert!(port.is_ready().unwrap());
+        port.read_char().unwrap();
+        assert!(port.is_ready().unwrap());
+        port.close().unwrap();
+        assert!(matches!(port.is_ready(), Err(RuntimeError::ClosedPort)));
+
+        let port = Port::output_string();
+        assert!(port.is_output());
+        port.write_str("a").unwrap();
+        port.write_str("b").unwrap();
+        assert_eq!(port.output(), Some("ab".to_string()));
+        assert_eq!(port.take_output(), Some("ab".to_string()));
+        assert_eq!(port.output(), Some("".to_string()));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn file_port_test() {
+        let path = std::env::temp_dir().join(format!("lisp-port-{}.txt", std::process::id()));
+
+        let port = Port::open_output_file(&path).unwrap();
+        assert!(port.is_output());
+        port.write_str("héllo\r\nworld").unwrap();
+        port.close().unwrap();
+        assert!(port.is_closed());
+        assert!(matches!(port.write_str("x"), Err(RuntimeError::ClosedPort)));
+
+        let port = Port::open_input_file(&path).unwrap();
+        assert!(port.is_input());
+        assert_eq!(port.read_char().unwrap(), Some('h'));
+        assert_eq!(port.read_char().unwrap(), Some('é'));
+        assert_eq!(port.read_line().unwrap(), Some("llo".to_string()));
+        assert_eq!(port.read_line().unwrap(), Some("world".to_string()));
+        assert_eq!(port.read_line().unwrap(), None);
+        assert_eq!(port.read_char().unwrap(), None);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,198 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use super::reader::{scan, TokenKind};
+
+// Forms whose arguments on later lines are a body indented by two, like
+// those starting with def or with-, rather than aligned with the first.
+const BODY_FORMS: &[&str] = &[
+    "lambda",
+    "let",
+    "let*",
+    "letrec",
+    "let-values",
+    "multiple-value-bind",
+    "once-only",
+    "when",
+    "unless",
+    "dolist",
+    "dotimes",
+    "while",
+    "begin",
+    "progn",
+    "block",
+    "delay",
+    "case",
+    "match",
+    "handler-case",
+    "unwind-protect",
+    "parameterize",
+];
+
+fn is_body_form(name: &str) -> bool {
+    name.starts_with("def") || name.starts_with("with-") || BODY_FORMS.contains(&name)
+}
+
+struct Open {
+    // The column just after the opening bracket.
+    column: usize,
+    line: usize,
+    quoted: bool,
+    // Quoted lists, table literals and lists not headed by a symbol, whose
+    // elements all line up.
+    data: bool,
+    body: bool,
+    elements: usize,
+    // The column of the first argument, when it's on the opener's line.
+    argument: Option<usize>,
+}
+
+impl Open {
+    fn indentation(&self) -> usize {
+        if self.data || self.elements == 0 {
+            self.column
+        } else if self.body {
+            self.column + 1
+        } else {
+            self.argument.unwrap_or(self.column)
+        }
+    }
+}
+
+// Reindents source and normalizes the spacing between tokens, keeping
+// comments and the line breaks between forms, but for closing brackets,
+// which join the line before. At most one blank line is kept in a row.
+// Source read would reject isn't formatted; the error is the range of the
+// first offending token.
+pub fn reformat(source: &str) -> Result<String, Range<usize>> {
+    let tokens = scan(source);
+    if let Some((_, range)) = tokens.iter().find(|(kind, _)| *kind == TokenKind::Error) {
+        return Err(range.clone());
+    }
+
+    let mut out = String::new();
+    let mut stack = Vec::<Open>::new();
+    let mut column = 0;
+    let mut line = 0;
+    let mut previous: Option<(TokenKind, &str, usize)> = None;
+    for (kind, range) in tokens {
+        let mut text = &source[range.clone()];
+        if kind == TokenKind::Comment {
+            text = text.trim_end();
+        }
+        let newlines = match previous {
+            Some((previous, _, end))
+                if previous == TokenKind::Comment || kind != TokenKind::Close =>
+            {
+                source[end..range.start].matches('\n').count()
+            }
+            _ => 0,
+        };
+        if newlines > 0 {
+            out.push_str(if newlines > 1 { "\n\n" } else { "\n" });
+            line += 1;
+            column = stack.last().map_or(0, Open::indentation);
+            out.push_str(&" ".repeat(column));
+        } else if let Some((previous, _, _)) = previous {
+            if !matches!(previous, TokenKind::Open | TokenKind::Quote) && kind != TokenKind::Close {
+                out.push(' ');
+                column += 1;
+            }
+        }
+
+        let after_quote = matches!(previous, Some((TokenKind::Quote, _, _)));
+        let quoted = match stack.last_mut() {
+            Some(open) => {
+                if !after_quote && !matches!(kind, TokenKind::Close | TokenKind::Comment) {
+                    open.elements += 1;
+                    match open.elements {
+                        1 if kind == TokenKind::Symbol => open.body = is_body_form(text),
+                        1 => open.data = true,
+                        2 if open.line == line => open.argument = Some(column),
+                        _ => (),
+                    }
+                }
+                open.quoted
+            }
+            None => false,
+        };
+
+        out.push_str(text);
+        column = match text.rfind('\n') {
+            Some(i) => text[i + 1..].chars().count(),
+            None => column + text.chars().count(),
+        };
+        match kind {
+            TokenKind::Open => {
+                let quoted = quoted || matches!(previous, Some((_, "'", _)));
+                stack.push(Open {
+                    column,
+                    line,
+                    quoted,
+                    data: quoted || text != "(",
+                    body: false,
+                    elements: 0,
+                    argument: None,
+                })
+            }
+            TokenKind::Close => {
+                stack.pop();
+            }
+            _ => (),
+        }
+        previous = Some((kind, text, range.end));
+    }
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reformat_test() {
+        let source = "
+  (defun f  (x)
+\"Doc.\"   ; keep this
+        (let ((a 1)
+   (b '(1
+2)))
+(+ a
+      b x)
+)
+)
+
+
+;; next
+(list #{a 1
+b 2} \"two
+  lines\" (g
+y))";
+        let expected = "(defun f (x)
+  \"Doc.\" ; keep this
+  (let ((a 1)
+        (b '(1
+             2)))
+    (+ a
+       b x)))
+
+;; next
+(list #{a 1
+        b 2} \"two
+  lines\" (g
+          y))
+";
+        assert_eq!(reformat(source).unwrap(), expected);
+        assert_eq!(reformat(expected).unwrap(), expected);
+
+        // A closing bracket after a comment stays on its own line.
+        assert_eq!(reformat("(a ; b\n   )").unwrap(), "(a ; b\n )\n");
+        assert_eq!(reformat("").unwrap(), "");
+        assert_eq!(reformat("a\n\"b"), Err(2..4));
+        assert_eq!(reformat("(a))"), Err(3..4));
+    }
+}
@@ -0,0 +1,85 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use std::process::Command;
+
+use super::env::Env;
+use super::error::RuntimeError;
+use super::eval::{check_num_args, check_num_args_range, EvalResult};
+use super::object::{self, Object, ObjectKind, ObjectType};
+use super::profile::Capability;
+use super::rc::{Rc, RefCell};
+
+// Builtins that reach outside the interpreter's process. They're only
+// defined with the system capability.
+pub fn init(env: &mut Env) {
+    if env.profile().allows(Capability::System) {
+        env.define_builtin("run-process", "command &rest args", run_process);
+        env.define_env_builtin("getenv", "name", getenv);
+        env.define_env_builtin("setenv", "name value", setenv);
+    }
+}
+
+fn string(x: &Object) -> Result<&str, RuntimeError> {
+    match &**x {
+        ObjectKind::String(s) => Ok(s),
+        _ => Err(RuntimeError::MismatchType(Rc::clone(x), ObjectType::String)),
+    }
+}
+
+// (getenv name) returns the value of the environment variable, or nil if
+// it isn't set, and (setenv name value) sets it, as far as the permissions
+// of env allow.
+fn getenv(args: &[Object], env: &Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args(args, 1)?;
+    let name = string(&args[0])?;
+    Env::permissions(env).check_getenv(name)?;
+    match std::env::var(name) {
+        Ok(value) => Ok(object::string(&value)),
+        Err(_) => Ok(object::nil()),
+    }
+}
+
+fn setenv(args: &[Object], env: &Rc<RefCell<Env>>) -> EvalResult {
+    check_num_args(args, 2)?;
+    let name = string(&args[0])?;
+    let value = string(&args[1])?;
+    Env::permissions(env).check_setenv(name)?;
+    // set_var panics on these.
+    if name.is_empty() || name.contains(['=', '\0']) || value.contains('\0') {
+        return Err(RuntimeError::InvalidEnvVar(
+            name.to_string(),
+            value.to_string(),
+        ));
+    }
+    // This races with any other thread of the process that reads the
+    // environment outside of std, such as a C library in the host, so
+    // setenv is for scripts that have the process to themselves.
+    std::env::set_var(name, value);
+    Ok(Rc::clone(&args[1]))
+}
+
+// (run-process command args...) runs command with args, without a shell,
+// waits for it and returns ((status . code) (stdout . string) (stderr .
+// string)). The status is nil if the process was killed by a signal.
+fn run_process(args: &[Object]) -> EvalResult {
+    check_num_args_range(args, 1, None)?;
+    let command = string(&args[0])?;
+    let command_args = args[1..]
+        .iter()
+        .map(string)
+        .collect::<Result<Vec<_>, _>>()?;
+    let output = Command::new(command).args(command_args).output()?;
+    let field = |name: &str, value: Object| object::cons(object::symbol(name), value);
+    let text = |bytes: &[u8]| object::string(&String::from_utf8_lossy(bytes));
+    Ok(crate::list![
+        field(
+            "status",
+            output
+                .status
+                .code()
+                .map_or_else(object::nil, |code| object::fixnum(code as isize))
+        ),
+        field("stdout", text(&output.stdout)),
+        field("stderr", text(&output.stderr))
+    ])
+}
@@ -0,0 +1,146 @@
+// Which groups of builtins a global environment gets. Pure builtins are
+// always there; io covers files and system covers the host process. A
+// group that's left out isn't defined at all, so its names are unbound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    Pure,
+    Io,
+    System,
+}
+
+impl Capability {
+    pub fn from_name(name: &str) -> Option<Capability> {
+        match name {
+            "pure" => Some(Capability::Pure),
+            "io" => Some(Capability::Io),
+            "system" => Some(Capability::System),
+            _ => None,
+        }
+    }
+}
+
+// The builtins by what they're about, for building a global environment
+// with only some of them. Core, which the rest of the language is written
+// in, is always there. The builtins of io and system also need their
+// capability.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Module {
+    Core,
+    // Lists, and the other collections.
+    List,
+    Math,
+    String,
+    // Ports, the standard streams, files and loading them.
+    Io,
+    // The host: processes, environment variables, the clock, and channels.
+    System,
+    // Promises, generators and functions made of functions.
+    Control,
+    // Weak references, finalizers, and how objects are allocated.
+    Memory,
+}
+
+impl Module {
+    pub fn all() -> [Module; 8] {
+        [
+            Module::Core,
+            Module::List,
+            Module::Math,
+            Module::String,
+            Module::Io,
+            Module::System,
+            Module::Control,
+            Module::Memory,
+        ]
+    }
+
+    pub fn from_name(name: &str) -> Option<Module> {
+        Module::all()
+            .iter()
+            .copied()
+            .find(|module| module.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Module::Core => "core",
+            Module::List => "list",
+            Module::Math => "math",
+            Module::String => "string",
+            Module::Io => "io",
+            Module::System => "system",
+            Module::Control => "control",
+            Module::Memory => "memory",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Profile {
+    io: bool,
+    system: bool,
+}
+
+impl Profile {
+    pub fn pure() -> Self {
+        Self {
+            io: false,
+            system: false,
+        }
+    }
+
+    pub fn full() -> Self {
+        Self {
+            io: true,
+            system: true,
+        }
+    }
+
+    pub fn with(mut self, capability: Capability) -> Self {
+        match capability {
+            Capability::Pure => (),
+            Capability::Io => self.io = true,
+            Capability::System => self.system = true,
+        }
+        self
+    }
+
+    pub fn allows(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::Pure => true,
+            Capability::Io => self.io,
+            Capability::System => self.system,
+        }
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self::full()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_test() {
+        let profile = Profile::pure();
+        assert!(profile.allows(Capability::Pure));
+        assert!(!profile.allows(Capability::Io));
+        let profile = profile.with(Capability::from_name("io").unwrap());
+        assert!(profile.allows(Capability::Io));
+        assert!(!profile.allows(Capability::System));
+        assert_eq!(Profile::default(), Profile::full());
+        assert_eq!(Capability::from_name("network"), None);
+    }
+
+    #[test]
+    fn module_test() {
+        for module in Module::all() {
+            assert_eq!(Module::from_name(module.name()), Some(module));
+        }
+        assert_eq!(Module::from_name("network"), None);
+    }
+}
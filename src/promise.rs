@@ -0,0 +1,110 @@
+use super::env::Env;
+use super::error::RuntimeError;
+use super::eval::{self, check_num_args, EvalResult};
+use super::object::{self, Object, ObjectKind, ObjectType};
+use super::rc::{Rc, RefCell};
+
+// What (delay expr) returns: a thunk evaluating expr, which force calls the
+// first time and replaces with its value.
+pub struct Promise {
+    state: RefCell<State>,
+}
+
+enum State {
+    Delayed(Object),
+    Forced(Object),
+}
+
+impl Promise {
+    pub fn new(thunk: Object) -> Self {
+        Self {
+            state: RefCell::new(State::Delayed(thunk)),
+        }
+    }
+
+    pub fn forced(value: Object) -> Self {
+        Self {
+            state: RefCell::new(State::Forced(value)),
+        }
+    }
+
+    pub fn is_forced(&self) -> bool {
+        matches!(*self.state.borrow(), State::Forced(_))
+    }
+
+    pub fn force(&self) -> EvalResult {
+        let thunk = match &*self.state.borrow() {
+            State::Forced(value) => return Ok(Rc::clone(value)),
+            State::Delayed(thunk) => Rc::clone(thunk),
+        };
+        let value = eval::apply(thunk, alloc::vec![])?;
+        // Forcing the promise again from its own thunk may have forced it
+        // already, in which case the first value wins.
+        if let State::Forced(value) = &*self.state.borrow() {
+            return Ok(Rc::clone(value));
+        }
+        *self.state.borrow_mut() = State::Forced(Rc::clone(&value));
+        Ok(value)
+    }
+}
+
+pub fn init(env: &mut Env) {
+    env.define_builtin("%delay", "thunk", delay);
+    env.define_builtin("make-promise", "value", make_promise);
+    env.define_builtin("force", "promise", force);
+    env.define_builtin("promise?", "x", is_promise);
+    env.define_builtin("promise-forced?", "promise", is_forced);
+}
+
+fn boolean(x: bool) -> Object {
+    if x {
+        object::symbol("t")
+    } else {
+        object::nil()
+    }
+}
+
+fn delay(args: &[Object]) -> EvalResult {
+    check_num_args(args, 1)?;
+    if !ObjectType::Function.contains(&args[0]) {
+        return Err(RuntimeError::MismatchType(
+            Rc::clone(&args[0]),
+            ObjectType::Function,
+        ));
+    }
+    Ok(object::promise(Promise::new(Rc::clone(&args[0]))))
+}
+
+// A promise that is already forced to value, or value if it's a promise.
+fn make_promise(args: &[Object]) -> EvalResult {
+    check_num_args(args, 1)?;
+    match &*args[0] {
+        ObjectKind::Promise(_) => Ok(Rc::clone(&args[0])),
+        _ => Ok(object::promise(Promise::forced(Rc::clone(&args[0])))),
+    }
+}
+
+// Values that aren't promises are returned as they are.
+fn force(args: &[Object]) -> EvalResult {
+    check_num_args(args, 1)?;
+    match &*args[0] {
+        ObjectKind::Promise(promise) => promise.force(),
+        _ => Ok(Rc::clone(&args[0])),
+    }
+}
+
+fn is_promise(args: &[Object]) -> EvalResult {
+    check_num_args(args, 1)?;
+    Ok(boolean(ObjectType::Promise.contains(&args[0])))
+}
+
+fn is_forced(args: &[Object]) -> EvalResult {
+    check_num_args(args, 1)?;
+    match &*args[0] {
+        ObjectKind::Promise(promise) => Ok(boolean(promise.is_forced())),
+        _ => Err(RuntimeError::MismatchType(
+            Rc::clone(&args[0]),
+            ObjectType::Promise,
+        )),
+    }
+}
@@ -0,0 +1,235 @@
+use alloc::vec::Vec;
+
+use super::env::Env;
+use super::error::RuntimeError;
+use super::eval::{check_num_args, EvalResult};
+use super::object::{self, Object, ObjectKind};
+use super::rc::Rc;
+
+// Expands (quasiquote x) into code built from quote and the builtins below,
+// which stand for list, append and cons under names of their own so that a
+// local variable called list can't capture them. Each nested quasiquote
+// raises the level and each unquote lowers it; only an unquote that brings
+// the level back to zero is evaluated, the others are rebuilt as data
+// (Bawden, "Quasiquotation in Lisp", appendix B).
+pub fn expand(x: &Object) -> Result<Object, RuntimeError> {
+    qq(x, 1)
+}
+
+pub fn init(env: &mut Env) {
+    env.define_builtin("%qq-list", "&rest elements", make_list);
+    env.define_builtin("%qq-append", "&rest lists", append);
+    env.define_builtin("%qq-cons", "car cdr", cons);
+}
+
+fn make_list(args: &[Object]) -> EvalResult {
+    Ok(object::from_iter(args.iter().cloned()))
+}
+
+// The last list becomes the tail of the result, as with append.
+fn append(args: &[Object]) -> EvalResult {
+    let (last, lists) = match args.split_last() {
+        Some(split) => split,
+        None => return Ok(object::nil()),
+    };
+    let mut elements = Vec::new();
+    for list in lists {
+        elements.extend(object::to_vec(list)?);
+    }
+    Ok(object::dotted_from_iter(elements, Rc::clone(last)))
+}
+
+fn cons(args: &[Object]) -> EvalResult {
+    check_num_args(args, 2)?;
+    Ok(object::cons(Rc::clone(&args[0]), Rc::clone(&args[1])))
+}
+
+// Returns the arguments if `x` is `(name ...)`.
+fn args<'a>(x: &'a Object, name: &str) -> Option<&'a Object> {
+    match &**x {
+        ObjectKind::Cons(cons) => match &*cons.car {
+            ObjectKind::Symbol(s) if s == name => Some(&cons.cdr),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn single(x: &Object, args: &Object) -> Result<Object, RuntimeError> {
+    match &**args {
+        ObjectKind::Cons(cons) if matches!(&*cons.cdr, ObjectKind::Nil) => Ok(Rc::clone(&cons.car)),
+        _ => Err(RuntimeError::MalformedForm(Rc::clone(x))),
+    }
+}
+
+fn quote(x: Object) -> Object {
+    crate::list![object::symbol("quote"), x]
+}
+
+fn call(name: &str, args: Vec<Object>) -> Object {
+    object::cons(object::symbol(name), object::from_iter(args))
+}
+
+// Rebuilds `(name . args)` from the expansion of its arguments, keeping
+// the result as (quote ...) or (%qq-list ...) where possible.
+fn rebuild(name: &str, args: Object) -> Object {
+    let name = object::symbol(name);
+    if let Some(args) = args_of(&args, "quote") {
+        return quote(object::cons(name, Rc::clone(args)));
+    }
+    if let Some(args) = args_of(&args, "%qq-list") {
+        return object::cons(
+            object::symbol("%qq-list"),
+            object::cons(quote(name), Rc::clone(args)),
+        );
+    }
+    call("%qq-cons", alloc::vec![quote(name), args])
+}
+
+// Like `args`, but for generated code, which is always well formed.
+fn args_of<'a>(x: &'a Object, name: &str) -> Option<&'a Object> {
+    match args(x, name) {
+        Some(args) if name == "quote" => match &**args {
+            ObjectKind::Cons(cons) => Some(&cons.car),
+            _ => unreachable!(),
+        },
+        args => args,
+    }
+}
+
+fn qq(x: &Object, depth: usize) -> Result<Object, RuntimeError> {
+    if !has_unquote(x, depth) {
+        return Ok(match &**x {
+            ObjectKind::Nil | ObjectKind::Symbol(_) | ObjectKind::Cons(_) => quote(Rc::clone(x)),
+            _ => Rc::clone(x),
+        });
+    }
+    if let Some(rest) = args(x, "unquote") {
+        return match depth {
+            1 => single(x, rest),
+            _ => Ok(rebuild("unquote", qq(rest, depth - 1)?)),
+        };
+    }
+    if let Some(rest) = args(x, "unquote-splicing") {
+        return match depth {
+            1 => Err(RuntimeError::MalformedForm(Rc::clone(x))),
+            _ => Ok(rebuild("unquote-splicing", qq(rest, depth - 1)?)),
+        };
+    }
+    if let Some(rest) = args(x, "quasiquote") {
+        return Ok(rebuild("quasiquote", qq(rest, depth + 1)?));
+    }
+    qq_list(x, depth)
+}
+
+// Builds (%qq-append segment...), one segment per element plus the tail.
+fn qq_list(x: &Object, depth: usize) -> Result<Object, RuntimeError> {
+    let mut segments = Vec::new();
+    let mut spliced = false;
+    let mut rest = x;
+    loop {
+        let cons = match &**rest {
+            // `(a . ,b) reads as (a unquote b), so a tail that is itself an
+            // unquote form is the dotted tail rather than two elements.
+            ObjectKind::Cons(cons)
+                if args(rest, "unquote").is_none() && args(rest, "quasiquote").is_none() =>
+            {
+                cons
+            }
+            ObjectKind::Nil => break,
+            _ => {
+                spliced = true;
+                segments.push(qq(rest, depth)?);
+                break;
+            }
+        };
+        match args(&cons.car, "unquote-splicing") {
+            Some(e) if depth == 1 => {
+                spliced = true;
+                segments.push(single(&cons.car, e)?);
+            }
+            _ => segments.push(call("%qq-list", alloc::vec![qq(&cons.car, depth)?])),
+        }
+        rest = &cons.cdr;
+    }
+
+    if spliced {
+        return Ok(call("%qq-append", segments));
+    }
+    // Without splicing, (append (list a) (list b)) is just (list a b).
+    let elements = segments
+        .iter()
+        .map(|segment| single(segment, args_of(segment, "%qq-list").unwrap()).unwrap())
+        .collect();
+    Ok(call("%qq-list", elements))
+}
+
+fn has_unquote(x: &Object, depth: usize) -> bool {
+    if let Some(rest) = args(x, "unquote").or_else(|| args(x, "unquote-splicing")) {
+        return depth == 1 || has_unquote(rest, depth - 1);
+    }
+    if let Some(rest) = args(x, "quasiquote") {
+        return has_unquote(rest, depth + 1);
+    }
+    match &**x {
+        ObjectKind::Cons(cons) => has_unquote(&cons.car, depth) || has_unquote(&cons.cdr, depth),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::reader::read_from_string;
+    use super::*;
+
+    fn read(input: &str) -> Object {
+        read_from_string(input).unwrap().0
+    }
+
+    fn verify(input: &str, expected: &str) {
+        let form = read(input);
+        let x = single(&form, args(&form, "quasiquote").unwrap()).unwrap();
+        assert_eq!(expand(&x).unwrap(), read(expected));
+    }
+
+    #[test]
+    fn expand_test() {
+        verify("`a", "'a");
+        verify("`1", "1");
+        verify("`(a b)", "'(a b)");
+        verify("`,x", "x");
+        verify("`(a ,b c)", "(%qq-list 'a b 'c)");
+        verify("`(a ,@b c)", "(%qq-append (%qq-list 'a) b (%qq-list 'c))");
+        verify("`(a . ,b)", "(%qq-append (%qq-list 'a) b)");
+        verify("`(a (b ,c))", "(%qq-list 'a (%qq-list 'b c))");
+    }
+
+    #[test]
+    fn nested_test() {
+        verify("`(a `(b ,c))", "'(a (quasiquote (b (unquote c))))");
+        verify(
+            "`(a `(b ,(c ,d)))",
+            "(%qq-list 'a (%qq-list 'quasiquote (%qq-list 'b (%qq-list 'unquote (%qq-list 'c d)))))",
+        );
+        verify(
+            "`(a `(b ,@,c))",
+            "(%qq-list 'a (%qq-list 'quasiquote (%qq-list 'b (%qq-list 'unquote-splicing c))))",
+        );
+        verify(
+            "`(a `(b ,@,@c))",
+            "(%qq-list 'a (%qq-list 'quasiquote (%qq-list 'b (%qq-cons 'unquote-splicing (%qq-append c)))))",
+        );
+    }
+
+    #[test]
+    fn error_test() {
+        assert!(matches!(
+            expand(&read(",@a")),
+            Err(RuntimeError::MalformedForm(_))
+        ));
+        assert!(matches!(
+            expand(&read("(unquote a b)")),
+            Err(RuntimeError::MalformedForm(_))
+        ));
+    }
+}
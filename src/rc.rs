@@ -0,0 +1,41 @@
+// Shared ownership and interior mutability used throughout the interpreter.
+// With the `sync` feature these are backed by Arc and RwLock, which makes
+// objects and environments Send + Sync so an Interpreter can be moved to a
+// worker thread or shared behind a Mutex.
+
+#[cfg(not(feature = "sync"))]
+pub use alloc::rc::Rc;
+#[cfg(not(feature = "sync"))]
+pub use alloc::rc::Weak;
+#[cfg(not(feature = "sync"))]
+pub use core::cell::RefCell;
+
+#[cfg(feature = "sync")]
+pub use std::sync::Arc as Rc;
+#[cfg(feature = "sync")]
+pub use std::sync::Weak;
+
+#[cfg(feature = "sync")]
+pub use self::sync::RefCell;
+
+#[cfg(feature = "sync")]
+mod sync {
+    use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+    #[derive(Debug, Default)]
+    pub struct RefCell<T>(RwLock<T>);
+
+    impl<T> RefCell<T> {
+        pub fn new(value: T) -> Self {
+            RefCell(RwLock::new(value))
+        }
+
+        pub fn borrow(&self) -> RwLockReadGuard<'_, T> {
+            self.0.read().unwrap()
+        }
+
+        pub fn borrow_mut(&self) -> RwLockWriteGuard<'_, T> {
+            self.0.write().unwrap()
+        }
+    }
+}
@@ -1,29 +1,13 @@
+use super::bitvector::BitVector;
+pub use super::error::ReadError;
 use super::object;
-use core::fmt;
+use super::table::{HashTable, Set};
+use alloc::vec::Vec;
+use core::ops::Range;
+use core::str::from_utf8;
+#[cfg(feature = "std")]
 use std::io::{self, BufRead};
-use std::str::from_utf8;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub enum ReadError {
-    EndOfFile,
-    UnmatchedClosedParen,
-    UnexpectedChar(char, char),
-}
-
-impl fmt::Display for ReadError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        use self::ReadError::*;
-        match self {
-            EndOfFile => write!(f, "End of file"),
-            UnmatchedClosedParen => write!(f, "Unmatched closed parenthesis"),
-            UnexpectedChar(actual, expected) => write!(
-                f,
-                "Expecting character {:?}, but it's character {:?}",
-                expected, actual
-            ),
-        }
-    }
-}
 type ReadResult = Result<object::Object, ReadError>;
 
 fn vec_to_cons(mut vec: Vec<object::Object>, last: object::Object) -> object::Object {
@@ -35,53 +19,132 @@ fn vec_to_cons(mut vec: Vec<object::Object>, last: object::Object) -> object::Ob
     head
 }
 
+// Whether s is an optional sign and then digits in base.
+fn is_integer(s: &str, base: u32) -> bool {
+    let digits = s.strip_prefix(['+', '-']).unwrap_or(s);
+    !digits.is_empty() && digits.chars().all(|c| c.is_digit(base))
+}
+
 fn is_delimiter(b: u8) -> bool {
     match b {
-        b'(' | b')' | b'\'' => true,
+        b'(' | b')' | b'}' | b'\'' | b'`' | b',' | b'"' | b';' => true,
         b if b.is_ascii_whitespace() => true,
         _ => false,
     }
 }
 
+fn read_bit_vector(bits: &str) -> ReadResult {
+    let bits = bits
+        .chars()
+        .map(|c| match c {
+            '0' => Ok(false),
+            '1' => Ok(true),
+            c => Err(ReadError::InvalidBit(c)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(object::bit_vector(BitVector::from_bits(bits)))
+}
+
+fn push_code_point(v: &mut Vec<u8>, digits: &[u8], escape: char) -> Result<(), ReadError> {
+    let c = from_utf8(digits)
+        .ok()
+        .filter(|digits| (1..=6).contains(&digits.len()))
+        .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+        .and_then(char::from_u32)
+        .ok_or(ReadError::InvalidEscape(escape))?;
+    v.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+    Ok(())
+}
+
+// Syntax that differs between dialects. The default is this lisp's own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReaderOptions {
+    // #t and #f, or #true and #false, read as t and nil.
+    pub booleans: bool,
+    // How many lists, literals and quotes may enclose a form before read
+    // gives up with TooDeep instead of overflowing the stack.
+    pub max_depth: usize,
+}
+
+impl Default for ReaderOptions {
+    fn default() -> Self {
+        Self {
+            booleans: false,
+            max_depth: 1000,
+        }
+    }
+}
+
+impl ReaderOptions {
+    pub fn scheme() -> Self {
+        Self {
+            booleans: true,
+            ..Self::default()
+        }
+    }
+}
+
 pub trait ReaderInternal {
     fn peek_char(&mut self) -> Result<u8, ReadError>;
     fn next_char(&mut self) -> Result<u8, ReadError>;
     fn clear(&mut self);
 
+    // How many bytes have been read, for errors to say where a form started.
+    fn pos(&self) -> usize;
+
+    // The radix of integers, which read takes from *read-base*.
+    fn base(&self) -> u32 {
+        10
+    }
+
+    fn options(&self) -> ReaderOptions {
+        ReaderOptions::default()
+    }
+
     fn skip_spaces(&mut self) {
         loop {
             match self.peek_char() {
                 Ok(c) if c.is_ascii_whitespace() => {
                     self.next_char().unwrap();
                 }
+                Ok(b';') => self.skip_comment(),
                 _ => return,
             }
         }
     }
 
-    fn read_list(&mut self) -> ReadResult {
+    fn skip_comment(&mut self) {
+        loop {
+            match self.next_char() {
+                Ok(b'\n') | Err(_) => return,
+                Ok(_) => (),
+            }
+        }
+    }
+
+    fn read_list(&mut self, depth: usize) -> ReadResult {
         let mut list = Vec::<object::Object>::new();
 
         self.skip_spaces();
-        match self.peek_char()? {
-            b')' => {
-                self.next_char().unwrap();
-                return Ok(object::nil());
-            }
-            _ => (),
+        if self.peek_char()? == b')' {
+            self.next_char().unwrap();
+            return Ok(object::nil());
         }
 
         let last = loop {
-            let obj = self.read_ahead()?;
+            let obj = self.read_nested(depth)?;
             self.skip_spaces();
             list.push(obj);
             match self.peek_char()? {
                 b'.' => {
                     self.next_char().unwrap();
-                    let last = self.read_ahead()?;
+                    let last = self.read_nested(depth)?;
                     self.skip_spaces();
                     match self.peek_char()? {
-                        b')' => break last,
+                        b')' => {
+                            self.next_char().unwrap();
+                            break last;
+                        }
                         b => {
                             return Err(ReadError::UnexpectedChar(
                                 b as char, // TODO: multibyte char
@@ -101,7 +164,11 @@ pub trait ReaderInternal {
     }
 
     fn read_atom(&mut self) -> ReadResult {
-        let mut v = Vec::new();
+        self.read_atom_from(Vec::new())
+    }
+
+    // Reads the rest of an atom whose first characters were already read.
+    fn read_atom_from(&mut self, mut v: Vec<u8>) -> ReadResult {
         loop {
             match self.peek_char() {
                 Ok(c) if is_delimiter(c) => break,
@@ -113,34 +180,177 @@ pub trait ReaderInternal {
             }
         }
         let s = from_utf8(&v).unwrap();
-        let obj = match s.parse() {
+        if let Some(bits) = s.strip_prefix("#*") {
+            return read_bit_vector(bits);
+        }
+        let obj = match isize::from_str_radix(s, self.base()) {
             Ok(n) => object::fixnum(n),
-            _ => object::symbol(&s),
+            // A token that's all digits only fails to parse by overflowing.
+            Err(_) if is_integer(s, self.base()) => return Err(ReadError::IntegerOverflow),
+            Err(_) => object::symbol(s),
         };
         Ok(obj)
     }
 
-    fn read_quote(&mut self) -> ReadResult {
-        let obj = self.read_ahead()?;
-        let obj = object::cons(object::symbol("quote"), object::cons(obj, object::nil()));
+    // Reads the rest of a string after its opening quote. Running out of
+    // input, even inside an escape, is UnterminatedString.
+    fn read_string(&mut self) -> ReadResult {
+        let start = self.pos().saturating_sub(1);
+        self.read_string_body().map_err(|e| match e {
+            ReadError::EndOfFile => ReadError::UnterminatedString(start),
+            e => e,
+        })
+    }
+
+    // The escapes are those write uses: \n, \t, \", \\, \xNN for other
+    // control characters and \u{...} for any code point.
+    fn read_string_body(&mut self) -> ReadResult {
+        let mut v = Vec::new();
+        loop {
+            match self.next_char()? {
+                b'"' => break,
+                b'\\' => match self.next_char()? {
+                    b'n' => v.push(b'\n'),
+                    b't' => v.push(b'\t'),
+                    c @ (b'"' | b'\\') => v.push(c),
+                    b'x' => {
+                        let digits = [self.next_char()?, self.next_char()?];
+                        push_code_point(&mut v, &digits, 'x')?;
+                    }
+                    b'u' => {
+                        if self.next_char()? != b'{' {
+                            return Err(ReadError::InvalidEscape('u'));
+                        }
+                        let mut digits = Vec::new();
+                        loop {
+                            match self.next_char()? {
+                                b'}' => break,
+                                c => digits.push(c),
+                            }
+                        }
+                        push_code_point(&mut v, &digits, 'u')?;
+                    }
+                    c => return Err(ReadError::InvalidEscape(c as char)),
+                },
+                c => v.push(c),
+            }
+        }
+        let s = from_utf8(&v).unwrap();
+        Ok(object::string(s))
+    }
+
+    fn read_quote(&mut self, depth: usize) -> ReadResult {
+        self.read_wrapped("quote", depth)
+    }
+
+    fn read_wrapped(&mut self, name: &str, depth: usize) -> ReadResult {
+        let obj = self.read_nested(depth)?;
+        let obj = object::cons(object::symbol(name), object::cons(obj, object::nil()));
         Ok(obj)
     }
 
+    // #{k v ...} is a hash table and #s(x ...) a set; other atoms starting
+    // with # are read as before, but for the booleans of ReaderOptions.
+    fn read_sharp(&mut self, depth: usize) -> ReadResult {
+        match self.peek_char() {
+            Ok(b'{') => {
+                self.next_char().unwrap();
+                let forms = self.read_until(b'}', depth)?;
+                if forms.len() % 2 != 0 {
+                    return Err(ReadError::UnpairedKey);
+                }
+                let mut forms = forms.into_iter();
+                let mut entries = Vec::new();
+                while let (Some(key), Some(value)) = (forms.next(), forms.next()) {
+                    entries.push((key, value));
+                }
+                Ok(object::hash_table(HashTable::from_entries(entries)))
+            }
+            Ok(b'(') => Err(ReadError::VectorLiteral),
+            Ok(b's') => {
+                self.next_char().unwrap();
+                if let Ok(b'(') = self.peek_char() {
+                    self.next_char().unwrap();
+                    let elements = self.read_until(b')', depth)?;
+                    return Ok(object::set(Set::from_elements(elements)));
+                }
+                self.read_atom_from(alloc::vec![b'#', b's'])
+            }
+            _ => {
+                let atom = self.read_atom_from(alloc::vec![b'#'])?;
+                if !self.options().booleans {
+                    return Ok(atom);
+                }
+                Ok(match &*atom {
+                    object::ObjectKind::Symbol(s) if s == "#t" || s == "#true" => {
+                        object::symbol("t")
+                    }
+                    object::ObjectKind::Symbol(s) if s == "#f" || s == "#false" => object::nil(),
+                    _ => atom,
+                })
+            }
+        }
+    }
+
+    fn read_until(&mut self, end: u8, depth: usize) -> Result<Vec<object::Object>, ReadError> {
+        let mut forms = Vec::new();
+        loop {
+            self.skip_spaces();
+            if self.peek_char()? == end {
+                self.next_char().unwrap();
+                return Ok(forms);
+            }
+            forms.push(self.read_nested(depth)?);
+        }
+    }
+
     fn read_ahead(&mut self) -> ReadResult {
+        self.read_nested(0)
+    }
+
+    // Reads a form inside depth enclosing ones. The rest of the input is
+    // dropped past the limit, as it's most likely more of the same.
+    fn read_nested(&mut self, depth: usize) -> ReadResult {
+        if depth > self.options().max_depth {
+            self.clear();
+            return Err(ReadError::TooDeep);
+        }
+        let depth = depth + 1;
         self.skip_spaces();
 
         match self.peek_char()? {
-            b')' => {
+            b')' | b'}' => {
                 self.clear();
                 Err(ReadError::UnmatchedClosedParen)
             }
             b'(' => {
                 self.next_char().unwrap();
-                self.read_list()
+                self.read_list(depth)
             }
             b'\'' => {
                 self.next_char().unwrap();
-                self.read_quote()
+                self.read_quote(depth)
+            }
+            b'`' => {
+                self.next_char().unwrap();
+                self.read_wrapped("quasiquote", depth)
+            }
+            b',' => {
+                self.next_char().unwrap();
+                if self.peek_char()? == b'@' {
+                    self.next_char().unwrap();
+                    self.read_wrapped("unquote-splicing", depth)
+                } else {
+                    self.read_wrapped("unquote", depth)
+                }
+            }
+            b'"' => {
+                self.next_char().unwrap();
+                self.read_string()
+            }
+            b'#' => {
+                self.next_char().unwrap();
+                self.read_sharp(depth)
             }
             _ => self.read_atom(),
         }
@@ -156,6 +366,8 @@ pub trait Reader: ReaderInternal {
 pub struct StringStream {
     buffer: Vec<u8>,
     pos: usize,
+    base: u32,
+    options: ReaderOptions,
 }
 
 impl StringStream {
@@ -163,17 +375,26 @@ impl StringStream {
         Self {
             buffer: str.as_bytes().to_vec(),
             pos: 0,
+            base: 10,
+            options: ReaderOptions::default(),
         }
     }
 
+    pub fn with_options(mut self, options: ReaderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    // Integers after this are read in base.
+    pub fn set_base(&mut self, base: u32) {
+        self.base = base;
+    }
+
+    #[cfg(feature = "std")]
     fn update(&mut self, buffer: Vec<u8>) {
         self.buffer = buffer;
         self.pos = 0;
     }
-
-    pub fn pos(&self) -> usize {
-        self.pos
-    }
 }
 
 impl ReaderInternal for StringStream {
@@ -186,9 +407,8 @@ impl ReaderInternal for StringStream {
     }
 
     fn next_char(&mut self) -> Result<u8, ReadError> {
-        self.peek_char().and_then(|c| {
+        self.peek_char().inspect(|_| {
             self.pos += 1;
-            Ok(c)
         })
     }
 
@@ -196,20 +416,37 @@ impl ReaderInternal for StringStream {
         self.buffer.clear();
         self.pos = 0;
     }
+
+    fn pos(&self) -> usize {
+        self.pos
+    }
+
+    fn base(&self) -> u32 {
+        self.base
+    }
+
+    fn options(&self) -> ReaderOptions {
+        self.options
+    }
 }
 
 impl Reader for StringStream {}
 
+#[cfg(feature = "std")]
 pub struct InputStream<R> {
     rdr: io::BufReader<R>,
     inner: StringStream,
+    // The bytes of the lines before the one in inner.
+    consumed: usize,
 }
 
+#[cfg(feature = "std")]
 impl<R: io::Read> InputStream<R> {
     pub fn from_reader(rdr: R) -> Self {
         InputStream {
             rdr: io::BufReader::new(rdr),
             inner: StringStream::new(""),
+            consumed: 0,
         }
     }
 
@@ -217,6 +454,7 @@ impl<R: io::Read> InputStream<R> {
         let mut buf = String::new();
         match self.rdr.read_line(&mut buf) {
             Ok(_) => {
+                self.consumed += self.inner.buffer.len();
                 self.inner.update(buf.as_bytes().to_vec());
                 Some(())
             }
@@ -225,6 +463,7 @@ impl<R: io::Read> InputStream<R> {
     }
 }
 
+#[cfg(feature = "std")]
 impl<R: io::Read> ReaderInternal for InputStream<R> {
     fn peek_char(&mut self) -> Result<u8, ReadError> {
         match self.inner.peek_char() {
@@ -245,10 +484,16 @@ impl<R: io::Read> ReaderInternal for InputStream<R> {
     }
 
     fn clear(&mut self) {
+        self.consumed += self.inner.buffer.len();
         self.inner.clear();
     }
+
+    fn pos(&self) -> usize {
+        self.consumed + self.inner.pos()
+    }
 }
 
+#[cfg(feature = "std")]
 impl<R: io::Read> Reader for InputStream<R> {}
 
 pub fn read_from_string(input: &str) -> Result<(object::Object, usize), ReadError> {
@@ -256,6 +501,120 @@ pub fn read_from_string(input: &str) -> Result<(object::Object, usize), ReadErro
     r.read_ahead().map(|x| (x, r.pos()))
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    // (, #{ and #s( open, ) and } close.
+    Open,
+    Close,
+    // ' ` , and ,@
+    Quote,
+    Dot,
+    Number,
+    String,
+    Symbol,
+    Keyword,
+    // Other atoms starting with #, like bit vectors.
+    Literal,
+    Comment,
+    // Anything read would reject, including brackets that don't match.
+    Error,
+}
+
+// Splits source into tokens with their byte ranges for syntax highlighting.
+// Atoms and strings are classified by the reader itself, and the scan
+// carries on past any error.
+pub fn scan(source: &str) -> Vec<(TokenKind, Range<usize>)> {
+    let mut s = StringStream::new(source);
+    let mut tokens = Vec::new();
+    // The index of each open bracket's token, with the byte closing it.
+    let mut open = Vec::<(usize, u8)>::new();
+    while let Ok(c) = s.peek_char() {
+        let start = s.pos();
+        if c.is_ascii_whitespace() {
+            s.next_char().unwrap();
+            continue;
+        }
+        let kind = match c {
+            b';' => {
+                while !matches!(s.peek_char(), Ok(b'\n') | Err(_)) {
+                    s.next_char().unwrap();
+                }
+                TokenKind::Comment
+            }
+            b'(' => {
+                s.next_char().unwrap();
+                open.push((tokens.len(), b')'));
+                TokenKind::Open
+            }
+            b')' | b'}' => {
+                s.next_char().unwrap();
+                match open.last() {
+                    Some(&(_, close)) if close == c => {
+                        open.pop();
+                        TokenKind::Close
+                    }
+                    _ => TokenKind::Error,
+                }
+            }
+            b'\'' | b'`' => {
+                s.next_char().unwrap();
+                TokenKind::Quote
+            }
+            b',' => {
+                s.next_char().unwrap();
+                if s.peek_char() == Ok(b'@') {
+                    s.next_char().unwrap();
+                }
+                TokenKind::Quote
+            }
+            b'"' => {
+                s.next_char().unwrap();
+                match s.read_string() {
+                    Ok(_) => TokenKind::String,
+                    Err(_) => {
+                        // Skip the rest of a string with a bad escape.
+                        loop {
+                            match s.next_char() {
+                                Ok(b'"') | Err(_) => break,
+                                Ok(b'\\') => {
+                                    let _ = s.next_char();
+                                }
+                                Ok(_) => (),
+                            }
+                        }
+                        TokenKind::Error
+                    }
+                }
+            }
+            b'#' if source[start..].starts_with("#{") => {
+                s.pos += 2;
+                open.push((tokens.len(), b'}'));
+                TokenKind::Open
+            }
+            b'#' if source[start..].starts_with("#s(") => {
+                s.pos += 3;
+                open.push((tokens.len(), b')'));
+                TokenKind::Open
+            }
+            _ => match s.read_atom() {
+                Ok(x) => match &*x {
+                    object::ObjectKind::Fixnum(_) => TokenKind::Number,
+                    object::ObjectKind::Symbol(name) if name == "." => TokenKind::Dot,
+                    object::ObjectKind::Symbol(name) if name.starts_with(':') => TokenKind::Keyword,
+                    object::ObjectKind::Symbol(name) if !name.starts_with('#') => TokenKind::Symbol,
+                    _ => TokenKind::Literal,
+                },
+                Err(_) => TokenKind::Error,
+            },
+        };
+        tokens.push((kind, start..s.pos()));
+    }
+    for (i, _) in open {
+        tokens[i].0 = TokenKind::Error;
+    }
+    tokens
+}
+
 #[cfg(test)]
 mod tests {
     use super::super::equal::equal;
@@ -270,6 +629,12 @@ mod tests {
         assert_eq!(s.next_char(), Ok(b'b'));
         assert_eq!(s.next_char(), Ok(b'c'));
         assert_eq!(s.next_char(), Err(ReadError::EndOfFile));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn string_stream_update() {
+        let mut s = StringStream::new("abc");
         s.update("xyz".as_bytes().to_vec());
         assert_eq!(s.next_char(), Ok(b'x'));
         assert_eq!(s.next_char(), Ok(b'y'));
@@ -305,6 +670,10 @@ mod tests {
             cons(symbol("a"), cons(symbol("b"), cons(symbol("c"), nil()))),
         );
         verify("(a . b)", cons(symbol("a"), symbol("b")));
+        verify(
+            "((a . b) c)",
+            cons(cons(symbol("a"), symbol("b")), cons(symbol("c"), nil())),
+        );
         verify("'foo", cons(symbol("quote"), cons(symbol("foo"), nil())));
         verify(
             "'(a b)",
@@ -314,5 +683,187 @@ mod tests {
             ),
         );
         verify("(() 1)", cons(nil(), cons(fixnum(1), nil())));
+        verify(
+            "; comment\n(a ; comment\n b)",
+            cons(symbol("a"), cons(symbol("b"), nil())),
+        );
+        verify("\"foo\"", string("foo"));
+        verify("\"a\\\"b\\\\c\\n\"", string("a\"b\\c\n"));
+        verify("(a\"b\")", cons(symbol("a"), cons(string("b"), nil())));
+        verify(
+            "`(a ,b ,@c)",
+            crate::list![
+                symbol("quasiquote"),
+                crate::list![
+                    symbol("a"),
+                    crate::list![symbol("unquote"), symbol("b")],
+                    crate::list![symbol("unquote-splicing"), symbol("c")]
+                ]
+            ],
+        );
+        verify(
+            "(#*0110)",
+            cons(
+                bit_vector(BitVector::from_bits([false, true, true, false])),
+                nil(),
+            ),
+        );
+        assert!(matches!(
+            read_from_string("#*012"),
+            Err(ReadError::InvalidBit('2'))
+        ));
+        assert!(matches!(
+            read_from_string("#(0 1)"),
+            Err(ReadError::VectorLiteral)
+        ));
+        assert!(matches!(
+            read_from_string("(1 99999999999999999999999)"),
+            Err(ReadError::IntegerOverflow)
+        ));
+        assert!(matches!(
+            read_from_string("-99999999999999999999999"),
+            Err(ReadError::IntegerOverflow)
+        ));
+        verify(
+            "99999999999999999999999abc",
+            symbol("99999999999999999999999abc"),
+        );
+        verify("1+", symbol("1+"));
+    }
+
+    #[test]
+    fn table_literal_test() {
+        let (x, _) = read_from_string("#{a 1 \"b\" (2 3)}").unwrap();
+        assert_eq!(x.to_string(), "#{a 1 \"b\" (2 3)}");
+        let (x, _) = read_from_string("(#s(1 2 1) #sym)").unwrap();
+        assert_eq!(x.to_string(), "(#s(1 2) #sym)");
+        assert!(matches!(
+            read_from_string("#{a 1 b}"),
+            Err(ReadError::UnpairedKey)
+        ));
+        assert!(matches!(
+            read_from_string("}"),
+            Err(ReadError::UnmatchedClosedParen)
+        ));
+    }
+
+    #[test]
+    fn boolean_option_test() {
+        let read = |input: &str, options| {
+            StringStream::new(input)
+                .with_options(options)
+                .read()
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(
+            read("(#t #f #true #false)", ReaderOptions::default()),
+            "(#t #f #true #false)"
+        );
+        assert_eq!(
+            read("(#t #f #true #false #tx)", ReaderOptions::scheme()),
+            "(t nil t nil #tx)"
+        );
+    }
+
+    #[test]
+    fn depth_limit_test() {
+        let deep = |n| "(".repeat(n) + &")".repeat(n);
+        assert!(read_from_string(&deep(1000)).is_ok());
+        assert!(matches!(
+            read_from_string(&deep(100_000)),
+            Err(ReadError::TooDeep)
+        ));
+        assert!(matches!(
+            read_from_string(&"'".repeat(100_000)),
+            Err(ReadError::TooDeep)
+        ));
+
+        let options = ReaderOptions {
+            max_depth: 3,
+            ..ReaderOptions::default()
+        };
+        let read = |input: &str| StringStream::new(input).with_options(options).read();
+        assert!(read("(a (b #{c d}))").is_ok());
+        assert!(matches!(read("(a (b #{c (d)}))"), Err(ReadError::TooDeep)));
+        assert!(matches!(read("(a '((b)))"), Err(ReadError::TooDeep)));
+    }
+
+    #[test]
+    fn string_escape_test() {
+        verify(r#""a\tb\x41\u{3bb}\u{1F600}""#, string("a\tbAλ😀"));
+        for input in [r#""\q""#, r#""\x4""#, r#""\u{110000}""#, r#""\u41""#] {
+            assert!(matches!(
+                read_from_string(input),
+                Err(ReadError::InvalidEscape(_))
+            ));
+        }
+
+        // Whatever write prints reads back as the same string.
+        for s in [
+            "",
+            "say \"hi\"",
+            "back\\slash\\",
+            "lines\n\tand\r\n",
+            "\0\x1b[0m\x7f",
+            "日本語 é λ 😀",
+        ] {
+            let (x, _) = read_from_string(&string(s).to_string()).unwrap();
+            assert!(equal(x, string(s)), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn unterminated_string_test() {
+        for (input, start) in [("\"abc", 0), ("(a \"b\" \"c\\\"", 7), ("(x \"\\u{3b", 3)] {
+            let e = read_from_string(input).unwrap_err();
+            assert_eq!(e, ReadError::UnterminatedString(start), "{:?}", input);
+            assert!(e.is_incomplete());
+        }
+        assert!(read_from_string("(a").unwrap_err().is_incomplete());
+        assert!(!read_from_string("\"\\q\"").unwrap_err().is_incomplete());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn input_stream_test() {
+        // Strings and lists carry on over lines; the position of an
+        // unterminated string counts the lines before it.
+        let mut s = InputStream::from_reader("(a \"b\nc\")\n\"d\n".as_bytes());
+        assert_eq!(s.read().unwrap().to_string(), "(a \"b\\nc\")");
+        assert_eq!(s.read(), Err(ReadError::UnterminatedString(10)));
+    }
+
+    #[test]
+    fn scan_test() {
+        use TokenKind::*;
+        let source = "(f 'x :k 12 . \"s\") ; c\n#{a #*01} `(,@y)";
+        let tokens = scan(source);
+        assert_eq!(
+            tokens.iter().map(|(kind, _)| *kind).collect::<Vec<_>>(),
+            alloc::vec![
+                Open, Symbol, Quote, Symbol, Keyword, Number, Dot, String, Close, Comment, Open,
+                Symbol, Literal, Close, Quote, Open, Quote, Symbol, Close
+            ]
+        );
+        let text = |i: usize| &source[tokens[i].1.clone()];
+        assert_eq!(text(7), "\"s\"");
+        assert_eq!(text(9), "; c");
+        assert_eq!(text(10), "#{");
+        assert_eq!(text(16), ",@");
+
+        let kinds = |source| {
+            scan(source)
+                .into_iter()
+                .map(|(kind, _)| kind)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(kinds("(a}"), alloc::vec![Error, Symbol, Error]);
+        assert_eq!(kinds(")(a)"), alloc::vec![Error, Open, Symbol, Close]);
+        assert_eq!(
+            kinds("\"\\q\" 99999999999999999999999 #*2"),
+            alloc::vec![Error; 3]
+        );
+        assert_eq!(kinds("a \"open"), alloc::vec![Symbol, Error]);
     }
 }
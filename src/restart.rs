@@ -0,0 +1,19 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::error::RuntimeError;
+use super::object::Object;
+
+// A way to continue from an error. with-restarts establishes restarts
+// around its body, and an unbound variable offers use-value and define-it.
+#[derive(Debug, Clone)]
+pub struct Restart {
+    pub id: usize,
+    pub name: String,
+    pub parameters: Vec<String>,
+}
+
+// Called when an error offering restarts occurs outside of %try. Returns the
+// index of the chosen restart and the forms for its arguments, which are
+// evaluated in the global environment; None lets the error propagate.
+pub type Debugger = fn(&RuntimeError, &[Restart]) -> Option<(usize, Vec<Object>)>;
@@ -0,0 +1,17 @@
+;;; Loaded after the stdlib for Scheme, where #t and #f are the booleans
+;;; and the test of an if has to be a boolean.
+
+(define (pair? x) (not (atom? x)))
+
+(define (list? x)
+  (if (null? x)
+      #t
+      (if (pair? x) (list? (cdr x)) #f)))
+
+(define (boolean? x) (if (null? x) #t (equal x #t)))
+
+(define (equal? x y) (equal x y))
+
+(define (zero? n) (equal n 0))
+
+(defmacro begin (&rest body) (cons 'progn body))
@@ -0,0 +1,114 @@
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+use super::interpreter::Interpreter;
+use super::object::{self, Object};
+use super::reader::{InputStream, Reader};
+
+// Each request is a single s-expression; each response is written on its own
+// line as `(:ok "printed value")` or `(:error "message")`.
+fn response(status: &str, text: String) -> Object {
+    crate::list!(object::symbol(status), object::string(&text))
+}
+
+pub fn serve_connection<R: Read, W: Write>(
+    input: R,
+    mut output: W,
+    interpreter: &Interpreter,
+) -> io::Result<()> {
+    let mut reader = InputStream::from_reader(input);
+    loop {
+        let response = match reader.read() {
+            Ok(form) => match interpreter.eval(form) {
+                Ok(value) => response(":ok", value.to_string()),
+                Err(e) => response(":error", e.to_string()),
+            },
+            // The connection closed, perhaps in the middle of a form.
+            Err(e) if e.is_incomplete() => return Ok(()),
+            Err(e) => response(":error", e.to_string()),
+        };
+        writeln!(output, "{}", response)?;
+        output.flush()?;
+    }
+}
+
+fn serve_stream(stream: TcpStream, interpreter: &Interpreter) -> io::Result<()> {
+    serve_connection(stream.try_clone()?, stream, interpreter)
+}
+
+// Every connection gets a fresh environment unless `shared` is set.
+pub fn serve(listener: TcpListener, shared: bool) -> io::Result<()> {
+    if shared {
+        return serve_shared(listener);
+    }
+    for stream in listener.incoming() {
+        let stream = stream?;
+        thread::spawn(move || serve_stream(stream, &Interpreter::new()));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "sync")]
+fn serve_shared(listener: TcpListener) -> io::Result<()> {
+    use std::sync::Arc;
+
+    let interpreter = Arc::new(Interpreter::new());
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let interpreter = Arc::clone(&interpreter);
+        thread::spawn(move || serve_stream(stream, &interpreter));
+    }
+    Ok(())
+}
+
+// Without the sync feature the environment can't cross threads, so
+// connections sharing it are served one at a time.
+#[cfg(not(feature = "sync"))]
+fn serve_shared(listener: TcpListener) -> io::Result<()> {
+    let interpreter = Interpreter::new();
+    for stream in listener.incoming() {
+        serve_stream(stream?, &interpreter)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(input: &str, interpreter: &Interpreter) -> String {
+        let mut output = Vec::new();
+        serve_connection(input.as_bytes(), &mut output, interpreter).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn serve_connection_test() {
+        let interpreter = Interpreter::new();
+        assert_eq!(
+            run("(define x 1)\n(+ x 2) \"a\" y)", &interpreter),
+            "(:ok \"1\")\n\
+             (:ok \"3\")\n\
+             (:ok \"\\\"a\\\"\")\n\
+             (:error \"Unbound variable: y\")\n\
+             (:error \"Unmatched closed parenthesis\")\n"
+        );
+        assert_eq!(run("x", &interpreter), "(:ok \"1\")\n");
+    }
+
+    #[test]
+    fn serve_test() {
+        use std::io::{BufRead, BufReader};
+        use std::net::TcpStream;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || serve(listener, false));
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let mut lines = BufReader::new(stream.try_clone().unwrap()).lines();
+        stream.write_all(b"(+ 1 2)\n").unwrap();
+        assert_eq!(lines.next().unwrap().unwrap(), "(:ok \"3\")");
+    }
+}
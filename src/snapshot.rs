@@ -0,0 +1,163 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+
+use super::env::Env;
+use super::eval::EvalResult;
+use super::load;
+use super::object::{self, Object, ObjectKind};
+use super::rc::{Rc, RefCell};
+
+// A snapshot is Lisp source that rebuilds the global environment when
+// evaluated on top of a fresh one. Builtins are already there; ports and
+// closures over local environments can't be written out, so they're skipped.
+
+fn is_data(x: &Object) -> bool {
+    match &**x {
+        ObjectKind::Nil | ObjectKind::Fixnum(_) | ObjectKind::Symbol(_) | ObjectKind::String(_) => {
+            true
+        }
+        ObjectKind::Cons(cons) => is_data(&cons.car) && is_data(&cons.cdr),
+        ObjectKind::HashTable(table) => table
+            .entries()
+            .iter()
+            .all(|(key, value)| is_data(key) && is_data(value)),
+        ObjectKind::Set(set) => set.elements().iter().all(is_data),
+        _ => false,
+    }
+}
+
+// Special variables are defined with defparameter, which makes them special
+// again.
+fn definition(
+    name: Object,
+    value: &Object,
+    special: bool,
+    root: &Rc<RefCell<Env>>,
+) -> Option<Object> {
+    let define = object::symbol(if special { "defparameter" } else { "define" });
+    let form = match &**value {
+        ObjectKind::Closure(closure) | ObjectKind::Macro(closure) => {
+            if !Rc::ptr_eq(&closure.env, root) || !closure.body.iter().all(is_data) {
+                return None;
+            }
+            match &**value {
+                // (defmacro name params body...) from (lambda params body...)
+                ObjectKind::Macro(_) => match &*closure.source() {
+                    ObjectKind::Cons(lambda) => object::cons(
+                        object::symbol("defmacro"),
+                        object::cons(name, Rc::clone(&lambda.cdr)),
+                    ),
+                    _ => unreachable!(),
+                },
+                _ => crate::list![define, name, closure.source()],
+            }
+        }
+        _ if is_data(value) => crate::list![
+            define,
+            name,
+            crate::list![object::symbol("quote"), Rc::clone(value)]
+        ],
+        _ => return None,
+    };
+    Some(form)
+}
+
+pub fn dump(env: &Rc<RefCell<Env>>) -> String {
+    let root = Env::root(env);
+    let root_env = root.borrow();
+    let mut bindings: Vec<(&String, &Object)> = root_env.bindings().collect();
+    bindings.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut forms = root_env.packages().definitions();
+    let current = forms.pop();
+    for (key, value) in bindings {
+        // Keys outside the default package are `pkg:sym`; `pkg::sym` names
+        // them regardless of what's exported.
+        let name = match key.find(':') {
+            Some(pos) if pos > 0 => {
+                object::symbol(&format!("{}::{}", &key[..pos], &key[pos + 1..]))
+            }
+            _ => object::symbol(key),
+        };
+        let special = root_env.is_special_key(key);
+        forms.extend(definition(name, value, special, &root));
+    }
+    forms.extend(current);
+
+    let mut out = String::new();
+    for form in forms {
+        out.push_str(&format!("{}\n", form));
+    }
+    out
+}
+
+// An image is a snapshot behind a header, so that booting from it can tell
+// it apart from an arbitrary file.
+const IMAGE_MAGIC: &[u8] = b"LISPIMG";
+const IMAGE_VERSION: u8 = 1;
+
+pub fn image(env: &Rc<RefCell<Env>>) -> Vec<u8> {
+    let source = dump(env);
+    let mut image = Vec::from(IMAGE_MAGIC);
+    image.push(IMAGE_VERSION);
+    image.extend_from_slice(&(source.len() as u32).to_le_bytes());
+    image.extend_from_slice(source.as_bytes());
+    image
+}
+
+// The snapshot in an image, or None if it isn't one this version wrote.
+pub fn image_source(image: &[u8]) -> Option<&str> {
+    let rest = image
+        .strip_prefix(IMAGE_MAGIC)?
+        .strip_prefix(&[IMAGE_VERSION])?;
+    let (len, source) = rest.split_at_checked(4)?;
+    let len = u32::from_le_bytes(len.try_into().ok()?) as usize;
+    if source.len() != len {
+        return None;
+    }
+    core::str::from_utf8(source).ok()
+}
+
+// Unlike load_str, the current package set by the snapshot is kept.
+pub fn restore(source: &str, env: Rc<RefCell<Env>>) -> EvalResult {
+    load::eval_forms(source, env)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dump_test() {
+        let env = Env::global_env();
+        load::load_str(
+            "(define x '(1 \"a\" b))
+             (define f (let ((y 1)) (lambda () y)))
+             (defun g (a &rest b) (cons a b))
+             (defun h () \"Returns one.\" 1)
+             (defvar *v* 2)",
+            Rc::clone(&env),
+        )
+        .unwrap();
+        let snapshot = dump(&env);
+        assert!(snapshot.contains("(define x (quote (1 \"a\" b)))\n"));
+        assert!(snapshot.contains("(define g (lambda (a &rest b) (cons a b)))\n"));
+        assert!(snapshot.contains("(define h (lambda nil \"Returns one.\" 1))\n"));
+        assert!(snapshot.contains("(defparameter *v* (quote 2))\n"));
+        assert!(!snapshot.contains("(define f "));
+        assert!(!snapshot.contains("(define *standard-output* "));
+        assert!(snapshot.ends_with("(in-package user)\n"));
+    }
+
+    #[test]
+    fn image_test() {
+        let env = Env::global_env();
+        load::load_str("(define x 1)", Rc::clone(&env)).unwrap();
+        let image = image(&env);
+        assert_eq!(image_source(&image), Some(dump(&env).as_str()));
+        assert_eq!(image_source(&image[..image.len() - 1]), None);
+        assert_eq!(image_source(b"(define x 1)"), None);
+    }
+}
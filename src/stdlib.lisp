@@ -0,0 +1,516 @@
+;;;; stdlib.lisp --- evaluated into every global environment
+
+(define list (lambda (&rest elements) elements))
+
+(defmacro defun (name params &rest body)
+  (list 'define name (cons 'lambda (cons params body))))
+
+(defun null? (x) (equal x nil))
+
+(defun not (x) (if x nil 't))
+
+(defun caar (x) (car (car x)))
+(defun cadr (x) (car (cdr x)))
+(defun cdar (x) (cdr (car x)))
+(defun cddr (x) (cdr (cdr x)))
+
+(defmacro when (test &rest body)
+  (list 'if test (cons 'progn body)))
+
+(defmacro unless (test &rest body)
+  (list 'if test nil (cons 'progn body)))
+
+(defmacro and (&rest forms)
+  (if (null? forms)
+      't
+      (if (null? (cdr forms))
+          (car forms)
+          (list 'if (car forms) (cons 'and (cdr forms)) nil))))
+
+;; The remaining forms are wrapped in a thunk that is created in the
+;; caller's environment, so the temporary parameter names can't capture
+;; any variables used by the caller.
+(defmacro or (&rest forms)
+  (if (null? forms)
+      nil
+      (if (null? (cdr forms))
+          (car forms)
+          (list (list 'lambda '(value rest) '(if value value (rest)))
+                (car forms)
+                (list 'lambda nil (cons 'or (cdr forms)))))))
+
+(defun length (list)
+  "Returns the number of elements in LIST."
+  (if (null? list)
+      0
+      (+ 1 (length (cdr list)))))
+
+(defun nth (n list)
+  "Returns the Nth element of LIST, counting from zero."
+  (if (equal n 0)
+      (car list)
+      (nth (+ n -1) (cdr list))))
+
+(defun last (list)
+  (if (null? (cdr list))
+      list
+      (last (cdr list))))
+
+(defun append2 (x y)
+  (if (null? x)
+      y
+      (cons (car x) (append2 (cdr x) y))))
+
+(defun append (&rest lists)
+  "Returns a new list with the elements of LISTS concatenated."
+  (if (null? lists)
+      nil
+      (if (null? (cdr lists))
+          (car lists)
+          (append2 (car lists) (apply append (cdr lists))))))
+
+(defun reverse (list)
+  "Returns a new list with the elements of LIST in reverse order."
+  (defun iter (list acc)
+    (if (null? list)
+        acc
+        (iter (cdr list) (cons (car list) acc))))
+  (iter list nil))
+
+(defun map (f list)
+  "Returns a list of the results of calling F on each element of LIST."
+  (if (null? list)
+      nil
+      (cons (f (car list)) (map f (cdr list)))))
+
+(defun filter (pred list)
+  "Returns the elements of LIST for which PRED is true."
+  (if (null? list)
+      nil
+      (if (pred (car list))
+          (cons (car list) (filter pred (cdr list)))
+          (filter pred (cdr list)))))
+
+(defun reduce (f initial list)
+  "Combines the elements of LIST from the left with F, starting from INITIAL."
+  (if (null? list)
+      initial
+      (reduce f (f initial (car list)) (cdr list))))
+
+(defun member (x list)
+  "Returns the tail of LIST starting at the first element equal to X, or nil."
+  (if (null? list)
+      nil
+      (if (equal x (car list))
+          list
+          (member x (cdr list)))))
+
+(defmacro let (bindings &rest body)
+  (cons (cons 'lambda (cons (map car bindings) body))
+        (map cadr bindings)))
+
+(defun %map2 (f xs ys)
+  (if (null? xs)
+      nil
+      (cons (f (car xs) (car ys)) (%map2 f (cdr xs) (cdr ys)))))
+
+(defmacro with-gensyms (names &rest body)
+  "Binds each of NAMES to a fresh symbol while evaluating BODY."
+  `(let ,(map (lambda (name) `(,name (gensym ',name))) names)
+     ,@body))
+
+;; (once-only (x) body) makes the expansion of BODY evaluate the form in X
+;; exactly once: X is rebound to a fresh symbol that the expansion binds to
+;; the original form.
+(defmacro once-only (names &rest body)
+  "Makes the forms in NAMES evaluate once, in order, in the expansion of BODY."
+  (let ((gensyms (map (lambda (name) (gensym)) names)))
+    `(let ,(map (lambda (g) `(,g (gensym))) gensyms)
+       (list 'let
+             (list ,@(%map2 (lambda (g name) `(list ,g ,name)) gensyms names))
+             (let ,(%map2 list names gensyms)
+               ,@body)))))
+
+;; A step that is a symbol is called with the value alone.
+(defun %thread (x steps last?)
+  (if (null? steps)
+      x
+      (%thread (let ((step (if (atom? (car steps)) (list (car steps)) (car steps))))
+                 (if last?
+                     (append step (list x))
+                     (cons (car step) (cons x (cdr step)))))
+               (cdr steps)
+               last?)))
+
+(defmacro -> (x &rest steps)
+  "Threads X through STEPS, inserting it as the first argument of the first
+step, whose result is inserted into the next, and so on."
+  (%thread x steps nil))
+
+(defmacro ->> (x &rest steps)
+  "Like ->, but inserts each result as the last argument of the next step."
+  (%thread x steps t))
+
+(defun %value (n results)
+  (if (null? results)
+      nil
+      (if (equal n 0)
+          (car results)
+          (%value (+ n -1) (cdr results)))))
+
+(defun %value-bindings (vars results)
+  (%map2 (lambda (var i) `(,var (%value ,i ,results)))
+         vars
+         (reverse (reduce (lambda (is var) (cons (length is) is)) nil vars))))
+
+(defmacro multiple-value-bind (vars form &rest body)
+  "Evaluates FORM and BODY with VARS bound to its values. Missing values are
+nil and extra ones are ignored."
+  (with-gensyms (results)
+    `(let ((,results (multiple-value-list ,form)))
+       (let ,(%value-bindings vars results)
+         ,@body))))
+
+(defmacro let-values (bindings &rest body)
+  "Like multiple-value-bind with several ((var...) form) BINDINGS, whose
+forms are all evaluated before any variable is bound."
+  (let ((gensyms (map (lambda (binding) (gensym)) bindings)))
+    `(let ,(%map2 (lambda (g binding) `(,g (multiple-value-list ,(cadr binding))))
+                  gensyms
+                  bindings)
+       (let ,(reduce append nil (%map2 (lambda (g binding) (%value-bindings (car binding) g))
+                                       gensyms
+                                       bindings))
+         ,@body))))
+
+(defun truncate (n &rest divisor)
+  "Returns the quotient of N and DIVISOR, 1 by default, rounded towards zero,
+and the remainder as a second value."
+  (let ((d (if divisor (car divisor) 1)))
+    (values (quotient n d) (remainder n d))))
+
+(defmacro delay (expr)
+  "Returns a promise to evaluate EXPR the first time it's forced."
+  `(%delay (lambda () ,expr)))
+
+;; A stream is nil or a pair whose cdr is a promise of the rest, so only as
+;; much of it as is looked at is ever computed, and it may be infinite.
+
+(defmacro stream-cons (x rest)
+  "Returns a stream of X followed by the stream REST, which isn't evaluated
+until it's needed."
+  `(cons ,x (delay ,rest)))
+
+(defun stream-car (stream) (car stream))
+(defun stream-cdr (stream) (force (cdr stream)))
+
+(defun stream-map (f stream)
+  "Returns the stream of the results of calling F on each element of STREAM."
+  (if (null? stream)
+      nil
+      (stream-cons (f (stream-car stream)) (stream-map f (stream-cdr stream)))))
+
+(defun stream-filter (pred stream)
+  "Returns the stream of the elements of STREAM for which PRED is true. It
+looks ahead in STREAM as far as the next such element."
+  (if (null? stream)
+      nil
+      (if (null? (pred (stream-car stream)))
+          (stream-filter pred (stream-cdr stream))
+          (stream-cons (stream-car stream) (stream-filter pred (stream-cdr stream))))))
+
+(defun stream-take (stream n)
+  "Returns a list of the first N elements of STREAM, or all of them if it's
+shorter."
+  (if (if (null? stream) t (equal n 0))
+      nil
+      (cons (stream-car stream) (stream-take (stream-cdr stream) (+ n -1)))))
+
+(defun integers-from (n)
+  "Returns the infinite stream of the integers from N up."
+  (stream-cons n (integers-from (+ n 1))))
+
+(defun current-input-port () *standard-input*)
+(defun current-output-port () *standard-output*)
+(defun current-error-port () *standard-error*)
+
+(defun %input-port (port) (if port (car port) *standard-input*))
+(defun %output-port (port) (if port (car port) *standard-output*))
+
+(define *read-base* 10)
+(define *print-base* 10)
+
+(defun read (&rest args)
+  (apply %read (cons (%input-port args)
+                     (cons *read-base* (if args (cdr args) nil)))))
+(defun read-line (&rest port) (%read-line (%input-port port)))
+(defun read-char (&rest port) (%read-char (%input-port port)))
+
+(defun char-ready? (&rest port)
+  "Returns whether reading a character from PORT, the standard input by
+default, wouldn't wait for input. At the end of input it's t."
+  (%char-ready? (%input-port port)))
+
+(defun read-char-no-hang (&rest port)
+  "Like read-char, but returns nil instead of waiting when no character is
+ready."
+  (let ((port (%input-port port)))
+    (if (%char-ready? port) (%read-char port) nil)))
+
+(defun write-string (string &rest port)
+  (%write-string string (%output-port port)))
+(defun write (x &rest port) (%write x (%output-port port) *print-base*))
+(defun display (x &rest port) (%display x (%output-port port) *print-base*))
+(defun newline (&rest port) (%write-string "\n" (%output-port port)) nil)
+
+(defun print (x &rest port)
+  (let ((port (%output-port port)))
+    (%write x port *print-base*)
+    (%write-string "\n" port)
+    x))
+
+(defun number->string (n &rest options)
+  "Returns the digits of the fixnum N in the radix given as :radix, or
+*print-base*."
+  (%number->string n (getf options :radix *print-base*)))
+
+(defun format (destination control &rest args)
+  "Formats ARGS as directed by the string CONTROL. DESTINATION nil returns
+the result as a string, t writes it to the standard output and a port
+writes it to the port."
+  (let ((s (apply %format (cons control args))))
+    (if (null? destination)
+        s
+        (progn
+          (%write-string s (if (equal destination t) *standard-output* destination))
+          nil))))
+
+(defun string-map (f string)
+  "Returns the string made of the results of calling F on each character of
+STRING, which like the results are strings of one character."
+  (list->string (map f (string->list string))))
+
+(defun string-for-each (f string)
+  "Calls F on each character of STRING, as a string of one character."
+  (defun iter (chars)
+    (unless (null? chars)
+      (f (car chars))
+      (iter (cdr chars))))
+  (iter (string->list string)))
+
+(defun dump-form (x &rest port)
+  "Prints X as a tree showing types, reference counts and shared structure."
+  (%write-string (%dump-form x) (%output-port port))
+  x)
+
+(defun assoc (key alist)
+  "Returns the first pair in ALIST whose car is equal to KEY, or nil."
+  (if (null? alist)
+      nil
+      (if (equal (caar alist) key)
+          (car alist)
+          (assoc key (cdr alist)))))
+
+(defun room (&rest port)
+  "Prints the numbers of live objects by type, live environments and
+allocations, from gc-stats."
+  (let ((stats (gc-stats))
+        (port (%output-port port)))
+    (format port "~a live objects in ~a environments, ~a allocated~%"
+            (cdr (assoc 'live-objects stats))
+            (cdr (assoc 'environments stats))
+            (cdr (assoc 'allocations stats)))
+    (map (lambda (entry) (format port "  ~12a ~a~%" (car entry) (cdr entry)))
+         (cdr (assoc 'by-type stats)))
+    nil))
+
+;; Each entry is (type parent).
+(define *condition-types*
+  '((condition nil)
+    (error condition)
+    (type-error error)
+    (not-callable type-error)
+    (arity-error error)
+    (unbound-variable error)
+    (permission-error error)
+    (timeout-error error)
+    (user-error error)))
+
+(defmacro define-condition (name parent)
+  "Defines the condition type NAME as a subtype of PARENT."
+  `(progn
+     (set! *condition-types* (cons (list ',name ',parent) *condition-types*))
+     ',name))
+
+(defun condition-subtype? (type parent)
+  "Returns t if the condition type TYPE is PARENT or one of its subtypes."
+  (if (null? type)
+      nil
+      (if (equal type parent)
+          t
+          (condition-subtype? (cadr (assoc type *condition-types*)) parent))))
+
+(defun condition-of-type? (condition type)
+  (condition-subtype? (condition-type condition) type))
+
+(defun condition-field (condition name)
+  (cdr (assoc name (condition-fields condition))))
+
+(defun type-error? (x) (condition-of-type? x 'type-error))
+(defun not-callable? (x) (condition-of-type? x 'not-callable))
+(defun arity-error? (x) (condition-of-type? x 'arity-error))
+(defun unbound-variable? (x) (condition-of-type? x 'unbound-variable))
+(defun user-error? (x) (condition-of-type? x 'user-error))
+(defun timeout-error? (x) (condition-of-type? x 'timeout-error))
+
+(defun type-error-datum (condition) (condition-field condition 'datum))
+(defun type-error-expected-type (condition) (condition-field condition 'expected-type))
+(defun not-callable-form (condition) (condition-field condition 'form))
+(defun arity-error-actual (condition) (condition-field condition 'actual))
+(defun arity-error-name (condition) (condition-field condition 'name))
+(defun unbound-variable-name (condition) (condition-field condition 'name))
+(defun timeout-error-seconds (condition) (condition-field condition 'seconds))
+
+(defun error (message &rest irritants)
+  "Signals a user-error with MESSAGE and IRRITANTS, and the current backtrace.
+If MESSAGE names a condition type, the condition is of that type and the
+message is the first of IRRITANTS."
+  ;; The first frame is this call to error.
+  (let ((frames (cdr (backtrace)))
+        (type 'user-error))
+    (when (assoc message *condition-types*)
+      (set! type message)
+      (set! message (car irritants))
+      (set! irritants (cdr irritants)))
+    (signal (%make-condition type message irritants nil frames))))
+
+(defmacro handler-case (form &rest clauses)
+  "Evaluates FORM. If it signals a condition, the first clause
+(type (var) body...) whose type it is of runs body with var bound to the
+condition, and its value is returned. Otherwise the condition is signalled
+again."
+  (with-gensyms (result condition)
+    `(let ((,result (%try (lambda () ,form))))
+       (if (car ,result)
+           (cdr ,result)
+           (let ((,condition (cdr ,result)))
+             ,(reduce (lambda (rest clause)
+                        `(if (condition-of-type? ,condition ',(car clause))
+                             ((lambda ,(if (cadr clause) (cadr clause) (list (gensym)))
+                                ,@(cddr clause))
+                              ,condition)
+                             ,rest))
+                      `(signal ,condition)
+                      (reverse clauses)))))))
+
+;; A pattern compiles to the tests that value must pass, in order, and the
+;; bindings of its variables, both in terms of ACCESS, the form that gets
+;; the part of the value being matched.
+(defun %match-pattern (pattern access)
+  ;; The tests and bindings of PATTERNS matched against the bits of ACCESS
+  ;; from I on.
+  (defun match-bits (patterns i)
+    (if (null? patterns)
+        (list nil nil)
+        (let ((x (%match-pattern (car patterns) `(bit-ref ,access ,i)))
+              (rest (match-bits (cdr patterns) (+ i 1))))
+          (list (append (car x) (car rest))
+                (append (cadr x) (cadr rest))))))
+  (if (equal pattern '_)
+      (list nil nil)
+      (if (and (symbol? pattern)
+               (not (keyword? pattern))
+               (not (member pattern '(nil t))))
+          (list nil (list (list pattern access)))
+          (if (atom? pattern)
+              (list (list `(equal ,access ',pattern)) nil)
+              (if (equal (car pattern) 'quote)
+                  (list (list `(equal ,access ,pattern)) nil)
+                  (if (equal (car pattern) 'cons)
+                      (let ((x (%match-pattern (cadr pattern) `(car ,access)))
+                            (y (%match-pattern (car (cddr pattern)) `(cdr ,access))))
+                        (list (cons `(not (atom? ,access))
+                                    (append (car x) (car y)))
+                              (append (cadr x) (cadr y))))
+                      (if (equal (car pattern) 'list)
+                          (%match-pattern (reduce (lambda (rest p) (list 'cons p rest))
+                                                  nil
+                                                  (reverse (cdr pattern)))
+                                          access)
+                          (if (equal (car pattern) 'bit-vector)
+                              (let ((bits (match-bits (cdr pattern) 0)))
+                                (list (append (list `(bit-vector? ,access)
+                                                    `(equal (bit-vector-length ,access)
+                                                            ,(length (cdr pattern))))
+                                              (car bits))
+                                      (cadr bits)))
+                              (error "Malformed match pattern" pattern)))))))))
+
+(defun %match-clause (value clause fail)
+  (let ((compiled (%match-pattern (car clause) value))
+        (guard nil)
+        (body (cdr clause)))
+    (when (and body (equal (car body) :when))
+      (set! guard (cadr body))
+      (set! body (cddr body)))
+    `(if (and ,@(car compiled))
+         (let ,(cadr compiled)
+           ,(if guard
+                `(if ,guard (progn ,@body) ,fail)
+                `(progn ,@body)))
+         ,fail)))
+
+;; Each clause after the first is wrapped in a thunk that the previous one
+;; calls when it doesn't match, so that no clause is expanded twice.
+(defun %match-clauses (value clauses)
+  (if (null? clauses)
+      `(error "No match clause matched" ,value)
+      (with-gensyms (fail)
+        `(let ((,fail (lambda () ,(%match-clauses value (cdr clauses)))))
+           ,(%match-clause value (car clauses) (list fail))))))
+
+(defmacro match (expr &rest clauses)
+  "Evaluates EXPR and runs the body of the first clause (pattern body...)
+whose pattern matches its value, with the pattern's variables bound to the
+matching parts. A clause (pattern :when guard body...) also requires GUARD
+to be true. Patterns are _, which matches anything, a variable, a literal
+or quoted datum compared with equal, (cons car-pattern cdr-pattern),
+(list pattern...) and (bit-vector pattern...), which matches a bit vector of
+that many bits. It's an error if no clause matches."
+  (with-gensyms (value)
+    `(let ((,value ,expr))
+       ,(%match-clauses value clauses))))
+
+(define *tests* nil)
+
+(defun %add-test (name thunk)
+  (set! *tests*
+        (append (filter (lambda (test) (not (equal (car test) name))) *tests*)
+                (list (cons name thunk))))
+  name)
+
+(defmacro deftest (name &rest body)
+  "Defines a test named NAME that run-tests runs. Redefining a test replaces it."
+  `(%add-test ',name (lambda () ,@body)))
+
+(defun run-tests ()
+  "Runs every test defined with deftest and returns the names of those that failed."
+  (let ((failed nil))
+    (map (lambda (test)
+           (let ((result (%try (cdr test))))
+             (unless (car result)
+               (set! failed (append failed (list (car test))))
+               (display "FAIL ")
+               (display (car test))
+               (display ": ")
+               (display (condition-message (cdr result)))
+               (map (lambda (x) (display " ") (write x))
+                    (condition-irritants (cdr result)))
+               (newline))))
+         *tests*)
+    (display (length *tests*))
+    (display " tests, ")
+    (display (length failed))
+    (display " failures")
+    (newline)
+    failed))
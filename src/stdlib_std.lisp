@@ -0,0 +1,22 @@
+;;;; stdlib_std.lisp --- evaluated into every global environment when built with std
+
+(defmacro with-output-to-file (path &rest body)
+  "Evaluates BODY with *standard-output* writing to the file PATH, which is
+closed afterwards even if BODY fails, and returns the value of BODY."
+  (with-gensyms (out)
+    `(with-open-file (,out ,path :direction :output)
+       (parameterize ((*standard-output* ,out))
+         ,@body))))
+
+(defun process-status (process) (cdr (assoc 'status process)))
+(defun process-output (process) (cdr (assoc 'stdout process)))
+(defun process-error-output (process) (cdr (assoc 'stderr process)))
+
+(defun dump-graph (x &rest path)
+  "Returns the graph of the objects reachable from X in Graphviz's dot
+language, or writes it to the file PATH and returns PATH."
+  (if path
+      (with-open-file (out (car path) :direction :output)
+        (write-string (%dump-graph x) out)
+        (car path))
+      (%dump-graph x)))
@@ -0,0 +1,14 @@
+;;;; stdlib_sync.lisp --- evaluated into every global environment when built with sync
+
+(defmacro define-generator (name params &rest body)
+  "Defines NAME as a function returning a generator that runs BODY, which
+hands out values with yield."
+  `(defun ,name ,params (make-generator (lambda () ,@body))))
+
+(defun generator->list (generator)
+  "Returns the values GENERATOR yields until it's done."
+  (let ((eof (gensym)))
+    (let ((x (generator-next generator eof)))
+      (if (equal x eof)
+          nil
+          (cons x (generator->list generator))))))
@@ -0,0 +1,79 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::interpreter::Interpreter;
+use super::port::Port;
+
+// A test file is evaluated in a fresh interpreter and passes when what it
+// printed matches the file next to it with the `.out` extension. An error
+// stops the file and is printed as `error: message`.
+pub struct TestResult {
+    pub path: PathBuf,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl TestResult {
+    pub fn passed(&self) -> bool {
+        self.expected == self.actual
+    }
+}
+
+pub fn run_test_file(path: impl AsRef<Path>) -> io::Result<TestResult> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path)?;
+    let expected = fs::read_to_string(path.with_extension("out"))?;
+
+    let interpreter = Interpreter::new();
+    let output = interpreter.set_output_port(Port::output_string());
+    let result = interpreter.eval_str(&source);
+    let mut actual = output.as_port().and_then(Port::output).unwrap_or_default();
+    if let Err(e) = result {
+        actual.push_str(&format!("error: {}\n", e));
+    }
+    Ok(TestResult {
+        path: path.to_path_buf(),
+        expected,
+        actual,
+    })
+}
+
+// Runs every `.lisp` file in `dir`, in name order.
+pub fn run_test_dir(dir: impl AsRef<Path>) -> io::Result<Vec<TestResult>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "lisp") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    paths.iter().map(run_test_file).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_test_file_test() {
+        let dir = std::env::temp_dir().join(format!("lisp-suite-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.lisp"), "(print 1) (car 1)").unwrap();
+        fs::write(
+            dir.join("a.out"),
+            "1\nerror: The value 1 is not of type Cons (in (car 1))\n",
+        )
+        .unwrap();
+        fs::write(dir.join("b.lisp"), "(print 2)").unwrap();
+        fs::write(dir.join("b.out"), "3\n").unwrap();
+
+        let results = run_test_dir(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].passed());
+        assert!(!results[1].passed());
+        assert_eq!(results[1].actual, "2\n");
+    }
+}
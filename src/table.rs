@@ -0,0 +1,345 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::collections::Map;
+use super::env::Env;
+use super::equal;
+use super::error::RuntimeError;
+use super::eval::{check_num_args, check_num_args_range, EvalResult};
+use super::object::{self, Object, ObjectKind, ObjectType};
+use super::rc::{Rc, RefCell};
+
+// Keys are compared with equal and kept in the order they were added, so a
+// table prints, and its keys come out, the same way every time. #{k v ...}
+// and #s(x ...) read as a table and a set.
+#[derive(Default)]
+pub struct HashTable {
+    entries: RefCell<Vec<(Object, Object)>>,
+    // Positions in entries by the hash of their key.
+    index: RefCell<Map<u64, Vec<usize>>>,
+}
+
+impl HashTable {
+    fn position(&self, key: &Object, hash: u64) -> Option<usize> {
+        let entries = self.entries.borrow();
+        self.index
+            .borrow()
+            .get(&hash)?
+            .iter()
+            .copied()
+            .find(|&i| equal::equal(Rc::clone(&entries[i].0), Rc::clone(key)))
+    }
+
+    pub fn get(&self, key: &Object) -> Option<Object> {
+        let i = self.position(key, equal::hash(key))?;
+        Some(Rc::clone(&self.entries.borrow()[i].1))
+    }
+
+    // Returns the value the key had. The key itself stays the one first
+    // added.
+    pub fn insert(&self, key: Object, value: Object) -> Option<Object> {
+        let hash = equal::hash(&key);
+        if let Some(i) = self.position(&key, hash) {
+            let old = core::mem::replace(&mut self.entries.borrow_mut()[i].1, value);
+            return Some(old);
+        }
+        let mut entries = self.entries.borrow_mut();
+        self.index
+            .borrow_mut()
+            .entry(hash)
+            .or_default()
+            .push(entries.len());
+        entries.push((key, value));
+        None
+    }
+
+    pub fn remove(&self, key: &Object) -> Option<Object> {
+        let hash = equal::hash(key);
+        let i = self.position(key, hash)?;
+        let (_, value) = self.entries.borrow_mut().remove(i);
+        let mut index = self.index.borrow_mut();
+        for positions in index.values_mut() {
+            positions.retain(|&j| j != i);
+            for j in positions.iter_mut().filter(|j| **j > i) {
+                *j -= 1;
+            }
+        }
+        index.retain(|_, positions| !positions.is_empty());
+        Some(value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn entries(&self) -> Vec<(Object, Object)> {
+        self.entries.borrow().clone()
+    }
+
+    pub fn from_entries(entries: impl IntoIterator<Item = (Object, Object)>) -> Self {
+        let table = Self::default();
+        for (key, value) in entries {
+            table.insert(key, value);
+        }
+        table
+    }
+
+    // Equal when both have the same keys with equal values, in any order.
+    pub fn equal(&self, other: &HashTable) -> bool {
+        self.len() == other.len()
+            && self.entries().iter().all(|(key, value)| {
+                other
+                    .get(key)
+                    .is_some_and(|x| equal::equal(x, Rc::clone(value)))
+            })
+    }
+
+    // Independent of the order of the entries, as equal is.
+    pub fn hash(&self) -> u64 {
+        self.entries()
+            .iter()
+            .fold(self.len() as u64, |h, (key, value)| {
+                h.wrapping_add(equal::hash(key) ^ equal::hash(value).rotate_left(1))
+            })
+    }
+}
+
+impl fmt::Display for HashTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{{")?;
+        for (i, (key, value)) in self.entries().iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{} {}", key, value)?;
+        }
+        write!(f, "}}")
+    }
+}
+
+#[derive(Default)]
+pub struct Set(HashTable);
+
+impl Set {
+    // Whether x wasn't there already.
+    pub fn insert(&self, x: Object) -> bool {
+        self.0.insert(Rc::clone(&x), x).is_none()
+    }
+
+    pub fn remove(&self, x: &Object) -> bool {
+        self.0.remove(x).is_some()
+    }
+
+    pub fn contains(&self, x: &Object) -> bool {
+        self.0.get(x).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn elements(&self) -> Vec<Object> {
+        self.0.entries().into_iter().map(|(x, _)| x).collect()
+    }
+
+    pub fn from_elements(elements: impl IntoIterator<Item = Object>) -> Self {
+        let set = Self::default();
+        for x in elements {
+            set.insert(x);
+        }
+        set
+    }
+
+    pub fn equal(&self, other: &Set) -> bool {
+        self.0.equal(&other.0)
+    }
+
+    pub fn hash(&self) -> u64 {
+        self.0.hash()
+    }
+}
+
+impl fmt::Display for Set {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#s(")?;
+        for (i, x) in self.elements().iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", x)?;
+        }
+        write!(f, ")")
+    }
+}
+
+pub fn init(env: &mut Env) {
+    env.define_builtin("make-hash-table", "", make_hash_table);
+    env.define_builtin("hash-table?", "x", is_hash_table);
+    env.define_builtin("hash-table-get", "table key &rest default", hash_table_get);
+    env.define_builtin("hash-table-put!", "table key value", hash_table_put);
+    env.define_builtin("hash-table-remove!", "table key", hash_table_remove);
+    env.define_builtin("hash-table-count", "table", hash_table_count);
+    env.define_builtin("hash-table-keys", "table", hash_table_keys);
+    env.define_builtin("hash-table->alist", "table", hash_table_to_alist);
+    env.define_builtin("make-set", "&rest elements", make_set);
+    env.define_builtin("set?", "x", is_set);
+    env.define_builtin("set-add!", "set x", set_add);
+    env.define_builtin("set-remove!", "set x", set_remove);
+    env.define_builtin("set-member?", "set x", is_set_member);
+    env.define_builtin("set-count", "set", set_count);
+    env.define_builtin("set->list", "set", set_to_list);
+}
+
+fn hash_table(x: &Object) -> Result<&HashTable, RuntimeError> {
+    match &**x {
+        ObjectKind::HashTable(table) => Ok(table),
+        _ => Err(RuntimeError::MismatchType(
+            Rc::clone(x),
+            ObjectType::HashTable,
+        )),
+    }
+}
+
+fn set(x: &Object) -> Result<&Set, RuntimeError> {
+    match &**x {
+        ObjectKind::Set(set) => Ok(set),
+        _ => Err(RuntimeError::MismatchType(Rc::clone(x), ObjectType::Set)),
+    }
+}
+
+fn boolean(b: bool) -> Object {
+    if b {
+        object::symbol("t")
+    } else {
+        object::nil()
+    }
+}
+
+fn make_hash_table(args: &[Object]) -> EvalResult {
+    check_num_args(args, 0)?;
+    Ok(object::hash_table(HashTable::default()))
+}
+
+fn is_hash_table(args: &[Object]) -> EvalResult {
+    check_num_args(args, 1)?;
+    Ok(boolean(ObjectType::HashTable.contains(&args[0])))
+}
+
+fn hash_table_get(args: &[Object]) -> EvalResult {
+    check_num_args_range(args, 2, 3)?;
+    match hash_table(&args[0])?.get(&args[1]) {
+        Some(value) => Ok(value),
+        None => Ok(args.get(2).cloned().unwrap_or_else(object::nil)),
+    }
+}
+
+fn hash_table_put(args: &[Object]) -> EvalResult {
+    check_num_args(args, 3)?;
+    hash_table(&args[0])?.insert(Rc::clone(&args[1]), Rc::clone(&args[2]));
+    Ok(Rc::clone(&args[2]))
+}
+
+fn hash_table_remove(args: &[Object]) -> EvalResult {
+    check_num_args(args, 2)?;
+    Ok(hash_table(&args[0])?
+        .remove(&args[1])
+        .unwrap_or_else(object::nil))
+}
+
+fn hash_table_count(args: &[Object]) -> EvalResult {
+    check_num_args(args, 1)?;
+    Ok(object::fixnum(hash_table(&args[0])?.len() as isize))
+}
+
+fn hash_table_keys(args: &[Object]) -> EvalResult {
+    check_num_args(args, 1)?;
+    let entries = hash_table(&args[0])?.entries();
+    Ok(object::from_iter(entries.into_iter().map(|(key, _)| key)))
+}
+
+fn hash_table_to_alist(args: &[Object]) -> EvalResult {
+    check_num_args(args, 1)?;
+    let entries = hash_table(&args[0])?.entries();
+    Ok(object::from_iter(
+        entries
+            .into_iter()
+            .map(|(key, value)| object::cons(key, value)),
+    ))
+}
+
+fn make_set(args: &[Object]) -> EvalResult {
+    Ok(object::set(Set::from_elements(args.iter().cloned())))
+}
+
+fn is_set(args: &[Object]) -> EvalResult {
+    check_num_args(args, 1)?;
+    Ok(boolean(ObjectType::Set.contains(&args[0])))
+}
+
+fn set_add(args: &[Object]) -> EvalResult {
+    check_num_args(args, 2)?;
+    set(&args[0])?.insert(Rc::clone(&args[1]));
+    Ok(Rc::clone(&args[1]))
+}
+
+// Whether x was in the set.
+fn set_remove(args: &[Object]) -> EvalResult {
+    check_num_args(args, 2)?;
+    Ok(boolean(set(&args[0])?.remove(&args[1])))
+}
+
+fn is_set_member(args: &[Object]) -> EvalResult {
+    check_num_args(args, 2)?;
+    Ok(boolean(set(&args[0])?.contains(&args[1])))
+}
+
+fn set_count(args: &[Object]) -> EvalResult {
+    check_num_args(args, 1)?;
+    Ok(object::fixnum(set(&args[0])?.len() as isize))
+}
+
+fn set_to_list(args: &[Object]) -> EvalResult {
+    check_num_args(args, 1)?;
+    Ok(object::from_iter(set(&args[0])?.elements()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use object::{fixnum, string, symbol};
+
+    #[test]
+    fn hash_table_test() {
+        let table = HashTable::default();
+        assert_eq!(table.insert(string("a"), fixnum(1)), None);
+        assert_eq!(table.insert(symbol("b"), fixnum(2)), None);
+        assert_eq!(table.insert(string("a"), fixnum(3)), Some(fixnum(1)));
+        assert_eq!(table.get(&string("a")), Some(fixnum(3)));
+        assert_eq!(table.to_string(), "#{\"a\" 3 b 2}");
+        assert_eq!(table.remove(&string("a")), Some(fixnum(3)));
+        assert_eq!(table.get(&symbol("b")), Some(fixnum(2)));
+        assert_eq!(table.len(), 1);
+
+        let other = HashTable::from_entries([(symbol("b"), fixnum(2))]);
+        assert!(table.equal(&other));
+        assert_eq!(table.hash(), other.hash());
+    }
+
+    #[test]
+    fn set_test() {
+        let set = Set::from_elements([fixnum(1), fixnum(2), fixnum(1)]);
+        assert_eq!(set.len(), 2);
+        assert!(set.contains(&fixnum(2)));
+        assert!(!set.insert(fixnum(2)));
+        assert!(set.remove(&fixnum(1)));
+        assert_eq!(set.to_string(), "#s(2)");
+    }
+}
@@ -0,0 +1,127 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+use std::thread;
+use std::time::Instant;
+
+use super::error::RuntimeError;
+use super::eval::EvalResult;
+
+// The deadlines of the with-timeout forms being evaluated on this thread,
+// innermost last. Each is checked at every evaluation step, and the first
+// step past it fails with Timeout, which unwinds like any other error except
+// that no %try inside the with-timeout stops it.
+// A coroutine is resumed with the deadlines of its owner, which share
+// whether they've expired, so a timeout fails on one thread only.
+#[derive(Clone)]
+pub struct Deadline {
+    at: Instant,
+    seconds: isize,
+    expired: Arc<AtomicBool>,
+}
+
+std::thread_local! {
+    static DEADLINES: core::cell::RefCell<Vec<Deadline>> =
+        const { core::cell::RefCell::new(Vec::new()) };
+    // How many of the deadlines came from whoever resumed this thread.
+    static OUTER: core::cell::Cell<usize> = const { core::cell::Cell::new(0) };
+}
+
+// The deadlines in force on this thread, to resume a coroutine with.
+pub fn deadlines() -> Vec<Deadline> {
+    DEADLINES.with(|deadlines| deadlines.borrow().clone())
+}
+
+// Puts outer in place of the deadlines this thread was last resumed with,
+// under those of the with-timeout forms it's evaluating itself.
+pub fn resume_with(outer: Vec<Deadline>) {
+    let n = OUTER.with(|n| n.replace(outer.len()));
+    DEADLINES.with(|deadlines| {
+        deadlines.borrow_mut().splice(0..n, outer);
+    });
+}
+
+pub fn with_timeout(seconds: isize, f: impl FnOnce() -> EvalResult) -> EvalResult {
+    let deadline = Deadline {
+        at: Instant::now() + Duration::from_secs(seconds.max(0) as u64),
+        seconds,
+        expired: Arc::new(AtomicBool::new(false)),
+    };
+    DEADLINES.with(|deadlines| deadlines.borrow_mut().push(deadline));
+    let result = f();
+    DEADLINES.with(|deadlines| deadlines.borrow_mut().pop());
+    result
+}
+
+// Called for each evaluation step. A deadline only fails one step, so that
+// handlers and cleanup forms can run while the timeout unwinds.
+pub fn check() -> Result<(), RuntimeError> {
+    DEADLINES.with(|deadlines| {
+        let deadlines = deadlines.borrow();
+        if deadlines.is_empty() {
+            return Ok(());
+        }
+        let now = Instant::now();
+        // The outermost one goes first, since it unwinds the inner ones too.
+        match deadlines
+            .iter()
+            .find(|d| d.at <= now && !d.expired.swap(true, Ordering::Relaxed))
+        {
+            Some(deadline) => Err(RuntimeError::Timeout(deadline.seconds)),
+            None => Ok(()),
+        }
+    })
+}
+
+// Whether a with-timeout being evaluated has run out, so that its timeout
+// is still unwinding and %try lets it through.
+pub fn expired() -> bool {
+    DEADLINES.with(|deadlines| {
+        deadlines
+            .borrow()
+            .iter()
+            .any(|d| d.expired.load(Ordering::Relaxed))
+    })
+}
+
+pub fn next_deadline() -> Option<Instant> {
+    DEADLINES.with(|deadlines| {
+        deadlines
+            .borrow()
+            .iter()
+            .filter(|d| !d.expired.load(Ordering::Relaxed))
+            .map(|d| d.at)
+            .min()
+    })
+}
+
+// Waits for duration, or until a with-timeout around it runs out.
+pub fn sleep(duration: Duration) -> Result<(), RuntimeError> {
+    let until = Instant::now() + duration;
+    loop {
+        check()?;
+        let now = Instant::now();
+        if now >= until {
+            return Ok(());
+        }
+        let wake = next_deadline().map_or(until, |deadline| deadline.min(until));
+        thread::sleep(wake.saturating_duration_since(Instant::now()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object;
+
+    #[test]
+    fn timeout_test() {
+        let result = with_timeout(0, || {
+            sleep(Duration::from_secs(10))?;
+            Ok(object::nil())
+        });
+        assert!(matches!(result, Err(RuntimeError::Timeout(0))));
+        assert!(check().is_ok());
+    }
+}
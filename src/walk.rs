@@ -0,0 +1,149 @@
+use super::object::{Object, ObjectKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quote {
+    Quote,
+    Quasiquote,
+    Unquote,
+    UnquoteSplicing,
+}
+
+impl Quote {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "quote" => Some(Quote::Quote),
+            "quasiquote" => Some(Quote::Quasiquote),
+            "unquote" => Some(Quote::Unquote),
+            "unquote-splicing" => Some(Quote::UnquoteSplicing),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Quote::Quote => "quote",
+            Quote::Quasiquote => "quasiquote",
+            Quote::Unquote => "unquote",
+            Quote::UnquoteSplicing => "unquote-splicing",
+        }
+    }
+}
+
+// Callbacks for Walk::walk, which all do nothing by default. An enter
+// returning false skips what the form contains, and its leave isn't called.
+pub trait Visitor {
+    // A list, visited once before its elements rather than once per cons.
+    fn enter_cons(&mut self, _list: &Object) -> bool {
+        true
+    }
+
+    fn leave_cons(&mut self, _list: &Object) {}
+
+    // (quote x) and the like with a single argument; form is the whole form
+    // and x is walked next.
+    fn enter_quote(&mut self, _quote: Quote, _form: &Object) -> bool {
+        true
+    }
+
+    fn leave_quote(&mut self, _quote: Quote, _form: &Object) {}
+
+    // Anything but a cons, including nil and vectors, which aren't entered.
+    fn visit_atom(&mut self, _atom: &Object) {}
+
+    // The final cdr of a dotted list, after its elements.
+    fn visit_tail(&mut self, tail: &Object) {
+        self.visit_atom(tail);
+    }
+}
+
+pub trait Walk {
+    fn walk<V: Visitor + ?Sized>(&self, visitor: &mut V);
+}
+
+impl Walk for Object {
+    fn walk<V: Visitor + ?Sized>(&self, visitor: &mut V) {
+        let cons = match &**self {
+            ObjectKind::Cons(cons) => cons,
+            _ => return visitor.visit_atom(self),
+        };
+        if let (ObjectKind::Symbol(name), ObjectKind::Cons(rest)) = (&*cons.car, &*cons.cdr) {
+            if let (Some(quote), ObjectKind::Nil) = (Quote::from_name(name), &*rest.cdr) {
+                if visitor.enter_quote(quote, self) {
+                    rest.car.walk(visitor);
+                    visitor.leave_quote(quote, self);
+                }
+                return;
+            }
+        }
+        if !visitor.enter_cons(self) {
+            return;
+        }
+        let mut iter = cons.iter();
+        for x in iter.by_ref() {
+            x.walk(visitor);
+        }
+        match iter.tail() {
+            Some(tail) if !matches!(&**tail, ObjectKind::Nil) => visitor.visit_tail(tail),
+            _ => (),
+        }
+        visitor.leave_cons(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::read_from_string;
+    use alloc::format;
+    use alloc::string::String;
+
+    // Writes each callback as it's made.
+    struct Trace(String);
+
+    impl Visitor for Trace {
+        fn enter_cons(&mut self, _list: &Object) -> bool {
+            self.0 += "(";
+            true
+        }
+
+        fn leave_cons(&mut self, _list: &Object) {
+            self.0 += ")";
+        }
+
+        fn enter_quote(&mut self, quote: Quote, _form: &Object) -> bool {
+            self.0 += &format!("<{}", quote.name());
+            quote != Quote::Quote
+        }
+
+        fn leave_quote(&mut self, _quote: Quote, _form: &Object) {
+            self.0 += ">";
+        }
+
+        fn visit_atom(&mut self, atom: &Object) {
+            self.0 += &format!(" {}", atom);
+        }
+
+        fn visit_tail(&mut self, tail: &Object) {
+            self.0 += &format!(" . {}", tail);
+        }
+    }
+
+    fn trace(source: &str) -> String {
+        let mut trace = Trace(String::new());
+        read_from_string(source).unwrap().0.walk(&mut trace);
+        trace.0
+    }
+
+    #[test]
+    fn walk_test() {
+        assert_eq!(trace("a"), " a");
+        assert_eq!(trace("(a (b . c) ())"), "( a( b . c) nil)");
+        assert_eq!(
+            trace("(f '(x y) `(g ,z ,@w))"),
+            "( f<quote<quasiquote( g<unquote z><unquote-splicing w>)>)"
+        );
+        // Only quoting forms with a single argument are quotes.
+        assert_eq!(trace("(quote a b)"), "( quote a b)");
+        assert_eq!(trace("(quote . a)"), "( quote . a)");
+    }
+}
@@ -0,0 +1,605 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::collections::{Map, Set};
+use super::env::Env;
+use super::error::plural;
+use super::eval::{self, LoopClause, SPECIAL_FORMS};
+use super::object::{self, Object, ObjectKind};
+use super::rc::{Rc, RefCell};
+
+// Warnings are collected in the global environment; the REPL prints them
+// and embedders take them with Interpreter::take_warnings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    // The variable and the function referring to it, if it has a name.
+    UndefinedVariable(String, Option<String>),
+    // The builtin, the number of arguments given and its arity.
+    WrongNumArgs(String, usize, (usize, Option<usize>)),
+    ShadowsSpecialForm(String),
+    // A global defined over a builtin.
+    ShadowsBuiltin(String),
+    // A function or macro redefined with another arity, the old one first.
+    ArityChanged(String, (usize, Option<usize>), (usize, Option<usize>)),
+    // Only reported by lint, with the function they're in like
+    // UndefinedVariable.
+    UnusedVariable(String, Option<String>),
+    ConstantCondition(String, Option<String>),
+    // The error of a finalizer that ran after an evaluation.
+    FinalizerFailed(String),
+}
+
+fn write_function(f: &mut fmt::Formatter<'_>, function: &Option<String>) -> fmt::Result {
+    match function {
+        Some(function) => write!(f, " (in {})", function),
+        None => Ok(()),
+    }
+}
+
+fn write_arity(f: &mut fmt::Formatter<'_>, (min, max): (usize, Option<usize>)) -> fmt::Result {
+    match max {
+        Some(max) if max == min => write!(f, "{}", min),
+        Some(max) => write!(f, "{} to {}", min, max),
+        None => write!(f, "at least {}", min),
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use self::Warning::*;
+        match self {
+            UndefinedVariable(name, function) => {
+                write!(f, "Undefined variable: {}", name)?;
+                write_function(f, function)
+            }
+            WrongNumArgs(name, actual, arity) => {
+                write!(
+                    f,
+                    "{} called with {} argument{}, but takes ",
+                    name,
+                    actual,
+                    plural(*actual)
+                )?;
+                write_arity(f, *arity)
+            }
+            ShadowsSpecialForm(name) => write!(f, "{} shadows a special form", name),
+            ShadowsBuiltin(name) => write!(f, "{} shadows a builtin", name),
+            ArityChanged(name, old, new) => {
+                write!(f, "{} redefined to take ", name)?;
+                write_arity(f, *new)?;
+                let n = match new {
+                    (min, Some(max)) if min != max => *max,
+                    (min, _) => *min,
+                };
+                write!(f, " argument{} instead of ", plural(n))?;
+                write_arity(f, *old)
+            }
+            UnusedVariable(name, function) => {
+                write!(f, "Unused variable: {}", name)?;
+                write_function(f, function)
+            }
+            ConstantCondition(test, function) => {
+                write!(f, "Constant condition: {}", test)?;
+                write_function(f, function)
+            }
+            FinalizerFailed(error) => write!(f, "Finalizer failed: {}", error),
+        }
+    }
+}
+
+pub fn is_special_form(name: &str) -> bool {
+    SPECIAL_FORMS.contains(&name)
+}
+
+// Walks the body of a lambda created in the global environment, where
+// anything that isn't bound yet and isn't bound by the body itself is
+// probably a mistake. Macros are expanded to see what they bind.
+pub fn check_lambda(
+    name: Option<&str>,
+    params: &[String],
+    rest: &Option<String>,
+    body: &[Object],
+    env: &Rc<RefCell<Env>>,
+) -> Vec<Warning> {
+    let mut checker = Checker::new(env, name.map(ToString::to_string));
+    // The function may call itself before the definition is complete.
+    if let Some(name) = name {
+        checker.scope.push((name.to_string(), true));
+    }
+    checker.lambda(params.iter().chain(rest.iter()).cloned().collect(), body);
+    checker.warnings
+}
+
+// Checks forms read from a file without evaluating them. Besides what
+// check_lambda warns about, unused parameters and let bindings are reported,
+// as are ifs whose condition is a constant, and calls to the file's own
+// functions are checked against their lambda lists. Bindings starting with
+// _ may go unused.
+pub fn lint(forms: &[Object]) -> Vec<Warning> {
+    let env = Env::global_env();
+    let mut checker = Checker::new(&env, None);
+    checker.lint = true;
+    for form in forms {
+        checker.declare(form);
+    }
+    checker.forms(forms);
+    checker.warnings
+}
+
+struct Checker<'a> {
+    env: &'a Rc<RefCell<Env>>,
+    function: Option<String>,
+    // Each local variable and whether it has been referred to.
+    scope: Vec<(String, bool)>,
+    warnings: Vec<Warning>,
+    lint: bool,
+    // How many lambdas the form being checked is in.
+    lambdas: usize,
+    // What the forms given to lint define, with the arity of functions.
+    globals: Map<String, Option<(usize, Option<usize>)>>,
+    macros: Set<String>,
+    specials: Set<String>,
+}
+
+fn lambda_arity(list: &Object) -> Option<(usize, Option<usize>)> {
+    let (params, rest) = eval::parse_lambda_list(Rc::clone(list)).ok()?;
+    Some(object::arity(&params, &rest))
+}
+
+// The lambda list of (lambda list ...).
+fn lambda_list(x: &Object) -> Option<&Object> {
+    match &**x {
+        ObjectKind::Cons(cons) => match (&*cons.car, &*cons.cdr) {
+            (ObjectKind::Symbol(s), ObjectKind::Cons(rest)) if s == "lambda" => Some(&rest.car),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// The name (define name ...) or (defun name ...) defines.
+fn definition(x: &Object) -> Option<String> {
+    let list = x.as_proper_list()?;
+    let args = match list.split_first()? {
+        (head, args) if matches!(&**head, ObjectKind::Symbol(s) if s == "defun") => args.to_vec(),
+        (head, args) if matches!(&**head, ObjectKind::Symbol(s) if s == "define") => {
+            match eval::define_shorthand(args) {
+                Some((var, _)) => alloc::vec![var],
+                None => args.to_vec(),
+            }
+        }
+        _ => return None,
+    };
+    match &**args.first()? {
+        ObjectKind::Symbol(name) => Some(name.clone()),
+        _ => None,
+    }
+}
+
+fn is_constant(x: &Object) -> bool {
+    match &**x {
+        ObjectKind::Symbol(name) => name == "t" || name.starts_with(':'),
+        ObjectKind::Cons(cons) => matches!(&*cons.car, ObjectKind::Symbol(s) if s == "quote"),
+        _ => true,
+    }
+}
+
+impl<'a> Checker<'a> {
+    fn new(env: &'a Rc<RefCell<Env>>, function: Option<String>) -> Self {
+        Checker {
+            env,
+            function,
+            scope: Vec::new(),
+            warnings: Vec::new(),
+            lint: false,
+            lambdas: 0,
+            globals: Map::new(),
+            macros: Set::new(),
+            specials: Set::new(),
+        }
+    }
+
+    // Records what a top-level form defines, so that the forms before the
+    // definition may refer to it.
+    fn declare(&mut self, form: &Object) {
+        let list = form.as_proper_list().unwrap_or_default();
+        let (head, args) = match list.split_first() {
+            Some((head, args)) => (head, args),
+            None => return,
+        };
+        let head = match &**head {
+            ObjectKind::Symbol(head) if head.starts_with("def") => head.as_str(),
+            _ => return,
+        };
+        let args = match eval::define_shorthand(args) {
+            Some((var, value)) if head == "define" => alloc::vec![var, value],
+            _ => args.to_vec(),
+        };
+        let name = match args.first().map(|name| &**name) {
+            Some(ObjectKind::Symbol(name)) => name.clone(),
+            _ => return,
+        };
+        let arity = match (head, args.get(1)) {
+            ("defmacro", _) => {
+                self.macros.insert(name);
+                return;
+            }
+            ("defvar" | "defparameter", _) => {
+                self.specials.insert(name.clone());
+                None
+            }
+            ("defun", Some(list)) => lambda_arity(list),
+            ("define", Some(value)) => lambda_list(value).and_then(lambda_arity),
+            _ => None,
+        };
+        self.globals.insert(name, arity);
+    }
+
+    fn is_local(&self, name: &str) -> bool {
+        self.scope.iter().any(|(s, _)| s == name)
+    }
+
+    // Whether name is bound, marking the innermost local of that name used.
+    fn refer(&mut self, name: &str) -> bool {
+        if let Some((_, used)) = self.scope.iter_mut().rev().find(|(s, _)| s == name) {
+            *used = true;
+            return true;
+        }
+        name.starts_with(':')
+            || self.globals.contains_key(name)
+            || self.macros.contains(name)
+            || self.env.borrow().get(name).is_some()
+    }
+
+    fn bind(&mut self, name: &str) {
+        if is_special_form(name) {
+            self.warnings
+                .push(Warning::ShadowsSpecialForm(name.to_string()));
+        }
+        self.scope.push((name.to_string(), false));
+    }
+
+    fn lambda(&mut self, params: Vec<String>, body: &[Object]) {
+        let depth = self.scope.len();
+        for param in params.iter() {
+            self.bind(param);
+        }
+        // The definitions at the start of a body may refer to each other.
+        for form in body {
+            match definition(form) {
+                Some(name) => self.scope.push((name, true)),
+                None if matches!(&**form, ObjectKind::String(_)) => (),
+                None => break,
+            }
+        }
+        self.lambdas += 1;
+        for form in body {
+            self.form(form);
+        }
+        self.lambdas -= 1;
+        if self.lint {
+            for (name, used) in &self.scope[depth..depth + params.len()] {
+                // Gensyms are bound by macros, which know what they're doing.
+                if !used
+                    && !name.starts_with('_')
+                    && !name.starts_with("#:")
+                    && !self.specials.contains(name)
+                    && !self.env.borrow().is_special(name)
+                {
+                    self.warnings
+                        .push(Warning::UnusedVariable(name.clone(), self.function.clone()));
+                }
+            }
+        }
+        self.scope.truncate(depth);
+    }
+
+    fn check_arity(&mut self, name: &str, actual: usize, (min, max): (usize, Option<usize>)) {
+        if actual < min || max.is_some_and(|max| actual > max) {
+            self.warnings
+                .push(Warning::WrongNumArgs(name.to_string(), actual, (min, max)));
+        }
+    }
+
+    fn forms(&mut self, forms: &[Object]) {
+        for form in forms {
+            self.form(form);
+        }
+    }
+
+    fn form(&mut self, x: &Object) {
+        let cons = match &**x {
+            ObjectKind::Symbol(name) => {
+                if !self.refer(name) {
+                    self.warnings.push(Warning::UndefinedVariable(
+                        name.clone(),
+                        self.function.clone(),
+                    ));
+                }
+                return;
+            }
+            ObjectKind::Cons(cons) => cons,
+            _ => return,
+        };
+        let args = match cons.cdr.as_proper_list() {
+            Some(args) => args,
+            None => return,
+        };
+        let name = match &*cons.car {
+            ObjectKind::Symbol(name) if !self.is_local(name) => name,
+            _ => {
+                self.form(&cons.car);
+                return self.forms(&args);
+            }
+        };
+        match name.as_str() {
+            "lambda" => {
+                if let Some((list, body)) = args.split_first() {
+                    if let Ok((params, rest)) = eval::parse_lambda_list(Rc::clone(list)) {
+                        self.lambda(params.into_iter().chain(rest).collect(), body);
+                    }
+                }
+            }
+            "define" => {
+                let args = match eval::define_shorthand(&args) {
+                    Some((var, value)) => alloc::vec![var, value],
+                    None => args,
+                };
+                if let [var, value] = args.as_slice() {
+                    let function = self.function.clone();
+                    if let ObjectKind::Symbol(var) = &**var {
+                        if self.lint && lambda_list(value).is_some() {
+                            self.function = Some(var.clone());
+                        }
+                        if !self.lint || self.lambdas > 0 {
+                            self.bind(var);
+                        }
+                    }
+                    self.form(value);
+                    self.function = function;
+                }
+            }
+            "defvar" | "defparameter" => {
+                if let [var, value] = args.as_slice() {
+                    self.form(value);
+                    if let ObjectKind::Symbol(var) = &**var {
+                        if !self.lint || self.lambdas > 0 {
+                            self.bind(var);
+                        }
+                    }
+                }
+            }
+            "with-open-file" => {
+                if let Some((spec, body)) = args.split_first() {
+                    let spec = spec.as_proper_list().unwrap_or_default();
+                    if let [var, path, ..] = spec.as_slice() {
+                        self.form(path);
+                        if let ObjectKind::Symbol(var) = &**var {
+                            let depth = self.scope.len();
+                            self.bind(var);
+                            self.forms(body);
+                            self.scope.truncate(depth);
+                        }
+                    }
+                }
+            }
+            "with-restarts" => {
+                if let Some((specs, body)) = args.split_first() {
+                    for spec in specs.as_proper_list().unwrap_or_default() {
+                        let spec = spec.as_proper_list().unwrap_or_default();
+                        if let [_, list, body @ ..] = spec.as_slice() {
+                            if let Ok((params, rest)) = eval::parse_lambda_list(Rc::clone(list)) {
+                                self.lambda(params.into_iter().chain(rest).collect(), body);
+                            }
+                        }
+                    }
+                    self.forms(body);
+                }
+            }
+            "loop" => {
+                if let Ok(clauses) = eval::parse_loop(x, &args) {
+                    let depth = self.scope.len();
+                    for clause in &clauses {
+                        match clause {
+                            LoopClause::In(_, list) => self.form(list),
+                            LoopClause::FromTo(_, from, to) => {
+                                self.forms(&[Rc::clone(from), Rc::clone(to)])
+                            }
+                            _ => (),
+                        }
+                    }
+                    for clause in &clauses {
+                        if let LoopClause::In(var, _) | LoopClause::FromTo(var, ..) = clause {
+                            self.bind(var);
+                        }
+                    }
+                    for clause in &clauses {
+                        match clause {
+                            LoopClause::Collect(x) | LoopClause::When(x) | LoopClause::While(x) => {
+                                self.form(x)
+                            }
+                            LoopClause::Do(forms) => self.forms(forms),
+                            LoopClause::In(..) | LoopClause::FromTo(..) => (),
+                        }
+                    }
+                    self.scope.truncate(depth);
+                }
+            }
+            "parameterize" => {
+                if let Some((bindings, body)) = args.split_first() {
+                    for binding in bindings.as_proper_list().unwrap_or_default() {
+                        let binding = binding.as_proper_list().unwrap_or_default();
+                        self.forms(binding.get(1..).unwrap_or_default());
+                    }
+                    self.forms(body);
+                }
+            }
+            "the" => self.forms(args.get(1..).unwrap_or_default()),
+            "if" => {
+                match args.first() {
+                    Some(test) if self.lint && is_constant(test) => self.warnings.push(
+                        Warning::ConstantCondition(test.to_string(), self.function.clone()),
+                    ),
+                    _ => (),
+                }
+                self.forms(&args)
+            }
+            "quote" | "quasiquote" | "declare" | "defmacro" | "require" | "provide"
+            | "defpackage" | "in-package" | "export" | "import" => (),
+            name if is_special_form(name) => self.forms(&args),
+            name => self.call(name, args),
+        }
+    }
+
+    fn call(&mut self, name: &str, args: Vec<Object>) {
+        // The arguments of a macro the file defines could be anything.
+        if self.macros.contains(name) {
+            return;
+        }
+        if let Some(arity) = self.globals.get(name) {
+            if let Some(arity) = *arity {
+                self.check_arity(name, args.len(), arity);
+            }
+            return self.forms(&args);
+        }
+        let function = self.env.borrow().get(name);
+        match function.as_deref() {
+            Some(ObjectKind::Macro(closure)) => {
+                if let Ok(expansion) = eval::apply_closure(closure, args) {
+                    self.form(&expansion);
+                }
+                return;
+            }
+            Some(ObjectKind::Func(builtin)) => self.check_arity(name, args.len(), builtin.arity()),
+            Some(ObjectKind::Closure(closure)) if self.lint => {
+                self.check_arity(name, args.len(), closure.arity())
+            }
+            Some(_) => (),
+            None => self.warnings.push(Warning::UndefinedVariable(
+                name.to_string(),
+                self.function.clone(),
+            )),
+        }
+        self.forms(&args);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::load;
+    use super::*;
+
+    fn warnings(source: &str) -> Vec<String> {
+        let env = Env::global_env();
+        env.borrow_mut().take_warnings();
+        load::load_str(source, Rc::clone(&env)).unwrap();
+        let warnings = env.borrow_mut().take_warnings();
+        warnings.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn stdlib_test() {
+        assert!(Env::global_env().borrow_mut().take_warnings().is_empty());
+    }
+
+    #[test]
+    fn undefined_variable_test() {
+        assert_eq!(
+            warnings("(defun f (x) (+ x y))"),
+            ["Undefined variable: y (in f)"]
+        );
+        assert_eq!(warnings("(lambda () (g 1))"), ["Undefined variable: g"]);
+        assert!(warnings(
+            "(defun fact (n) (if n (fact n) 1))
+             (defun h (x)
+               (defun iter (y) (iter y))
+               (let ((z x)) (list z :key (lambda (w) w) '(quoted) `(a ,x))))"
+        )
+        .is_empty());
+        assert_eq!(
+            warnings("(defun f (xs) (loop for x in xs for i from 1 to n when x collect (g i)))"),
+            [
+                "Undefined variable: n (in f)",
+                "Undefined variable: g (in f)"
+            ]
+        );
+    }
+
+    #[test]
+    fn wrong_num_args_test() {
+        assert_eq!(
+            warnings("(defun f (x) (car x x))"),
+            ["car called with 2 arguments, but takes 1"]
+        );
+        assert_eq!(
+            warnings("(defun f () (%read))"),
+            ["%read called with 0 arguments, but takes at least 2"]
+        );
+        assert!(warnings("(defun f (x) (+ x x x))").is_empty());
+    }
+
+    #[test]
+    fn shadows_special_form_test() {
+        assert_eq!(
+            warnings("(define if 1) (defun f (quote) quote)"),
+            ["if shadows a special form", "quote shadows a special form"]
+        );
+    }
+
+    #[test]
+    fn redefinition_test() {
+        assert_eq!(
+            warnings(
+                "(defun f (x) x) (defun f (a) a) (defun f (x y) x)
+                 (defmacro m (&rest xs) xs) (defmacro m (x) x)
+                 (define car 1)"
+            ),
+            [
+                "f redefined to take 2 arguments instead of 1",
+                "m redefined to take 1 argument instead of at least 0",
+                "car shadows a builtin",
+            ]
+        );
+    }
+
+    fn lint_warnings(source: &str) -> Vec<String> {
+        let forms = load::read_forms(source).unwrap();
+        lint(&forms).iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn lint_test() {
+        assert_eq!(
+            lint_warnings(
+                "(defun f (x y _z) (let ((a 1) (b 2)) (g a x)))
+                 (defun g (a b) (if 'x (h a) b))
+                 (defvar *depth* 0)
+                 (defun k (*depth*) (when t (f 1 2 3 4)))"
+            ),
+            [
+                "Unused variable: b (in f)",
+                "Unused variable: y (in f)",
+                "Constant condition: (quote x) (in g)",
+                "Undefined variable: h (in g)",
+                "Constant condition: t (in k)",
+                "f called with 4 arguments, but takes 3",
+            ]
+        );
+        // Definitions may come after their use, and a body's definitions
+        // may refer to each other.
+        assert!(lint_warnings(
+            "(defun f (n) (even? n) (twice n))
+             (defmacro twice (x) (list 'progn x x))
+             (defun even? (n)
+               (define (odd? n) (if (equal n 0) nil (ev? (+ n -1))))
+               (define (ev? n) (if (equal n 0) t (odd? (+ n -1))))
+               (ev? n))"
+        )
+        .is_empty());
+        assert_eq!(
+            lint_warnings("(car 1 2)"),
+            ["car called with 2 arguments, but takes 1"]
+        );
+    }
+}
@@ -0,0 +1,71 @@
+use alloc::string::{String, ToString};
+
+use super::interpreter::Interpreter;
+use super::object::Object;
+use super::port::Port;
+
+// Evaluates source text and renders anything printed followed by the result
+// or error as a string, so a host without stdin/stdout (e.g. a browser
+// playground) can display it.
+pub struct Playground {
+    interpreter: Interpreter,
+    output: Object,
+}
+
+impl Default for Playground {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Playground {
+    pub fn new() -> Self {
+        let interpreter = Interpreter::new();
+        let output = interpreter.set_output_port(Port::output_string());
+        interpreter.set_error_port(Port::output_string());
+        Self {
+            interpreter,
+            output,
+        }
+    }
+
+    pub fn eval_str(&self, source: &str) -> String {
+        let result = match self.interpreter.eval_str(source) {
+            Ok(result) => result.to_string(),
+            Err(e) => e.to_string(),
+        };
+        let mut text = self
+            .output
+            .as_port()
+            .and_then(Port::take_output)
+            .unwrap_or_default();
+        text.push_str(&result);
+        text
+    }
+}
+
+pub fn eval_str(source: &str) -> String {
+    Playground::new().eval_str(source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eval_str_test() {
+        assert_eq!(eval_str("(+ 1 2)"), "3");
+        assert_eq!(eval_str("\"a\""), "\"a\"");
+        assert_eq!(eval_str("x"), "Unbound variable: x");
+        assert_eq!(eval_str("(car"), "End of file");
+    }
+
+    #[test]
+    fn playground_test() {
+        let playground = Playground::new();
+        assert_eq!(playground.eval_str("(define x 1)"), "1");
+        assert_eq!(playground.eval_str("(+ x 1)"), "2");
+        assert_eq!(playground.eval_str("(print x)"), "1\n1");
+        assert_eq!(playground.eval_str("x"), "1");
+    }
+}
@@ -0,0 +1,212 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::collections::Map;
+use super::env::Env;
+use super::error::RuntimeError;
+use super::eval::{check_num_args, check_num_args_range, EvalResult};
+use super::object::{self, Object, ObjectKind, ObjectType};
+use super::rc::{Rc, RefCell, Weak};
+
+// Objects are freed when their last strong reference goes away, so a weak
+// reference is simply one that doesn't count. Cycles are never freed, weak
+// or not.
+pub struct WeakRef(Weak<ObjectKind>);
+
+impl WeakRef {
+    pub fn new(x: &Object) -> Self {
+        Self(Rc::downgrade(x))
+    }
+
+    pub fn get(&self) -> Option<Object> {
+        self.0.upgrade()
+    }
+}
+
+// A table whose keys are compared by identity and held weakly: an entry
+// goes away with its key. Values are held strongly, so a value that refers
+// to its own key keeps the entry alive.
+#[derive(Default)]
+pub struct WeakTable {
+    // Keyed by address. A weak reference keeps the allocation, though not
+    // the object, so the address of a live entry is never reused.
+    entries: RefCell<Map<usize, (Weak<ObjectKind>, Object)>>,
+}
+
+fn address(x: &Object) -> usize {
+    Rc::as_ptr(x) as *const () as usize
+}
+
+impl WeakTable {
+    pub fn get(&self, key: &Object) -> Option<Object> {
+        self.entries
+            .borrow()
+            .get(&address(key))
+            .map(|(_, value)| Rc::clone(value))
+    }
+
+    pub fn insert(&self, key: &Object, value: Object) {
+        self.prune();
+        self.entries
+            .borrow_mut()
+            .insert(address(key), (Rc::downgrade(key), value));
+    }
+
+    pub fn remove(&self, key: &Object) -> Option<Object> {
+        self.entries
+            .borrow_mut()
+            .remove(&address(key))
+            .map(|(_, value)| value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.prune();
+        self.entries.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Drops the entries whose keys are gone.
+    fn prune(&self) {
+        let dead: Vec<usize> = self
+            .entries
+            .borrow()
+            .iter()
+            .filter(|(_, (key, _))| key.strong_count() == 0)
+            .map(|(address, _)| *address)
+            .collect();
+        let mut entries = self.entries.borrow_mut();
+        for address in dead {
+            entries.remove(&address);
+        }
+    }
+}
+
+impl fmt::Display for WeakRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = if self.0.strong_count() > 0 {
+            "alive"
+        } else {
+            "dead"
+        };
+        write!(f, "#<weak-ref {}>", state)
+    }
+}
+
+impl fmt::Display for WeakTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#<weak-table {}>", self.len())
+    }
+}
+
+pub fn init(env: &mut Env) {
+    env.define_builtin("make-weak-ref", "x", make_weak_ref);
+    env.define_builtin("weak-ref-value", "weak-ref", weak_ref_value);
+    env.define_builtin("weak-ref-alive?", "weak-ref", is_weak_ref_alive);
+    env.define_builtin("make-weak-table", "", make_weak_table);
+    env.define_builtin("weak-table-get", "table key &rest default", weak_table_get);
+    env.define_builtin("weak-table-put!", "table key value", weak_table_put);
+    env.define_builtin("weak-table-remove!", "table key", weak_table_remove);
+    env.define_builtin("weak-table-count", "table", weak_table_count);
+}
+
+fn weak_ref(x: &Object) -> Result<&WeakRef, RuntimeError> {
+    match &**x {
+        ObjectKind::WeakRef(weak_ref) => Ok(weak_ref),
+        _ => Err(RuntimeError::MismatchType(
+            Rc::clone(x),
+            ObjectType::WeakRef,
+        )),
+    }
+}
+
+fn weak_table(x: &Object) -> Result<&WeakTable, RuntimeError> {
+    match &**x {
+        ObjectKind::WeakTable(table) => Ok(table),
+        _ => Err(RuntimeError::MismatchType(
+            Rc::clone(x),
+            ObjectType::WeakTable,
+        )),
+    }
+}
+
+fn make_weak_ref(args: &[Object]) -> EvalResult {
+    check_num_args(args, 1)?;
+    Ok(object::weak_ref(WeakRef::new(&args[0])))
+}
+
+// nil once the object is gone.
+fn weak_ref_value(args: &[Object]) -> EvalResult {
+    check_num_args(args, 1)?;
+    Ok(weak_ref(&args[0])?.get().unwrap_or_else(object::nil))
+}
+
+fn is_weak_ref_alive(args: &[Object]) -> EvalResult {
+    check_num_args(args, 1)?;
+    match weak_ref(&args[0])?.get() {
+        Some(_) => Ok(object::symbol("t")),
+        None => Ok(object::nil()),
+    }
+}
+
+fn make_weak_table(args: &[Object]) -> EvalResult {
+    check_num_args(args, 0)?;
+    Ok(object::weak_table(WeakTable::default()))
+}
+
+fn weak_table_get(args: &[Object]) -> EvalResult {
+    check_num_args_range(args, 2, 3)?;
+    match weak_table(&args[0])?.get(&args[1]) {
+        Some(value) => Ok(value),
+        None => Ok(args.get(2).cloned().unwrap_or_else(object::nil)),
+    }
+}
+
+fn weak_table_put(args: &[Object]) -> EvalResult {
+    check_num_args(args, 3)?;
+    weak_table(&args[0])?.insert(&args[1], Rc::clone(&args[2]));
+    Ok(Rc::clone(&args[2]))
+}
+
+fn weak_table_remove(args: &[Object]) -> EvalResult {
+    check_num_args(args, 2)?;
+    Ok(weak_table(&args[0])?
+        .remove(&args[1])
+        .unwrap_or_else(object::nil))
+}
+
+fn weak_table_count(args: &[Object]) -> EvalResult {
+    check_num_args(args, 1)?;
+    Ok(object::fixnum(weak_table(&args[0])?.len() as isize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weak_ref_test() {
+        let x = object::string("x");
+        let weak_ref = WeakRef::new(&x);
+        assert!(weak_ref.get().is_some_and(|y| Rc::ptr_eq(&x, &y)));
+        drop(x);
+        assert!(weak_ref.get().is_none());
+    }
+
+    #[test]
+    fn weak_table_test() {
+        let table = WeakTable::default();
+        let (x, y) = (object::string("key"), object::string("key"));
+        table.insert(&x, object::fixnum(1));
+        table.insert(&y, object::fixnum(2));
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.get(&x), Some(object::fixnum(1)));
+        assert_eq!(table.get(&object::string("key")), None);
+        drop(x);
+        assert_eq!(table.len(), 1);
+        assert_eq!(table.remove(&y), Some(object::fixnum(2)));
+        assert!(table.is_empty());
+    }
+}
@@ -0,0 +1,872 @@
+#[cfg(feature = "sync")]
+use lisp::interpreter::Step;
+use lisp::{
+    complete,
+    env::Env,
+    error::RuntimeError,
+    eval::{key_args, EvalResult},
+    object::{fixnum, string, symbol, Object, ObjectKind, ObjectType},
+    permissions::Permissions,
+    profile::{Capability, Module, Profile},
+    restart::Restart,
+    Error, Interpreter,
+};
+
+fn assert_eval(expected: Object, result: Result<Object, Error>) {
+    assert_eq!(result.unwrap(), expected);
+}
+
+fn double(args: &[Object]) -> EvalResult {
+    match &*args[0] {
+        ObjectKind::Fixnum(n) => Ok(fixnum(n * 2)),
+        _ => Err(RuntimeError::MismatchType(
+            args[0].clone(),
+            ObjectType::Number,
+        )),
+    }
+}
+
+// (greet name [:greeting s] [:punctuation s])
+fn greet(args: &[Object]) -> EvalResult {
+    let [greeting, punctuation] = key_args(&args[1..], [":greeting", ":punctuation"])?;
+    let text = |x: Option<Object>, default: &str| match x.as_deref() {
+        Some(ObjectKind::String(s)) => s.clone(),
+        _ => default.to_string(),
+    };
+    Ok(string(&format!(
+        "{}, {}{}",
+        text(greeting, "Hello"),
+        text(Some(args[0].clone()), ""),
+        text(punctuation, "!")
+    )))
+}
+
+#[test]
+fn eval_str_test() {
+    let interpreter = Interpreter::new();
+    assert_eval(fixnum(3), interpreter.eval_str("(define x 1) (+ x 2)"));
+    assert_eval(fixnum(1), interpreter.eval_str("x"));
+    assert!(matches!(interpreter.eval_str("(+ 1"), Err(Error::Read(_))));
+    assert!(matches!(
+        interpreter.eval_str("(car 1)"),
+        Err(Error::Runtime(e)) if matches!(e.inner(), RuntimeError::MismatchType(_, _))
+    ));
+}
+
+#[test]
+fn eval_file_test() {
+    let path = std::env::temp_dir().join(format!("lisp-interpreter-{}.lisp", std::process::id()));
+    std::fs::write(&path, "(define greeting \"hello\")").unwrap();
+
+    let interpreter = Interpreter::new();
+    assert_eval(string("hello"), interpreter.eval_file(&path));
+    assert_eval(string("hello"), interpreter.eval_str("greeting"));
+    assert!(matches!(
+        interpreter.eval_file("/no/such/file.lisp"),
+        Err(Error::Io(_))
+    ));
+}
+
+#[test]
+fn globals_test() {
+    let interpreter = Interpreter::new();
+    assert!(interpreter.get("answer").is_none());
+    assert!(!interpreter.set("answer", fixnum(41)));
+
+    interpreter.define("answer", fixnum(41));
+    assert!(interpreter.set("answer", fixnum(42)));
+    assert_eq!(interpreter.get("answer").unwrap(), fixnum(42));
+    assert_eval(fixnum(42), interpreter.eval_str("answer"));
+
+    interpreter.define_fn("double", double);
+    assert_eval(fixnum(84), interpreter.eval_str("(double answer)"));
+}
+
+#[test]
+fn key_args_test() {
+    let interpreter = Interpreter::new();
+    interpreter.define_fn("greet", greet);
+    let greet = |source| interpreter.eval_str(source).map(|x| x.to_string());
+    assert_eq!(greet("(greet \"you\")").unwrap(), "\"Hello, you!\"");
+    assert_eq!(
+        greet("(greet \"you\" :punctuation \".\" :greeting \"Hi\" :greeting \"Hey\")").unwrap(),
+        "\"Hi, you.\""
+    );
+    assert_eq!(
+        greet("(greet \"you\" :volume 11)").unwrap_err().to_string(),
+        "Unknown keyword argument :volume, expecting one of :greeting :punctuation (in (greet \"you\" :volume 11))"
+    );
+    assert!(greet("(greet \"you\" :greeting)").is_err());
+}
+
+#[test]
+fn eval_test() {
+    let interpreter = Interpreter::new();
+    assert_eval(
+        symbol("a"),
+        interpreter
+            .eval(lisp::object::cons(
+                symbol("quote"),
+                lisp::object::cons(symbol("a"), lisp::object::nil()),
+            ))
+            .map_err(Error::from),
+    );
+}
+
+#[cfg(feature = "sync")]
+#[test]
+fn send_to_thread_test() {
+    use std::sync::{Arc, Mutex};
+
+    let interpreter = Arc::new(Mutex::new(Interpreter::new()));
+    interpreter
+        .lock()
+        .unwrap()
+        .eval_str("(define counter 0)")
+        .unwrap();
+
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let interpreter = Arc::clone(&interpreter);
+            std::thread::spawn(move || {
+                interpreter
+                    .lock()
+                    .unwrap()
+                    .eval_str("(set! counter (+ counter 1))")
+                    .unwrap();
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    assert_eval(fixnum(4), interpreter.lock().unwrap().eval_str("counter"));
+}
+
+#[test]
+fn output_port_test() {
+    use lisp::port::Port;
+
+    let interpreter = Interpreter::new();
+    let output = interpreter.set_output_port(Port::output_string());
+    let error = interpreter.set_error_port(Port::output_string());
+    interpreter
+        .eval_str("(print 'hello) (display \"oops\" (current-error-port))")
+        .unwrap();
+    assert_eq!(output.as_port().unwrap().output().unwrap(), "hello\n");
+    assert_eq!(error.as_port().unwrap().output().unwrap(), "oops");
+    assert_eq!(interpreter.output_port(), output);
+    assert_eq!(interpreter.error_port(), error);
+}
+
+#[test]
+fn dump_restore_test() {
+    let interpreter = Interpreter::new();
+    interpreter
+        .eval_str(
+            "(define config '(:name \"demo\" :level 3))
+             (defun twice (f x) (f (f x)))
+             (defmacro swap-args (f a b) (list f b a))
+             (defpackage geometry (:export area))
+             (in-package geometry)
+             (defun area (w h) (+ w w h))
+             (in-package user)
+             (import 'geometry:area)",
+        )
+        .unwrap();
+
+    let mut image = Vec::new();
+    interpreter.dump(&mut image).unwrap();
+    let restored = Interpreter::restore(image.as_slice()).unwrap();
+
+    assert_eval(
+        lisp::list![symbol(":name"), string("demo"), symbol(":level"), fixnum(3)],
+        restored.eval_str("config"),
+    );
+    assert_eval(
+        fixnum(5),
+        restored.eval_str("(twice (lambda (x) (+ x 1)) 3)"),
+    );
+    assert_eval(
+        lisp::list![fixnum(2), fixnum(1)],
+        restored.eval_str("(swap-args list 1 2)"),
+    );
+    assert_eval(fixnum(5), restored.eval_str("(area 1 3)"));
+    assert_eval(fixnum(5), restored.eval_str("(geometry:area 1 3)"));
+}
+
+#[test]
+fn save_image_test() {
+    let path = std::env::temp_dir().join("lisp-save-image-test.img");
+    let interpreter = Interpreter::new();
+    interpreter
+        .eval_str(&format!(
+            "(defun square (x) (+ x x x x))
+             (define table (list (square 1) (square 2)))
+             (save-image {:?})",
+            path.display().to_string()
+        ))
+        .unwrap();
+
+    let restored = Interpreter::restore_image(std::fs::File::open(&path).unwrap()).unwrap();
+    assert_eval(
+        lisp::list![fixnum(4), fixnum(8)],
+        restored.eval_str("table"),
+    );
+    assert_eval(fixnum(12), restored.eval_str("(square 3)"));
+
+    let mut image = Vec::new();
+    interpreter.save_image(&mut image).unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), image);
+    assert!(Interpreter::restore_image(&b"(define x 1)"[..]).is_err());
+}
+
+// Rewrites (twice x) into (+ x x).
+fn twice(args: &[Object]) -> EvalResult {
+    let form = &args[0];
+    match &**form {
+        ObjectKind::Cons(cons) if cons.car == symbol("twice") => {
+            let x = match &*cons.cdr {
+                ObjectKind::Cons(arg) => arg.car.clone(),
+                _ => return Err(RuntimeError::MalformedForm(form.clone())),
+            };
+            Ok(lisp::list![symbol("+"), x.clone(), x])
+        }
+        _ => Ok(form.clone()),
+    }
+}
+
+#[test]
+fn expansion_hook_test() {
+    let interpreter = Interpreter::new();
+    let hook = interpreter.add_expansion_hook("twice", twice);
+    assert_eval(fixnum(6), interpreter.eval_str("(twice (+ 1 2))"));
+    assert_eval(fixnum(8), interpreter.eval_str("(twice (twice 2))"));
+    assert!(interpreter.remove_expansion_hook(&hook));
+    assert!(!interpreter.remove_expansion_hook(&hook));
+    assert!(matches!(
+        interpreter.eval_str("(twice 1)"),
+        Err(Error::Runtime(e)) if matches!(e.inner(), RuntimeError::UnboundVariable(..))
+    ));
+}
+
+#[test]
+fn warnings_test() {
+    let interpreter = Interpreter::new();
+    assert!(interpreter.take_warnings().is_empty());
+    interpreter.eval_str("(defun f (x) (car x y))").unwrap();
+    let warnings: Vec<String> = interpreter
+        .take_warnings()
+        .iter()
+        .map(|w| w.to_string())
+        .collect();
+    assert_eq!(
+        warnings,
+        [
+            "car called with 2 arguments, but takes 1",
+            "Undefined variable: y (in f)"
+        ]
+    );
+    assert!(interpreter.take_warnings().is_empty());
+}
+
+#[test]
+fn failing_finalizer_test() {
+    let interpreter = Interpreter::new();
+    interpreter
+        .eval_str("(progn (add-finalizer (list 1) (lambda () (car 1))) 2)")
+        .unwrap();
+    interpreter
+        .eval_str(
+            "(define ran nil)
+             (progn
+               (add-finalizer (list 1) (lambda () (cdr 1)))
+               (add-finalizer (list 2) (lambda () (set! ran t)))
+               nil)",
+        )
+        .unwrap();
+    assert_eq!(interpreter.eval_str("ran").unwrap().to_string(), "t");
+    let warnings: Vec<String> = interpreter
+        .take_warnings()
+        .iter()
+        .map(|w| w.to_string())
+        .collect();
+    assert_eq!(
+        warnings,
+        [
+            "Finalizer failed: The value 1 is not of type Cons (in (car 1))",
+            "Finalizer failed: The value 1 is not of type Cons (in (cdr 1))"
+        ]
+    );
+}
+
+// Picks use-value for x, define-it for y, the innermost other restart for
+// z, and aborts otherwise.
+fn debugger(error: &RuntimeError, restarts: &[Restart]) -> Option<(usize, Vec<Object>)> {
+    match error {
+        RuntimeError::UnboundVariable(name, _) if name == "x" => Some((0, vec![fixnum(42)])),
+        RuntimeError::UnboundVariable(name, _) if name == "y" => Some((1, vec![fixnum(1)])),
+        RuntimeError::UnboundVariable(name, _) if name == "z" => {
+            assert_eq!(restarts[2].name, "skip");
+            Some((2, vec![]))
+        }
+        _ => None,
+    }
+}
+
+#[test]
+fn debugger_test() {
+    let interpreter = Interpreter::new();
+    interpreter.set_debugger(Some(debugger));
+    assert_eval(fixnum(43), interpreter.eval_str("(+ x 1)"));
+    assert!(interpreter.get("x").is_none());
+    assert_eval(fixnum(2), interpreter.eval_str("(+ y 1)"));
+    assert_eq!(interpreter.get("y").unwrap(), fixnum(1));
+    assert_eval(
+        symbol("skipped"),
+        interpreter.eval_str("(with-restarts ((skip () 'skipped)) (+ z 1))"),
+    );
+    assert!(interpreter.eval_str("w").is_err());
+    // Errors inside %try are left to it.
+    assert_eval(
+        lisp::object::nil(),
+        interpreter.eval_str("(car (%try (lambda () x)))"),
+    );
+}
+
+#[test]
+fn profile_test() {
+    let path = std::env::temp_dir().join(format!("lisp-profile-{}.lisp", std::process::id()));
+    std::fs::write(&path, "(define loaded t)").unwrap();
+    let load = format!("(load {:?})", path.to_str().unwrap());
+
+    let interpreter = Interpreter::with_profile(Profile::pure());
+    assert_eval(fixnum(3), interpreter.eval_str("(+ 1 2)"));
+    for source in [
+        "(open-input-file \"/etc/hosts\")",
+        load.as_str(),
+        "(with-open-file (in \"/etc/hosts\") (read-line in))",
+        "(getenv \"HOME\")",
+        "(run-process \"true\")",
+    ] {
+        assert!(matches!(
+            interpreter.eval_str(source),
+            Err(Error::Runtime(e)) if matches!(e.inner(), RuntimeError::UnboundVariable(..))
+        ));
+    }
+    assert!(matches!(
+        interpreter.eval_str("(require 'no-such-module)"),
+        Err(Error::Runtime(e)) if matches!(e.inner(), RuntimeError::ModuleNotFound(_))
+    ));
+    assert!(interpreter.get("loaded").is_none());
+
+    let interpreter = Interpreter::with_profile(Profile::pure().with(Capability::Io));
+    assert_eval(symbol("t"), interpreter.eval_str(&load));
+}
+
+#[test]
+fn env_builder_test() {
+    let interpreter = Interpreter::with_env(
+        Env::builder()
+            .with(Module::Core)
+            .with(Module::Math)
+            .profile(Profile::pure())
+            .build(),
+    );
+    assert_eval(fixnum(3), interpreter.eval_str("(+ 1 2)"));
+    assert_eval(symbol("a"), interpreter.eval_str("(car (list 'a))"));
+    for source in [
+        "(string->list \"ab\")",
+        "(copy-list '(1 2))",
+        "(%read-line *standard-input*)",
+        "(open-input-file \"/etc/hosts\")",
+        "(make-promise 1)",
+        "(make-weak-ref 1)",
+        "(make-channel)",
+    ] {
+        assert!(matches!(
+            interpreter.eval_str(source),
+            Err(Error::Runtime(e)) if matches!(e.inner(), RuntimeError::UnboundVariable(..))
+        ));
+    }
+
+    let interpreter = Interpreter::with_env(Env::builder().with(Module::String).build());
+    assert_eval(
+        string("ab"),
+        interpreter.eval_str("(list->string (string->list \"ab\"))"),
+    );
+    assert!(interpreter.get("+").is_none());
+    assert!(interpreter.get("car").is_some());
+
+    // The capabilities allow them, but the modules were left out.
+    let interpreter = Interpreter::with_env(Env::builder().with(Module::Math).build());
+    for source in [
+        "(with-open-file (in \"/etc/hosts\") 1)",
+        "(load \"/etc/hosts\")",
+        "(getenv \"HOME\")",
+        "(current-time)",
+    ] {
+        assert!(matches!(
+            interpreter.eval_str(source),
+            Err(Error::Runtime(e)) if matches!(e.inner(), RuntimeError::UnboundVariable(..))
+        ));
+    }
+}
+
+#[test]
+fn scheme_test() {
+    let interpreter = Interpreter::with_env(Env::builder().scheme().build());
+    assert_eval(
+        symbol("t"),
+        interpreter.eval_str("(define (f x) (pair? x)) (f '(1))"),
+    );
+    assert_eval(
+        interpreter.eval_str("'(t nil t)").unwrap(),
+        interpreter.eval_str("(list #t #f (list? '(1 2)))"),
+    );
+    assert_eval(symbol("y"), interpreter.eval_str("(or (member 5 '(1)) 'y)"));
+    // The stdlib's functions aren't strict.
+    assert_eval(
+        interpreter.eval_str("'(1)").unwrap(),
+        interpreter.eval_str("(filter (lambda (x) (member x '(1))) '(1 2))"),
+    );
+    assert!(matches!(
+        interpreter.eval_str("(if '(1) 'yes 'no)"),
+        Err(Error::Runtime(e)) if matches!(e.inner(), RuntimeError::MismatchType(_, ObjectType::Boolean))
+    ));
+
+    let interpreter = Interpreter::new();
+    assert_eval(symbol("yes"), interpreter.eval_str("(if '(1) 'yes 'no)"));
+    assert_eval(
+        fixnum(2),
+        interpreter.eval_str("(defun f (null?) (and null? 2)) (f 1)"),
+    );
+}
+
+#[test]
+fn complete_test() {
+    let interpreter = Interpreter::new();
+    interpreter.eval_str("(define list-total 0)").unwrap();
+    let candidates = interpreter.complete("list");
+    assert_eq!(candidates[0].name, "list");
+    assert_eq!(candidates[0].kind, complete::Kind::Function);
+    assert!(candidates
+        .iter()
+        .any(|c| c.name == "list-total" && c.kind == complete::Kind::Variable));
+
+    let interpreter = Interpreter::with_env(Env::builder().with(Module::Core).build());
+    assert!(interpreter.complete("string->").is_empty());
+}
+
+#[test]
+fn definition_source_test() {
+    let path = std::env::temp_dir().join(format!("lisp-source-test-{}.lisp", std::process::id()));
+    std::fs::write(&path, "(define one 1)\n\n; two\n(defun two ()\n  2)\n").unwrap();
+    let path = path.to_str().unwrap().to_string();
+
+    let interpreter = Interpreter::new();
+    interpreter.eval_str(&format!("(load {:?})", path)).unwrap();
+    assert_eval(
+        interpreter.eval_str(&format!("'({:?} 4)", path)).unwrap(),
+        interpreter.eval_str("(definition-source 'two)"),
+    );
+    assert_eval(
+        interpreter.eval_str("'(nil 2)").unwrap(),
+        interpreter.eval_str("(define x 1)\n(defvar *y* 2)\n(definition-source '*y*)"),
+    );
+
+    // Redefining a global elsewhere than in a file forgets its source.
+    let form = lisp::reader::read_from_string("(define one 'one)")
+        .unwrap()
+        .0;
+    interpreter.eval(form).unwrap();
+    assert_eval(
+        symbol("nil"),
+        interpreter.eval_str("(definition-source 'one)"),
+    );
+    assert_eval(
+        symbol("nil"),
+        interpreter.eval_str("(definition-source 'undefined)"),
+    );
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn watch_load_test() {
+    let path = std::env::temp_dir().join(format!("lisp-watch-test-{}.lisp", std::process::id()));
+    std::fs::write(&path, "(defvar *count* 0)\n(defun f () 1)\n").unwrap();
+    let set_modified = |seconds| {
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        let time = std::time::UNIX_EPOCH + std::time::Duration::from_secs(seconds);
+        file.set_modified(time).unwrap();
+    };
+    set_modified(1_000_000);
+
+    let interpreter = Interpreter::new();
+    interpreter
+        .eval_str(&format!("(watch-load {:?})", path.to_str().unwrap()))
+        .unwrap();
+    interpreter.eval_str("(set! *count* 5)").unwrap();
+    assert!(interpreter.reload_changed().is_empty());
+
+    // The file is reloaded into the same environment, where *count* keeps
+    // its value.
+    std::fs::write(&path, "(defvar *count* 0)\n(defun f () (+ *count* 2))\n").unwrap();
+    set_modified(2_000_000);
+    let reloaded = interpreter.reload_changed();
+    assert_eq!(reloaded.len(), 1);
+    assert_eq!(reloaded[0].0, path);
+    assert!(reloaded[0].1.is_ok());
+    assert_eval(fixnum(7), interpreter.eval_str("(f)"));
+    assert!(interpreter.reload_changed().is_empty());
+
+    std::fs::write(&path, "(defun f ()").unwrap();
+    set_modified(3_000_000);
+    assert!(interpreter.reload_changed()[0].1.is_err());
+    std::fs::remove_file(&path).unwrap();
+    assert!(interpreter.reload_changed().is_empty());
+}
+
+#[test]
+fn isolation_test() {
+    let a = Interpreter::new();
+    let b = Interpreter::new();
+    a.eval_str("(defun f () 'a) (defvar *x* 1) (set! *print-base* 16) (hash-consing t)")
+        .unwrap();
+    assert!(b.eval_str("(f)").is_err());
+    assert!(b.eval_str("*x*").is_err());
+    assert_eval(fixnum(10), b.eval_str("*print-base*"));
+    assert_eval(symbol("nil"), b.eval_str("(hash-consing)"));
+    assert_eval(symbol("t"), a.eval_str("(hash-consing)"));
+
+    // Redefining a prelude function in one leaves the other's alone.
+    b.eval_str("(defun cadr (x) 'b)").unwrap();
+    assert_eval(fixnum(2), a.eval_str("(cadr '(1 2 3))"));
+    assert_eval(symbol("b"), b.eval_str("(cadr '(1 2 3))"));
+}
+
+#[test]
+fn permissions_test() {
+    let dir = std::env::temp_dir().join(format!("lisp-permissions-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("data.lisp");
+    std::fs::write(&path, "(define loaded t)").unwrap();
+    let path = format!("{:?}", path.to_str().unwrap());
+
+    let interpreter = Interpreter::new();
+    interpreter.set_permissions(Permissions::none().allow_read(&dir));
+    assert_eval(
+        symbol("t"),
+        interpreter.eval_str(&format!("(load {})", path)),
+    );
+    assert_eval(
+        string("(define loaded t)"),
+        interpreter.eval_str(&format!("(read-line (open-input-file {}))", path)),
+    );
+    for source in [
+        format!("(open-output-file {})", path),
+        format!("(with-open-file (out {} :direction :output) 1)", path),
+        "(load \"/etc/hosts\")".to_string(),
+    ] {
+        assert!(matches!(
+            interpreter.eval_str(&source),
+            Err(Error::Runtime(e)) if matches!(e.inner(), RuntimeError::PermissionDenied(_))
+        ));
+    }
+    // A module outside the paths that may be read isn't found, as if it
+    // weren't there.
+    let secret =
+        std::env::temp_dir().join(format!("lisp-permissions-secret-{}", std::process::id()));
+    std::fs::create_dir_all(&secret).unwrap();
+    std::fs::write(secret.join("hidden.lisp"), "(define hidden t)").unwrap();
+    interpreter
+        .eval_str(&format!(
+            "(set! *load-path* (list {:?}))",
+            secret.to_str().unwrap()
+        ))
+        .unwrap();
+    for name in ["hidden", "missing"] {
+        assert!(matches!(
+            interpreter.eval_str(&format!("(require '{})", name)),
+            Err(Error::Runtime(e)) if matches!(e.inner(), RuntimeError::ModuleNotFound(_))
+        ));
+    }
+    std::fs::remove_dir_all(secret).unwrap();
+    assert_eval(
+        symbol("permission-error"),
+        interpreter.eval_str(
+            "(handler-case (open-input-file \"/etc/hosts\") (error (c) (condition-type c)))",
+        ),
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn eval_script_test() {
+    let path = std::env::temp_dir().join(format!("lisp-script-{}.lisp", std::process::id()));
+    std::fs::write(&path, "(cons *script-path* *args*)").unwrap();
+    let path = path.to_str().unwrap();
+
+    let interpreter = Interpreter::new();
+    assert_eval(lisp::object::nil(), interpreter.eval_str("*args*"));
+    let result = interpreter.eval_script(path, &["-v", "input.txt"]).unwrap();
+    assert_eq!(
+        result.to_string(),
+        format!("({:?} \"-v\" \"input.txt\")", path)
+    );
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn run_process_test() {
+    let interpreter = Interpreter::new();
+    interpreter
+        .eval_str("(define p (run-process \"sh\" \"-c\" \"echo out; echo err >&2; exit 3\"))")
+        .unwrap();
+    assert_eval(fixnum(3), interpreter.eval_str("(process-status p)"));
+    assert_eval(string("out\n"), interpreter.eval_str("(process-output p)"));
+    assert_eval(
+        string("err\n"),
+        interpreter.eval_str("(process-error-output p)"),
+    );
+    assert!(matches!(
+        interpreter.eval_str("(run-process \"no-such-command-for-lisp\")"),
+        Err(Error::Runtime(e)) if matches!(e.inner(), RuntimeError::Io(_))
+    ));
+}
+
+#[test]
+fn environment_variable_test() {
+    let interpreter = Interpreter::new();
+    assert_eval(
+        string("set"),
+        interpreter.eval_str("(setenv \"LISP_ENVIRONMENT_TEST\" \"set\")"),
+    );
+    assert_eval(
+        string("set"),
+        interpreter.eval_str("(getenv \"LISP_ENVIRONMENT_TEST\")"),
+    );
+    assert_eval(
+        lisp::object::nil(),
+        interpreter.eval_str("(getenv \"LISP_ENVIRONMENT_TEST_UNSET\")"),
+    );
+    for source in [
+        "(setenv \"\" \"x\")",
+        "(setenv \"A=B\" \"x\")",
+        "(setenv \"LISP_ENVIRONMENT_TEST\" \"a\\u{0}b\")",
+    ] {
+        assert!(matches!(
+            interpreter.eval_str(source),
+            Err(Error::Runtime(e)) if matches!(e.inner(), RuntimeError::InvalidEnvVar(..))
+        ));
+    }
+    assert_eval(
+        lisp::list![string("set")],
+        interpreter.eval_str("(map getenv (list \"LISP_ENVIRONMENT_TEST\"))"),
+    );
+
+    interpreter.set_permissions(Permissions::none().allow_getenv("LISP_ENVIRONMENT_TEST"));
+    assert_eval(
+        string("set"),
+        interpreter.eval_str("(getenv \"LISP_ENVIRONMENT_TEST\")"),
+    );
+    for source in [
+        "(getenv \"HOME\")",
+        "(setenv \"LISP_ENVIRONMENT_TEST\" \"changed\")",
+    ] {
+        assert!(matches!(
+            interpreter.eval_str(source),
+            Err(Error::Runtime(e)) if matches!(e.inner(), RuntimeError::PermissionDenied(_))
+        ));
+    }
+    assert_eq!(std::env::var("LISP_ENVIRONMENT_TEST").unwrap(), "set");
+}
+
+// Builtins that consult the global environment are bindings like any
+// other, so a definition of the same name replaces them.
+#[test]
+fn env_builtin_test() {
+    let interpreter = Interpreter::new();
+    assert_eval(
+        fixnum(1),
+        interpreter.eval_str("(define (sleep x) x) (sleep 1)"),
+    );
+    assert_eval(
+        fixnum(3),
+        interpreter.eval_str("(defun values (a b) (+ a b)) (values 1 2)"),
+    );
+    assert_eval(
+        lisp::list![fixnum(1), fixnum(2)],
+        Interpreter::new().eval_str("(multiple-value-list (apply values '(1 2)))"),
+    );
+}
+
+#[test]
+fn accounting_test() {
+    let interpreter = Interpreter::new();
+    assert_eq!(interpreter.tally(), None);
+    interpreter.set_accounting(true);
+    assert_eval(fixnum(3), interpreter.eval_str("(+ 1 2)"));
+    assert_eq!(interpreter.tally().unwrap().reductions, 4);
+
+    let source = "(defun f (n) (if (equal n 0) nil (cons n (f (+ n -1))))) (f 10)";
+    let tallies: Vec<_> = (0..2)
+        .map(|_| {
+            let interpreter = Interpreter::new();
+            interpreter.set_accounting(true);
+            interpreter.eval_str(source).unwrap();
+            interpreter.tally().unwrap()
+        })
+        .collect();
+    assert_eq!(tallies[0], tallies[1]);
+    assert!(tallies[0].allocations > 10);
+    assert!(tallies[0].conses >= 10 && tallies[0].conses < tallies[0].allocations);
+
+    interpreter.set_accounting(false);
+    interpreter.eval_str("(+ 1 2)").unwrap();
+    assert_eq!(interpreter.tally(), None);
+}
+
+#[cfg(feature = "sync")]
+#[test]
+fn eval_stepwise_test() {
+    let interpreter = Interpreter::new();
+    interpreter
+        .eval_str("(defun count (n acc) (if (equal n 0) acc (count (+ n -1) (+ acc 1))))")
+        .unwrap();
+    let form = lisp::reader::read_from_string("(count 100 0)").unwrap().0;
+    let mut stepwise = interpreter.eval_stepwise(form);
+    let mut resumes = 0;
+    let result = loop {
+        resumes += 1;
+        // Other Lisp code can run between steps.
+        assert_eval(fixnum(2), interpreter.eval_str("(+ 1 1)"));
+        match stepwise.resume(50) {
+            Step::Paused => assert!(!stepwise.is_done()),
+            Step::Done(result) => break result,
+        }
+    };
+    assert_eq!(result.unwrap(), fixnum(100));
+    assert!(stepwise.is_done());
+    assert!(resumes > 10);
+
+    // Dropping an unfinished evaluation cancels it, even inside %try.
+    let form = lisp::reader::read_from_string("(%try (lambda () (count 1000 0)))")
+        .unwrap()
+        .0;
+    let mut stepwise = interpreter.eval_stepwise(form);
+    assert!(matches!(stepwise.resume(10), Step::Paused));
+    drop(stepwise);
+    assert_eval(fixnum(3), interpreter.eval_str("(count 3 0)"));
+}
+
+// Ready on the second poll, like an async read that has to wait once.
+#[cfg(feature = "sync")]
+struct Delayed {
+    value: Object,
+    polled: bool,
+}
+
+#[cfg(feature = "sync")]
+impl std::future::Future for Delayed {
+    type Output = Object;
+
+    fn poll(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Object> {
+        if self.polled {
+            return std::task::Poll::Ready(self.value.clone());
+        }
+        self.polled = true;
+        cx.waker().wake_by_ref();
+        std::task::Poll::Pending
+    }
+}
+
+#[cfg(feature = "sync")]
+fn delayed_double(args: Vec<Object>) -> lisp::future::BoxFuture {
+    Box::pin(async move {
+        let value = double(&args)?;
+        Ok(Delayed {
+            value,
+            polled: false,
+        }
+        .await)
+    })
+}
+
+#[cfg(feature = "sync")]
+#[test]
+fn eval_async_test() {
+    use std::future::Future;
+    use std::task::{Context, Poll, Waker};
+
+    let interpreter = Interpreter::new();
+    interpreter.define_async_fn("delayed-double", delayed_double);
+    interpreter
+        .eval_str("(defun count (n acc) (if (equal n 0) acc (count (+ n -1) (+ acc 1))))")
+        .unwrap();
+    let form = lisp::reader::read_from_string("(+ (count 500 0) (delayed-double 21))")
+        .unwrap()
+        .0;
+    let mut future = std::pin::pin!(interpreter.eval_async(form));
+    let mut cx = Context::from_waker(Waker::noop());
+    let mut polls = 0;
+    let result = loop {
+        polls += 1;
+        if let Poll::Ready(result) = future.as_mut().poll(&mut cx) {
+            break result;
+        }
+    };
+    assert_eq!(result.unwrap(), fixnum(542));
+    // The long loop gave control back, and so did the async builtin.
+    assert!(polls > 3);
+
+    // Outside eval_async it blocks.
+    assert_eval(fixnum(4), interpreter.eval_str("(delayed-double 2)"));
+}
+
+#[test]
+fn channel_test() {
+    use lisp::channel::Channel;
+    use lisp::object::channel;
+
+    let (requests, replies) = (Channel::new(), Channel::new());
+    let worker = {
+        let (requests, replies) = (requests.clone(), replies.clone());
+        std::thread::spawn(move || {
+            let interpreter = Interpreter::new();
+            interpreter.define("requests", channel(requests));
+            interpreter.define("replies", channel(replies));
+            interpreter
+                .eval_str(
+                    "(defun serve ()
+                       (let ((n (recv requests)))
+                         (unless (equal n 'stop)
+                           (send replies (list n (+ n n)))
+                           (serve))))
+                     (serve)",
+                )
+                .unwrap();
+        })
+    };
+
+    let interpreter = Interpreter::new();
+    interpreter.define("requests", channel(requests));
+    interpreter.define("replies", channel(replies));
+    assert_eval(
+        lisp::reader::read_from_string("((1 2) (5 10))").unwrap().0,
+        interpreter.eval_str(
+            "(send requests 1)
+             (send requests 5)
+             (send requests 'stop)
+             (list (recv replies) (recv replies))",
+        ),
+    );
+    worker.join().unwrap();
+    assert!(matches!(
+        interpreter.eval_str("(send requests (lambda () 1))"),
+        Err(Error::Runtime(e)) if matches!(e.inner(), RuntimeError::NotTransferable(_))
+    ));
+}
@@ -0,0 +1,8 @@
+(print (+ 1 2))
+(print (cons 1 '(2 3)))
+(print "string")
+(display "display")
+(newline)
+(print (length '(a b c)))
+(print (reverse '(1 2 3)))
+(print (map (lambda (x) (+ x x)) '(1 2 3)))
@@ -0,0 +1,32 @@
+(define bits #*1010)
+(print bits)
+(print (list (bit-ref bits 0) (bit-ref bits 1) (bit-vector-length bits)))
+(print (list (bit-and bits #*1100) (bit-or bits #*1100) (bit-xor bits #*1100) (bit-not bits)))
+(print (equal (bit-not (bit-not bits)) bits))
+(print (the bit-vector #*))
+
+;; Strikes every step-th position from pos on, starting countdown
+;; positions later.
+(defun strike (sieve step pos countdown n)
+  (unless (equal pos n)
+    (if (equal countdown 0)
+        (progn
+          (bit-set! sieve pos 0)
+          (strike sieve step (+ pos 1) (+ step -1) n))
+        (strike sieve step (+ pos 1) (+ countdown -1) n))))
+
+(defun sieve-from (sieve i n)
+  (unless (equal i n)
+    (when (equal (bit-ref sieve i) 1)
+      (strike sieve i (+ i 1) (+ i -1) n))
+    (sieve-from sieve (+ i 1) n)))
+
+(define primes (make-bit-vector 30 1))
+(bit-set! primes 0 0)
+(bit-set! primes 1 0)
+(sieve-from primes 2 30)
+(print primes)
+(print (bit-count primes))
+(print (handler-case (bit-and #*1 #*10) (error (c) (condition-message c))))
+(print (handler-case (bit-set! primes 0 2) (type-error (c) (type-error-expected-type c))))
+(bit-ref primes 30)
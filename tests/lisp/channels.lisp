@@ -0,0 +1,13 @@
+(define ch (make-channel))
+(print (try-recv ch 'empty))
+(send ch '(1 "two" three))
+(print (recv ch))
+
+(send ch 1)
+(send ch 2)
+(print (list (recv ch) (recv ch)))
+
+;; recv gives up once a with-timeout around it runs out.
+(print (handler-case (with-timeout 0 (recv ch))
+         (timeout-error () 'timed-out)))
+(send ch car)
@@ -0,0 +1,8 @@
+(define port (open-input-string "ab"))
+(print (char-ready? port))
+(print (read-char-no-hang port))
+(print (read-char port))
+(print (char-ready? port))
+(print (read-char-no-hang port))
+(close-port port)
+(char-ready? port)
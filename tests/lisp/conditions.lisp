@@ -0,0 +1,18 @@
+(print (handler-case (car 1)
+         (type-error (c) (list (type-error-datum c) (type-error-expected-type c)))))
+(print (handler-case undefined-thing
+         (type-error () 'wrong)
+         (error (c) (list (condition-type c) (unbound-variable-name c)))))
+(print (handler-case ((lambda (x) x))
+         (arity-error (c) (list (arity-error-actual c) (arity-error-name c)))))
+(print (handler-case (error "Bad value:" 1)
+         (user-error (c) (list (condition-message c) (condition-irritants c)))))
+
+(define-condition parse-error user-error)
+(print (condition-subtype? 'parse-error 'error))
+(print (handler-case (error 'parse-error "Unexpected token" 'x)
+         (parse-error (c) (list (condition-type c) (condition-message c)))))
+(print (handler-case (+ 1 2) (error () 'unreachable)))
+(print (handler-case (handler-case (car 1) (arity-error () 'inner))
+         (condition (c) (type-error? c))))
+(handler-case (car 1) (arity-error () 'unreachable))
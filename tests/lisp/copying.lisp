@@ -0,0 +1,9 @@
+(define xs '(1 (2 3) 4))
+(print (copy-list xs))
+(print (copy-tree xs))
+(print (equal (copy-tree xs) xs))
+(print (copy-list '(1 2 . 3)))
+(print (copy-tree '((a . b) (c (d . e)))))
+(print (copy-list nil))
+(print (copy-tree 'atom))
+(copy-list)
@@ -0,0 +1,13 @@
+(define t0 (parse-iso8601 "2024-02-29T23:59:58+09:00"))
+(print t0)
+(print (format-iso8601 t0))
+(print (decode-time t0))
+(print (equal (encode-time 2024 2 29 14 59 58) t0))
+(print (encode-time 1970 1 2))
+(print (format-iso8601 (+ t0 2)))
+(print (atom? (decode-time (current-time))))
+(define start (monotonic-time))
+(sleep 0)
+(print (atom? (elapsed-time start)))
+(print (handler-case (parse-iso8601 "2023-02-29") (error (c) (condition-message c))))
+(encode-time 2024 13 1)
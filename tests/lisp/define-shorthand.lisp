@@ -0,0 +1,13 @@
+(define (square x) (+ x x))
+(print (square 3))
+(define (sum first &rest rest) (reduce + first rest))
+(print (sum 1 2 3))
+(define ((adder n) x) (+ n x))
+(print ((adder 2) 5))
+(print adder)
+(define (outer x)
+  (define (inner y) (+ x y))
+  (inner 10))
+(print (outer 1))
+(define (g))
+(print (g))
@@ -0,0 +1,10 @@
+(deftest append-test
+  (assert (equal (append '(1) '(2 3)) '(1 2 3))))
+
+(deftest member-test
+  (assert (equal (member 2 '(1 2 3)) '(2 3)))
+  (assert (null? (member 4 '(1 2 3)))))
+
+(run-tests)
+(assert (equal 1 2))
+(print 'unreachable)
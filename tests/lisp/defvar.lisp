@@ -0,0 +1,21 @@
+(print (defvar *count* 0))
+(set! *count* 5)
+(defvar *count* (error "not evaluated"))
+(print *count*)
+(defparameter *limit* 10)
+(set! *limit* 20)
+(defparameter *limit* 10)
+(print *limit*)
+(defun show () *count*)
+(print (let ((*count* 1)) (show)))
+(print *count*)
+(defun show-with (*count*) (show))
+(print (show-with 7))
+(print (handler-case (let ((*count* 3)) (error "failed"))
+         (error () *count*)))
+(define lexical 1)
+(defun show-lexical () lexical)
+(print (let ((lexical 2)) (show-lexical)))
+(defun define-later () (defvar *later* 'set))
+(define-later)
+(print *later*)
@@ -0,0 +1,6 @@
+(define shared (list 'b))
+(display (dump-graph (cons shared shared)))
+(display (dump-graph "quoted \"string\""))
+(define path "/tmp/lisp-dump-graph-test.dot")
+(print (dump-graph (list 1 2) path))
+(with-open-file (in path) (print (read-line in)))
@@ -0,0 +1,21 @@
+(define log nil)
+(define port (open-output-string))
+(print (equal (add-finalizer port (lambda () (set! log (cons 'port-closed log)))) port))
+(print log)
+(set! port nil)
+(print log)
+
+(define kept (list 1))
+(add-finalizer kept (lambda () (set! log (cons 'kept log))))
+(add-finalizer (list 2) (lambda () (set! log (cons 'temporary log))))
+(print log)
+
+(defun make-chained ()
+  (let ((inner (list 'inner)))
+    (add-finalizer inner (lambda () (set! log (cons 'inner log))))
+    (add-finalizer (list 'outer) (lambda () (set! inner nil) (set! log (cons 'outer log))))
+    nil))
+(make-chained)
+(print log)
+(print (run-finalizers))
+(add-finalizer 1 2)
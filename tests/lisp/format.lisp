@@ -0,0 +1,11 @@
+(print (format nil "~a + ~a = ~a" 1 2 (+ 1 2)))
+(print (format nil "~s and ~a" "quoted" "plain"))
+(print (format nil "~16r ~2,8,'0r ~:d" 255 5 1234567))
+(print (format nil "~{~a~^, ~}" '(a b c)))
+(define rows '(("apples" 3) ("pears" 12) ("figs" 100)))
+(format t "~{~{~8a~5@a~}~%~}" rows)
+(let ((port (open-output-string)))
+  (format port "~5,'.d|" 42)
+  (print (get-output-string port)))
+(print (handler-case (format nil "~a ~a" 1) (error (c) (condition-message c))))
+(format nil "~z")
@@ -0,0 +1,13 @@
+(print (hash-consing))
+(define seen (make-weak-table))
+(define key (list 1 "two" 'three))
+(weak-table-put! seen key 'found)
+(print (weak-table-get seen (list 1 "two" 'three) 'missing))
+(print (hash-consing t))
+(define key (list 1 "two" 'three))
+(weak-table-put! seen key 'found)
+(print (weak-table-get seen (list 1 "two" 'three) 'missing))
+(print (weak-table-get seen '(1 "two" three) 'missing))
+(print (equal (list key key) (list (list 1 "two" 'three) key)))
+(print (hash-consing nil))
+(print (weak-table-get seen (list 1 "two" 'three) 'missing))
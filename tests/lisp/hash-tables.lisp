@@ -0,0 +1,30 @@
+(define config #{:name "demo" :ports (80 443) "limits" #{:cpu 2}})
+(print config)
+(print (hash-table-get config :ports))
+(print (hash-table-get (hash-table-get config "limits") :cpu))
+(print (hash-table-get config :missing 'none))
+(print (hash-table-keys config))
+
+(define counts (make-hash-table))
+(loop for word in '(a b a c a b)
+      do (hash-table-put! counts word (+ 1 (hash-table-get counts word 0))))
+(print (hash-table->alist counts))
+(print (hash-table-remove! counts 'b))
+(print (hash-table-count counts))
+(print (hash-table-put! counts '(1 2) "list key"))
+(print (hash-table-get counts (list 1 2)))
+(print (equal #{a 1 b 2} #{b 2 a 1}))
+(print (equal #{a 1} #{a 2}))
+
+(define primes #s(2 3 5 7 3))
+(print primes)
+(print (set-member? primes 5))
+(print (set-member? primes 4))
+(print (set-add! primes 11))
+(print (set-remove! primes 2))
+(print (set-remove! primes 2))
+(print (set->list primes))
+(print (set-count (make-set 1 1 2)))
+(print (equal #s(1 2) #s(2 1)))
+(print (list (hash-table? config) (set? primes) (set? config)))
+(print (the set primes))
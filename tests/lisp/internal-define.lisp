@@ -0,0 +1,21 @@
+(define x 'global)
+(defun f ()
+  (define x 'local)
+  (defun even? (n) (if (equal n 0) t (odd? (+ n -1))))
+  (defun odd? (n) (if (equal n 0) nil (even? (+ n -1))))
+  (list x (even? 4) (odd? 4)))
+(print (f))
+(print x)
+(print (let ((y 1))
+         (define z (+ y 1))
+         (list y z)))
+(print (handler-case ((lambda () (print 'start) (define late 1) late))
+         (error (e) (condition-message e))))
+(print (handler-case ((lambda () 1 (if t (define nested 1))))
+         (error (e) (condition-message e))))
+(print (handler-case (defun k (flag) (when flag (define w 1)) (define v 2) v)
+         (error (e) (condition-message e))))
+(defmacro define-two (a b) `(progn (define ,a 1) (define ,b 2)))
+(defun two () (define-two p q) (list p q))
+(print (two))
+(defun g () 1 (define h 2))
@@ -0,0 +1,17 @@
+(print :key)
+(print (list (keyword? :key) (keyword? 'key) (keyword? "key")))
+(print (keyword->symbol :key))
+(print (symbol->keyword 'key))
+(print (equal (symbol->keyword 'key) :key))
+(define options '(:width 80 :height 24))
+(print (list (getf options :width) (getf options :height) (getf options :depth)))
+(print (plist-get options :depth 1))
+(print (plist-member '(:a 1 :b nil) :b))
+(print (plist-member '(:a 1 :b nil) :c))
+(print (plist-put options :height 25))
+(print (plist-put options :depth 3))
+(print options)
+(print (with-open-file (in "/dev/null" :direction :input) (read-line in)))
+(print (handler-case (with-open-file (in "/dev/null" :mode :input) 1)
+         (error (e) (condition-message e))))
+(print (getf '(:a) :a))
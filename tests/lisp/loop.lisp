@@ -0,0 +1,17 @@
+(print (loop for x in '(1 2 3) collect (+ x x)))
+(print (loop for i from 1 to 5 collect i))
+(print (loop for i from 3 to 1 collect i))
+(print (loop for x in '(1 2 3 4 5 6) when (member x '(2 4 6)) collect x))
+(print (loop for x in '(1 2 3 nil 5) while x collect x))
+(print (loop for x in '(a b c) for i from 1 to 10 collect (cons i x)))
+(define total 0)
+(print (loop for i from 1 to 10000 do (set! total (+ total i))))
+(print total)
+(defun evens (n)
+  (loop for i from 1 to n
+        when (member i '(2 4 6 8))
+        collect i))
+(print (evens 6))
+(print (loop for x in '(1 2) for y in '(3 4) collect x collect y))
+(print (loop for x in '((1 . 2) (3 . 4)) do (display (car x)) (display (cdr x)) collect (car x)))
+(loop for x of '(1 2))
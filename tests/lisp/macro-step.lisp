@@ -0,0 +1,10 @@
+(defmacro swap! (a b)
+  `(let ((tmp ,a)) (set! ,a ,b) (set! ,b tmp)))
+(define form '(when ready (swap! x y)))
+(print (macro-calls form))
+(print (macro-step form))
+(print (macro-step form 1))
+(print (macro-calls '(lambda (when) (when 1))))
+(print (macro-step '(car x)))
+(print (handler-case (macro-step form -1)
+         (type-error (e) 'negative-index)))
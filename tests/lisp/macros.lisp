@@ -0,0 +1,13 @@
+(defmacro swap! (a b)
+  (with-gensyms (tmp)
+    `(let ((,tmp ,a)) (set! ,a ,b) (set! ,b ,tmp))))
+
+(define tmp 1)
+(define other 2)
+(swap! tmp other)
+(print (list tmp other))
+
+(print (when t 1 2))
+(print (unless t 1))
+(print (and 1 2))
+(print (or nil 3))
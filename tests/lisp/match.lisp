@@ -0,0 +1,51 @@
+(defun describe (x)
+  (match x
+    (0 'zero)
+    ("hello" 'greeting)
+    (:key 'keyword)
+    ('sym 'quoted-symbol)
+    (nil 'empty)
+    ((list 'point x y) (list 'point-at x y))
+    ((cons 'add (cons a (cons b nil))) (+ a b))
+    ((cons head _) :when (equal head 'tagged) 'tagged-anything)
+    ((cons n rest) :when (equal n 1) (list 'starts-with-one rest))
+    ((cons _ _) 'some-list)
+    (other (list 'atom other))))
+
+(print (describe 0))
+(print (describe "hello"))
+(print (describe :key))
+(print (describe 'sym))
+(print (describe nil))
+(print (describe '(point 1 2)))
+(print (describe '(point 1 2 3)))
+(print (describe '(add 3 4)))
+(print (describe '(tagged 1 2)))
+(print (describe '(1 2 3)))
+(print (describe '(2 3)))
+(print (describe 42))
+
+(defun sum-tree (tree)
+  (match tree
+    (nil 0)
+    ((cons left right) (+ (sum-tree left) (sum-tree right)))
+    (n n)))
+(print (sum-tree '(1 (2 3) (4 (5)))))
+
+(define calls 0)
+(print (match (progn (set! calls (+ calls 1)) '(1 2))
+         ((list a) a)
+         ((list a b) (list b a))))
+(print calls)
+(print (match 5 (_)))
+
+(defun bits (x)
+  (match x
+    (#*00 'zeros)
+    ((bit-vector 1 b) (list 'starts-with-one b))
+    ((bit-vector a _ c) (list a c))
+    (_ 'other)))
+(print (list (bits #*00) (bits #*10) (bits #*11) (bits #*011) (bits #*0) (bits '(1 0))))
+(print (handler-case (match 1 ((vector a b) a))
+         (error (c) (condition-message c))))
+(match 5 (6 'six))
@@ -0,0 +1,24 @@
+(define calls 0)
+(define slow-double
+  (memoize (lambda (x) (set! calls (+ calls 1)) (+ x x))))
+(print (slow-double 21))
+(print (slow-double 21))
+(print (slow-double 4))
+(print calls)
+
+(define fib
+  (memoize (lambda (n)
+             (if (member n '(0 1))
+                 n
+                 (+ (fib (+ n -1)) (fib (+ n -2)))))))
+(print (fib 60))
+
+(define pairs 0)
+(define pair (memoize (lambda (x y) (set! pairs (+ pairs 1)) (cons x y))))
+(pair '(a b) "s")
+(pair '(a b) "s")
+(pair '(a b) "t")
+(print pairs)
+(print (map (memoize car) '((1 2) (3 4))))
+(print slow-double)
+(memoize 1)
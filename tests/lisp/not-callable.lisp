@@ -0,0 +1,8 @@
+(define x 3)
+(print (handler-case ((+ 1 2))
+         (not-callable (e) (list (type-error-datum e) (not-callable-form e)))))
+(print (handler-case (x 1)
+         (type-error (e) (condition-message e))))
+(print (handler-case (apply 1 nil)
+         (type-error (e) (not-callable? e))))
+(1 2 3)
@@ -0,0 +1,15 @@
+(define *depth* 0)
+(defun depth () *depth*)
+(print (parameterize ((*depth* 1)) (depth)))
+(print *depth*)
+(print (parameterize ((*depth* (+ *depth* 1)))
+         (list (depth) (parameterize ((*depth* (+ *depth* 1))) (depth)))))
+(print (handler-case (parameterize ((*depth* 5)) (error "failed"))
+         (error (c) (list 'caught *depth*))))
+(print (parameterize ((*depth* 7)) (set! *depth* 8) (depth)))
+(print *depth*)
+(define port (open-output-string))
+(parameterize ((*standard-output* port))
+  (display "captured"))
+(print (get-output-string port))
+(parameterize ((*undefined* 1)) nil)
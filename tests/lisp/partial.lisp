@@ -0,0 +1,13 @@
+(define add10 (partial + 10))
+(print (add10 5))
+(print (map (partial + 1) '(1 2 3)))
+(print (map (partial cons 'x) '(1 2)))
+(defun double (x) (+ x x))
+(define double-then-inc (compose (partial + 1) double))
+(print (double-then-inc 5))
+(print ((compose car cdr) '(1 2 3)))
+(print (map (compose double double) '(1 2 3)))
+(print ((compose) 'same))
+(print ((partial (partial list 1) 2) 3))
+(print add10)
+(partial 1 2)
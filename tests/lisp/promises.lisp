@@ -0,0 +1,25 @@
+(define count 0)
+(define p (delay (progn (set! count (+ count 1)) 'value)))
+(print p)
+(print (promise-forced? p))
+(print (force p))
+(print (force p))
+(print count)
+(print p)
+(print (force 5))
+(print (force (make-promise 'ready)))
+(print (promise? (delay 1)))
+(print (promise? 1))
+
+;; An infinite stream of integers as a pair whose cdr is a promise.
+(defun integers-from (n) (cons n (delay (integers-from (+ n 1)))))
+(defun stream-take (stream n)
+  (if (equal n 0)
+      nil
+      (cons (car stream) (stream-take (force (cdr stream)) (+ n -1)))))
+(print (stream-take (integers-from 1) 5))
+
+(define self nil)
+(define reentrant (delay (if self 'outer (progn (set! self t) (force reentrant)))))
+(print (force reentrant))
+(promise-forced? 1)
@@ -0,0 +1,6 @@
+(define b 1)
+(define c '(2 3))
+(print `(a ,b ,@c))
+(print `(a . ,b))
+(print `(a `(b ,(c ,(+ 1 2)))))
+(print `(1 ,@nil 2))
@@ -0,0 +1,23 @@
+(print (number->string 255 :radix 16))
+(print (number->string -5 :radix 2))
+(print (number->string 42))
+(parameterize ((*print-base* 16))
+  (print '(255 (16 . 17) "x" sym))
+  (display 10)
+  (newline)
+  (print (number->string 31)))
+(print 255)
+(define in (open-input-string "ff (10 -1) zz 9"))
+(parameterize ((*read-base* 16))
+  (print (read in))
+  (print (read in))
+  (print (read in)))
+(print (read in))
+(parameterize ((*read-base* 2))
+  (print (read (open-input-string "1010"))))
+(set! *read-base* 16)
+(print (+ 10 1))
+(set! *read-base* a)
+(print (+ 10 1))
+(print (handler-case (number->string 1 :radix 37)
+         (error (e) (condition-message e))))
@@ -0,0 +1,7 @@
+(define stats (gc-stats))
+(print (map car stats))
+(print (atom? (cdr (assoc 'live-objects stats))))
+(print (not (null? (member 'cons (map car (cdr (assoc 'by-type stats)))))))
+(define out (open-output-string))
+(print (room out))
+(print (equal (get-output-string out) ""))
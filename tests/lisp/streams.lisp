@@ -0,0 +1,19 @@
+(print (stream-take (integers-from 1) 5))
+(print (stream-take (stream-map (lambda (n) (+ n n)) (integers-from 0)) 4))
+(print (stream-take (stream-filter (lambda (n) (equal (remainder n 3) 0)) (integers-from 1)) 3))
+(print (stream-take (stream-cons 'a (stream-cons 'b nil)) 5))
+(print (stream-take (integers-from 1) 0))
+
+;; The rest of a stream is computed once, when it's first needed.
+(define evaluated 0)
+(define s (stream-cons 1 (progn (set! evaluated (+ evaluated 1)) (stream-cons 2 nil))))
+(print evaluated)
+(print (stream-car (stream-cdr s)))
+(print (stream-take s 2))
+(print evaluated)
+
+(defun sieve (stream)
+  (stream-cons (stream-car stream)
+               (sieve (stream-filter (lambda (n) (not (equal (remainder n (stream-car stream)) 0)))
+                                     (stream-cdr stream)))))
+(print (stream-take (sieve (integers-from 2)) 8))
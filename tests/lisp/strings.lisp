@@ -0,0 +1,10 @@
+(print (string->list "abc"))
+(print (string->list ""))
+(print (list->string '("a" "bc" "d")))
+(print (list->string (reverse (string->list "stressed"))))
+(print (string-map (lambda (c) (if (equal c "a") "4" c)) "banana"))
+(define count 0)
+(string-for-each (lambda (c) (when (equal c "s") (set! count (+ count 1)))) "mississippi")
+(print count)
+(print (string->list "日本"))
+(list->string '("a" 1))
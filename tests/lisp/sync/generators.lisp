@@ -0,0 +1,44 @@
+(defun %count-from (i n)
+  (unless (equal i n)
+    (yield i)
+    (%count-from (+ i 1) n)))
+
+(define-generator upto (n) (%count-from 0 n))
+(print (generator->list (upto 5)))
+
+(define g (upto 2))
+(print (generator-next g))
+(print (generator-next g))
+(print (generator-next g 'done))
+(print (generator-done? g))
+
+;; An infinite generator is dropped without being finished.
+(define-generator naturals () (%count-from 0 -1))
+(define n (naturals))
+(print (list (generator-next n) (generator-next n) (generator-next n)))
+(set! n nil)
+
+(define-generator sends () (print (yield 1)) (yield 2))
+(define s (sends))
+(print (generator-next s))
+(print (generator-next s))
+
+(define-generator failing () (yield 1) (car 1))
+(define f (failing))
+(print (generator-next f))
+(print (handler-case (generator-next f) (type-error () 'caught)))
+(print (generator-done? f))
+;; A with-timeout around generator-next applies to the body too.
+(defun spin () (spin))
+(define-generator spinning () (spin))
+(print (handler-case (with-timeout 0 (generator-next (spinning)))
+         (timeout-error (c) (list 'timed-out (timeout-error-seconds c)))))
+;; So does one around a loop that keeps resuming an infinite generator.
+(define-generator counting () (loop for i from 0 to 1000000000 do (yield i)))
+(define c (counting))
+(print (handler-case (with-timeout 0 (loop for i from 0 to 1000000000 do (generator-next c)))
+         (timeout-error (c) (list 'timed-out (timeout-error-seconds c)))))
+(define-generator timed () (with-timeout 10 (yield 1) (yield 2)))
+(define w (timed))
+(print (list (with-timeout 5 (generator-next w)) (generator-next w)))
+(yield 1)
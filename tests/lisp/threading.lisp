@@ -0,0 +1,6 @@
+(print (-> 1 (+ 10) (cons '(x))))
+(print (->> 1 (+ 10) (cons 'x)))
+(print (->> '(1 2 3 4) (filter (lambda (x) (member x '(1 3)))) (map (partial + 10)) (reduce + 0)))
+(print (-> '(1 2 3) cdr car))
+(print (-> 5))
+(print (->> '((a . 1) (b . 2)) (assoc 'b) cdr))
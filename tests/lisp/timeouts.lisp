@@ -0,0 +1,17 @@
+(defun spin () (spin))
+(print (sleep 0))
+(print (with-timeout 10 (+ 1 2)))
+(print (handler-case (with-timeout 0 (spin))
+         (timeout-error (c) (list 'timed-out (timeout-error-seconds c)))))
+(print (handler-case (with-timeout 1 (sleep 30) 'slept)
+         (error (c) (timeout-error? c))))
+;; The outer timeout unwinds the inner one.
+(print (handler-case (with-timeout 10 (with-timeout 0 (spin)) 'inner-caught)
+         (timeout-error (c) (timeout-error-seconds c))))
+(print (with-timeout 10 (handler-case (with-timeout 0 (spin))
+                          (timeout-error () 'caught-inside))))
+;; A handler inside the body doesn't stop the timeout, but cleanup runs.
+(defun retry () (handler-case (sleep 2) (error () 'retrying)) (retry))
+(print (handler-case (with-timeout 1 (unwind-protect (retry) (print 'cleaned-up)))
+         (timeout-error (c) (list 'timed-out (timeout-error-seconds c)))))
+(with-timeout 0 (spin))
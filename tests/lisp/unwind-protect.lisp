@@ -0,0 +1,17 @@
+(print (unwind-protect 1 (print 'cleanup)))
+(print (multiple-value-list (unwind-protect (values 1 2) (values 3 4))))
+
+;; The cleanup forms run when the protected form fails, and the error
+;; carries on.
+(define log nil)
+(print (handler-case (unwind-protect (car 1) (set! log 'cleaned))
+         (error (e) 'caught)))
+(print log)
+
+;; A port opened by with-open-file is closed when its body fails.
+(define port nil)
+(print (handler-case (with-open-file (out "/dev/null" :direction :output)
+                       (set! port out)
+                       (car 1))
+         (error (e) 'caught)))
+(write-string "x" port)
@@ -0,0 +1,25 @@
+(print (multiple-value-list (values 1 2 3)))
+(print (multiple-value-list (values)))
+(print (multiple-value-list 5))
+(print (values 1 2))
+(print (multiple-value-list (truncate 17 5)))
+(print (multiple-value-list (truncate -17 5)))
+(print (multiple-value-list (truncate 7)))
+(multiple-value-bind (q r) (truncate 17 5)
+  (print (list 'quotient q 'remainder r)))
+(multiple-value-bind (a b c) (values 1)
+  (print (list a b c)))
+(print (let-values (((q r) (truncate 9 4)) ((x) (values 'x 'ignored)))
+         (list q r x)))
+(defun two () (if t (values 'a 'b) nil))
+(print (multiple-value-list (two)))
+(print (multiple-value-list (car (list (values 1 2)))))
+(print (multiple-value-list (let ((x (values 1 2))) x)))
+;; Only the values form whose value is returned counts.
+(print (multiple-value-list (let ((x 1)) (values x 2) x)))
+(print (multiple-value-list (progn (values) nil)))
+(print (multiple-value-bind (a b) (let ((x 1)) (values x 2) x) (list a b)))
+(print (multiple-value-list (if (values 1 2) 5)))
+(print (+ 1 (truncate 7 2)))
+(print (let ((x 1)) (let-values (((x) (values 2)) ((y) (values x))) (list x y))))
+(quotient 1 0)
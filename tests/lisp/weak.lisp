@@ -0,0 +1,23 @@
+(define x (list 1 2))
+(define r (make-weak-ref x))
+(print r)
+(print (weak-ref-value r))
+(set! x nil)
+(print (weak-ref-value r))
+(print (weak-ref-alive? r))
+(print r)
+
+(define cache (make-weak-table))
+(define key (list 'key))
+(define other (list 'key))
+(weak-table-put! cache key 'cached)
+(weak-table-put! cache (list 'temporary) 'gone)
+(print (weak-table-get cache key))
+(print (weak-table-get cache other 'missing))
+(print (weak-table-count cache))
+(set! key nil)
+(print (weak-table-count cache))
+(weak-table-put! cache other 1)
+(print (weak-table-remove! cache other))
+(print cache)
+(weak-ref-value 1)
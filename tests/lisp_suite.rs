@@ -0,0 +1,36 @@
+use std::path::Path;
+
+use lisp::suite::run_test_dir;
+
+fn check_test_dir(dir: &str) {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(dir);
+    let results = run_test_dir(&dir).unwrap();
+    assert!(!results.is_empty());
+
+    let failures: Vec<String> = results
+        .iter()
+        .filter(|result| !result.passed())
+        .map(|result| {
+            format!(
+                "{}\n--- expected\n{}--- actual\n{}",
+                result.path.display(),
+                result.expected,
+                result.actual
+            )
+        })
+        .collect();
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+// Each tests/lisp/NAME.lisp is checked against the output in NAME.out.
+#[test]
+fn lisp_suite() {
+    check_test_dir("tests/lisp");
+}
+
+// The tests under tests/lisp/sync use generators, which need sync.
+#[cfg(feature = "sync")]
+#[test]
+fn lisp_suite_sync() {
+    check_test_dir("tests/lisp/sync");
+}
@@ -1,23 +1,24 @@
 use lisp::{
     env::Env,
-    equal::equal,
     error::RuntimeError,
     eval::{eval, EvalResult},
-    object::{cons, fixnum, nil, symbol, Object, ObjectType},
+    list,
+    object::{cons, fixnum, nil, string, symbol, Object, ObjectType},
     reader::read_from_string,
 };
 
-use std::cell::RefCell;
-use std::rc::Rc;
+use lisp::rc::{Rc, RefCell};
 
 extern crate lisp;
 
+// Most tests check what went wrong rather than where, so the form an error
+// occurred in is dropped; form_in_error_test checks it.
 fn call_eval_with_env(input: &str, env: Rc<RefCell<Env>>) -> EvalResult {
     let x = match read_from_string(input) {
         Ok((x, _)) => x,
         _ => unreachable!(),
     };
-    eval(x, env)
+    eval(x, env).map_err(RuntimeError::into_inner)
 }
 
 fn call_eval(input: &str) -> EvalResult {
@@ -25,8 +26,7 @@ fn call_eval(input: &str) -> EvalResult {
 }
 
 fn assert_eval(expected: Object, result: EvalResult) {
-    let actual = result.unwrap();
-    assert!(equal(actual, expected));
+    assert_eq!(result.unwrap(), expected);
 }
 
 fn verify_eval(expected: Object, input: &str) {
@@ -43,10 +43,10 @@ fn atom_test() {
     verify_eval(symbol("t"), "(atom? 'foo)");
     verify_eval(symbol("t"), "(atom? 'foo)");
     verify_eval(nil(), "(atom? (cons 1 2))");
-    assert!(match call_eval("(atom?)") {
-        Err(RuntimeError::WrongNumArgs(0, 1)) => true,
-        _ => false,
-    });
+    assert!(matches!(
+        call_eval("(atom?)"),
+        Err(RuntimeError::WrongArity(name, 0, (1, Some(1)))) if name == "atom?"
+    ));
 }
 
 #[test]
@@ -55,19 +55,19 @@ fn add_test() {
     verify_eval(fixnum(1), "(+ 1)");
     verify_eval(fixnum(3), "(+ 1 2)");
     verify_eval(fixnum(6), "(+ 1 2 3)");
-    assert!(match call_eval("(+ 'a)") {
-        Err(RuntimeError::MismatchType(_, ObjectType::Number)) => true,
-        _ => false,
-    });
+    assert!(matches!(
+        call_eval("(+ 'a)"),
+        Err(RuntimeError::MismatchType(_, ObjectType::Number))
+    ));
 }
 
 #[test]
 fn quote_test() {
     verify_eval(symbol("a"), "'a");
-    assert!(match call_eval("(quote)") {
-        Err(RuntimeError::WrongNumArgs(0, 1)) => true,
-        _ => false,
-    });
+    assert!(matches!(
+        call_eval("(quote)"),
+        Err(RuntimeError::WrongNumArgs(0, 1))
+    ));
 }
 
 #[test]
@@ -77,36 +77,40 @@ fn cons_test() {
         cons(cons(fixnum(1), fixnum(2)), cons(fixnum(3), fixnum(4))),
         "(cons (cons 1 2) (cons 3 4))",
     );
-    assert!(match call_eval("(cons)") {
-        Err(RuntimeError::WrongNumArgs(0, 2)) => true,
-        _ => false,
-    });
+    assert!(matches!(
+        call_eval("(cons)"),
+        Err(RuntimeError::WrongArity(name, 0, (2, Some(2)))) if name == "cons"
+    ));
 }
 
 #[test]
 fn car_test() {
     verify_eval(fixnum(1), "(car (cons 1 2))");
-    assert!(match call_eval("(car)") {
-        Err(RuntimeError::WrongNumArgs(0, 1)) => true,
-        _ => false,
-    });
-    assert!(match call_eval("(car 'a)") {
-        Err(RuntimeError::MismatchType(_, ObjectType::Cons)) => true,
-        _ => false,
-    });
+    assert!(matches!(
+        call_eval("(car)"),
+        Err(RuntimeError::WrongArity(name, 0, (1, Some(1)))) if name == "car"
+    ));
+    assert_eq!(
+        call_eval("(car)").unwrap_err().to_string(),
+        "car expects 1 argument, got 0"
+    );
+    assert!(matches!(
+        call_eval("(car 'a)"),
+        Err(RuntimeError::MismatchType(_, ObjectType::Cons))
+    ));
 }
 
 #[test]
 fn cdr_test() {
     verify_eval(fixnum(2), "(cdr (cons 1 2))");
-    assert!(match call_eval("(cdr)") {
-        Err(RuntimeError::WrongNumArgs(0, 1)) => true,
-        _ => false,
-    });
-    assert!(match call_eval("(cdr 'a)") {
-        Err(RuntimeError::MismatchType(_, ObjectType::Cons)) => true,
-        _ => false,
-    });
+    assert!(matches!(
+        call_eval("(cdr)"),
+        Err(RuntimeError::WrongArity(name, 0, (1, Some(1)))) if name == "cdr"
+    ));
+    assert!(matches!(
+        call_eval("(cdr 'a)"),
+        Err(RuntimeError::MismatchType(_, ObjectType::Cons))
+    ));
 }
 
 #[test]
@@ -114,18 +118,18 @@ fn if_test() {
     verify_eval(symbol("true"), "(if (equal 1 1) 'true 'false)");
     verify_eval(symbol("false"), "(if (equal 1 2) 'true 'false)");
     verify_eval(nil(), "(if (equal 1 2) 'true)");
-    assert!(match call_eval("(if)") {
-        Err(RuntimeError::TooFewArguments(0, 2)) => true,
-        _ => false,
-    });
-    assert!(match call_eval("(if x)") {
-        Err(RuntimeError::TooFewArguments(1, 2)) => true,
-        _ => false,
-    });
-    assert!(match call_eval("(if test then else extra)") {
-        Err(RuntimeError::TooManyArguments(4, 3)) => true,
-        _ => false,
-    });
+    assert!(matches!(
+        call_eval("(if)"),
+        Err(RuntimeError::TooFewArguments(0, 2))
+    ));
+    assert!(matches!(
+        call_eval("(if x)"),
+        Err(RuntimeError::TooFewArguments(1, 2))
+    ));
+    assert!(matches!(
+        call_eval("(if test then else extra)"),
+        Err(RuntimeError::TooManyArguments(4, 3))
+    ));
 }
 
 #[test]
@@ -144,10 +148,10 @@ fn lambda_test() {
 
 #[test]
 fn set_test() -> Result<(), RuntimeError> {
-    assert!(match call_eval("(set! x 0)") {
-        Err(RuntimeError::UnboundVariable(var)) if var == "x" => true,
-        _ => false,
-    });
+    assert!(matches!(
+        call_eval("(set! x 0)"),
+        Err(RuntimeError::UnboundVariable(var, _)) if var == "x"
+    ));
     let env = Env::global_env();
     call_eval_with_env("(define foo nil)", Rc::clone(&env))?;
     verify_eval_with_env(fixnum(10), "(set! foo 10)", Rc::clone(&env));
@@ -165,3 +169,724 @@ fn closure_test() -> Result<(), RuntimeError> {
     verify_eval_with_env(fixnum(3), "(c)", Rc::clone(&env));
     Ok(())
 }
+
+fn module_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("lisp-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn require_test() -> Result<(), RuntimeError> {
+    let dir = module_dir("require");
+    std::fs::write(
+        dir.join("counter.lisp"),
+        "(set! count (+ count 1))\n(define from-module 'counter)\n",
+    )
+    .unwrap();
+
+    let env = Env::global_env();
+    call_eval_with_env(
+        &format!("(set! *load-path* '({}))", string(dir.to_str().unwrap())),
+        Rc::clone(&env),
+    )?;
+    call_eval_with_env("(define count 0)", Rc::clone(&env))?;
+    verify_eval_with_env(symbol("counter"), "(require 'counter)", Rc::clone(&env));
+    verify_eval_with_env(symbol("counter"), "(require 'counter)", Rc::clone(&env));
+    verify_eval_with_env(fixnum(1), "count", Rc::clone(&env));
+    verify_eval_with_env(symbol("counter"), "from-module", Rc::clone(&env));
+    verify_eval_with_env(
+        cons(symbol("counter"), nil()),
+        "*loaded-modules*",
+        Rc::clone(&env),
+    );
+
+    assert!(matches!(
+        call_eval_with_env("(require 'no-such-module)", Rc::clone(&env)),
+        Err(RuntimeError::ModuleNotFound(name)) if name == "no-such-module"
+    ));
+    assert!(matches!(
+        call_eval("(require \"counter\")"),
+        Err(RuntimeError::MismatchType(_, ObjectType::Symbol))
+    ));
+    std::fs::remove_dir_all(dir).unwrap();
+    Ok(())
+}
+
+#[test]
+fn provide_test() -> Result<(), RuntimeError> {
+    let dir = module_dir("provide");
+    std::fs::write(dir.join("feature.lisp"), "(define loaded 'yes)").unwrap();
+
+    let env = Env::global_env();
+    call_eval_with_env(
+        &format!("(set! *load-path* '({}))", string(dir.to_str().unwrap())),
+        Rc::clone(&env),
+    )?;
+    verify_eval_with_env(symbol("feature"), "(provide 'feature)", Rc::clone(&env));
+    verify_eval_with_env(symbol("feature"), "(require 'feature)", Rc::clone(&env));
+    assert!(matches!(
+        call_eval_with_env("loaded", Rc::clone(&env)),
+        Err(RuntimeError::UnboundVariable(..))
+    ));
+    std::fs::remove_dir_all(dir).unwrap();
+    Ok(())
+}
+
+#[test]
+fn load_test() -> Result<(), RuntimeError> {
+    let dir = module_dir("load");
+    let path = dir.join("script.lisp");
+    std::fs::write(&path, "(define x 1)\n(define y (+ x 1))\n").unwrap();
+
+    let env = Env::global_env();
+    verify_eval_with_env(
+        fixnum(2),
+        &format!("(load {})", string(path.to_str().unwrap())),
+        Rc::clone(&env),
+    );
+    verify_eval_with_env(fixnum(1), "x", env);
+
+    assert!(matches!(
+        call_eval("(load \"/no/such/file.lisp\")"),
+        Err(RuntimeError::LoadError(_, _))
+    ));
+    std::fs::remove_dir_all(dir).unwrap();
+    Ok(())
+}
+
+#[test]
+fn package_test() -> Result<(), RuntimeError> {
+    let env = Env::global_env();
+    for form in [
+        "(defpackage a (:export map))",
+        "(defpackage b (:export map))",
+        "(in-package a)",
+        "(define map (lambda (x) (+ x 1)))",
+        "(define hidden 'a)",
+        "(in-package b)",
+        "(define map (lambda (x) (+ x 2)))",
+        "(in-package user)",
+    ]
+    .iter()
+    {
+        call_eval_with_env(form, Rc::clone(&env))?;
+    }
+    verify_eval_with_env(fixnum(2), "(a:map 1)", Rc::clone(&env));
+    verify_eval_with_env(fixnum(3), "(b:map 1)", Rc::clone(&env));
+    verify_eval_with_env(symbol("a"), "a::hidden", Rc::clone(&env));
+    assert!(matches!(
+        call_eval_with_env("a:hidden", Rc::clone(&env)),
+        Err(RuntimeError::UnboundVariable(..))
+    ));
+    verify_eval_with_env(
+        list![fixnum(1), fixnum(2)],
+        "(map car '((1) (2)))",
+        Rc::clone(&env),
+    );
+
+    call_eval_with_env("(import 'b:map)", Rc::clone(&env))?;
+    verify_eval_with_env(fixnum(3), "(map 1)", Rc::clone(&env));
+    assert!(matches!(
+        call_eval_with_env("(import 'a:hidden)", Rc::clone(&env)),
+        Err(RuntimeError::SymbolNotExported(_, _))
+    ));
+    assert!(matches!(
+        call_eval_with_env("(in-package c)", Rc::clone(&env)),
+        Err(RuntimeError::PackageNotFound(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn load_in_package_test() -> Result<(), RuntimeError> {
+    let dir = module_dir("package");
+    std::fs::write(
+        dir.join("geometry.lisp"),
+        "(defpackage geometry (:export double))\n(in-package geometry)\n(define double (lambda (x) (+ x x)))\n",
+    )
+    .unwrap();
+
+    let env = Env::global_env();
+    call_eval_with_env(
+        &format!("(set! *load-path* '({}))", string(dir.to_str().unwrap())),
+        Rc::clone(&env),
+    )?;
+    call_eval_with_env("(require 'geometry)", Rc::clone(&env))?;
+    verify_eval_with_env(fixnum(4), "(geometry:double 2)", Rc::clone(&env));
+    call_eval_with_env("(define double 'user)", Rc::clone(&env))?;
+    verify_eval_with_env(symbol("user"), "double", env);
+    std::fs::remove_dir_all(dir).unwrap();
+    Ok(())
+}
+
+#[test]
+fn progn_test() {
+    verify_eval(nil(), "(progn)");
+    verify_eval(fixnum(2), "(progn 1 2)");
+}
+
+#[test]
+fn rest_parameter_test() {
+    verify_eval(nil(), "((lambda (&rest xs) xs))");
+    verify_eval(
+        cons(
+            fixnum(1),
+            cons(cons(fixnum(2), cons(fixnum(3), nil())), nil()),
+        ),
+        "((lambda (x &rest xs) (cons x (cons xs nil))) 1 2 3)",
+    );
+    assert!(matches!(
+        call_eval("((lambda (x &rest xs) x))"),
+        Err(RuntimeError::WrongArity(name, 0, (1, None))) if name == "lambda"
+    ));
+    assert_eq!(
+        call_eval("(progn (define f (lambda (x y &rest zs) x)) (f 1))")
+            .unwrap_err()
+            .to_string(),
+        "f expects at least 2 arguments, got 1"
+    );
+    assert!(matches!(
+        call_eval("(lambda (&rest) nil)"),
+        Err(RuntimeError::MalformedLambdaList(_))
+    ));
+    assert!(matches!(
+        call_eval("(lambda (&rest a b) nil)"),
+        Err(RuntimeError::MalformedLambdaList(_))
+    ));
+}
+
+#[test]
+fn defmacro_test() -> Result<(), RuntimeError> {
+    let env = Env::global_env();
+    call_eval_with_env("(defmacro swap-args (f a b) (list f b a))", Rc::clone(&env))?;
+    verify_eval_with_env(
+        cons(fixnum(2), fixnum(1)),
+        "(swap-args cons 1 2)",
+        Rc::clone(&env),
+    );
+    verify_eval_with_env(
+        symbol("yes"),
+        "(when (equal 1 1) 'no 'yes)",
+        Rc::clone(&env),
+    );
+    verify_eval_with_env(nil(), "(unless (equal 1 1) 'yes)", env);
+    Ok(())
+}
+
+#[test]
+fn stdlib_test() {
+    let list = |xs: &[isize]| lisp::object::from_iter(xs.iter().map(|&x| fixnum(x)));
+    verify_eval(list(&[1, 2, 3]), "(list 1 2 3)");
+    verify_eval(symbol("t"), "(null? nil)");
+    verify_eval(nil(), "(not 1)");
+    verify_eval(fixnum(1), "(caar '((1)))");
+    verify_eval(fixnum(2), "(cadr '(1 2))");
+    verify_eval(fixnum(2), "(cdar '((1 . 2)))");
+    verify_eval(list(&[3]), "(cddr '(1 2 3))");
+    verify_eval(fixnum(3), "(length '(a b c))");
+    verify_eval(symbol("c"), "(nth 2 '(a b c))");
+    verify_eval(list(&[3]), "(last '(1 2 3))");
+    verify_eval(list(&[1, 2, 3, 4]), "(append '(1) '(2 3) nil '(4))");
+    verify_eval(list(&[3, 2, 1]), "(reverse '(1 2 3))");
+    verify_eval(list(&[2, 3]), "(map (lambda (x) (+ x 1)) '(1 2))");
+    verify_eval(
+        list(&[1, 3]),
+        "(filter (lambda (x) (not (equal x 2))) '(1 2 3))",
+    );
+    verify_eval(fixnum(6), "(reduce + 0 '(1 2 3))");
+    verify_eval(list(&[2, 3]), "(member 2 '(1 2 3))");
+    verify_eval(fixnum(3), "(let ((x 1) (y 2)) (+ x y))");
+    verify_eval(symbol("t"), "(and)");
+    verify_eval(fixnum(2), "(and 1 2)");
+    verify_eval(nil(), "(and 1 nil 2)");
+    verify_eval(nil(), "(or)");
+    verify_eval(fixnum(1), "(or nil 1 2)");
+    verify_eval(fixnum(5), "(let ((value 5)) (or nil value))");
+    verify_eval(fixnum(3), "(apply + '(1 2))");
+}
+
+#[test]
+fn file_port_test() -> Result<(), RuntimeError> {
+    let dir = module_dir("file-port");
+    let path = string(dir.join("out.txt").to_str().unwrap());
+
+    let env = Env::global_env();
+    call_eval_with_env(&format!("(define path {})", path), Rc::clone(&env))?;
+    verify_eval_with_env(
+        string("b"),
+        "(with-open-file (out path :direction :output)
+           (write-string \"a\n\" out)
+           (write-string \"b\" out))",
+        Rc::clone(&env),
+    );
+    verify_eval_with_env(
+        list![string("a"), string("b"), nil()],
+        "(with-open-file (in path)
+           (list (read-line in) (read-char in) (read-line in)))",
+        Rc::clone(&env),
+    );
+
+    // The file is closed, and standard output restored, however the body
+    // finishes.
+    assert!(call_eval_with_env("(with-output-to-file path (car 1))", Rc::clone(&env)).is_err());
+    verify_eval_with_env(
+        fixnum(1),
+        "(with-output-to-file path (print 'a) (display \"b\") 1)",
+        Rc::clone(&env),
+    );
+    verify_eval_with_env(
+        list![string("a"), string("b")],
+        "(with-open-file (in path) (list (read-line in) (read-line in)))",
+        Rc::clone(&env),
+    );
+
+    call_eval_with_env("(define in (open-input-file path))", Rc::clone(&env))?;
+    verify_eval_with_env(string("a"), "(read-line in)", Rc::clone(&env));
+    verify_eval_with_env(nil(), "(close-port in)", Rc::clone(&env));
+    assert!(matches!(
+        call_eval_with_env("(read-line in)", Rc::clone(&env)),
+        Err(RuntimeError::ClosedPort)
+    ));
+    assert!(matches!(
+        call_eval_with_env("(with-open-file (p path) (write-string \"x\" p))", env),
+        Err(RuntimeError::MismatchType(_, ObjectType::OutputPort))
+    ));
+    assert!(matches!(
+        call_eval("(open-input-file \"/no/such/file\")"),
+        Err(RuntimeError::Io(_))
+    ));
+    std::fs::remove_dir_all(dir).unwrap();
+    Ok(())
+}
+
+#[test]
+fn string_port_test() -> Result<(), RuntimeError> {
+    verify_eval(
+        string("(1 \"a\")\na\n"),
+        "(with-output-to-string (print (list 1 \"a\")) (display \"a\") (newline))",
+    );
+    verify_eval(
+        string("ab"),
+        "(let ((out (open-output-string)))
+           (write-string \"a\" out)
+           (write 'b out)
+           (get-output-string out))",
+    );
+    verify_eval(
+        list![symbol("foo"), list![fixnum(1), fixnum(2)], symbol("eof")],
+        "(let ((in (open-input-string \"foo (1 2)\")))
+           (list (read in) (read in) (read in 'eof)))",
+    );
+    verify_eval(
+        list![string("a"), string("b")],
+        "(let ((in (open-input-string \"ab\")))
+           (list (read-char in) (read-line in)))",
+    );
+
+    let env = Env::global_env();
+    call_eval_with_env("(define out *standard-output*)", Rc::clone(&env))?;
+    assert!(call_eval_with_env("(with-output-to-string (car 1))", Rc::clone(&env)).is_err());
+    verify_eval_with_env(symbol("t"), "(equal out *standard-output*)", env);
+    Ok(())
+}
+
+#[test]
+fn docstring_test() -> Result<(), RuntimeError> {
+    let env = Env::global_env();
+    call_eval_with_env(
+        "(defun square (x) \"Multiplies X by itself.\" (* x x))",
+        Rc::clone(&env),
+    )?;
+    call_eval_with_env("(defun name () \"square\")", Rc::clone(&env))?;
+    call_eval_with_env("(defmacro ignore (x) \"Discards X.\" nil)", Rc::clone(&env))?;
+    verify_eval_with_env(
+        string("Multiplies X by itself."),
+        "(doc 'square)",
+        Rc::clone(&env),
+    );
+    verify_eval_with_env(nil(), "(doc 'name)", Rc::clone(&env));
+    verify_eval_with_env(string("square"), "(name)", Rc::clone(&env));
+    verify_eval_with_env(string("Discards X."), "(doc 'ignore)", Rc::clone(&env));
+    verify_eval_with_env(
+        string("Returns the number of elements in LIST."),
+        "(doc 'length)",
+        Rc::clone(&env),
+    );
+    verify_eval_with_env(nil(), "(doc 'car)", Rc::clone(&env));
+    assert!(matches!(
+        call_eval_with_env("(doc 'undefined)", env),
+        Err(RuntimeError::UnboundVariable(..))
+    ));
+    Ok(())
+}
+
+#[test]
+fn function_metadata_test() -> Result<(), RuntimeError> {
+    assert_eq!(call_eval("car")?.to_string(), "#<function car (list)>");
+    assert_eq!(call_eval("cons")?.to_string(), "#<function cons (car cdr)>");
+
+    let env = Env::global_env();
+    call_eval_with_env("(defun f (a b &rest c) a)", Rc::clone(&env))?;
+    call_eval_with_env("(define g f)", Rc::clone(&env))?;
+    match &*call_eval_with_env("g", Rc::clone(&env))? {
+        lisp::object::ObjectKind::Closure(closure) => {
+            assert_eq!(closure.name.as_deref(), Some("f"));
+            assert_eq!(closure.arity(), (2, None));
+        }
+        _ => unreachable!(),
+    }
+    match &*call_eval_with_env("(lambda (x) x)", Rc::clone(&env))? {
+        lisp::object::ObjectKind::Closure(closure) => {
+            assert_eq!(closure.name, None);
+            assert_eq!(closure.arity(), (1, Some(1)));
+        }
+        _ => unreachable!(),
+    }
+    match &*call_eval_with_env("when", env)? {
+        lisp::object::ObjectKind::Macro(closure) => {
+            assert_eq!(closure.name.as_deref(), Some("when"))
+        }
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
+#[test]
+fn closure_printing_test() -> Result<(), RuntimeError> {
+    let env = Env::global_env();
+    call_eval_with_env(
+        "(defun add (x &rest ys) \"Adds.\" (apply + (cons x ys)))",
+        Rc::clone(&env),
+    )?;
+    assert_eq!(
+        call_eval_with_env("add", Rc::clone(&env))?.to_string(),
+        "#<closure add (x &rest ys)>"
+    );
+    assert_eq!(
+        call_eval_with_env("(lambda () 1)", Rc::clone(&env))?.to_string(),
+        "#<closure ()>"
+    );
+    assert_eq!(
+        call_eval_with_env("when", Rc::clone(&env))?.to_string(),
+        "#<macro when (test &rest body)>"
+    );
+    assert_eq!(
+        call_eval_with_env("(closure-source add)", Rc::clone(&env))?.to_string(),
+        "(lambda (x &rest ys) \"Adds.\" (apply + (cons x ys)))"
+    );
+    verify_eval_with_env(nil(), "(closure-source car)", Rc::clone(&env));
+    assert!(matches!(
+        call_eval_with_env("(closure-source 1)", env),
+        Err(RuntimeError::MismatchType(_, ObjectType::Function))
+    ));
+    Ok(())
+}
+
+#[test]
+fn dump_form_test() -> Result<(), RuntimeError> {
+    let dump = match &*call_eval("(with-output-to-string (dump-form (list 'a \"b\")))")? {
+        lisp::object::ObjectKind::String(dump) => dump.clone(),
+        _ => unreachable!(),
+    };
+    // Reference counts depend on the evaluator, so only the shape is checked.
+    let lines: Vec<&str> = dump
+        .lines()
+        .map(|line| line.split(" rc=").next().unwrap())
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            "Cons",
+            "  car: Symbol a",
+            "  cdr: Cons",
+            "    car: String \"b\"",
+            "    cdr: Nil",
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn quasiquote_test() {
+    let read = |input: &str| read_from_string(input).unwrap().0;
+    verify_eval(
+        read("(a 1 2 3 d)"),
+        "(let ((b 1) (c '(2 3))) `(a ,b ,@c d))",
+    );
+    verify_eval(cons(symbol("a"), fixnum(1)), "(let ((b 1)) `(a . ,b))");
+    verify_eval(
+        read("(a (quasiquote (b (unquote (c 3)))))"),
+        "`(a `(b ,(c ,(+ 1 2))))",
+    );
+    verify_eval(
+        read("(a (quasiquote (b (unquote-splicing 1 2))))"),
+        "(let ((x '(1 2))) `(a `(b ,@,@x)))",
+    );
+    // The expansion doesn't call list, append or cons by name, so local
+    // variables with those names don't capture it.
+    verify_eval(
+        read("((1 2) (3) 4 5)"),
+        "(let ((list '(1 2)) (append '(3)) (cons '(4 5))) `(,list ,append ,@cons))",
+    );
+
+    let env = Env::global_env();
+    call_eval_with_env(
+        "(defmacro my-when (c &rest body) `(if ,c (progn ,@body) nil))",
+        Rc::clone(&env),
+    )
+    .unwrap();
+    verify_eval_with_env(fixnum(2), "(my-when t 1 2)", env);
+}
+
+#[test]
+fn hygiene_test() -> Result<(), RuntimeError> {
+    let env = Env::global_env();
+    let eval = |input: &str| call_eval_with_env(input, Rc::clone(&env));
+    assert_ne!(eval("(gensym)")?, eval("(gensym)")?);
+    assert!(eval("(gensym 'tmp)")?.to_string().starts_with("#:tmp"));
+    // A gensym isn't the symbol its printed name reads as.
+    eval("(define g (gensym))")?;
+    let name = eval("g")?.to_string();
+    assert!(name.starts_with("#:G"));
+    assert_eq!(eval(&format!("(equal g '{})", name))?, nil());
+
+    eval(
+        "(defmacro swap! (a b)
+           (with-gensyms (tmp)
+             `(let ((,tmp ,a)) (set! ,a ,b) (set! ,b ,tmp))))",
+    )?;
+    eval("(define tmp 1)")?;
+    eval("(define other 2)")?;
+    eval("(swap! tmp other)")?;
+    assert_eq!(eval("(list tmp other)")?, list![fixnum(2), fixnum(1)]);
+
+    eval("(defmacro double (x) (once-only (x) `(+ ,x ,x)))")?;
+    eval("(define n 0)")?;
+    assert_eq!(eval("(double (progn (set! n (+ n 1)) n))")?, fixnum(2));
+    assert_eq!(eval("n")?, fixnum(1));
+    Ok(())
+}
+
+#[test]
+fn expansion_hook_test() -> Result<(), RuntimeError> {
+    let env = Env::global_env();
+    let eval = |input: &str| call_eval_with_env(input, Rc::clone(&env));
+    eval(
+        "(define answer-hook
+           (lambda (form) (if (equal form '(answer)) 42 form)))",
+    )?;
+    eval("(defmacro answer () 0)")?;
+    eval("(defmacro deferred () '(answer))")?;
+    assert_eq!(eval("(answer)")?, fixnum(0));
+
+    eval("(add-expansion-hook answer-hook)")?;
+    // Hooks run before the macro is expanded, and again on its expansion.
+    assert_eq!(eval("(answer)")?, fixnum(42));
+    assert_eq!(eval("(deferred)")?, fixnum(42));
+    assert_eq!(eval("(list (answer))")?, list![fixnum(42)]);
+
+    assert_eq!(eval("(remove-expansion-hook answer-hook)")?, symbol("t"));
+    assert_eq!(eval("(remove-expansion-hook answer-hook)")?, nil());
+    assert_eq!(eval("(answer)")?, fixnum(0));
+    Ok(())
+}
+
+#[test]
+fn type_declaration_test() -> Result<(), RuntimeError> {
+    verify_eval(fixnum(3), "(the fixnum (+ 1 2))");
+    verify_eval(string("a"), "(the string \"a\")");
+    assert!(matches!(
+        call_eval("(the fixnum 'a)"),
+        Err(RuntimeError::MismatchDeclaredType(value, ObjectType::Number, form))
+            if value == symbol("a") && form.to_string() == "(the fixnum (quote a))"
+    ));
+    assert!(matches!(
+        call_eval("(the float 1)"),
+        Err(RuntimeError::MalformedForm(_))
+    ));
+
+    let env = Env::global_env();
+    call_eval_with_env(
+        "(defun add (x y)
+           \"Adds two fixnums.\"
+           (declare (type fixnum x y) (ignore z))
+           (+ x y))",
+        Rc::clone(&env),
+    )?;
+    verify_eval_with_env(fixnum(3), "(add 1 2)", Rc::clone(&env));
+    assert!(matches!(
+        call_eval_with_env("(add 1 \"2\")", Rc::clone(&env)),
+        Err(RuntimeError::MismatchDeclaredType(_, ObjectType::Number, spec))
+            if spec.to_string() == "(type fixnum x y)"
+    ));
+    verify_eval(fixnum(1), "(let ((x 1)) (declare (type fixnum x)) x)");
+    Ok(())
+}
+
+#[test]
+fn assert_test() {
+    verify_eval(symbol("t"), "(assert (equal (+ 1 1) 2))");
+    let e = call_eval("(assert (equal (+ 1 1) 3))").unwrap_err();
+    assert!(matches!(e, RuntimeError::AssertionFailed(_)));
+    assert_eq!(e.to_string(), "Assertion failed: (equal (+ 1 1) 3)");
+}
+
+#[test]
+fn deftest_test() -> Result<(), RuntimeError> {
+    let env = Env::global_env();
+    let eval = |input: &str| call_eval_with_env(input, Rc::clone(&env));
+    eval("(deftest addition (assert (equal (+ 1 2) 3)))")?;
+    eval("(deftest broken (assert (equal (car '(1)) 2)))")?;
+    eval("(deftest unbound (undefined-function))")?;
+    assert_eq!(
+        eval("(with-output-to-string (run-tests))")?,
+        string(
+            "FAIL broken: Assertion failed: (equal (car (quote (1))) 2)\n\
+             FAIL unbound: Unbound variable: undefined-function (in (undefined-function))\n\
+             3 tests, 2 failures\n"
+        )
+    );
+    eval("(with-output-to-string (define failed (run-tests)))")?;
+    assert_eq!(eval("failed")?, list![symbol("broken"), symbol("unbound")]);
+
+    eval("(deftest broken (assert t))")?;
+    eval("(deftest unbound t)")?;
+    assert_eq!(
+        eval("(with-output-to-string (run-tests))")?,
+        string("3 tests, 0 failures\n")
+    );
+    Ok(())
+}
+
+#[test]
+fn form_in_error_test() {
+    let env = Env::global_env();
+    let eval = |input: &str| eval(read_from_string(input).unwrap().0, Rc::clone(&env));
+    eval("(defun f (x) (+ x 1))").unwrap();
+
+    let e = eval("(list 1 (f 'a))").unwrap_err();
+    assert_eq!(e.form().unwrap().to_string(), "(+ x 1)");
+    assert!(matches!(
+        e.inner(),
+        RuntimeError::MismatchType(_, ObjectType::Number)
+    ));
+    assert_eq!(
+        e.to_string(),
+        "The value a is not of type Number (in (+ x 1))"
+    );
+
+    let e = eval("(car (cdr 1))").unwrap_err();
+    assert_eq!(e.form().unwrap().to_string(), "(cdr 1)");
+    assert!(eval("undefined").unwrap_err().form().is_none());
+}
+
+#[test]
+fn not_callable_test() {
+    let env = Env::global_env();
+    let eval = |input: &str| eval(read_from_string(input).unwrap().0, Rc::clone(&env));
+
+    let e = eval("(list ((+ 1 2)))").unwrap_err();
+    match &e {
+        RuntimeError::NotCallable(form, value) => {
+            assert_eq!(form.to_string(), "((+ 1 2))");
+            assert_eq!(*value, fixnum(3));
+        }
+        e => panic!("{:?}", e),
+    }
+    assert_eq!(
+        e.to_string(),
+        "Cannot call 3, the value of (+ 1 2), in ((+ 1 2))"
+    );
+    assert_eq!(
+        eval("(1 2 3)").unwrap_err().to_string(),
+        "Cannot call 1 in (1 2 3)"
+    );
+}
+
+#[test]
+fn backtrace_test() -> Result<(), RuntimeError> {
+    let env = Env::global_env();
+    let eval = |input: &str| call_eval_with_env(input, Rc::clone(&env));
+    verify_eval(nil(), "(backtrace)");
+    eval("(defun inner (x) (backtrace))")?;
+    eval("(defun outer () (car (inner 1)))")?;
+    assert_eq!(
+        eval("(outer)")?,
+        read_from_string("(inner (inner 1))").unwrap().0
+    );
+
+    eval("(defun fail (x) (error \"Bad value:\" x))")?;
+    eval("(define result (%try (lambda () (list (fail 42)))))")?;
+    assert_eq!(eval("(car result)")?, nil());
+    assert_eq!(
+        eval("(condition-message (cdr result))")?,
+        string("Bad value:")
+    );
+    assert_eq!(
+        eval("(condition-irritants (cdr result))")?,
+        list![fixnum(42)]
+    );
+    assert_eq!(
+        eval("(condition-backtrace (cdr result))")?,
+        read_from_string("((fail (fail 42)))").unwrap().0
+    );
+    assert_eq!(eval("(backtrace)")?, nil());
+
+    let e = eval("(fail 1)").unwrap_err();
+    assert_eq!(e.to_string(), "Bad value: 1");
+    Ok(())
+}
+
+#[test]
+fn restart_test() -> Result<(), RuntimeError> {
+    let env = Env::global_env();
+    let eval = |input: &str| call_eval_with_env(input, Rc::clone(&env));
+    assert_eq!(
+        eval("(with-restarts ((retry () 1) (skip (x) (list 'skipped x))) (+ 1 (invoke-restart 'skip 2)))")?,
+        list![symbol("skipped"), fixnum(2)]
+    );
+    assert_eq!(eval("(with-restarts ((retry () 1)) 2)")?, fixnum(2));
+    assert_eq!(
+        eval("(with-restarts ((a () 1)) (with-restarts ((b () 2)) (compute-restarts)))")?,
+        list![symbol("b"), symbol("a")]
+    );
+    assert_eq!(eval("(compute-restarts)")?, nil());
+
+    // The innermost restart with the name is used, and %try doesn't stop it.
+    assert_eq!(
+        eval(
+            "(with-restarts ((r () 'outer))
+               (with-restarts ((r () 'inner))
+                 (%try (lambda () (invoke-restart 'r)))))"
+        )?,
+        symbol("inner")
+    );
+    assert!(matches!(
+        eval("(invoke-restart 'missing)"),
+        Err(RuntimeError::RestartNotFound(name)) if name == "missing"
+    ));
+    Ok(())
+}
+
+#[test]
+fn unbound_variable_suggestion_test() {
+    let env = Env::global_env();
+    let eval = |input: &str| eval(read_from_string(input).unwrap().0, Rc::clone(&env));
+
+    assert_eq!(
+        eval("(lenght '(1 2))").unwrap_err().inner().to_string(),
+        "Unbound variable: lenght \u{2014} did you mean length?"
+    );
+    assert_eq!(
+        eval("((lambda (counter) countr) 1)")
+            .unwrap_err()
+            .inner()
+            .to_string(),
+        "Unbound variable: countr \u{2014} did you mean counter?"
+    );
+    assert_eq!(
+        eval("zzzzzz").unwrap_err().to_string(),
+        "Unbound variable: zzzzzz"
+    );
+}